@@ -0,0 +1,300 @@
+// a tiny language server for the editor's tests, `:lsp start target/debug/examples/mock_lsp`
+// it keeps the document in sync from incremental changes and knows about words:
+// lines with "error" or "warn" get a diagnostic, `fn name` defines name,
+// hover names the word, references and rename find every whole word like it
+// formatting trims trailing spaces and completion always offers process and print
+#[path = "../src/editor/view/lsp/json.rs"]
+#[allow(dead_code)]
+mod json;
+
+use json::Value;
+use std::io::{self, BufRead, Write};
+
+fn main() {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut server = Server::default();
+    while let Some(message) = read_message(&mut input) {
+        if !server.handle(&message) {
+            break;
+        }
+    }
+}
+
+#[derive(Default)]
+struct Server {
+    uri: String,
+    lines: Vec<String>,
+}
+
+fn read_message(input: &mut impl BufRead) -> Option<Value> {
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok()?;
+        }
+    }
+    let mut body = vec![0; length];
+    input.read_exact(&mut body).ok()?;
+    json::parse(&String::from_utf8_lossy(&body)).ok()
+}
+
+fn send(message: &Value) {
+    let body = message.to_string();
+    let mut stdout = io::stdout().lock();
+    let _ = write!(stdout, "Content-Length: {}\r\n\r\n{body}", body.len());
+    let _ = stdout.flush();
+}
+
+fn position(line: usize, character: usize) -> Value {
+    Value::object([("line", line.into()), ("character", character.into())])
+}
+
+fn range(line: usize, start: usize, end: usize) -> Value {
+    Value::object([
+        ("start", position(line, start)),
+        ("end", position(line, end)),
+    ])
+}
+
+impl Server {
+    /// false once the editor said exit
+    fn handle(&mut self, message: &Value) -> bool {
+        let params = message.get("params");
+        let result = match message.get("method").as_str().unwrap_or_default() {
+            "initialize" => Value::object([(
+                "capabilities",
+                Value::object([
+                    ("textDocumentSync", 2.into()),
+                    ("hoverProvider", true.into()),
+                    ("definitionProvider", true.into()),
+                    ("referencesProvider", true.into()),
+                    ("renameProvider", true.into()),
+                    ("documentFormattingProvider", true.into()),
+                    (
+                        "completionProvider",
+                        Value::object([("triggerCharacters", vec![".".into()].into())]),
+                    ),
+                ]),
+            )]),
+            "textDocument/didOpen" => {
+                let document = params.get("textDocument");
+                self.uri = document.get("uri").as_str().unwrap_or_default().into();
+                let text = document.get("text").as_str().unwrap_or_default();
+                self.lines = text.lines().map(str::to_string).collect();
+                self.publish();
+                return true;
+            }
+            "textDocument/didChange" => {
+                for change in params.get("contentChanges").as_array() {
+                    self.change(change);
+                }
+                self.publish();
+                return true;
+            }
+            "textDocument/hover" => match self.word(params) {
+                Some((_, _, word)) => Value::object([(
+                    "contents",
+                    Value::object([
+                        ("kind", "markdown".into()),
+                        ("value", format!("```\n{word}\n```\na word").into()),
+                    ]),
+                )]),
+                None => Value::Null,
+            },
+            "textDocument/definition" => {
+                let word = self.word(params).map(|(_, _, word)| word);
+                let found = word.and_then(|word| {
+                    let definition = format!("fn {word}");
+                    self.lines.iter().enumerate().find_map(|(line, text)| {
+                        let start = text.find(&definition)?.saturating_add(3);
+                        Some(self.location(line, start, start.saturating_add(word.len())))
+                    })
+                });
+                found.unwrap_or(Value::Null)
+            }
+            "textDocument/references" => match self.word(params) {
+                Some((_, _, word)) => self
+                    .occurrences(&word)
+                    .into_iter()
+                    .map(|(line, start)| {
+                        self.location(line, start, start.saturating_add(word.len()))
+                    })
+                    .collect::<Vec<_>>()
+                    .into(),
+                None => Value::Array(Vec::new()),
+            },
+            "textDocument/rename" => {
+                let new_name = params.get("newName").as_str().unwrap_or_default();
+                let edits: Vec<Value> = match self.word(params) {
+                    Some((_, _, word)) => self
+                        .occurrences(&word)
+                        .into_iter()
+                        .map(|(line, start)| {
+                            Value::object([
+                                (
+                                    "range",
+                                    range(line, start, start.saturating_add(word.len())),
+                                ),
+                                ("newText", new_name.into()),
+                            ])
+                        })
+                        .collect(),
+                    None => Vec::new(),
+                };
+                Value::Object(vec![(
+                    "changes".into(),
+                    Value::Object(vec![(self.uri.clone(), edits.into())]),
+                )])
+            }
+            "textDocument/formatting" => self
+                .lines
+                .iter()
+                .enumerate()
+                .filter(|(_, text)| text.ends_with(' '))
+                .map(|(line, text)| {
+                    Value::object([
+                        ("range", range(line, text.trim_end().len(), text.len())),
+                        ("newText", "".into()),
+                    ])
+                })
+                .collect::<Vec<_>>()
+                .into(),
+            "textDocument/completion" => vec![
+                Value::object([
+                    ("label", "process".into()),
+                    ("kind", 3.into()),
+                    ("detail", "fn process(input: &str)".into()),
+                    ("documentation", "Runs the input".into()),
+                ]),
+                Value::object([
+                    ("label", "print".into()),
+                    ("kind", 3.into()),
+                    ("detail", "fn print()".into()),
+                ]),
+            ]
+            .into(),
+            "exit" => return false,
+            _ => Value::Null,
+        };
+        // notifications get no answer
+        if !message.get("id").is_null() {
+            send(&Value::object([
+                ("jsonrpc", "2.0".into()),
+                ("id", message.get("id").clone()),
+                ("result", result),
+            ]));
+        }
+        true
+    }
+
+    fn location(&self, line: usize, start: usize, end: usize) -> Value {
+        Value::object([
+            ("uri", self.uri.clone().into()),
+            ("range", range(line, start, end)),
+        ])
+    }
+
+    /// applies a range change, the mock only sees ascii so offsets are bytes
+    fn change(&mut self, change: &Value) {
+        let range = change.get("range");
+        let text = change.get("text").as_str().unwrap_or_default();
+        if range.is_null() {
+            self.lines = text.lines().map(str::to_string).collect();
+            return;
+        }
+        let point = |name: &str| {
+            let at = range.get(name);
+            (
+                at.get("line").as_usize().unwrap_or_default(),
+                at.get("character").as_usize().unwrap_or_default(),
+            )
+        };
+        let mut document: String = self.lines.iter().map(|line| format!("{line}\n")).collect();
+        let offset = |document: &str, (line, character): (usize, usize)| {
+            let start: usize = document
+                .split_inclusive('\n')
+                .take(line)
+                .map(str::len)
+                .sum();
+            std::cmp::min(start.saturating_add(character), document.len())
+        };
+        let start = offset(&document, point("start"));
+        let end = offset(&document, point("end"));
+        document.replace_range(start..end, text);
+        self.lines = document.lines().map(str::to_string).collect();
+    }
+
+    fn publish(&self) {
+        let diagnostics: Vec<Value> = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter_map(|(line, text)| {
+                let (severity, start) = match (text.find("error"), text.find("warn")) {
+                    (Some(start), _) => (1, start),
+                    (None, Some(start)) => (2, start),
+                    (None, None) => return None,
+                };
+                Some(Value::object([
+                    ("range", range(line, start, text.len())),
+                    ("severity", severity.into()),
+                    (
+                        "message",
+                        format!("{} on line {}", text.trim(), line.saturating_add(1)).into(),
+                    ),
+                ]))
+            })
+            .collect();
+        send(&Value::object([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            (
+                "params",
+                Value::object([
+                    ("uri", self.uri.clone().into()),
+                    ("diagnostics", diagnostics.into()),
+                ]),
+            ),
+        ]));
+    }
+
+    /// the word at the position of a request, with its line and start
+    fn word(&self, params: &Value) -> Option<(usize, usize, String)> {
+        let at = params.get("position");
+        let line = at.get("line").as_usize()?;
+        let character = at.get("character").as_usize()?;
+        let text = self.lines.get(line)?;
+        let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let start = text[..character.min(text.len())]
+            .rfind(|c| !is_word(c))
+            .map_or(0, |index| index.saturating_add(1));
+        let end = text[start..]
+            .find(|c| !is_word(c))
+            .map_or(text.len(), |index| start.saturating_add(index));
+        (end > start).then(|| (line, start, text[start..end].to_string()))
+    }
+
+    fn occurrences(&self, word: &str) -> Vec<(usize, usize)> {
+        let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let mut found = Vec::new();
+        for (line, text) in self.lines.iter().enumerate() {
+            for (start, _) in text.match_indices(word) {
+                let before = text[..start].chars().next_back();
+                let after = text[start.saturating_add(word.len())..].chars().next();
+                if !before.is_some_and(is_word) && !after.is_some_and(is_word) {
+                    found.push((line, start));
+                }
+            }
+        }
+        found
+    }
+}
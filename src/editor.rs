@@ -1,56 +1,103 @@
-use crossterm::cursor::SetCursorStyle;
-use crossterm::event::{read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use backend::read;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+pub mod backend;
+pub mod screen;
 pub mod terminal;
-use std::env::args;
-use std::io::{Error, ErrorKind};
+use screen::Screen;
+use std::io::{self, Error, ErrorKind};
 use std::panic::{set_hook, take_hook};
+use std::path::Path;
 use std::{thread, time::Duration};
 use terminal::Terminal;
 mod view;
-use view::View;
+use view::{buffer::Buffer, View};
+pub mod cli;
+use cli::{Args, Source};
+mod config;
 pub mod editorcommands;
-use editorcommands::EditorCommand;
+#[cfg(test)]
+mod headless;
+use editorcommands::{EditorCommand, MenuCommand};
 
 #[derive(Default)]
 pub struct Editor {
+    should_quit: bool,
+    start_in_vim: bool,
     view: View,
 }
 
 impl Editor {
-    pub fn new() -> Result<Self, Error> {
+    pub fn new(args: &Args) -> Result<Self, Error> {
         let current_hook = take_hook();
         set_hook(Box::new(move |panic_info| {
             let _ = Terminal::terminate();
             current_hook(panic_info);
         }));
-        Terminal::initialize()?;
-        let args: Vec<String> = args().collect();
-        let mut view = View::default();
-        if let Some(filename) = args.get(1) {
-            if view.load(filename).is_err() {
+        // the terminal editor keeps its files where the user wants them
+        config::set_config_dir(args.config.clone().or_else(config::default_dir));
+        // piped text is read before the terminal takes over
+        let piped = match args.source {
+            Source::Stdin => Some(io::read_to_string(io::stdin())?),
+            _ => None,
+        };
+        backend::initialize()?;
+        Self::open(args, piped.as_deref())
+    }
+
+    /// an editor drawing to the backend of the current thread, on an empty buffer, a file or piped text
+    /// a directory is browsed first, leaving it without picking a file quits
+    pub fn open(args: &Args, piped: Option<&str>) -> Result<Self, Error> {
+        let mut editor = Self::default();
+        editor.start_in_vim = args.vim;
+        let view = &mut editor.view;
+        if let Some(text) = piped {
+            view.buffer = Buffer::from_text(text);
+        } else if let Some(filename) = args.filename() {
+            if Path::new(filename).is_dir() {
+                if !view.explore(filename)? {
+                    editor.should_quit = true;
+                    return Ok(editor);
+                }
+            } else if view.load(filename).is_err() {
                 return Err(Error::new(
                     ErrorKind::InvalidInput,
-                    format!("{filename} is a directory"),
+                    format!("cannot open {filename}"),
                 ));
-            };
+            }
+            view.offer_recovery()?;
         }
-        Ok(Self {
-            // should_quit: false,
-            view,
-        })
+        // a file that could not be read stays read only without -R
+        editor.view.buffer.read_only |= args.read_only;
+        editor.startup(args)?;
+        Ok(editor)
+    }
+
+    /// the start position and -c commands of the command line
+    fn startup(&mut self, args: &Args) -> Result<(), Error> {
+        let keep_going = self
+            .view
+            .startup(args)
+            .map_err(|message| Error::new(ErrorKind::InvalidInput, message))?;
+        self.should_quit = !keep_going;
+        Ok(())
     }
 
     pub fn run(&mut self) -> Result<(), Error> {
+        if self.should_quit {
+            return Ok(());
+        }
         // inital render
-        let res = self.view.start();
-        debug_assert!(res.is_ok());
+        self.view
+            .start()
+            .map_err(|err| Error::other(err.to_string()))?;
+        if self.start_in_vim && !matches!(self.view.handle_event(EditorCommand::VimMode), Ok(true))
+        {
+            self.view.buffer.discard_swap();
+            return Ok(());
+        }
         loop {
-            /*
-                        if self.should_quit {
-                            break;
-                        }
-            */
-            match read() {
+            let view = &mut self.view;
+            match backend::read_or_idle(view.idle_interval(), |idle| view.idle(idle)) {
                 Ok(event) => {
                     let cont = self.evaluate_event(event)?;
                     if !cont {
@@ -65,33 +112,46 @@ impl Editor {
                 }
             }
         }
+        // leaving normally, saved or not, leaves nothing to recover
+        self.view.buffer.discard_swap();
         Ok(())
     }
     #[allow(clippy::needless_pass_by_value)]
     fn evaluate_event(&mut self, event: Event) -> Result<bool, Error> {
         let should_process = match &event {
             Event::Key(KeyEvent { kind, .. }) => kind == &KeyEventKind::Press,
-            Event::Resize(_, _) => true,
+            Event::Resize(_, _) | Event::FocusGained | Event::FocusLost => true,
             _ => false,
         };
 
         if should_process {
+            // an open completion menu takes the keys that move through it
+            if self.view.completing() {
+                if let Ok(command) = MenuCommand::try_from(event.clone()) {
+                    self.view
+                        .handle_menu(command)
+                        .map_err(|err| Error::other(err.to_string()))?;
+                    return Ok(true);
+                }
+            }
             match EditorCommand::try_from(event) {
                 Ok(command) => {
                     if matches!(command, EditorCommand::Quit) {
                         if !self.view.buffer.is_saved && !self.view.buffer.is_empty() {
                             let exit = Self::exit_without_saving()?;
                             if exit {
-                                Terminal::clear_screen()?;
-                                Terminal::render_line(0, "Exiting without saving...")?;
-                                Terminal::execute()?;
+                                Screen::clear();
+                                Screen::render_line(0, "Exiting without saving...");
+                                Screen::present()?;
                                 thread::sleep(Duration::from_millis(300));
                             } else {
-                                if self.view.buffer.filename.is_none() {
-                                    self.view.get_file_name();
-                                }
-                                if self.view.buffer.filename.is_some() {
-                                    self.view.buffer.save();
+                                self.view.save();
+                                if !self.view.buffer.is_saved {
+                                    // the name prompt was cancelled, stay rather than lose the changes
+                                    self.view
+                                        .start()
+                                        .map_err(|err| Error::other(err.to_string()))?;
+                                    return Ok(true);
                                 }
                             }
                         }
@@ -119,12 +179,23 @@ impl Editor {
         Ok(true)
     }
 
+    /// what the process exits with, the :cq code when the edit was abandoned
+    pub fn exit_code(&self) -> u8 {
+        self.view.exit_code
+    }
+
+    /// the text for the other end of a pipe when editing what came from one
+    /// nothing when the edit was abandoned with :cq
+    pub fn output(&self, source: &Source) -> Option<String> {
+        (*source == Source::Stdin && self.exit_code() == 0).then(|| self.view.buffer.contents())
+    }
+
     fn exit_without_saving() -> Result<bool, Error> {
-        Terminal::clear_screen()?;
-        Terminal::hide_cursor()?;
-        Terminal::render_line(0, "Leave without saving:")?;
-        Terminal::render_line(1, "Ctrl-y = exit | Ctrl-n = save")?;
-        Terminal::execute()?;
+        Screen::clear();
+        Screen::hide_cursor();
+        Screen::render_line(0, "Leave without saving:");
+        Screen::render_line(1, "Ctrl-y = exit | Ctrl-n = save");
+        Screen::present()?;
 
         loop {
             match read() {
@@ -161,8 +232,10 @@ impl Editor {
 
 impl Drop for Editor {
     fn drop(&mut self) {
-        let _ = Terminal::set_cursor_style(SetCursorStyle::DefaultUserShape);
-        let _ = Terminal::terminate();
-        let _ = Terminal::print("Goodbye.\r\n");
+        if thread::panicking() {
+            // keep what was typed for the next start to recover
+            self.view.buffer.flush_swap(true);
+        }
+        let _ = backend::terminate();
     }
 }
@@ -0,0 +1,199 @@
+// where events come from and where frames go
+// the editor reads and draws through the backend installed on its thread
+// the default backend is the real terminal, tests install a scripted one
+use crate::editor::screen::{Change, Style};
+use crate::editor::terminal::{ScreenPosition, Size, Terminal};
+use crossterm::cursor::SetCursorStyle;
+use crossterm::event::Event;
+use std::cell::RefCell;
+use std::io::Error;
+use std::time::Duration;
+
+/// a source of key and resize events
+pub trait InputSource {
+    fn read(&mut self) -> Result<Event, Error>;
+
+    /// whether an event arrives within the timeout, a source that is never idle says yes
+    fn poll(&mut self, _timeout: Duration) -> Result<bool, Error> {
+        Ok(true)
+    }
+}
+
+/// something a presented frame can be written to
+pub trait OutputBackend {
+    fn initialize(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn terminate(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn size(&self) -> Result<Size, Error>;
+
+    /// writes the changed runs of a frame, see `Frame::diff`
+    /// a full draw starts from a blank screen
+    fn draw(
+        &mut self,
+        changes: &[Change],
+        cursor: Option<ScreenPosition>,
+        defaults: Style,
+        full: bool,
+    ) -> Result<(), Error>;
+
+    fn set_cursor_style(&mut self, _style: SetCursorStyle) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+pub struct TerminalInput;
+
+impl InputSource for TerminalInput {
+    fn read(&mut self) -> Result<Event, Error> {
+        crossterm::event::read()
+    }
+
+    fn poll(&mut self, timeout: Duration) -> Result<bool, Error> {
+        crossterm::event::poll(timeout)
+    }
+}
+
+pub struct TerminalOutput;
+
+impl OutputBackend for TerminalOutput {
+    fn initialize(&mut self) -> Result<(), Error> {
+        Terminal::initialize()
+    }
+
+    fn terminate(&mut self) -> Result<(), Error> {
+        Terminal::set_cursor_style(SetCursorStyle::DefaultUserShape)?;
+        Terminal::terminate()?;
+        Terminal::print("Goodbye.\r\n")?;
+        Terminal::execute()
+    }
+
+    fn size(&self) -> Result<Size, Error> {
+        Terminal::size()
+    }
+
+    fn draw(
+        &mut self,
+        changes: &[Change],
+        cursor: Option<ScreenPosition>,
+        defaults: Style,
+        full: bool,
+    ) -> Result<(), Error> {
+        Terminal::draw(changes, cursor, defaults, full)
+    }
+
+    fn set_cursor_style(&mut self, style: SetCursorStyle) -> Result<(), Error> {
+        Terminal::set_cursor_style(style)?;
+        Terminal::execute()
+    }
+}
+
+thread_local! {
+    static INPUT: RefCell<Box<dyn InputSource>> = RefCell::new(Box::new(TerminalInput));
+    static OUTPUT: RefCell<Box<dyn OutputBackend>> = RefCell::new(Box::new(TerminalOutput));
+}
+
+/// replaces the backend of the current thread
+#[cfg(test)]
+pub fn install(input: Box<dyn InputSource>, output: Box<dyn OutputBackend>) {
+    INPUT.with(|current| *current.borrow_mut() = input);
+    OUTPUT.with(|current| *current.borrow_mut() = output);
+}
+
+/// blocks until the next event, every mode loop reads through this
+pub fn read() -> Result<Event, Error> {
+    INPUT.with(|input| input.borrow_mut().read())
+}
+
+/// how long a read has waited for an event, handed to the idle callback every tick
+#[derive(Clone, Copy, Debug)]
+pub struct Idle {
+    pub waited: Duration,
+    tick: Duration,
+}
+
+impl Idle {
+    /// whether the wait reached the period on this tick
+    pub fn reached(&self, period: Duration) -> bool {
+        self.waited >= period && self.waited < period.saturating_add(self.tick)
+    }
+
+    /// whether the wait reached the period, or a multiple of it, on this tick
+    pub fn every(&self, period: Duration) -> bool {
+        self.waited >= period
+            && self
+                .waited
+                .as_nanos()
+                .checked_rem(period.as_nanos())
+                .is_some_and(|rest| rest < self.tick.as_nanos())
+    }
+}
+
+/// like read, calling `on_idle` each time a tick passes without an event
+pub fn read_or_idle(tick: Duration, mut on_idle: impl FnMut(Idle)) -> Result<Event, Error> {
+    let mut idle = Idle {
+        waited: Duration::ZERO,
+        tick,
+    };
+    while !INPUT.with(|input| input.borrow_mut().poll(tick))? {
+        idle.waited = idle.waited.saturating_add(tick);
+        on_idle(idle);
+    }
+    read()
+}
+
+pub fn initialize() -> Result<(), Error> {
+    OUTPUT.with(|output| output.borrow_mut().initialize())
+}
+
+pub fn terminate() -> Result<(), Error> {
+    OUTPUT.with(|output| output.borrow_mut().terminate())
+}
+
+pub fn size() -> Result<Size, Error> {
+    OUTPUT.with(|output| output.borrow().size())
+}
+
+pub fn draw(
+    changes: &[Change],
+    cursor: Option<ScreenPosition>,
+    defaults: Style,
+    full: bool,
+) -> Result<(), Error> {
+    OUTPUT.with(|output| output.borrow_mut().draw(changes, cursor, defaults, full))
+}
+
+pub fn set_cursor_style(style: SetCursorStyle) -> Result<(), Error> {
+    OUTPUT.with(|output| output.borrow_mut().set_cursor_style(style))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_ticks_reach_periods_once() {
+        let tick = Duration::from_millis(100);
+        let ticks = |period| {
+            (1..=25_u32)
+                .map(|n| Idle {
+                    waited: tick.saturating_mul(n),
+                    tick,
+                })
+                .filter(|idle| idle.every(period))
+                .count()
+        };
+        assert_eq!(ticks(Duration::from_secs(1)), 2);
+        assert_eq!(ticks(Duration::from_millis(250)), 10);
+        let idle = Idle {
+            waited: Duration::from_millis(1050),
+            tick,
+        };
+        assert!(idle.reached(Duration::from_secs(1)));
+        assert!(!idle.reached(Duration::from_millis(900)));
+    }
+}
@@ -0,0 +1,227 @@
+// the command line, parsed before the terminal is touched
+// mini-vim [options] [file | file:line[:col] | dir | -]
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "\
+usage: mini-vim [options] [file | file:line[:col] | dir | -]
+
+  file:line[:col]  open a file at a line and column, as printed by compilers
+  dir              browse a directory and pick a file from it
+  -                edit what is piped to stdin
+
+options:
+  +N               start at line N, + alone starts at the last line
+  +/pattern        start at the first match of pattern
+  -R               read only, writing the file needs :w!
+  --vim            start in vim mode
+  -c command       run an ex command after loading, can be given several times
+  --config PATH    keep history and settings in PATH instead of ~/.config/mini-vim
+  --               treat everything after as a file name
+  -h, --help       show this help
+  --version        show the version";
+
+/// where the cursor starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartPosition {
+    /// line and column counted from 1, a line of 0 is the last line
+    Line(usize, Option<usize>),
+    Search(String),
+}
+
+/// what the buffer is loaded from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Source {
+    #[default]
+    Empty,
+    File(String),
+    Stdin,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Args {
+    pub source: Source,
+    pub position: Option<StartPosition>,
+    pub read_only: bool,
+    pub vim: bool,
+    pub commands: Vec<String>,
+    pub config: Option<PathBuf>,
+}
+
+impl Args {
+    #[cfg(test)]
+    pub fn file(filename: &str) -> Self {
+        Self {
+            source: Source::File(filename.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn filename(&self) -> Option<&str> {
+        match &self.source {
+            Source::File(filename) => Some(filename),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    Edit(Args),
+    Help,
+    Version,
+}
+
+/// parses the arguments after the program name
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Action, String> {
+    let mut parsed = Args::default();
+    let mut location = None;
+    let mut args = args.into_iter();
+    let mut options_done = false;
+    while let Some(arg) = args.next() {
+        if options_done {
+            set_source(&mut parsed, Source::File(arg))?;
+            continue;
+        }
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Action::Help),
+            "--version" => return Ok(Action::Version),
+            "--" => options_done = true,
+            "-" => set_source(&mut parsed, Source::Stdin)?,
+            "-R" => parsed.read_only = true,
+            "--vim" => parsed.vim = true,
+            "-c" => {
+                let command = args.next().ok_or("-c needs an ex command")?;
+                parsed.commands.push(command);
+            }
+            "--config" => {
+                let path = args.next().ok_or("--config needs a path")?;
+                parsed.config = Some(PathBuf::from(path));
+            }
+            _ => {
+                if let Some(path) = arg.strip_prefix("--config=") {
+                    parsed.config = Some(PathBuf::from(path));
+                } else if let Some(position) = arg.strip_prefix('+') {
+                    parsed.position = Some(parse_position(position)?);
+                } else if arg.starts_with('-') {
+                    return Err(format!("unknown option {arg}"));
+                } else {
+                    let (filename, at) = split_location(&arg);
+                    location = at;
+                    set_source(&mut parsed, Source::File(filename))?;
+                }
+            }
+        }
+    }
+    // an explicit + wins over a location in the file name
+    parsed.position = parsed.position.or(location);
+    Ok(Action::Edit(parsed))
+}
+
+fn set_source(args: &mut Args, source: Source) -> Result<(), String> {
+    if args.source != Source::Empty {
+        return Err("only one file can be edited at a time".into());
+    }
+    args.source = source;
+    Ok(())
+}
+
+fn parse_position(position: &str) -> Result<StartPosition, String> {
+    if position.is_empty() {
+        return Ok(StartPosition::Line(0, None));
+    }
+    if let Some(pattern) = position.strip_prefix('/') {
+        if pattern.is_empty() {
+            return Err("+/ needs a pattern".into());
+        }
+        return Ok(StartPosition::Search(pattern.to_string()));
+    }
+    match position.parse::<usize>() {
+        // +0 is the first line like +1
+        Ok(line) => Ok(StartPosition::Line(line.max(1), None)),
+        Err(_) => Err(format!("+{position} is not a line number or /pattern")),
+    }
+}
+
+/// splits `file:line:col` into the file and where to start in it
+/// a file that exists under the full name is taken as it is
+fn split_location(arg: &str) -> (String, Option<StartPosition>) {
+    if Path::new(arg).exists() {
+        return (arg.to_string(), None);
+    }
+    // compilers print file:line:col: followed by the message
+    let trimmed = arg.strip_suffix(':').unwrap_or(arg);
+    let number = |text: &str| text.parse::<usize>().ok().filter(|&n| n > 0);
+    let Some((rest, last)) = trimmed.rsplit_once(':') else {
+        return (arg.to_string(), None);
+    };
+    let Some(last) = number(last) else {
+        return (arg.to_string(), None);
+    };
+    if let Some((file, line)) = rest.rsplit_once(':') {
+        if let Some(line) = number(line) {
+            if !file.is_empty() {
+                return (
+                    file.to_string(),
+                    Some(StartPosition::Line(line, Some(last))),
+                );
+            }
+        }
+    }
+    if rest.is_empty() {
+        return (arg.to_string(), None);
+    }
+    (rest.to_string(), Some(StartPosition::Line(last, None)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(args: &[&str]) -> Args {
+        match parse(args.iter().map(|arg| (*arg).to_string())) {
+            Ok(Action::Edit(args)) => args,
+            other => panic!("expected arguments to edit with, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_options_and_file() {
+        let args = edit(&["-R", "--vim", "-c", "set nu", "+12", "notes.txt"]);
+        assert_eq!(args.source, Source::File("notes.txt".into()));
+        assert_eq!(args.position, Some(StartPosition::Line(12, None)));
+        assert!(args.read_only && args.vim);
+        assert_eq!(args.commands, ["set nu"]);
+
+        assert_eq!(
+            edit(&["+/fn main", "--config=/tmp/conf", "-"]),
+            Args {
+                source: Source::Stdin,
+                position: Some(StartPosition::Search("fn main".into())),
+                config: Some(PathBuf::from("/tmp/conf")),
+                ..Default::default()
+            }
+        );
+        assert_eq!(edit(&["--", "-R"]).source, Source::File("-R".into()));
+        assert_eq!(parse(["--help".to_string()]), Ok(Action::Help));
+        assert!(parse(["-x".to_string()]).is_err());
+        assert!(parse(["a".to_string(), "b".to_string()]).is_err());
+        assert!(parse(["-c".to_string()]).is_err());
+    }
+
+    #[test]
+    fn splits_compiler_locations() {
+        assert_eq!(
+            split_location("src/no_such_file.rs:10:5:"),
+            (
+                "src/no_such_file.rs".into(),
+                Some(StartPosition::Line(10, Some(5)))
+            )
+        );
+        assert_eq!(
+            split_location("no_such_file.rs:7"),
+            ("no_such_file.rs".into(), Some(StartPosition::Line(7, None)))
+        );
+        assert_eq!(split_location("notes:txt"), ("notes:txt".into(), None));
+        assert_eq!(split_location(":3"), (":3".into(), None));
+    }
+}
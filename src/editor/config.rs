@@ -0,0 +1,34 @@
+use crate::editor::view::PROGRAM_NAME;
+use std::env;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// the directory the editor started with, set once by whoever starts it
+static DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// the directory of the editor files from now on, None keeps them all unread and unwritten
+pub fn set_config_dir(dir: Option<PathBuf>) {
+    let _ = DIR.set(dir);
+}
+
+/// directory for the user's editor files, ie prompt history
+/// an editor that was not given one, like the headless one, has none
+pub fn config_dir() -> Option<PathBuf> {
+    DIR.get().cloned().flatten()
+}
+
+/// where the editor files are unless --config says otherwise
+/// $XDG_CONFIG_HOME/mini-vim, falling back to ~/.config/mini-vim
+/// on windows %APPDATA%\mini-vim
+pub fn default_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir).join(PROGRAM_NAME));
+    }
+    #[cfg(windows)]
+    if let Some(dir) = env::var_os("APPDATA").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir).join(PROGRAM_NAME));
+    }
+    env::var_os("HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(|home| PathBuf::from(home).join(".config").join(PROGRAM_NAME))
+}
@@ -32,14 +32,22 @@ impl Direction {
         match self {
             //if not on last line, move down
             //if the next line is shorter, snap to the end of that line
+            //a closed fold is one line, moving over it goes to the line after
             Direction::Down => {
-                cursor_position.down(1, buffer.len().saturating_sub(1));
+                let next = buffer.folds.next_visible(cursor_position.height);
+                if next < buffer.len() {
+                    cursor_position.down(
+                        next.saturating_sub(cursor_position.height),
+                        buffer.len().saturating_sub(1),
+                    );
+                }
                 cursor_position.resolve_width(buffer.text[cursor_position.height].grapheme_len());
             }
             //if we are not in row 0, move up
             //if the line above is shorter than the previous line, snap to the end
             Direction::Up => {
-                cursor_position.up(1);
+                cursor_position.height =
+                    buffer.folds.head(cursor_position.height.saturating_sub(1));
                 cursor_position.resolve_width(buffer.text[cursor_position.height].grapheme_len());
             }
             //move left
@@ -48,7 +56,8 @@ impl Direction {
             //else move left 1
             Direction::Left => match (cursor_position.at_left_edge(), cursor_position.at_top()) {
                 (true, false) => {
-                    cursor_position.up(1);
+                    cursor_position.height =
+                        buffer.folds.head(cursor_position.height.saturating_sub(1));
                     cursor_position.snap_right(buffer.text[cursor_position.height].grapheme_len());
                 }
                 _ => {
@@ -67,8 +76,11 @@ impl Direction {
                     cursor_position.at_max_height(text_height),
                 ) {
                     (true, false) => {
-                        cursor_position.down(1, text_height);
-                        cursor_position.snap_left();
+                        let next = buffer.folds.next_visible(cursor_position.height);
+                        if next <= text_height {
+                            cursor_position.height = next;
+                            cursor_position.snap_left();
+                        }
                     }
                     _ => cursor_position.right(1, grapheme_len),
                 };
@@ -110,6 +122,11 @@ pub enum EditorCommand {
     VimMode,
     Search,
     Help,
+    Terminal,
+    Comment,              // comments the cursor line out or back in
+    AddCursor(AddCursor), // another cursor that edits along with the first
+    SingleCursor,         // back to the one cursor
+    Focus(bool),          // true when the terminal gained focus
     None,
     Quit,
 }
@@ -134,6 +151,15 @@ impl TryFrom<Event> for EditorCommand {
                 (KeyCode::Char('v'), KeyModifiers::CONTROL) => Ok(Self::Paste),
                 (KeyCode::Char('c'), KeyModifiers::CONTROL) => Ok(Self::Highlight),
                 (KeyCode::Char('n'), KeyModifiers::CONTROL) => Ok(Self::VimMode),
+                (KeyCode::Char('o'), KeyModifiers::CONTROL) => Ok(Self::Terminal),
+                // most terminals send Ctrl-/ as Ctrl-7
+                (KeyCode::Char('/' | '7'), KeyModifiers::CONTROL) => Ok(Self::Comment),
+                (KeyCode::Char('d'), KeyModifiers::CONTROL) => {
+                    Ok(Self::AddCursor(AddCursor::NextMatch))
+                }
+                (KeyCode::Up, KeyModifiers::ALT) => Ok(Self::AddCursor(AddCursor::Above)),
+                (KeyCode::Down, KeyModifiers::ALT) => Ok(Self::AddCursor(AddCursor::Below)),
+                (KeyCode::Esc, _) => Ok(Self::SingleCursor),
                 (KeyCode::Left, KeyModifiers::SHIFT) => Ok(Self::JumpWord(Direction::Left)),
                 (KeyCode::Right, KeyModifiers::SHIFT) => Ok(Self::JumpWord(Direction::Right)),
                 (KeyCode::Up, _) => Ok(Self::Move(Direction::Up)),
@@ -153,37 +179,132 @@ impl TryFrom<Event> for EditorCommand {
                 let width = width_16 as usize;
                 Ok(Self::Resize(Size { height, width }))
             }
+            Event::FocusGained => Ok(Self::Focus(true)),
+            Event::FocusLost => Ok(Self::Focus(false)),
             _ => Err(format!("Event not supported {event:?}")),
         }
     }
 }
 
+/// where another cursor goes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AddCursor {
+    NextMatch, // the next whole word like the one under the cursor
+    Above,     // the line above the first cursor
+    Below,     // the line below the last cursor
+}
+
+/// the keys of an open completion menu, every other key goes on to the text
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MenuCommand {
+    Next,
+    Previous,
+    Accept,
+    Close,
+}
+
+impl TryFrom<Event> for MenuCommand {
+    type Error = String;
+    fn try_from(event: Event) -> Result<Self, Self::Error> {
+        let Event::Key(KeyEvent {
+            code, modifiers, ..
+        }) = event
+        else {
+            return Err(format!("Not a menu key {event:?}"));
+        };
+        match (code, modifiers) {
+            (KeyCode::Tab | KeyCode::Down, _) | (KeyCode::Char('n'), KeyModifiers::CONTROL) => {
+                Ok(Self::Next)
+            }
+            (KeyCode::BackTab | KeyCode::Up, _) | (KeyCode::Char('p'), KeyModifiers::CONTROL) => {
+                Ok(Self::Previous)
+            }
+            (KeyCode::Enter, _) | (KeyCode::Char('y'), KeyModifiers::CONTROL) => Ok(Self::Accept),
+            (KeyCode::Esc, _) | (KeyCode::Char('e'), KeyModifiers::CONTROL) => Ok(Self::Close),
+            _ => Err(format!("Not a menu key {event:?}")),
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub enum SearchCommand {
-    Insert(char),
     Next,
     Previous,
-    BackSpace,
-    RevertState,
-    AssumeState,
+    Edit(PromptCommand),
+}
+
+impl TryFrom<Event> for SearchCommand {
+    type Error = String;
+    fn try_from(event: Event) -> Result<Self, Self::Error> {
+        match event {
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('n'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            }) => Ok(Self::Next),
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('p'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            }) => Ok(Self::Previous),
+            _ => Ok(Self::Edit(PromptCommand::try_from(event)?)),
+        }
+    }
+}
+
+/// line editing shared by every prompt, ie ':' commands, search, file name and jump to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PromptCommand {
+    Insert(char),
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    WordLeft,
+    WordRight,
+    Home,
+    End,
+    DeleteWord,
+    DeleteToStart,
+    HistoryPrevious,
+    HistoryNext,
+    Complete,
+    CompletePrevious,
+    Submit,
+    Cancel,
     Resize(Size),
     NoAction,
 }
 
-impl TryFrom<Event> for SearchCommand {
+impl TryFrom<Event> for PromptCommand {
     type Error = String;
     fn try_from(event: Event) -> Result<Self, Self::Error> {
         match event {
             Event::Key(KeyEvent {
                 code, modifiers, ..
             }) => match (code, modifiers) {
-                (KeyCode::Char('n'), KeyModifiers::CONTROL) => Ok(Self::Next),
-                (KeyCode::Char('p'), KeyModifiers::CONTROL) => Ok(Self::Previous),
-                (_, KeyModifiers::CONTROL) => Ok(Self::NoAction),
+                (KeyCode::Char('w'), KeyModifiers::CONTROL) => Ok(Self::DeleteWord),
+                (KeyCode::Char('u'), KeyModifiers::CONTROL) => Ok(Self::DeleteToStart),
+                (KeyCode::Char('a'), KeyModifiers::CONTROL) => Ok(Self::Home),
+                (KeyCode::Char('e'), KeyModifiers::CONTROL) => Ok(Self::End),
+                (KeyCode::Char('b'), KeyModifiers::ALT)
+                | (KeyCode::Left, KeyModifiers::CONTROL) => Ok(Self::WordLeft),
+                (KeyCode::Char('f'), KeyModifiers::ALT)
+                | (KeyCode::Right, KeyModifiers::CONTROL) => Ok(Self::WordRight),
+                (_, KeyModifiers::CONTROL | KeyModifiers::ALT) => Ok(Self::NoAction),
                 (KeyCode::Char(c), _) => Ok(Self::Insert(c)),
-                (KeyCode::Enter, _) => Ok(Self::AssumeState),
-                (KeyCode::Esc, _) => Ok(Self::RevertState),
-                (KeyCode::Backspace, _) => Ok(Self::BackSpace),
+                (KeyCode::Backspace, _) => Ok(Self::Backspace),
+                (KeyCode::Delete, _) => Ok(Self::Delete),
+                (KeyCode::Left, _) => Ok(Self::Left),
+                (KeyCode::Right, _) => Ok(Self::Right),
+                (KeyCode::Home, _) => Ok(Self::Home),
+                (KeyCode::End, _) => Ok(Self::End),
+                (KeyCode::Up, _) => Ok(Self::HistoryPrevious),
+                (KeyCode::Down, _) => Ok(Self::HistoryNext),
+                (KeyCode::Tab, _) => Ok(Self::Complete),
+                (KeyCode::BackTab, _) => Ok(Self::CompletePrevious),
+                (KeyCode::Enter, _) => Ok(Self::Submit),
+                (KeyCode::Esc, _) => Ok(Self::Cancel),
                 _ => Ok(Self::NoAction),
             },
             #[allow(clippy::as_conversions)]
@@ -205,6 +326,10 @@ pub enum HighlightCommand {
     #[default]
     NoAction,
     Delete,
+    Indent(Shift, bool), // the selected lines, true when the selection ends with it
+    Wrap(char),          // an opener typed puts the selection between it and its closer
+    Comment,             // comments the selected lines out or back in
+    Cursors,             // a cursor on every selected line
 }
 
 impl TryFrom<Event> for HighlightCommand {
@@ -229,6 +354,11 @@ impl TryFrom<Event> for HighlightCommand {
                 }
                 (KeyCode::Esc, KeyModifiers::NONE) => Ok(Self::RevertState),
                 (KeyCode::Backspace, KeyModifiers::NONE) => Ok(Self::Delete),
+                (KeyCode::Tab, KeyModifiers::NONE) => Ok(Self::Indent(Shift::Right, false)),
+                (KeyCode::BackTab, _) => Ok(Self::Indent(Shift::Left, false)),
+                (KeyCode::Char('/' | '7'), KeyModifiers::CONTROL) => Ok(Self::Comment),
+                (KeyCode::Char('d'), KeyModifiers::CONTROL) => Ok(Self::Cursors),
+                (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => Ok(Self::Wrap(c)),
                 _ => Ok(Self::NoAction),
             },
             #[allow(clippy::as_conversions)]
@@ -253,6 +383,8 @@ impl TryFrom<VimHighlightCommand> for HighlightCommand {
             VimHighlightCommand::RevertState => Ok(Self::RevertState),
             VimHighlightCommand::NoAction => Ok(Self::NoAction),
             VimHighlightCommand::Delete => Ok(Self::Delete),
+            VimHighlightCommand::Indent(shift, done) => Ok(Self::Indent(shift, done)),
+            VimHighlightCommand::Comment => Ok(Self::Comment),
         }
     }
 }
@@ -266,12 +398,19 @@ pub enum VimHighlightCommand {
     #[default]
     NoAction,
     Delete,
+    Indent(Shift, bool),
+    Comment,
 }
 
 impl TryFrom<Event> for VimHighlightCommand {
     type Error = String;
     fn try_from(event: Event) -> Result<Self, Self::Error> {
         match event {
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('/' | '7'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            }) => Ok(Self::Comment),
             Event::Key(KeyEvent { code, .. }) => match code {
                 KeyCode::Char('y') => Ok(Self::Copy),
                 KeyCode::Char('k') => Ok(Self::Move(Direction::Up)),
@@ -281,6 +420,16 @@ impl TryFrom<Event> for VimHighlightCommand {
                 KeyCode::Char('$') => Ok(Self::Move(Direction::End)),
                 KeyCode::Char('0') => Ok(Self::Move(Direction::Home)),
                 KeyCode::Char('d') => Ok(Self::Delete),
+                KeyCode::Char(key @ ('>' | '<' | '=')) => Ok(Self::Indent(
+                    match key {
+                        '>' => Shift::Right,
+                        '<' => Shift::Left,
+                        _ => Shift::Reindent,
+                    },
+                    true,
+                )),
+                KeyCode::Tab => Ok(Self::Indent(Shift::Right, false)),
+                KeyCode::BackTab => Ok(Self::Indent(Shift::Left, false)),
                 KeyCode::Esc => Ok(Self::RevertState),
                 _ => Ok(Self::NoAction),
             },
@@ -294,30 +443,6 @@ impl TryFrom<Event> for VimHighlightCommand {
     }
 }
 
-pub enum FileNameCommand {
-    Insert(char),
-    BackSpace,
-    SaveFileName,
-    Quit,
-    NoAction,
-}
-
-impl TryFrom<Event> for FileNameCommand {
-    type Error = String;
-    fn try_from(event: Event) -> Result<Self, Self::Error> {
-        match event {
-            Event::Key(KeyEvent { code, .. }) => match code {
-                KeyCode::Char(c) => Ok(Self::Insert(c)),
-                KeyCode::Backspace => Ok(Self::BackSpace),
-                KeyCode::Enter => Ok(Self::SaveFileName),
-                KeyCode::Esc => Ok(Self::Quit),
-                _ => Ok(Self::NoAction),
-            },
-            _ => Ok(Self::NoAction),
-        }
-    }
-}
-
 #[derive(Debug)]
 pub enum QueueInitCommand {
     PageUp,
@@ -325,6 +450,28 @@ pub enum QueueInitCommand {
     Colon,
     Delete,
     Yank,
+    Hover,         // K, shows what the language server knows about the word under the cursor
+    Indent(Shift), // >, < and =, on the lines of the next motion
+    Fold,          // z, zf makes a fold and the others open and close them
+}
+
+/// how a block of lines is indented
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Shift {
+    Right,    // a level deeper
+    Left,     // a level shallower
+    Reindent, // the indent the filetype gives it below the line above
+}
+
+impl Shift {
+    /// the vim key of the operator, typed twice it works on the cursor line
+    pub fn key(self) -> char {
+        match self {
+            Self::Right => '>',
+            Self::Left => '<',
+            Self::Reindent => '=',
+        }
+    }
 }
 
 pub enum VimModeCommands {
@@ -334,12 +481,13 @@ pub enum VimModeCommands {
     StartOfNextWord,
     EndOfCurrentWord,
     BeginingOfCurrentWord,
-    NewLine,
+    OpenLine(bool), // true for O, above the cursor
     Highlight,
     Search,
     Paste,
     NoAction,
     Resize(Size),
+    Focus(bool), // true when the terminal gained focus
     ComplexCommand(QueueInitCommand),
     Exit,
 }
@@ -361,7 +509,10 @@ impl TryFrom<Event> for VimModeCommands {
                 (KeyCode::Char('b'), KeyModifiers::NONE) => Ok(Self::BeginingOfCurrentWord),
                 (KeyCode::Char('e'), KeyModifiers::NONE) => Ok(Self::EndOfCurrentWord),
                 (KeyCode::Char('w'), KeyModifiers::NONE) => Ok(Self::StartOfNextWord),
-                (KeyCode::Char('o'), KeyModifiers::NONE) => Ok(Self::NewLine),
+                (KeyCode::Char('o'), KeyModifiers::NONE) => Ok(Self::OpenLine(false)),
+                (KeyCode::Char('O'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                    Ok(Self::OpenLine(true))
+                }
                 (KeyCode::Char('/'), KeyModifiers::NONE) => Ok(Self::Search),
                 (KeyCode::Char('d'), KeyModifiers::NONE) => {
                     Ok(Self::ComplexCommand(QueueInitCommand::Delete))
@@ -375,6 +526,21 @@ impl TryFrom<Event> for VimModeCommands {
                 (KeyCode::Char('G'), KeyModifiers::NONE) => {
                     Ok(Self::ComplexCommand(QueueInitCommand::PageDown))
                 }
+                (KeyCode::Char('K'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                    Ok(Self::ComplexCommand(QueueInitCommand::Hover))
+                }
+                (KeyCode::Char('z'), KeyModifiers::NONE) => {
+                    Ok(Self::ComplexCommand(QueueInitCommand::Fold))
+                }
+                (KeyCode::Char('>'), _) => {
+                    Ok(Self::ComplexCommand(QueueInitCommand::Indent(Shift::Right)))
+                }
+                (KeyCode::Char('<'), _) => {
+                    Ok(Self::ComplexCommand(QueueInitCommand::Indent(Shift::Left)))
+                }
+                (KeyCode::Char('='), KeyModifiers::NONE) => Ok(Self::ComplexCommand(
+                    QueueInitCommand::Indent(Shift::Reindent),
+                )),
                 (KeyCode::Char('p'), KeyModifiers::NONE) => Ok(Self::Paste),
                 (KeyCode::Char('v'), KeyModifiers::NONE) => Ok(Self::Highlight),
                 (KeyCode::Char('$'), KeyModifiers::NONE) => Ok(Self::Move(Direction::End)), //represents $
@@ -390,103 +556,74 @@ impl TryFrom<Event> for VimModeCommands {
                 height: height_u16 as usize,
                 width: width_u16 as usize,
             })),
+            Event::FocusGained => Ok(Self::Focus(true)),
+            Event::FocusLost => Ok(Self::Focus(false)),
             _ => Ok(Self::NoAction),
         }
     }
 }
 
-pub enum VimColonQueue {
-    New(char),
-    Other,
-    Execute,
-    Backspace,
+pub enum HelpCommand {
+    Exit,
+    NoAction,
     Resize(Size),
 }
 
-impl TryFrom<Event> for VimColonQueue {
+impl TryFrom<Event> for HelpCommand {
     type Error = String;
     fn try_from(event: Event) -> Result<Self, Self::Error> {
         match event {
-            Event::Key(KeyEvent { code, .. }) => match code {
-                KeyCode::Backspace => Ok(Self::Backspace),
-                KeyCode::Enter => Ok(Self::Execute),
-                KeyCode::Char(c) => Ok(Self::New(c)),
-                _ => Ok(Self::Other),
+            Event::Key(KeyEvent {
+                code, modifiers, ..
+            }) => match (code, modifiers) {
+                (KeyCode::Char('h'), KeyModifiers::CONTROL)
+                | (KeyCode::Esc, KeyModifiers::NONE) => Ok(Self::Exit),
+                _ => Ok(Self::NoAction),
             },
             #[allow(clippy::as_conversions)]
             Event::Resize(width_u16, height_u16) => Ok(Self::Resize(Size {
                 height: height_u16 as usize,
                 width: width_u16 as usize,
             })),
-            _ => Ok(Self::Other),
-        }
-    }
-}
-
-pub enum ColonQueueActions {
-    Write,
-    Quit,
-    Override,
-    Jump(usize),
-}
-
-impl TryFrom<char> for ColonQueueActions {
-    type Error = String;
-    fn try_from(val: char) -> Result<Self, Self::Error> {
-        match val {
-            'w' => Ok(Self::Write),
-            'q' => Ok(Self::Quit),
-            '!' => Ok(Self::Override),
-            _ => Err("invalid command".into()),
-        }
-    }
-}
-
-pub enum JumpCommand {
-    Enter(usize),
-    Delete,
-    Move,
-    Exit,
-    NoAction,
-}
-
-impl TryFrom<Event> for JumpCommand {
-    type Error = String;
-    fn try_from(event: Event) -> Result<Self, Self::Error> {
-        match event {
-            Event::Key(KeyEvent { code, .. }) => match code {
-                KeyCode::Char(val) => {
-                    if let Some(digit) = val.to_digit(10) {
-                        Ok(Self::Enter(digit.try_into().unwrap()))
-                    } else {
-                        Ok(Self::NoAction)
-                    }
-                }
-                KeyCode::Backspace => Ok(Self::Delete),
-                KeyCode::Esc => Ok(Self::Exit),
-                KeyCode::Enter => Ok(Self::Move),
-                _ => Ok(Self::NoAction),
-            },
             _ => Ok(Self::NoAction),
         }
     }
 }
 
-pub enum HelpCommand {
-    Exit,
-    NoAction,
+/// the keys of the theme picker
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PickerCommand {
+    Move(bool),                             // true goes down the list
+    Color { background: bool, next: bool }, // another color for the slot picked
+    Toggle(char),                           // bold, italic or underline of the slot, b i or u
+    Switch,                                 // between the themes and the slots of the one picked
+    Keep,
+    Restore,
     Resize(Size),
+    NoAction,
 }
 
-impl TryFrom<Event> for HelpCommand {
+impl TryFrom<Event> for PickerCommand {
     type Error = String;
     fn try_from(event: Event) -> Result<Self, Self::Error> {
         match event {
             Event::Key(KeyEvent {
                 code, modifiers, ..
             }) => match (code, modifiers) {
-                (KeyCode::Char('h'), KeyModifiers::CONTROL)
-                | (KeyCode::Esc, KeyModifiers::NONE) => Ok(Self::Exit),
+                (KeyCode::Up, _) | (KeyCode::Char('k'), KeyModifiers::NONE) => {
+                    Ok(Self::Move(false))
+                }
+                (KeyCode::Down, _) | (KeyCode::Char('j'), KeyModifiers::NONE) => {
+                    Ok(Self::Move(true))
+                }
+                (KeyCode::Left | KeyCode::Right, _) => Ok(Self::Color {
+                    background: modifiers.contains(KeyModifiers::SHIFT),
+                    next: code == KeyCode::Right,
+                }),
+                (KeyCode::Char(c @ ('b' | 'i' | 'u')), KeyModifiers::NONE) => Ok(Self::Toggle(c)),
+                (KeyCode::Tab | KeyCode::BackTab, _) => Ok(Self::Switch),
+                (KeyCode::Enter, _) => Ok(Self::Keep),
+                (KeyCode::Esc, _) => Ok(Self::Restore),
                 _ => Ok(Self::NoAction),
             },
             #[allow(clippy::as_conversions)]
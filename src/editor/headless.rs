@@ -0,0 +1,1015 @@
+// drives a whole editor session without a terminal
+// keys come from a script and frames are drawn into an in-memory screen
+// the session can then be checked through the buffer, the file on disk and the rendered rows
+use crate::editor::backend::{self, InputSource, OutputBackend};
+use crate::editor::cli::Args;
+use crate::editor::screen::{Change, Frame, Style};
+use crate::editor::terminal::{ScreenPosition, Size};
+use crate::editor::Editor;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::Error;
+use std::rc::Rc;
+
+/// vim style key notation, ie "3dd:wq<CR>"
+/// special keys are written <Esc>, <CR>, <BS>, <Tab>, <Up>, <Down>, <Left>, <Right>, <lt>
+/// and control keys <C-x>, <FocusGained> and <FocusLost> stand for the terminal events
+pub fn keys(script: &str) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut rest = script;
+    while let Some(c) = rest.chars().next() {
+        let special = rest
+            .strip_prefix('<')
+            .and_then(|tail| tail.split_once('>'))
+            .and_then(|(name, tail)| special_event(name).map(|event| (event, tail)));
+        if let Some((event, tail)) = special {
+            events.push(event);
+            rest = tail;
+        } else {
+            events.push(Event::Key(KeyEvent::new(
+                KeyCode::Char(c),
+                KeyModifiers::NONE,
+            )));
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    events
+}
+
+fn special_event(name: &str) -> Option<Event> {
+    let code = match name {
+        "FocusGained" => return Some(Event::FocusGained),
+        "FocusLost" => return Some(Event::FocusLost),
+        "Esc" => KeyCode::Esc,
+        "CR" => KeyCode::Enter,
+        "BS" => KeyCode::Backspace,
+        "Tab" => KeyCode::Tab,
+        "S-Tab" => KeyCode::BackTab,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "lt" => KeyCode::Char('<'),
+        "A-Up" => return Some(Event::Key(KeyEvent::new(KeyCode::Up, KeyModifiers::ALT))),
+        "A-Down" => return Some(Event::Key(KeyEvent::new(KeyCode::Down, KeyModifiers::ALT))),
+        "S-Left" => {
+            return Some(Event::Key(KeyEvent::new(
+                KeyCode::Left,
+                KeyModifiers::SHIFT,
+            )))
+        }
+        "S-Right" => {
+            return Some(Event::Key(KeyEvent::new(
+                KeyCode::Right,
+                KeyModifiers::SHIFT,
+            )))
+        }
+        _ => {
+            let c = name.strip_prefix("C-")?;
+            let mut chars = c.chars();
+            let (Some(c), None) = (chars.next(), chars.next()) else {
+                return None;
+            };
+            return Some(Event::Key(KeyEvent::new(
+                KeyCode::Char(c),
+                KeyModifiers::CONTROL,
+            )));
+        }
+    };
+    Some(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)))
+}
+
+/// hands out scripted events, running out means the session did not end when expected
+pub struct ScriptedInput {
+    events: VecDeque<Event>,
+}
+
+impl InputSource for ScriptedInput {
+    fn read(&mut self) -> Result<Event, Error> {
+        Ok(self
+            .events
+            .pop_front()
+            .expect("the script ran out of events before the editor exited"))
+    }
+}
+
+/// a screen kept in memory, every draw is applied to it like a terminal would
+/// the text of every drawn screen is kept so a test can look at the steps of a session
+pub struct VirtualScreen {
+    size: Size,
+    frame: Rc<RefCell<Frame>>,
+    history: Rc<RefCell<Vec<Vec<String>>>>,
+}
+
+impl OutputBackend for VirtualScreen {
+    fn size(&self) -> Result<Size, Error> {
+        Ok(self.size)
+    }
+
+    fn draw(
+        &mut self,
+        changes: &[Change],
+        cursor: Option<ScreenPosition>,
+        _defaults: Style,
+        full: bool,
+    ) -> Result<(), Error> {
+        let mut frame = self.frame.borrow_mut();
+        if full {
+            frame.clear();
+        }
+        for change in changes {
+            frame.put(
+                change.position.height,
+                change.position.width,
+                &change.text,
+                change.style,
+            );
+        }
+        frame.set_cursor(cursor);
+        self.history.borrow_mut().push(rows(&frame, self.size));
+        Ok(())
+    }
+}
+
+/// the rows of a frame without trailing blanks
+fn rows(frame: &Frame, size: Size) -> Vec<String> {
+    (0..size.height)
+        .map(|row| frame.row_text(row).trim_end().to_string())
+        .collect()
+}
+
+/// an editor on a scripted backend
+pub struct Session {
+    pub editor: Editor,
+    size: Size,
+    screen: Rc<RefCell<Frame>>,
+    history: Rc<RefCell<Vec<Vec<String>>>>,
+}
+
+impl Session {
+    pub fn new(filename: Option<&str>, size: Size, script: &str) -> Self {
+        let args = filename.map(Args::file).unwrap_or_default();
+        Self::with_args(&args, size, script)
+    }
+
+    /// a session started like the editor was given a command line
+    pub fn with_args(args: &Args, size: Size, script: &str) -> Self {
+        Self::with_input(args, None, size, script)
+    }
+
+    /// a session editing text piped to it, like `cmd | mini-vim -`
+    pub fn with_input(args: &Args, piped: Option<&str>, size: Size, script: &str) -> Self {
+        let screen = Rc::new(RefCell::new(Frame::new(size)));
+        let history = Rc::new(RefCell::new(Vec::new()));
+        backend::install(
+            Box::new(ScriptedInput {
+                events: keys(script).into(),
+            }),
+            Box::new(VirtualScreen {
+                size,
+                frame: Rc::clone(&screen),
+                history: Rc::clone(&history),
+            }),
+        );
+        let editor = Editor::open(args, piped).expect("the file should open");
+        Self {
+            editor,
+            size,
+            screen,
+            history,
+        }
+    }
+
+    /// runs the script until the editor exits
+    pub fn run(&mut self) {
+        self.editor.run().expect("the session should not fail");
+    }
+
+    /// the rows of the virtual screen without trailing blanks
+    pub fn rows(&self) -> Vec<String> {
+        rows(&self.screen.borrow(), self.size)
+    }
+
+    /// the rows of every screen drawn so far, oldest first
+    pub fn history(&self) -> Vec<Vec<String>> {
+        self.history.borrow().clone()
+    }
+
+    pub fn cursor(&self) -> Option<ScreenPosition> {
+        self.screen.borrow().cursor()
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.editor
+            .view
+            .buffer
+            .text
+            .iter()
+            .map(|line| line.raw_string.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::cli::{Source, StartPosition};
+    use crate::editor::view::swap::swap_path;
+    use crate::editor::view::theme::Colors;
+    use std::fs;
+    use std::path::PathBuf;
+
+    const SIZE: Size = Size {
+        height: 8,
+        width: 40,
+    };
+
+    /// a file in the temp dir that is removed when the test ends
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str, text: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("mini-vim-headless-{}-{name}", std::process::id()));
+            fs::write(&path, text).expect("the scratch file should be writable");
+            Self(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().expect("temp paths are utf-8 here")
+        }
+
+        fn read(&self) -> String {
+            fs::read_to_string(&self.0).expect("the scratch file should be readable")
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    /// a directory in the temp dir that is removed with everything in it when the test ends
+    struct ScratchDir(PathBuf);
+
+    impl ScratchDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("mini-vim-headless-{}-{name}", std::process::id()));
+            fs::create_dir_all(&path).expect("the scratch dir should be writable");
+            Self(path)
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().expect("temp paths are utf-8 here")
+        }
+    }
+
+    impl Drop for ScratchDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn key_notation() {
+        let key = |code, modifiers| Event::Key(KeyEvent::new(code, modifiers));
+        assert_eq!(
+            keys("d<C-n><lt><CR><Esc>"),
+            vec![
+                key(KeyCode::Char('d'), KeyModifiers::NONE),
+                key(KeyCode::Char('n'), KeyModifiers::CONTROL),
+                key(KeyCode::Char('<'), KeyModifiers::NONE),
+                key(KeyCode::Enter, KeyModifiers::NONE),
+                key(KeyCode::Esc, KeyModifiers::NONE),
+            ]
+        );
+        // an unknown name is typed as it is
+        assert_eq!(keys("<x>").len(), 3);
+    }
+
+    #[test]
+    fn typing_renders_the_buffer() {
+        let mut session = Session::new(None, SIZE, "hello<CR>world<C-q><C-y>");
+        session.run();
+        assert_eq!(session.lines(), vec!["hello", "world"]);
+        // quitting without saving leaves the confirmation on screen
+        assert_eq!(session.rows()[0], "Exiting without saving...");
+    }
+
+    #[test]
+    fn delete_lines_and_write_quit() {
+        let file = Scratch::new("dd", "one\ntwo\nthree\nfour\nfive\n");
+        let mut session = Session::new(Some(file.path()), SIZE, "<C-n>jdddddd:wq<CR>");
+        session.run();
+        assert_eq!(file.read(), "one\nfive\n");
+        assert_eq!(session.lines(), vec!["one", "five"]);
+        // the last frame drawn before the editor exited
+        let rows = session.rows();
+        assert_eq!(rows[..7], ["one", "five", "~", "~", "~", "~", ":wq"]);
+        assert!(rows[7].starts_with(" Vim  …mini-vim-headless-") && rows[7].ends_with(" 2:1"));
+        // still in the command prompt
+        assert_eq!(
+            session.cursor(),
+            Some(ScreenPosition {
+                height: 6,
+                width: 3
+            })
+        );
+    }
+
+    #[test]
+    fn recover_from_a_swap_file() {
+        let file = Scratch::new("recover", "one\n");
+        let swap = swap_path(file.path());
+        fs::write(&swap, "one\ntwo\n").unwrap();
+        // look at the diff, then recover and save
+        let mut session = Session::new(Some(file.path()), SIZE, "dr<C-w><C-q>");
+        let history = session.history();
+        assert!(history[0][0].starts_with("Found a swap file for /"));
+        assert_eq!(history[1][..2], ["  one", "+ two"]);
+        assert_eq!(history[1][7], "r = recover | d = diff | x = discard");
+        session.run();
+        assert_eq!(file.read(), "one\ntwo\n");
+        assert!(!swap.exists());
+    }
+
+    #[test]
+    fn discard_a_swap_file() {
+        let file = Scratch::new("discard", "one\n");
+        let swap = swap_path(file.path());
+        fs::write(&swap, "one\ntwo\n").unwrap();
+        let mut session = Session::new(Some(file.path()), SIZE, "x<C-q>");
+        session.run();
+        assert_eq!(session.lines(), vec!["one"]);
+        assert!(!swap.exists());
+    }
+
+    #[test]
+    fn reload_a_file_changed_on_disk() {
+        let file = Scratch::new("reload", "one\n");
+        let mut session = Session::new(Some(file.path()), SIZE, "<FocusGained>l<C-q>");
+        fs::write(&file.0, "one\ntwo\n").unwrap();
+        session.run();
+        // the question, before the file was loaded
+        let asked = &session.history()[1];
+        assert_eq!(asked[1], "The buffer has no unsaved changes");
+        assert_eq!(asked[7], "l = load | k = keep mine | d = diff");
+        assert_eq!(session.lines(), vec!["one", "two"]);
+    }
+
+    #[test]
+    fn keep_the_buffer_when_saving_over_a_changed_file() {
+        let file = Scratch::new("keep", "one\n");
+        let mut session = Session::new(Some(file.path()), SIZE, "x<C-w>k<C-q>");
+        fs::write(&file.0, "changed\n").unwrap();
+        session.run();
+        assert_eq!(file.read(), "xone\n");
+    }
+
+    #[test]
+    fn autoread_reloads_without_asking() {
+        let file = Scratch::new("autoread", "one\n");
+        let mut session = Session::new(
+            Some(file.path()),
+            SIZE,
+            "<C-n>:set ar<CR><Esc><FocusGained><C-q>",
+        );
+        fs::write(&file.0, "one\ntwo\n").unwrap();
+        session.run();
+        assert_eq!(session.lines(), vec!["one", "two"]);
+    }
+
+    #[test]
+    fn open_a_file_from_a_directory_listing() {
+        let dir = ScratchDir::new("explore");
+        fs::create_dir(dir.0.join("sub")).unwrap();
+        fs::write(dir.0.join("sub/notes.txt"), "hello\n").unwrap();
+        fs::write(dir.0.join("top.txt"), "").unwrap();
+        // into sub/, back out with -, into it again and open the file
+        let session = Session::new(Some(dir.path()), SIZE, "<CR>-<CR><CR>");
+        let history = session.history();
+        assert_eq!(history[0][1..4], ["../", "sub/", "top.txt"]);
+        assert_eq!(history[1][1..3], ["../", "notes.txt"]);
+        assert!(
+            history[1][7].starts_with(" Explorer  …")
+                && history[1][7].ends_with("explore/sub  2/2  100%")
+        );
+        assert_eq!(session.lines(), vec!["hello"]);
+        assert!(session
+            .editor
+            .view
+            .buffer
+            .filename
+            .as_ref()
+            .is_some_and(|name| name.ends_with("sub/notes.txt")));
+    }
+
+    #[test]
+    fn create_rename_and_delete_in_a_directory_listing() {
+        let dir = ScratchDir::new("manage");
+        fs::create_dir(dir.0.join("full")).unwrap();
+        fs::write(dir.0.join("full/inner.txt"), "").unwrap();
+        let script = concat!(
+            "%a.txt<CR>",       // new file
+            "dlib<CR>",         // new directory
+            "jR<C-u>b.txt<CR>", // a.txt, the entry after lib/, renamed
+            "kkDn",             // asked about full/, kept
+            "Dy",               // deleted after all
+            "q",
+        );
+        let mut session = Session::new(Some(dir.path()), SIZE, script);
+        assert!(dir.0.join("b.txt").is_file());
+        assert!(!dir.0.join("a.txt").exists());
+        assert!(dir.0.join("lib").is_dir());
+        assert!(!dir.0.join("full").exists());
+        let asked = session
+            .history()
+            .into_iter()
+            .find(|rows| rows[6].starts_with("Delete full/"))
+            .expect("deleting asks first");
+        assert_eq!(asked[6], "Delete full/ and everything in it? (y/n)");
+        // quitting the listing quits the editor
+        session.run();
+    }
+
+    #[test]
+    fn start_at_a_location_in_vim_mode() {
+        let file = Scratch::new("location", "one\ntwo\nthree\nfour\n");
+        let args = Args {
+            position: Some(StartPosition::Line(3, Some(2))),
+            commands: vec!["set nu".into()],
+            vim: true,
+            ..Args::file(file.path())
+        };
+        let mut session = Session::with_args(&args, SIZE, ":q<CR>");
+        assert_eq!(session.editor.view.cursor_position.height, 2);
+        assert_eq!(session.editor.view.cursor_position.width, 1);
+        session.run();
+        let rows = session.rows();
+        assert_eq!(rows[2], "  3 three");
+        assert!(rows[7].starts_with(" Vim ") && rows[7].ends_with(" 3:2"));
+    }
+
+    #[test]
+    fn commands_can_end_the_session() {
+        let file = Scratch::new("commands", "one\ntwo\n");
+        let args = Args {
+            commands: vec!["1d".into(), "wq".into()],
+            ..Args::file(file.path())
+        };
+        // no keys are read at all
+        Session::with_args(&args, SIZE, "").run();
+        assert_eq!(file.read(), "two\n");
+    }
+
+    #[test]
+    fn global_matches_the_pattern_as_plain_text() {
+        // like / search, the dot and the caret are characters, not a regular expression
+        let file = Scratch::new("global", "abc\na.c\n^x\nxyz\n");
+        let args = Args {
+            commands: vec!["g/a.c/d".into(), "v/^/d".into(), "wq".into()],
+            ..Args::file(file.path())
+        };
+        Session::with_args(&args, SIZE, "").run();
+        assert_eq!(file.read(), "^x\n");
+    }
+
+    #[test]
+    fn read_only_needs_a_bang_to_write() {
+        let file = Scratch::new("readonly", "one\ntwo\n");
+        let args = Args {
+            read_only: true,
+            vim: true,
+            ..Args::file(file.path())
+        };
+        let mut session = Session::with_args(&args, SIZE, ":1d<CR>:w<CR>:q!<CR>");
+        session.run();
+        assert_eq!(file.read(), "one\ntwo\n");
+        assert!(session
+            .history()
+            .iter()
+            .any(|rows| rows[6].starts_with("E45: 'readonly' option is set")));
+        assert!(!swap_path(file.path()).exists());
+    }
+
+    #[test]
+    fn cquit_abandons_the_edit_with_an_exit_code() {
+        let file = Scratch::new("cquit", "one\n");
+        let args = Args {
+            vim: true,
+            ..Args::file(file.path())
+        };
+        let mut session = Session::with_args(&args, SIZE, ":1d<CR>:cq<CR>");
+        session.run();
+        assert_eq!(session.editor.exit_code(), 1);
+        assert_eq!(file.read(), "one\n");
+
+        let mut session = Session::with_args(&args, SIZE, ":cq 3<CR>");
+        session.run();
+        assert_eq!(session.editor.exit_code(), 3);
+    }
+
+    #[test]
+    fn piped_text_goes_on_down_the_pipe_unless_abandoned() {
+        let args = Args {
+            source: Source::Stdin,
+            vim: true,
+            ..Args::default()
+        };
+        let mut session = Session::with_input(&args, Some("b\na\n"), SIZE, "dd:q!<CR>");
+        session.run();
+        assert_eq!(session.editor.exit_code(), 0);
+        assert_eq!(session.editor.output(&args.source), Some("a\n".into()));
+
+        // :cq writes nothing
+        let mut session = Session::with_input(&args, Some("b\na\n"), SIZE, "dd:cq<CR>");
+        session.run();
+        assert_eq!(session.editor.exit_code(), 1);
+        assert_eq!(session.editor.output(&args.source), None);
+
+        // a file is written to itself, not down a pipe
+        let file = Scratch::new("notpiped", "a\n");
+        let session = Session::new(Some(file.path()), SIZE, "");
+        assert_eq!(
+            session.editor.output(&Source::File(file.path().into())),
+            None
+        );
+    }
+
+    #[test]
+    fn move_lines_anywhere_but_into_themselves() {
+        let file = Scratch::new("move", "a\nb\nc\nd\ne\nf\ng\n");
+        let args = Args {
+            vim: true,
+            ..Args::file(file.path())
+        };
+        // after a line of the range itself
+        let mut session = Session::with_args(&args, SIZE, ":3,5m4<CR>:q!<CR>");
+        session.run();
+        assert_eq!(session.lines(), vec!["a", "b", "c", "d", "e", "f", "g"]);
+        assert!(session
+            .history()
+            .iter()
+            .any(|rows| rows[6].starts_with("E134: Cannot move a range")));
+
+        // after the line just before it, and the one at its end, leaves the lines in place
+        let mut session = Session::with_args(&args, SIZE, ":3,5m2<CR>:3,5m5<CR>:q!<CR>");
+        session.run();
+        assert_eq!(session.lines(), vec!["a", "b", "c", "d", "e", "f", "g"]);
+        assert!(!session
+            .history()
+            .iter()
+            .any(|rows| rows[6].starts_with("E134")));
+
+        let mut session = Session::with_args(&args, SIZE, ":3,5m0<CR>:q!<CR>");
+        session.run();
+        assert_eq!(session.lines(), vec!["c", "d", "e", "a", "b", "f", "g"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn filter_lines_through_shell_commands() {
+        let file = Scratch::new("filter", "pear\napple\nfig\n");
+        let script = concat!(
+            "<C-n>",
+            ":%!sort<CR>",                       // apple fig pear
+            ":1!tr a-z A-Z; echo upper >&2<CR>", // stderr on the message line
+            ":$r !echo kiwi<CR>",                // below the last line
+            ":2!exit 1<CR>",                     // a failed filter keeps the line
+            ":!echo shown<CR>x",                 // output screen, any key leaves it
+            ":q!<CR>",
+        );
+        let mut session = Session::new(Some(file.path()), SIZE, script);
+        session.run();
+        assert_eq!(session.lines(), vec!["APPLE", "fig", "pear", "kiwi"]);
+        let history = session.history();
+        assert!(history.iter().any(|rows| rows[6] == "upper"));
+        assert!(history
+            .iter()
+            .any(|rows| rows[6] == "shell returned an error"));
+        assert!(history
+            .iter()
+            .any(|rows| rows[..2] == [":!echo shown", "shown"]
+                && rows[7] == "Press any key to continue"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn terminal_pane_shares_the_screen_with_the_text() {
+        // Ctrl-o opens the pane with the keys on it, Ctrl-o again gives them back to the text
+        let mut session = Session::new(None, SIZE, "<C-o><C-o>hi<C-q><C-y>");
+        session.run();
+        assert_eq!(session.lines(), vec!["hi"]);
+        let history = session.history();
+        // the text keeps the top rows and its status line, the pane and its own status the rest
+        assert!(history.iter().any(|rows| rows[0] == "hi"
+            && rows[4].starts_with(" Insert ")
+            && rows[7].starts_with("Mode: Terminal | Shell: ")));
+    }
+    #[test]
+    fn complete_words_of_the_buffer() {
+        // "al" offers alpine before the longer alphabet, Ctrl-n moves to it and Enter takes it
+        let script = "alphabet alpine al<C-n><CR><C-q><C-y>";
+        let mut session = Session::new(None, SIZE, script);
+        session.run();
+        assert_eq!(session.lines(), vec!["alphabet alpine alphabet"]);
+        // the menu below the word and the line of the selected word beside it
+        assert!(session
+            .history()
+            .iter()
+            .any(|rows| rows[0] == "alphabet alpine al"
+                && rows[1] == " line 1          alpine    word"
+                && rows[2].ends_with(" alphabet  word")));
+    }
+
+    #[test]
+    fn new_lines_keep_and_follow_the_indent() {
+        // a brace opens a level, Enter in a pair of them puts the closer a line further down
+        let file = Scratch::new("indent.rs", "");
+        let script = "fn main() {<CR>if x {<CR>y<C-n>Ow<C-q><C-y>";
+        let mut session = Session::new(Some(file.path()), SIZE, script);
+        session.run();
+        assert_eq!(
+            session.lines(),
+            vec![
+                "fn main() {",
+                "    if x {",
+                "        w",
+                "        y",
+                "    }",
+                "}"
+            ]
+        );
+    }
+
+    #[test]
+    fn shift_lines_by_motion_selection_and_range() {
+        let file = Scratch::new("shift.rs", "fn f() {\nx\ny\n}\n");
+        let args = Args {
+            vim: true,
+            ..Args::file(file.path())
+        };
+        // = follows the braces, >j and << shift from the cursor line and :> takes a range
+        let script = "=Gj>j<lt><lt>:4>><CR>:wq<CR>";
+        Session::with_args(&args, SIZE, script).run();
+        assert_eq!(file.read(), "fn f() {\n    x\n        y\n        }\n");
+
+        // Tab and Shift-Tab shift every line of a selection and keep it
+        let file = Scratch::new("shift.txt", "a\nb\nc\n");
+        let script = "<C-c><Down><Tab><Tab><S-Tab><Esc><C-q><C-y>";
+        let mut session = Session::new(Some(file.path()), SIZE, script);
+        session.run();
+        assert_eq!(session.lines(), vec!["    a", "    b", "c"]);
+    }
+
+    #[test]
+    fn brackets_and_quotes_come_in_pairs() {
+        // the closers typed along are stepped over, backspace takes an empty pair away
+        let script = "f(\"x\", [<BS>y)<C-q><C-y>";
+        let mut session = Session::new(None, SIZE, script);
+        session.run();
+        assert_eq!(session.lines(), vec!["f(\"x\", y)"]);
+
+        // an opener typed over a selection wraps it
+        let file = Scratch::new("wrap.txt", "word\n");
+        let script = "<C-c><Right><Right><Right><Right>(<C-q><C-y>";
+        let mut session = Session::new(Some(file.path()), SIZE, script);
+        session.run();
+        assert_eq!(session.lines(), vec!["(word)"]);
+    }
+
+    #[test]
+    fn status_line_shows_the_file_and_keeps_its_line_endings() {
+        let file = Scratch::new("status.txt", "ab\r\ncd\r\n");
+        let script = "<C-c><Right><Esc>x<C-w><C-q>";
+        let size = Size {
+            height: 8,
+            width: 80,
+        };
+        let mut session = Session::new(Some(file.path()), size, script);
+        session.run();
+        assert_eq!(file.read(), "xab\r\ncd\r\n");
+        let history = session.history();
+        let status: Vec<&str> = history.iter().map(|rows| rows[7].as_str()).collect();
+        assert!(
+            status[0].starts_with(" Insert  /")
+                && status[0].ends_with("  txt  utf-8  CRLF  1:1  50%")
+        );
+        // a selection says how much it holds
+        assert!(status[2].starts_with(" Highlight  "));
+        assert!(status[2].contains("  2 selected  ") && status[2].contains("  1:2  "));
+        assert!(status
+            .iter()
+            .any(|row| row.contains("status.txt  [+]  txt")));
+        assert!(!status.last().unwrap().contains("[+]"));
+    }
+
+    #[test]
+    fn toggle_comments_by_motion_and_chord() {
+        let file = Scratch::new("comment.py", "def f():\n    return 1\nf()\n");
+        let args = Args {
+            vim: true,
+            ..Args::file(file.path())
+        };
+        // gcj comments two lines at the indent of the first, gcc takes the comment away again
+        Session::with_args(&args, SIZE, "gcjgcc:wq<CR>").run();
+        assert_eq!(file.read(), "def f():\n#     return 1\nf()\n");
+
+        let file = Scratch::new("comment.rs", "    let x = 1;\n");
+        let mut session = Session::new(Some(file.path()), SIZE, "<C-/><C-q><C-y>");
+        session.run();
+        assert_eq!(session.lines(), vec!["    // let x = 1;"]);
+    }
+
+    #[test]
+    fn closed_folds_show_as_one_line() {
+        let file = Scratch::new("fold", "a\nb\nc\nd\ne\n");
+        let args = Args {
+            vim: true,
+            ..Args::file(file.path())
+        };
+        // zfj folds two lines closed, j moves over them
+        let mut session = Session::with_args(&args, SIZE, "jzfjjdd:wq<CR>");
+        session.run();
+        assert_eq!(file.read(), "a\nb\nc\ne\n");
+        assert_eq!(session.rows()[..4], ["a", "+--  2 lines: b", "e", "~"]);
+
+        let file = Scratch::new("fold.rs", "fn a() {\n    b\n}\nc\n");
+        let args = Args {
+            commands: vec!["set fdm=indent".into()],
+            vim: true,
+            ..Args::file(file.path())
+        };
+        // the folds of the method start closed, zo opens the one at the cursor
+        let mut session = Session::with_args(&args, SIZE, "jddkzojdd:wq<CR>");
+        session.run();
+        assert_eq!(file.read(), "fn a() {\nc\n");
+        let folded = session
+            .history()
+            .iter()
+            .any(|rows| rows[..3] == ["+--  2 lines: fn a() {", "}", "c"]);
+        assert!(folded);
+    }
+
+    #[test]
+    fn search_and_selections_keep_folds_closed() {
+        let file = Scratch::new("fold-search", "a\nb\nc\nd\ne\n");
+        let args = Args {
+            vim: true,
+            ..Args::file(file.path())
+        };
+        // searching past the fold draws it closed, a match inside it opens it
+        let script = "jzfjgg/e<Esc>vj<Esc>/c<CR>:q<CR>";
+        let mut session = Session::with_args(&args, SIZE, script);
+        session.run();
+        let history = session.history();
+        let shown = |mode: &str, rows: [&str; 4]| {
+            history
+                .iter()
+                .any(|screen| screen[6..].concat().contains(mode) && screen[..4] == rows)
+        };
+        assert!(shown("Search: e", ["a", "+--  2 lines: b", "d", "e"]));
+        assert!(shown("Highlight", ["a", "+--  2 lines: b", "d", "e"]));
+        assert!(shown("Search: c", ["a", "b", "c", "d"]));
+        assert!(!session.editor.view.buffer.folds.hidden(2));
+    }
+
+    #[test]
+    fn search_and_selections_keep_lines_wrapped() {
+        let long = "one two three four five six seven\n".repeat(4);
+        let file = Scratch::new("wrap-search", &format!("{long}x\ntarget\n"));
+        let args = Args {
+            commands: vec!["set wrap".into()],
+            vim: true,
+            ..Args::file(file.path())
+        };
+        let size = Size {
+            height: 8,
+            width: 20,
+        };
+        // the match is below the view once the lines above it take two rows each
+        let script = "/seven<Esc>vl<Esc>/target<Esc>:q<CR>";
+        let mut session = Session::with_args(&args, size, script);
+        session.run();
+        let history = session.history();
+        let shown = |mode: &str, rows: [&str; 2]| {
+            history
+                .iter()
+                .any(|screen| screen[6..].concat().contains(mode) && screen[..2] == rows)
+        };
+        let wrapped = ["one two three four", "↪ five six seven"];
+        assert!(shown("Search: seven", wrapped));
+        assert!(shown("Highlight", wrapped));
+        assert!(history.iter().any(|screen| {
+            screen[6..].concat().contains("Search: target")
+                && screen[..6].contains(&"target".to_string())
+        }));
+    }
+
+    #[test]
+    fn themes_color_the_screen() {
+        let file = Scratch::new("theme.rs", "let x = 1; // one\n");
+        let args = Args {
+            vim: true,
+            ..Args::file(file.path())
+        };
+        let script = ":colo nothing<CR>:colorscheme gruvbox<CR>:colo<CR>:q<CR>";
+        let mut session = Session::with_args(&args, SIZE, script);
+        session.run();
+        let history = session.history();
+        let shown = |message: &str| history.iter().any(|rows| rows[6] == message);
+        assert!(shown("E185: Cannot find color scheme 'nothing'"));
+        assert!(shown("gruvbox"));
+
+        let theme = &session.editor.view.theme;
+        let style = |column: usize| session.screen.borrow().cell(0, column).unwrap().style;
+        // the cursor line is under the colors of the words, written in the ones the terminal shows
+        let written = |style: Style| style.fit(|color| Colors::detect().fit(color));
+        assert_eq!(style(0), written(theme.keyword.over(theme.cursor_line)));
+        assert_eq!(style(3), written(theme.cursor_line));
+        assert_eq!(style(8), written(theme.number.over(theme.cursor_line)));
+        assert_eq!(style(11), written(theme.comment.over(theme.cursor_line)));
+    }
+
+    #[test]
+    fn pick_a_theme_while_the_buffer_shows_it() {
+        // Esc puts the theme from before back
+        let script = "<C-t><Down><Down><Esc><C-q><C-y>";
+        let mut session = Session::new(None, SIZE, script);
+        session.run();
+        assert_eq!(session.editor.view.theme.name, "default");
+        let history = session.history();
+        assert!(history
+            .iter()
+            .any(|rows| rows[0].contains("Themes, Tab for the slots")));
+
+        // Enter keeps the one picked, a slot changed by hand with it
+        let script = "<C-t><Down><Tab><Down>b<CR><C-q><C-y>";
+        let mut session = Session::new(None, SIZE, script);
+        session.run();
+        let theme = &session.editor.view.theme;
+        // gruvbox follows default, its status line is bold
+        assert_eq!(theme.name, "gruvbox");
+        assert!(!theme.status.bold);
+        assert!(session
+            .history()
+            .iter()
+            .any(|rows| rows[2].contains("status")));
+    }
+
+    #[test]
+    fn select_onto_an_empty_line() {
+        // the status line counts what is selected, a line break is one
+        let file = Scratch::new("select", "abc\n\nxyz\n");
+        let script = "<C-c><Down><Down><Up><Esc><C-q><C-y>";
+        let mut session = Session::new(
+            Some(file.path()),
+            Size {
+                height: 8,
+                width: 80,
+            },
+            script,
+        );
+        session.run();
+        let history = session.history();
+        assert!(history.iter().any(|rows| rows[7].contains("4 selected")));
+        assert!(history.iter().any(|rows| rows[7].contains("6 selected")));
+        assert_eq!(session.lines(), vec!["abc", "", "xyz"]);
+    }
+
+    #[test]
+    fn edit_at_several_cursors() {
+        // Ctrl-d adds a cursor on the next foo, typing and deleting happen at both
+        let file = Scratch::new("cursors", "foo bar\nfood\nfoo baz\n");
+        let script = "<C-d>x<Right><BS><Esc>y<C-q><C-y>";
+        let mut session = Session::new(Some(file.path()), SIZE, script);
+        session.run();
+        assert_eq!(session.lines(), vec!["xyoo bar", "food", "xoo baz"]);
+
+        // a cursor below each line, then word motions move them all
+        let file = Scratch::new("cursors", "a b c\nd e f\ng h i\n");
+        let script = "<A-Down><A-Down><S-Right>-<C-q><C-y>";
+        let mut session = Session::new(Some(file.path()), SIZE, script);
+        session.run();
+        assert_eq!(session.lines(), vec!["a b- c", "d e- f", "g h- i"]);
+
+        // and one on every line of a selection
+        let file = Scratch::new("cursors", "a\nb\nc\n");
+        let script = "<C-c><Down><Down><C-d>#<C-q><C-y>";
+        let mut session = Session::new(Some(file.path()), SIZE, script);
+        session.run();
+        assert_eq!(session.lines(), vec!["#a", "#b", "#c"]);
+    }
+
+    #[test]
+    fn failed_writes_show_an_error_kept_by_messages() {
+        // the directory of the file is not there, the buffer stays unsaved
+        let script = "a<C-w><C-q><C-y>";
+        let mut session = Session::new(Some("/no/such/dir/file"), SIZE, script);
+        session.run();
+        assert!(session
+            .history()
+            .iter()
+            .any(|rows| rows[6].starts_with("E212: Can't open file for writing")));
+
+        // vim mode posts the same, :messages lists it
+        let args = Args {
+            vim: true,
+            ..Args::file("/no/such/dir/file")
+        };
+        let script = ":w<CR>:messages<CR><Esc>:q!<CR>";
+        let mut session = Session::with_args(&args, SIZE, script);
+        session.run();
+        let history = session.history();
+        assert!(history
+            .iter()
+            .any(|rows| rows[6].starts_with("E212: Can't open file for writing")));
+        assert!(history
+            .iter()
+            .any(|rows| rows.iter().any(|row| row.contains("E E212: Can't open"))));
+    }
+
+    /// the mock language server of `examples/mock_lsp.rs`, a plain `cargo test` builds it
+    /// next to the tests, a run that only builds the binary's tests does not
+    fn mock_server() -> std::path::PathBuf {
+        let exe = std::env::current_exe().expect("the test binary has a path");
+        let server = exe
+            .parent()
+            .and_then(std::path::Path::parent)
+            .map(|dir| dir.join("examples").join("mock_lsp"))
+            .expect("the test binary is in target/debug/deps");
+        assert!(
+            server.exists(),
+            "the mock language server is not built at {}, \
+             run `cargo build --example mock_lsp` or a plain `cargo test` first",
+            server.display()
+        );
+        server
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn complete_what_the_language_server_offers() {
+        let file = Scratch::new("complete.rs", "fn greet() {}\n");
+        let args = Args {
+            commands: vec![format!("lsp start {}", mock_server().display())],
+            ..Args::file(file.path())
+        };
+        // a word opens the menu, the trigger character '.' opens it on nothing typed
+        let script = "<C-r><CR>pro<CR>.pri<CR><C-q><C-y>";
+        let size = Size {
+            height: 8,
+            width: 80,
+        };
+        let mut session = Session::with_args(&args, size, script);
+        session.run();
+        assert_eq!(session.lines(), vec!["fn greet() {}", "process.print"]);
+        // the signature and the documentation of the selected item beside it
+        assert!(session.history().iter().any(|rows| rows[1] == "  pro"
+            && rows[2] == "~  process  function  fn process(input: &str)"
+            && rows[3].ends_with("  Runs the input")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn language_server_features_through_the_mock_server() {
+        let server = mock_server();
+        let file = Scratch::new("lsp.rs", "fn greet() {}\ngreet() error\ngreet() warn  \n");
+        let args = Args {
+            commands: vec![format!("lsp start {}", server.display())],
+            vim: true,
+            ..Args::file(file.path())
+        };
+        let script = concat!(
+            "jgd",               // to the definition on the first line
+            ":lsp<CR>",          // the server and its diagnostics
+            ":rename hello<CR>", // every greet in the buffer
+            ":format<CR>",       // trailing blanks go
+            ":q!<CR>",
+        );
+        let size = Size {
+            height: 8,
+            width: 80,
+        };
+        let mut session = Session::with_args(&args, size, script);
+        session.run();
+        assert_eq!(
+            session.lines(),
+            vec!["fn hello() {}", "hello() error", "hello() warn"]
+        );
+        assert_eq!(session.editor.view.cursor_position.height, 0);
+        let history = session.history();
+        assert!(history
+            .iter()
+            .any(|rows| rows[1] == "E greet() error" && rows[2].starts_with("W greet() warn")));
+        assert!(history
+            .iter()
+            .any(|rows| rows[6].ends_with("mock_lsp | E:1 W:1")));
+        assert!(history
+            .iter()
+            .any(|rows| rows[6] == "Renamed 3 occurrences in 1 files"));
+    }
+}
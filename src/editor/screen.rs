@@ -0,0 +1,489 @@
+// the shared screen model every mode draws into
+// modes write rows, styled segments and the cursor into a frame of cells
+// presenting the frame diffs it against the last presented one and writes only the changed cells
+use crate::editor::backend;
+use crate::editor::terminal::{ScreenPosition, Size};
+use crossterm::style::Color;
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::io::Error;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// how a cell is drawn, colors left as None use the screen defaults set by the theme
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct Style {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl Style {
+    /// this style drawn over another, the colors it leaves None and attributes it lacks show through
+    #[must_use]
+    pub fn over(self, below: Self) -> Self {
+        Self {
+            foreground: self.foreground.or(below.foreground),
+            background: self.background.or(below.background),
+            bold: self.bold || below.bold,
+            italic: self.italic || below.italic,
+            underline: self.underline || below.underline,
+        }
+    }
+
+    /// this style with its colors turned into others, ie ones the terminal shows
+    #[must_use]
+    pub fn fit(self, fit: impl Fn(Color) -> Color) -> Self {
+        Self {
+            foreground: self.foreground.map(&fit),
+            background: self.background.map(&fit),
+            ..self
+        }
+    }
+}
+
+/// one column of the screen
+/// the column after a wide grapheme holds an empty grapheme
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub grapheme: String,
+    pub style: Style,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            grapheme: " ".to_string(),
+            style: Style::default(),
+        }
+    }
+}
+
+impl Cell {
+    fn is_continuation(&self) -> bool {
+        self.grapheme.is_empty()
+    }
+}
+
+/// a run of changed cells on one row sharing a style
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub position: ScreenPosition,
+    pub text: String,
+    pub style: Style,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    size: Size,
+    cells: Vec<Cell>,
+    cursor: Option<ScreenPosition>, // None hides the cursor
+}
+
+impl Frame {
+    pub fn new(size: Size) -> Self {
+        Self {
+            size,
+            cells: vec![Cell::default(); size.width.saturating_mul(size.height)],
+            cursor: None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn cursor(&self) -> Option<ScreenPosition> {
+        self.cursor
+    }
+
+    pub fn cell(&self, row: usize, column: usize) -> Option<&Cell> {
+        if column >= self.size.width {
+            return None;
+        }
+        self.cells
+            .get(row.saturating_mul(self.size.width).saturating_add(column))
+    }
+
+    /// the text of a row, trailing blanks included
+    #[cfg(test)]
+    pub fn row_text(&self, row: usize) -> String {
+        (0..self.size.width)
+            .filter_map(|column| self.cell(row, column))
+            .map(|cell| cell.grapheme.as_str())
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.fill(Cell::default());
+    }
+
+    pub fn clear_row(&mut self, row: usize) {
+        if row >= self.size.height {
+            return;
+        }
+        let start = row.saturating_mul(self.size.width);
+        self.cells[start..start.saturating_add(self.size.width)].fill(Cell::default());
+    }
+
+    /// writes text from a column, clipped at the end of the row
+    /// returns the column after the text
+    pub fn put(&mut self, row: usize, column: usize, text: &str, style: Style) -> usize {
+        if row >= self.size.height {
+            return column;
+        }
+        let start = row.saturating_mul(self.size.width);
+        let mut column = column;
+        for grapheme in text.graphemes(true) {
+            // zero width graphemes still take a cell so the grid stays aligned
+            let width = std::cmp::max(grapheme.width(), 1);
+            if column.saturating_add(width) > self.size.width {
+                break;
+            }
+            let index = start.saturating_add(column);
+            // never leave half of a wide grapheme behind
+            if self.cells[index].is_continuation() && column > 0 {
+                self.cells[index.saturating_sub(1)] = Cell::default();
+            }
+            let after = index.saturating_add(width);
+            if column.saturating_add(width) < self.size.width && self.cells[after].is_continuation()
+            {
+                self.cells[after] = Cell::default();
+            }
+            self.cells[index] = Cell {
+                grapheme: grapheme.to_string(),
+                style,
+            };
+            if width > 1 {
+                self.cells[index.saturating_add(1)] = Cell {
+                    grapheme: String::new(),
+                    style,
+                };
+            }
+            column = column.saturating_add(width);
+        }
+        column
+    }
+
+    /// draws a style over the cells of a row without changing their text
+    pub fn restyle(&mut self, row: usize, columns: Range<usize>, style: Style) {
+        if row >= self.size.height {
+            return;
+        }
+        let start = row.saturating_mul(self.size.width);
+        let end = std::cmp::min(columns.end, self.size.width);
+        for column in columns.start..end {
+            let cell = &mut self.cells[start.saturating_add(column)];
+            cell.style = style.over(cell.style);
+        }
+    }
+
+    pub fn set_cursor(&mut self, position: Option<ScreenPosition>) {
+        self.cursor = position;
+    }
+
+    /// the runs of cells that differ from the previous frame
+    /// a frame of a different size, or no previous frame, differs from a blank screen
+    pub fn diff(&self, previous: Option<&Frame>) -> Vec<Change> {
+        let blank = Cell::default();
+        let previous = previous.filter(|frame| frame.size == self.size);
+        let mut changes = Vec::new();
+
+        for row in 0..self.size.height {
+            let mut run: Option<Change> = None;
+            for column in 0..self.size.width {
+                let Some(cell) = self.cell(row, column) else {
+                    break;
+                };
+                let before = previous
+                    .and_then(|frame| frame.cell(row, column))
+                    .unwrap_or(&blank);
+                if cell == before {
+                    if let Some(change) = run.take() {
+                        changes.push(change);
+                    }
+                    continue;
+                }
+
+                if cell.is_continuation() {
+                    // the wide grapheme left of this cell covers it
+                    if run.is_none() {
+                        // only the second half changed, redraw the whole grapheme
+                        let lead_column = column.saturating_sub(1);
+                        if let Some(lead) = self.cell(row, lead_column) {
+                            run = Some(Change {
+                                position: ScreenPosition {
+                                    height: row,
+                                    width: lead_column,
+                                },
+                                text: lead.grapheme.clone(),
+                                style: lead.style,
+                            });
+                        }
+                    }
+                    continue;
+                }
+
+                match run.as_mut() {
+                    Some(change) if change.style == cell.style => {
+                        change.text.push_str(&cell.grapheme);
+                    }
+                    _ => {
+                        if let Some(change) = run.take() {
+                            changes.push(change);
+                        }
+                        run = Some(Change {
+                            position: ScreenPosition {
+                                height: row,
+                                width: column,
+                            },
+                            text: cell.grapheme.clone(),
+                            style: cell.style,
+                        });
+                    }
+                }
+            }
+            if let Some(change) = run {
+                changes.push(change);
+            }
+        }
+        changes
+    }
+}
+
+struct ScreenState {
+    frame: Frame,
+    presented: Option<Frame>, // what the terminal shows, None forces a full redraw
+    defaults: Style,
+    status: Style,                    // drawn over the whole status line
+    fit: Box<dyn Fn(Color) -> Color>, // the colors written for the ones drawn
+}
+
+thread_local! {
+    static SCREEN: RefCell<ScreenState> = RefCell::new(ScreenState {
+        frame: Frame::new(backend::size().unwrap_or_default()),
+        presented: None,
+        defaults: Style::default(),
+        status: Style::default(),
+        fit: Box::new(|color| color),
+    });
+}
+
+/// the frame being drawn, shared by every mode
+pub struct Screen;
+
+impl Screen {
+    fn with_frame<T>(draw: impl FnOnce(&mut Frame) -> T) -> T {
+        SCREEN.with(|screen| draw(&mut screen.borrow_mut().frame))
+    }
+
+    /// blanks the whole frame, nothing is written until the frame is presented
+    pub fn clear() {
+        Self::with_frame(Frame::clear);
+    }
+
+    /// replaces a row with unstyled text
+    pub fn render_line<T: Display>(row: usize, line: T) {
+        Self::with_frame(|frame| {
+            frame.clear_row(row);
+            frame.put(row, 0, &line.to_string(), Style::default());
+        });
+    }
+
+    /// writes styled text without clearing the rest of the row
+    /// returns the column after the text
+    pub fn print_at<T: Display>(position: ScreenPosition, text: T, style: Style) -> usize {
+        Self::with_frame(|frame| {
+            frame.put(position.height, position.width, &text.to_string(), style)
+        })
+    }
+
+    /// draws a style over cells already written, their text stays
+    pub fn restyle(row: usize, columns: Range<usize>, style: Style) {
+        Self::with_frame(|frame| frame.restyle(row, columns, style));
+    }
+
+    pub fn move_cursor_to(position: ScreenPosition) {
+        Self::with_frame(|frame| frame.set_cursor(Some(position)));
+    }
+
+    pub fn hide_cursor() {
+        Self::with_frame(|frame| frame.set_cursor(None));
+    }
+
+    /// the style of cells without colors of their own and the one of the status line
+    /// changing them redraws everything
+    pub fn set_styles(defaults: Style, status: Style) {
+        SCREEN.with(|screen| {
+            let mut screen = screen.borrow_mut();
+            screen.defaults = defaults;
+            screen.status = status;
+            screen.presented = None;
+        });
+    }
+
+    /// how the colors drawn are written, the frame keeps them as they were drawn
+    /// changing it redraws everything
+    pub fn set_colors(fit: impl Fn(Color) -> Color + 'static) {
+        SCREEN.with(|screen| {
+            let mut screen = screen.borrow_mut();
+            screen.fit = Box::new(fit);
+            screen.presented = None;
+        });
+    }
+
+    /// writes the cells that changed since the last present in a single batch
+    pub fn present() -> Result<(), Error> {
+        let size = backend::size()?;
+        SCREEN.with(|screen| {
+            let mut screen = screen.borrow_mut();
+            if screen.frame.size != size {
+                // the content is stale after a resize, modes redraw on the resize event
+                let cursor = screen.frame.cursor;
+                screen.frame = Frame::new(size);
+                screen.frame.cursor = cursor;
+                screen.presented = None;
+            }
+            let full = screen.presented.is_none();
+            let fit = &screen.fit;
+            let changes: Vec<Change> = screen
+                .frame
+                .diff(screen.presented.as_ref())
+                .into_iter()
+                .map(|change| Change {
+                    style: change.style.fit(fit),
+                    ..change
+                })
+                .collect();
+            let defaults = screen.defaults.fit(fit);
+            backend::draw(&changes, screen.frame.cursor, defaults, full)?;
+            screen.presented = Some(screen.frame.clone());
+            Ok(())
+        })
+    }
+
+    /// the status line on the last row, the theme's status style under the styles of its parts
+    pub fn render_status_line(size: &Size, text: &str, styles: &[(Range<usize>, Style)]) {
+        let row = size.height.saturating_sub(1);
+        Self::render_line(row, text);
+        let status = SCREEN.with(|screen| screen.borrow().status);
+        Self::restyle(row, 0..size.width, status);
+        for (columns, style) in styles {
+            Self::restyle(row, columns.clone(), *style);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(width: usize, height: usize) -> Frame {
+        Frame::new(Size { height, width })
+    }
+
+    fn colored(foreground: Color, background: Color) -> Style {
+        Style {
+            foreground: Some(foreground),
+            background: Some(background),
+            ..Style::default()
+        }
+    }
+
+    #[test]
+    fn put_clips_and_tracks_wide_graphemes() {
+        let mut frame = frame(5, 2);
+        // the text is clipped at the row end
+        assert_eq!(frame.put(0, 0, "ab界cd", Style::default()), 5);
+        assert_eq!(frame.row_text(0), "ab界c");
+        assert!(frame.cell(0, 3).unwrap().grapheme.is_empty());
+        // a wide grapheme that does not fit is left out
+        assert_eq!(frame.put(1, 4, "界", Style::default()), 4);
+    }
+
+    #[test]
+    fn diff_against_nothing_draws_everything_but_blanks() {
+        let mut frame = frame(6, 2);
+        frame.put(1, 2, "hi", Style::default());
+        let changes = frame.diff(None);
+        assert_eq!(
+            changes,
+            vec![Change {
+                position: ScreenPosition {
+                    height: 1,
+                    width: 2
+                },
+                text: "hi".to_string(),
+                style: Style::default(),
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_only_reports_changed_runs() {
+        let mut before = frame(11, 1);
+        before.put(0, 0, "hello world", Style::default());
+        let mut after = before.clone();
+        after.put(0, 6, "wired", Style::default());
+        let changes = after.diff(Some(&before));
+        let texts: Vec<&str> = changes.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec!["i", "e"]);
+        assert_eq!(changes[1].position.width, 9);
+        assert!(after.diff(Some(&after)).is_empty());
+    }
+
+    #[test]
+    fn diff_splits_runs_by_style() {
+        let before = frame(6, 1);
+        let mut after = before.clone();
+        let column = after.put(0, 0, "ab", Style::default());
+        after.put(0, column, "cd", colored(Color::White, Color::Blue));
+        let changes = after.diff(Some(&before));
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[1].text, "cd");
+        assert_eq!(changes[1].position.width, 2);
+    }
+
+    #[test]
+    fn restyle_keeps_the_text_and_the_colors_it_leaves_out() {
+        let mut frame = frame(4, 1);
+        frame.put(0, 0, "abcd", colored(Color::Red, Color::Black));
+        let line = Style {
+            background: Some(Color::Blue),
+            italic: true,
+            ..Style::default()
+        };
+        frame.restyle(0, 1..9, line);
+        assert_eq!(frame.row_text(0), "abcd");
+        assert_eq!(
+            frame.cell(0, 0).unwrap().style.background,
+            Some(Color::Black)
+        );
+        let style = frame.cell(0, 3).unwrap().style;
+        assert_eq!(style.foreground, Some(Color::Red));
+        assert_eq!(style.background, Some(Color::Blue));
+        assert!(style.italic && !style.bold);
+    }
+
+    #[test]
+    fn overwriting_half_a_wide_grapheme_blanks_the_other_half() {
+        let mut frame = frame(4, 1);
+        frame.put(0, 0, "界界", Style::default());
+        frame.put(0, 1, "x", Style::default());
+        assert_eq!(frame.row_text(0), " x界");
+        frame.put(0, 2, "y", Style::default());
+        assert_eq!(frame.row_text(0), " xy ");
+    }
+
+    #[test]
+    fn diff_keeps_wide_graphemes_whole() {
+        let mut before = frame(4, 1);
+        before.put(0, 0, "ab", Style::default());
+        let mut after = before.clone();
+        after.put(0, 0, "界", Style::default());
+        let changes = after.diff(Some(&before));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].text, "界");
+        assert_eq!(changes[0].position.width, 0);
+    }
+}
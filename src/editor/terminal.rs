@@ -1,9 +1,15 @@
+use crate::editor::screen::{Change, Style};
 use crate::editor::view::{PROGRAM_NAME, PROGRAM_VERSION};
 use crossterm::cursor::{Hide, MoveTo, SetCursorStyle, Show};
-use crossterm::style::{Color, Print, SetBackgroundColor, SetForegroundColor};
+use crossterm::event::{DisableFocusChange, EnableFocusChange};
+use crossterm::style::{
+    Attribute, Color, Print, SetAttribute, SetBackgroundColor, SetForegroundColor,
+};
 use crossterm::terminal::{self, disable_raw_mode, enable_raw_mode, size, Clear, ClearType};
-use crossterm::{queue, Command};
-use std::io::{stdout, Error, Write};
+use crossterm::{queue, Command, QueueableCommand};
+use std::fs::{File, OpenOptions};
+use std::io::{stdout, Error, IsTerminal, Write};
+use std::sync::OnceLock;
 
 /// Setting the terminal size and position to usize
 /// This also handles edge cases
@@ -15,7 +21,7 @@ pub struct Size {
     pub width: usize,
 }
 
-#[derive(Copy, Clone, Default, Debug)]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
 pub struct ScreenPosition {
     pub height: usize,
     pub width: usize,
@@ -113,13 +119,6 @@ impl Coordinate for Position {
 }
 
 impl Position {
-    pub fn to_screen_position(self) -> ScreenPosition {
-        ScreenPosition {
-            height: self.height,
-            width: self.width,
-        }
-    }
-
     pub fn diff_height(&self, other: &Position) -> usize {
         if self.height > other.height {
             return self.height.saturating_sub(other.height);
@@ -161,12 +160,13 @@ impl Position {
         size: &Size,
         reserved_lines: usize,
     ) -> usize {
+        let text_width = offset.gutter.text_width(size);
         let width_displacement: usize = if self.width < offset.width {
             offset.width.saturating_sub(self.width)
-        } else if self.width >= offset.width.saturating_add(size.width) {
+        } else if self.width >= offset.width.saturating_add(text_width) {
             self.width
                 .saturating_sub(offset.width)
-                .saturating_add(size.width)
+                .saturating_add(text_width)
         } else {
             0_usize
         };
@@ -190,12 +190,15 @@ impl Position {
     pub fn relative_view_position(&self, offset: &ScreenOffset) -> ScreenPosition {
         ScreenPosition {
             height: self.height.saturating_sub(offset.height),
-            width: self.width.saturating_sub(offset.width),
+            width: self
+                .width
+                .saturating_sub(offset.width)
+                .saturating_add(offset.gutter.width),
         }
     }
 
     pub fn right_of_view(&self, offset: &ScreenOffset, size: &Size) -> bool {
-        self.width > offset.width.saturating_add(size.width)
+        self.width > offset.width.saturating_add(offset.gutter.text_width(size))
     }
 
     pub fn left_of_view(&self, offset: &ScreenOffset) -> bool {
@@ -219,20 +222,103 @@ impl Position {
     }
 }
 
+/// which line numbers are drawn left of the text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineNumbers {
+    #[default]
+    Off,
+    Absolute,
+    Relative,
+    Hybrid, // relative numbers with the cursor line absolute
+}
+
+impl LineNumbers {
+    /// turns absolute numbers on or off, keeping relative numbers as they are
+    pub fn with_number(self, on: bool) -> Self {
+        match (self, on) {
+            (Self::Off, true) => Self::Absolute,
+            (Self::Relative, true) => Self::Hybrid,
+            (Self::Absolute, false) => Self::Off,
+            (Self::Hybrid, false) => Self::Relative,
+            (numbers, _) => numbers,
+        }
+    }
+
+    /// turns relative numbers on or off, keeping absolute numbers as they are
+    pub fn with_relative(self, on: bool) -> Self {
+        match (self, on) {
+            (Self::Off, true) => Self::Relative,
+            (Self::Absolute, true) => Self::Hybrid,
+            (Self::Relative, false) => Self::Off,
+            (Self::Hybrid, false) => Self::Absolute,
+            (numbers, _) => numbers,
+        }
+    }
+
+    pub fn is_relative(self) -> bool {
+        matches!(self, Self::Relative | Self::Hybrid)
+    }
+}
+
+/// the line number column left of the text
+/// the width includes the space seperating the numbers from the text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Gutter {
+    pub numbers: LineNumbers,
+    pub width: usize,
+    pub signs: bool, // a column for diagnostic signs while a language server is attached
+}
+
+impl Gutter {
+    const MIN_DIGITS: usize = 3;
+    const SIGN_WIDTH: usize = 2;
+
+    /// sizes the gutter to fit the largest line number of the buffer
+    pub fn resize(&mut self, buffer_len: usize) {
+        let numbers = if self.numbers == LineNumbers::Off {
+            0
+        } else {
+            let digits = buffer_len.max(1).to_string().len();
+            std::cmp::max(digits, Self::MIN_DIGITS).saturating_add(1)
+        };
+        let signs = if self.signs { Self::SIGN_WIDTH } else { 0 };
+        self.width = numbers.saturating_add(signs);
+    }
+
+    /// the columns left over for the text
+    #[inline]
+    pub fn text_width(&self, size: &Size) -> usize {
+        size.width.saturating_sub(self.width)
+    }
+
+    /// the gutter text for a buffer line, 0 based like the cursor
+    /// the sign column is left blank, signs are drawn over it
+    pub fn label(&self, line: usize, cursor_line: usize) -> String {
+        let signs = if self.signs { Self::SIGN_WIDTH } else { 0 };
+        let digits = self.width.saturating_sub(signs).saturating_sub(1);
+        let distance = line.abs_diff(cursor_line);
+        let number = match self.numbers {
+            LineNumbers::Off => String::new(),
+            LineNumbers::Absolute => format!("{:>digits$} ", line.saturating_add(1)),
+            LineNumbers::Hybrid if distance == 0 => {
+                format!("{:<digits$} ", line.saturating_add(1))
+            }
+            LineNumbers::Relative | LineNumbers::Hybrid => format!("{distance:>digits$} "),
+        };
+        format!("{}{number}", " ".repeat(signs))
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct ScreenOffset {
     pub height: usize,
     pub width: usize,
+    pub gutter: Gutter,
+    pub wrap: bool, // soft wrap long lines instead of scrolling sideways
+    pub row: usize, // when wrapping, the display row of the top line shown first
 }
 
 impl ScreenOffset {
-    pub fn to_position(self) -> ScreenPosition {
-        ScreenPosition {
-            height: self.height,
-            width: self.width,
-        }
-    }
-
     pub fn handle_offset_screen_snap(
         &mut self,
         pos: &Position,
@@ -265,8 +351,9 @@ impl ScreenOffset {
             self.snap_left();
         }
 
-        if pos.width >= size.width.saturating_add(self.width) {
-            self.width = pos.width.saturating_sub(size.width).saturating_add(1);
+        let text_width = self.gutter.text_width(size);
+        if pos.width >= text_width.saturating_add(self.width) {
+            self.width = pos.width.saturating_sub(text_width).saturating_add(1);
         } else if pos.width < self.width {
             self.width = pos.width;
         }
@@ -346,6 +433,10 @@ pub enum Mode {
     Vim,
     Search,
     Highlight,
+    Explorer,
+    Terminal,
+    Scrollback,
+    List,
 }
 
 impl Mode {
@@ -355,22 +446,48 @@ impl Mode {
             Self::Vim => "Vim",
             Self::Search => "Search",
             Self::Highlight => "Highlight",
+            Self::Explorer => "Explorer",
+            Self::Terminal => "Terminal",
+            Self::Scrollback => "Scrollback",
+            Self::List => "List",
         }
     }
 }
 
+/// the terminal itself when stdout is piped, so the editor can sit in the middle of a pipe
+static TTY: OnceLock<Option<File>> = OnceLock::new();
+
+fn open_tty() -> Option<File> {
+    if stdout().is_terminal() {
+        return None;
+    }
+    let path = if cfg!(windows) { "CONOUT$" } else { "/dev/tty" };
+    OpenOptions::new().write(true).open(path).ok()
+}
+
+/// runs a write against whatever the screen is drawn on
+fn with_output<T>(write: impl FnOnce(&mut dyn Write) -> Result<T, Error>) -> Result<T, Error> {
+    match TTY.get_or_init(open_tty) {
+        Some(tty) => write(&mut &*tty),
+        None => write(&mut stdout()),
+    }
+}
+
 pub struct Terminal;
 
 impl Terminal {
     pub fn initialize() -> Result<(), Error> {
         enable_raw_mode()?;
         Self::enter_alternate_screen()?;
+        // focus events tell the editor to look for changes made by other programs
+        Self::queue_command(EnableFocusChange)?;
         Self::clear_screen()?;
         Self::execute()?;
         Ok(())
     }
 
     pub fn terminate() -> Result<(), Error> {
+        Self::queue_command(DisableFocusChange)?;
         Self::leave_alternate_screen()?;
         Self::show_cursor()?;
         Self::set_cursor_style(SetCursorStyle::DefaultUserShape)?;
@@ -384,31 +501,11 @@ impl Terminal {
         Ok(())
     }
 
-    pub fn set_background_color(color: Color) -> Result<(), Error> {
-        Self::queue_command(SetBackgroundColor(color))?;
-        Ok(())
-    }
-
-    pub fn set_foreground_color(color: Color) -> Result<(), Error> {
-        Self::queue_command(SetForegroundColor(color))?;
-        Ok(())
-    }
-
     pub fn clear_screen() -> Result<(), Error> {
         Self::queue_command(Clear(ClearType::All))?;
         Ok(())
     }
 
-    pub fn clear_line() -> Result<(), Error> {
-        Self::queue_command(Clear(ClearType::CurrentLine))?;
-        Ok(())
-    }
-    pub fn move_cursor_to(position: ScreenPosition) -> Result<(), Error> {
-        #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
-        Self::queue_command(MoveTo(position.width as u16, position.height as u16))?;
-        Ok(())
-    }
-
     ///Returns the size of the terminal
     ///When usize < u16, defaults to usize
     pub fn size() -> Result<Size, Error> {
@@ -420,29 +517,81 @@ impl Terminal {
         })
     }
 
-    pub fn render_line<T: std::fmt::Display>(row: usize, line: T) -> Result<(), Error> {
-        Terminal::move_cursor_to(ScreenPosition {
-            width: 0,
-            height: row,
-        })?;
-        Terminal::clear_line()?;
-        Terminal::print(line)?;
-        Ok(())
-    }
-
     pub fn print<T: std::fmt::Display>(output: T) -> Result<(), Error> {
         Self::queue_command(Print(output))?;
         Ok(())
     }
 
-    pub fn execute() -> Result<(), Error> {
-        stdout().flush()?;
-        Ok(())
+    /// writes the changed runs of a frame in one batch and places the cursor
+    /// a full draw clears the screen first, the changes then cover everything not blank
+    pub fn draw(
+        changes: &[Change],
+        cursor: Option<ScreenPosition>,
+        defaults: Style,
+        full: bool,
+    ) -> Result<(), Error> {
+        let color = |color: Option<Color>, default: Option<Color>| {
+            color.or(default).unwrap_or(Color::Reset)
+        };
+        let mut batch: Vec<u8> = Vec::new();
+        queue!(batch, Hide)?;
+        if full {
+            queue!(
+                batch,
+                SetForegroundColor(color(None, defaults.foreground)),
+                SetBackgroundColor(color(None, defaults.background)),
+                Clear(ClearType::All)
+            )?;
+        }
+        for change in changes {
+            let style = change.style;
+            #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+            let position = MoveTo(change.position.width as u16, change.position.height as u16);
+            queue!(
+                batch,
+                position,
+                SetForegroundColor(color(style.foreground, defaults.foreground)),
+                SetBackgroundColor(color(style.background, defaults.background)),
+                SetAttribute(if style.bold {
+                    Attribute::Bold
+                } else {
+                    Attribute::NormalIntensity
+                }),
+                SetAttribute(if style.italic {
+                    Attribute::Italic
+                } else {
+                    Attribute::NoItalic
+                }),
+                SetAttribute(if style.underline {
+                    Attribute::Underlined
+                } else {
+                    Attribute::NoUnderline
+                }),
+                Print(&change.text)
+            )?;
+        }
+        queue!(
+            batch,
+            SetAttribute(Attribute::Reset),
+            SetForegroundColor(color(None, defaults.foreground)),
+            SetBackgroundColor(color(None, defaults.background))
+        )?;
+        if let Some(position) = cursor {
+            #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+            queue!(
+                batch,
+                MoveTo(position.width as u16, position.height as u16),
+                Show
+            )?;
+        }
+        with_output(|out| {
+            out.write_all(&batch)?;
+            out.flush()
+        })
     }
 
-    pub fn hide_cursor() -> Result<(), Error> {
-        Self::queue_command(Hide)?;
-        Ok(())
+    pub fn execute() -> Result<(), Error> {
+        with_output(|out| out.flush())
     }
 
     pub fn show_cursor() -> Result<(), Error> {
@@ -451,8 +600,7 @@ impl Terminal {
     }
 
     pub fn queue_command<T: Command>(command: T) -> Result<(), Error> {
-        queue!(stdout(), command)?;
-        Ok(())
+        with_output(|out| out.queue(command).map(|_| ()))
     }
 
     fn enter_alternate_screen() -> Result<(), Error> {
@@ -465,32 +613,6 @@ impl Terminal {
         Ok(())
     }
 
-    #[inline]
-    pub fn render_status_line(
-        mode: &Mode,
-        saved: bool,
-        size: &Size,
-        filename: Option<&str>,
-        line_pos: Option<(usize, usize)>,
-    ) -> Result<(), Error> {
-        let saved = if saved { "saved" } else { "modified" };
-        let filename = filename.unwrap_or("-");
-        let mut render_message = if let Some((line, len)) = line_pos {
-            format!(
-                "Mode: {} | Filename: {filename} | Status: {saved} | Line: {line} / {len}",
-                mode.to_string()
-            )
-        } else {
-            format!(
-                "Mode: {} | Filename: {filename} | Status: {saved} | Line: -",
-                mode.to_string()
-            )
-        };
-        render_message.truncate(size.width);
-        Self::render_line(size.height.saturating_sub(1), render_message)?;
-        Ok(())
-    }
-
     #[inline]
     pub fn get_welcome_message(size: &Size, screen_offset: &ScreenOffset) -> String {
         let mut welcome_message = format!("{PROGRAM_NAME} editor -- version {PROGRAM_VERSION}");
@@ -529,6 +651,7 @@ mod test {
         let offset1 = ScreenOffset {
             height: 1,
             width: 1,
+            ..Default::default()
         };
         let pos1 = Position {
             height: 12,
@@ -549,6 +672,7 @@ mod test {
         let offset1 = ScreenOffset {
             height: 1,
             width: 1,
+            ..Default::default()
         };
         let pos1 = Position {
             height: 0,
@@ -570,6 +694,7 @@ mod test {
         let offset1 = ScreenOffset {
             height: 2,
             width: 2,
+            ..Default::default()
         };
         let pos1 = Position {
             height: 0,
@@ -591,6 +716,7 @@ mod test {
         let offset1 = ScreenOffset {
             height: 1,
             width: 0,
+            ..Default::default()
         };
         let pos1 = Position {
             height: 0,
@@ -612,6 +738,7 @@ mod test {
         let offset1 = ScreenOffset {
             height: 1,
             width: 2,
+            ..Default::default()
         };
         let pos1 = Position {
             height: 0,
@@ -632,6 +759,7 @@ mod test {
         let offset1 = ScreenOffset {
             height: 9,
             width: 8,
+            ..Default::default()
         };
         let pos1 = Position {
             height: 0,
@@ -641,4 +769,78 @@ mod test {
 
         assert_eq!(pos1.max_displacement_from_view(&offset1, &size1, 1), 9);
     }
+
+    #[test]
+    fn test_gutter_shifts_cursor() {
+        let mut offset = ScreenOffset::default();
+        offset.gutter.numbers = LineNumbers::Absolute;
+        offset.gutter.resize(12_345);
+        assert_eq!(offset.gutter.width, 6);
+
+        let pos = Position {
+            height: 3,
+            width: 2,
+            max_width: usize::default(),
+        };
+        assert_eq!(pos.relative_view_position(&offset).width, 8);
+    }
+
+    #[test]
+    fn test_gutter_narrows_view() {
+        let size = Size {
+            height: 20,
+            width: 20,
+        };
+        let mut offset = ScreenOffset::default();
+        offset.gutter.numbers = LineNumbers::Relative;
+        offset.gutter.resize(10);
+        let pos = Position {
+            height: 0,
+            width: 17,
+            max_width: usize::default(),
+        };
+        // 16 columns are left for the text
+        assert!(pos.max_displacement_from_view(&offset, &size, 1) > 0);
+        assert!(pos.right_of_view(&offset, &size));
+    }
+
+    #[test]
+    fn test_gutter_labels() {
+        let mut gutter = Gutter {
+            numbers: LineNumbers::Absolute,
+            width: 0,
+            signs: false,
+        };
+        gutter.resize(100);
+        assert_eq!(gutter.label(4, 9), "  5 ");
+
+        gutter.numbers = LineNumbers::Relative;
+        assert_eq!(gutter.label(4, 9), "  5 ");
+        assert_eq!(gutter.label(9, 9), "  0 ");
+
+        gutter.numbers = LineNumbers::Hybrid;
+        assert_eq!(gutter.label(9, 9), "10  ");
+        assert_eq!(gutter.label(12, 9), "  3 ");
+
+        gutter.numbers = LineNumbers::Off;
+        gutter.resize(100);
+        assert_eq!(gutter.width, 0);
+        assert_eq!(gutter.label(1, 1), "");
+
+        gutter.signs = true;
+        gutter.resize(100);
+        assert_eq!(gutter.label(1, 1), "  ");
+        gutter.numbers = LineNumbers::Absolute;
+        gutter.resize(100);
+        assert_eq!(gutter.label(4, 9), "    5 ");
+    }
+
+    #[test]
+    fn test_line_number_toggles() {
+        let numbers = LineNumbers::Off.with_number(true).with_relative(true);
+        assert_eq!(numbers, LineNumbers::Hybrid);
+        assert_eq!(numbers.with_number(false), LineNumbers::Relative);
+        assert_eq!(numbers.with_relative(false), LineNumbers::Absolute);
+        assert_eq!(LineNumbers::Relative.with_relative(false), LineNumbers::Off);
+    }
 }
@@ -1,31 +1,55 @@
+use super::cli::{Args, StartPosition};
 use super::editorcommands::{
-    parse_highlight_normal_mode, Direction, EditorCommand, FileNameCommand, JumpCommand,
+    parse_highlight_normal_mode, AddCursor, Direction, EditorCommand, MenuCommand, PickerCommand,
+    PromptCommand,
 };
+use super::screen::Screen;
 use super::terminal::{Coordinate, Mode, Position, ScreenOffset, ScreenPosition, Size, Terminal};
-use crossterm::event::read;
-use std::{error::Error, path::Path};
+use crate::editor::backend::{self, read, Idle};
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use std::{error::Error, path::Path, time::Duration};
 pub mod buffer;
 use buffer::Buffer;
 pub mod line;
-mod theme;
-use theme::Theme;
+use line::Line;
+pub mod theme;
+use theme::{Picker, PickerAction, Theme};
 mod search;
 use search::Search;
 pub mod help;
 use help::Help;
 mod highlight;
 use highlight::Highlight;
+mod lsp;
+mod messages;
 mod vim_mode;
 use vim_mode::VimMode;
+mod complete;
+use complete::Menu;
 mod clipboard_interface;
 use clipboard_interface::ClipboardUtils;
-
-enum ScreenUpdateType {
-    FullScreen,
-    MultiLineRender,
-    SingleLineRender,
-    DefaultAction,
-}
+mod choice;
+use choice::{choose, Texts};
+mod cursors;
+use cursors::Cursors;
+mod disk;
+use disk::Resolution;
+mod explorer;
+use explorer::{Exit, Explorer};
+mod filetype;
+mod fold;
+mod pairs;
+use pairs::AutoPairs;
+mod prompt;
+use prompt::{Completion, HistoryKind, Prompt, PromptAction};
+mod shell;
+mod status;
+pub mod swap;
+mod syntax;
+use status::{Status, StatusLine};
+mod terminal_pane;
+use terminal_pane::TerminalPane;
+mod wrap;
 
 pub const PROGRAM_NAME: &str = env!("CARGO_PKG_NAME");
 pub const PROGRAM_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -43,95 +67,180 @@ pub struct View {
     pub screen_offset: ScreenOffset,
     pub theme: Theme,
     pub buffer: Buffer,
+    pub exit_code: u8, // 0 unless the session was abandoned, see :cq
+    pane: Option<TerminalPane>,
+    completion: Option<Menu>,
+    pairs: AutoPairs,
+    cursors: Cursors, // the ones besides cursor_position, edits happen at all of them
 }
 
 impl Default for View {
     fn default() -> Self {
         Self {
             buffer: Buffer::default(),
-            size: Terminal::size().unwrap_or_default(),
+            size: backend::size().unwrap_or_default(),
             cursor_position: Position::default(),
             screen_offset: ScreenOffset::default(),
-            theme: Theme::default(),
+            theme: Theme::configured(),
+            exit_code: 0,
+            pane: None,
+            completion: None,
+            pairs: AutoPairs::default(),
+            cursors: Cursors::default(),
         }
     }
 }
 
 impl View {
     pub fn start(&self) -> Result<(), Box<dyn Error>> {
-        self.full_screen_render()?;
-        self.set_cursor_and_status()?;
-        Terminal::execute()?;
+        self.theme.apply()?;
+        StatusLine::configured().install();
+        self.draw()
+    }
+
+    /// draws the whole view into the frame, only changed cells reach the terminal
+    fn draw(&self) -> Result<(), Box<dyn Error>> {
+        self.compose();
+        Screen::present()?;
         Ok(())
     }
 
-    pub fn render(&self, full_screen: bool) {
-        let start = if full_screen {
-            self.screen_offset.height
-        } else {
-            self.cursor_position.height.saturating_sub(1)
+    /// draws the whole view into the frame without showing it
+    fn compose(&self) {
+        self.render();
+        self.draw_cursors();
+        Status::of(Mode::Insert, &self.buffer, &self.cursor_position).render(&self.size);
+        messages::draw(&self.size, &self.theme);
+        if let Some(menu) = &self.completion {
+            let anchor = wrap::screen_position(
+                &menu.anchor(),
+                &self.screen_offset,
+                &self.buffer,
+                &self.size,
+            );
+            menu.draw(&self.buffer, anchor, &self.size, &self.theme);
+        }
+        let cursor = match &self.pane {
+            Some(pane) => {
+                pane.render(self.size.height);
+                if pane.focused {
+                    pane.cursor(self.size.height)
+                } else {
+                    Some(self.cursor_screen_position())
+                }
+            }
+            None => Some(self.cursor_screen_position()),
         };
-        #[allow(clippy::integer_division)]
-        for current_row in start
-            ..self
-                .screen_offset
-                .height
-                .saturating_add(self.size.height)
-                .saturating_sub(1)
-        {
-            let relative_row = current_row.saturating_sub(self.screen_offset.height);
+        match cursor {
+            Some(position) => Screen::move_cursor_to(position),
+            None => Screen::hide_cursor(),
+        }
+    }
 
-            if let Some(line) = self.buffer.text.get(current_row) {
-                Self::render_line(
-                    relative_row,
-                    line.get_line_subset(
-                        self.screen_offset.width
-                            ..self.screen_offset.width.saturating_add(self.size.width),
-                    ),
-                );
+    pub fn render(&self) {
+        if self.screen_offset.wrap && !self.buffer.is_empty() {
+            // a wrapped line can push every row below it, so always draw them all
+            let rows = wrap::screen_rows(
+                &self.buffer,
+                &self.screen_offset,
+                &self.size,
+                self.cursor_position.height,
+                self.size.height.saturating_sub(1),
+            );
+            for (row, text) in rows.iter().enumerate() {
+                Screen::render_line(row, text);
+            }
+        } else {
+            self.render_rows();
+        }
+        self.theme.paint(
+            &self.buffer,
+            &self.screen_offset,
+            &self.size,
+            self.cursor_position.height,
+        );
+        lsp::ui::draw_signs(&self.buffer, &self.screen_offset, &self.size, &self.theme);
+    }
+
+    fn render_rows(&self) {
+        // a closed fold takes one row, the next row shows the line after it
+        let mut current_row = self.screen_offset.height;
+        #[allow(clippy::integer_division)]
+        for relative_row in 0..self.size.height.saturating_sub(1) {
+            if self.buffer.text.get(current_row).is_some() {
+                Screen::render_line(relative_row, self.numbered_line(current_row));
             } else if self.buffer.is_empty() & (current_row == self.size.height / 3) {
-                Self::render_line(
+                Screen::render_line(
                     relative_row,
                     Terminal::get_welcome_message(&self.size, &self.screen_offset),
                 );
             } else {
-                Self::render_line(relative_row, "~");
+                Screen::render_line(relative_row, "~");
             }
+            current_row = self.buffer.folds.next_visible(current_row);
         }
     }
 
-    #[inline] // this should be very hot
-    fn evaluate_view_state_change(&mut self) -> ScreenUpdateType {
-        let view_delta = self.check_offset();
-        if view_delta == 0 {
-            ScreenUpdateType::SingleLineRender
-        } else {
-            ScreenUpdateType::FullScreen
+    /// the cursors besides the primary one, each a highlighted cell
+    fn draw_cursors(&self) {
+        let style = self.theme.selection;
+        let offset = &self.screen_offset;
+        let text_width = offset.gutter.text_width(&self.size);
+        for pos in self.cursors.iter() {
+            let outside = pos.height < offset.height
+                || self.buffer.folds.hidden(pos.height)
+                || (!offset.wrap
+                    && (pos.width < offset.width
+                        || pos.width >= offset.width.saturating_add(text_width)));
+            if outside {
+                continue;
+            }
+            let at = wrap::screen_position(pos, offset, &self.buffer, &self.size);
+            if at.height >= self.size.height.saturating_sub(1) {
+                continue;
+            }
+            let grapheme = self
+                .buffer
+                .text
+                .get(pos.height)
+                .and_then(|line| line.string.get(pos.width))
+                .map_or(" ", |fragment| fragment.grapheme.as_str());
+            Screen::print_at(at, grapheme, style);
         }
     }
 
-    fn full_screen_render(&self) -> Result<(), Box<dyn Error>> {
-        Terminal::hide_cursor()?;
-        Terminal::move_cursor_to(self.screen_offset.to_position())?;
-        Terminal::clear_screen()?;
-        self.render(true);
-        Terminal::move_cursor_to(
-            self.cursor_position
-                .relative_view_position(&self.screen_offset),
-        )?;
-        Terminal::show_cursor()?;
-        Terminal::execute()?;
-        Ok(())
+    #[inline]
+    fn cursor_screen_position(&self) -> ScreenPosition {
+        wrap::screen_position(
+            &self.cursor_position,
+            &self.screen_offset,
+            &self.buffer,
+            &self.size,
+        )
     }
 
+    /// the visible part of a buffer line after its gutter label
     #[inline]
-    fn render_line<T: std::fmt::Display>(row: usize, line: T) {
-        let result = Terminal::render_line(row, line);
-        debug_assert!(result.is_ok(), "Failed to render line");
+    fn numbered_line(&self, line: usize) -> String {
+        let gutter = &self.screen_offset.gutter;
+        let start = self.screen_offset.width;
+        let text = &self.buffer.text[line];
+        format!(
+            "{}{}",
+            gutter.label(line, self.cursor_position.height),
+            self.buffer.folds.summary(line, text).unwrap_or_else(|| {
+                text.get_line_subset(start..start.saturating_add(gutter.text_width(&self.size)))
+                    .to_string()
+            })
+        )
     }
 
     fn resize(&mut self, size: Size) {
         self.size = size;
+        if self.screen_offset.wrap {
+            self.check_offset();
+            return;
+        }
         self.screen_offset.handle_offset_screen_snap(
             &self.cursor_position,
             &self.size,
@@ -145,20 +254,82 @@ impl View {
         if path.is_dir() {
             return Err(format!("{filename} is a directory").into());
         }
-        if let Ok(buffer) = Buffer::load(filename) {
-            self.buffer = buffer;
-        } else {
-            self.buffer = Buffer::load_named_empty(filename, self.size.height);
-        }
+        self.buffer = Buffer::open(filename, self.size.height);
+        // a server that is missing or fails to start leaves the editor without one, see :lsp
+        let _ = self.buffer.attach_server(None);
 
         Ok(())
     }
 
+    /// puts the cursor where the command line asked and runs its -c commands
+    /// false when one of them ended the session
+    pub fn startup(&mut self, args: &Args) -> Result<bool, String> {
+        match &args.position {
+            Some(StartPosition::Line(line, column)) => {
+                self.cursor_position.height = if *line == 0 {
+                    self.buffer.len()
+                } else {
+                    line.saturating_sub(1)
+                };
+                self.cursor_position.width = column.unwrap_or(1).saturating_sub(1);
+            }
+            Some(StartPosition::Search(pattern)) => {
+                // a pattern that is not found leaves the cursor at the top, like vim
+                if let Some(found) = self.buffer.search(pattern).first() {
+                    self.cursor_position = *found;
+                }
+            }
+            None => {}
+        }
+        self.clamp_cursor();
+        let mut keep_going = true;
+        if !args.commands.is_empty() {
+            let mut vim_mode = VimMode::new(
+                self.cursor_position,
+                self.screen_offset,
+                self.size,
+                &mut self.buffer,
+                self.theme.clone(),
+            );
+            keep_going = vim_mode.run_commands(
+                &args.commands,
+                &mut self.cursor_position,
+                &mut self.screen_offset,
+                &mut self.size,
+            )?;
+            self.exit_code = vim_mode.exit_code;
+            self.theme = vim_mode.theme;
+        }
+        self.check_offset();
+        Ok(keep_going)
+    }
+
+    /// browses a directory and loads the file picked there, false when none was
+    pub fn explore(&mut self, dir: &str) -> Result<bool, std::io::Error> {
+        match Explorer::new(Path::new(dir), self.theme.selection)?.run(&mut self.size)? {
+            Exit::Open(path) => {
+                if let Err(err) = self.load(&path.to_string_lossy()) {
+                    messages::error(err.to_string());
+                }
+                Ok(true)
+            }
+            Exit::Quit => Ok(false),
+        }
+    }
+
     // inlining because it is a rather straight forward computation
     #[inline]
     fn move_cursor(&mut self, key_code: Direction) {
         if self.buffer.is_empty() {
             self.cursor_position = ORIGIN_POSITION;
+        } else if self.screen_offset.wrap && matches!(key_code, Direction::Up | Direction::Down) {
+            // moving by display rows, like gj and gk
+            wrap::move_display_row(
+                &mut self.cursor_position,
+                &self.buffer,
+                self.screen_offset.gutter.text_width(&self.size),
+                matches!(key_code, Direction::Down),
+            );
         } else {
             key_code.move_cursor(&mut self.cursor_position, &self.buffer);
         }
@@ -167,14 +338,38 @@ impl View {
     #[inline]
     fn insert_char(&mut self, insert_char: char) {
         self.buffer
-            .update_line_insert(&mut self.cursor_position, insert_char);
+            .dedent_for(&mut self.cursor_position, insert_char);
+        if !self
+            .pairs
+            .insert(&mut self.buffer, &mut self.cursor_position, insert_char)
+        {
+            self.buffer
+                .update_line_insert(&mut self.cursor_position, insert_char);
+        }
 
         self.buffer.is_saved = false;
     }
 
+    /// comments the cursor line out or back in, the cursor stays with its text
+    fn toggle_comment(&mut self) {
+        let line = self.cursor_position.height;
+        let Some(before) = self.buffer.text.get(line).map(Line::grapheme_len) else {
+            return;
+        };
+        if self.buffer.toggle_comment(line..=line) {
+            let after = self.buffer.text[line].grapheme_len();
+            self.cursor_position.width = std::cmp::min(
+                self.cursor_position
+                    .width
+                    .saturating_add(after)
+                    .saturating_sub(before),
+                after,
+            );
+        }
+    }
+
     fn insert_tab(&mut self) {
-        self.buffer.insert_tab(&self.cursor_position, 1);
-        self.cursor_position.width = self.cursor_position.width.saturating_add(4);
+        self.cursor_position.width = self.buffer.insert_tab(&self.cursor_position, 1);
     }
 
     #[inline]
@@ -183,121 +378,322 @@ impl View {
         self.buffer.update_line_delete(&mut self.cursor_position);
     }
 
-    pub fn get_file_name(&mut self) {
+    fn get_file_name(&mut self, label: &'static str) {
         // clear_screen and render screen to get file name
-        let mut filename_buffer = String::new();
-        let mut curr_position: usize = 10;
-        Self::render_filename_screen(&filename_buffer, curr_position);
+        let mut prompt = Prompt::new(label, Completion::Files).with_history(HistoryKind::FileName);
         loop {
+            if let Err(err) = Self::render_filename_screen(&prompt, &self.size) {
+                messages::draw_failed(Err(err));
+                return;
+            }
             let Ok(read_event) = read() else { continue };
+            let Ok(command) = PromptCommand::try_from(read_event) else {
+                continue;
+            };
 
-            match FileNameCommand::try_from(read_event) {
-                Ok(event) => match event {
-                    FileNameCommand::Insert(c) => {
-                        filename_buffer.push(c);
-                        curr_position = curr_position.saturating_add(1);
+            match prompt.apply(command) {
+                PromptAction::Submit(filename) => {
+                    if !filename.is_empty() {
+                        self.buffer.assume_file_name(filename);
                     }
-                    FileNameCommand::BackSpace => {
-                        filename_buffer.pop();
-                        curr_position = std::cmp::max(10, curr_position.saturating_sub(1));
-                    }
-                    FileNameCommand::SaveFileName => break,
-                    FileNameCommand::NoAction => continue,
-                    FileNameCommand::Quit => return,
-                },
-                _ => continue,
+                    return;
+                }
+                PromptAction::Cancel => return,
+                PromptAction::Resize(size) => self.size = size,
+                PromptAction::Continue => {}
             }
+        }
+    }
 
-            Self::render_filename_screen(&filename_buffer, curr_position);
+    /// offers the changes a crashed session left in the swap file of the loaded file
+    pub fn offer_recovery(&mut self) -> Result<(), std::io::Error> {
+        let Some(filename) = self.buffer.filename.clone() else {
+            return Ok(());
+        };
+        let Some(recovery) = swap::find(&filename) else {
+            return Ok(());
+        };
+        let mut size = self.size;
+        let key = choose(
+            &mut size,
+            &[
+                format!("Found a swap file for {filename}"),
+                "It holds changes that were never saved".to_string(),
+            ],
+            "r = recover | d = diff | x = discard",
+            &['r', 'x'],
+            &Texts {
+                old: &recovery.file_text,
+                new: &recovery.text,
+            },
+        )?;
+        self.resize(size);
+        if key == 'r' {
+            self.buffer.text = recovery.text.lines().map(Line::from).collect();
+            self.buffer.is_saved = false;
+            self.buffer.swap.adopt(&recovery.text);
+        } else {
+            self.buffer.discard_swap();
         }
+        Ok(())
+    }
 
-        self.buffer.assume_file_name(filename_buffer);
+    /// how long to wait for a key before calling idle
+    pub fn idle_interval(&self) -> Duration {
+        if self.pane.is_some() {
+            terminal_pane::TICK
+        } else if self.buffer.lsp.is_some() {
+            lsp::TICK
+        } else if messages::showing() {
+            messages::TICK
+        } else {
+            swap::IDLE
+        }
     }
 
-    fn render_filename_screen(curr_filename: &str, curr_position: usize) {
-        Terminal::hide_cursor().unwrap();
-        Terminal::move_cursor_to(ScreenPosition {
-            height: 0,
-            width: 0,
-        })
-        .unwrap();
-        Terminal::clear_screen().unwrap();
-        Self::render_line(0, format!("Filename: {}", &curr_filename));
-        Terminal::move_cursor_to(ScreenPosition {
-            height: 0,
-            width: curr_position,
-        })
-        .unwrap();
-        Terminal::show_cursor().unwrap();
-        Terminal::execute().unwrap();
+    /// called while no key is pressed, once the idle time passed the swap file is written
+    /// with a terminal pane or a language server it is called every tick to show what they say,
+    /// the file on disk is still only checked every idle time
+    pub fn idle(&mut self, idle: Idle) {
+        let mut redraw = self.update_pane();
+        redraw |= self.buffer.update_lsp();
+        redraw |= messages::expired();
+        if idle.reached(swap::IDLE) {
+            self.buffer.flush_swap(true);
+        }
+        if idle.every(swap::IDLE) {
+            redraw |= self.check_disk();
+        }
+        if redraw {
+            self.check_offset();
+            // nothing waits on the idle work to hear about it, so the message line does
+            messages::draw_failed(self.draw());
+        }
     }
 
-    fn set_cursor_and_status(&self) -> Result<(), Box<dyn Error>> {
-        Terminal::render_status_line(
-            &Mode::Insert,
-            self.buffer.is_saved,
-            &self.size,
-            self.buffer.filename.as_deref(),
-            Some((
-                self.cursor_position.height.saturating_add(1),
-                std::cmp::max(self.buffer.len(), 1),
-            )),
-        )?;
-        Terminal::move_cursor_to(
-            self.cursor_position
-                .relative_view_position(&self.screen_offset),
-        )?;
-        Terminal::show_cursor()?;
+    /// opens a terminal pane below the text or gives it the keys
+    /// Ctrl-o hands them back to the text, the pane stays until its shell exits
+    fn enter_terminal(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.pane.is_none() {
+            match TerminalPane::open(self.size) {
+                Ok(pane) => self.pane = Some(pane),
+                Err(err) => {
+                    messages::error(format!("Cannot open a terminal: {err}"));
+                    return Ok(());
+                }
+            }
+        }
+        self.focus_pane(true);
+        loop {
+            self.fit_pane();
+            self.check_offset();
+            self.draw()?;
+            let Ok(event) = backend::read_or_idle(terminal_pane::TICK, |idle| self.idle(idle))
+            else {
+                continue;
+            };
+            let Some(pane) = &mut self.pane else {
+                return Ok(());
+            };
+            let resized = match event {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    match (key.code, key.modifiers) {
+                        (KeyCode::Char('o'), KeyModifiers::CONTROL) => break,
+                        (KeyCode::Char('n'), KeyModifiers::CONTROL) => {
+                            pane.scrollback(self.size.height, self.theme.selection)?
+                        }
+                        _ => {
+                            pane.send(key);
+                            None
+                        }
+                    }
+                }
+                #[allow(clippy::as_conversions)]
+                Event::Resize(width, height) => Some(Size {
+                    height: height as usize,
+                    width: width as usize,
+                }),
+                _ => None,
+            };
+            if let Some(size) = resized {
+                self.resize(size);
+            }
+            // the shell may have exited on the key
+            self.update_pane();
+            if self.pane.is_none() {
+                return Ok(());
+            }
+        }
+        self.focus_pane(false);
         Ok(())
     }
 
-    fn save(&mut self) -> Result<(), Box<dyn Error>> {
-        // no need to render here
+    fn focus_pane(&mut self, focused: bool) {
+        if let Some(pane) = &mut self.pane {
+            pane.focused = focused;
+        }
+    }
+
+    /// gives the bottom of the screen to the terminal pane while it is open
+    fn fit_pane(&mut self) {
+        if let Some(pane) = &mut self.pane {
+            let screen = backend::size().unwrap_or(Size {
+                height: self.size.height.saturating_add(pane.height()),
+                width: self.size.width,
+            });
+            self.size = pane.fit(screen);
+        }
+    }
+
+    /// takes in the shell output, the text gets the whole screen back once the shell has exited
+    /// returns whether the screen needs drawing again
+    fn update_pane(&mut self) -> bool {
+        let Some(pane) = &mut self.pane else {
+            return false;
+        };
+        let changed = pane.update();
+        if pane.exited() {
+            self.size.height = self.size.height.saturating_add(pane.height());
+            self.pane = None;
+        }
+        changed
+    }
+
+    /// looks for changes to the file by other programs
+    /// returns whether the screen needs drawing again
+    fn check_disk(&mut self) -> bool {
+        let mut size = self.size;
+        let resolution = disk::resolve(&mut self.buffer, &mut size);
+        self.resize(size);
+        match resolution {
+            Ok(Resolution::Unchanged) => false,
+            Ok(Resolution::Reloaded) => {
+                self.clamp_cursor();
+                true
+            }
+            Ok(Resolution::Kept) | Err(_) => true,
+        }
+    }
+
+    /// keeps the cursor inside a buffer whose text was replaced
+    fn clamp_cursor(&mut self) {
+        let last = self.buffer.len().saturating_sub(1);
+        self.cursor_position.height = std::cmp::min(self.cursor_position.height, last);
+        let width = self
+            .buffer
+            .text
+            .get(self.cursor_position.height)
+            .map_or(0, Line::len);
+        self.cursor_position.width = std::cmp::min(self.cursor_position.width, width);
+        self.cursor_position.max_width = self.cursor_position.width;
+        self.cursors.clamp(&self.buffer);
+    }
+
+    fn render_filename_screen(prompt: &Prompt, size: &Size) -> Result<(), std::io::Error> {
+        Screen::clear();
+        prompt.render(0, size.width);
+        Screen::present()
+    }
+
+    pub fn save(&mut self) {
+        if self.buffer.read_only {
+            // a read only file is kept as it is, the changes go to a new name
+            let filename = self.buffer.filename.take();
+            self.get_file_name("Read only, save as: ");
+            if self.buffer.filename.is_none() {
+                self.buffer.filename = filename;
+                return;
+            }
+            self.buffer.read_only = false;
+            self.buffer.stamp = None;
+        }
         if self.buffer.filename.is_none() {
-            self.get_file_name();
+            self.get_file_name("Filename: ");
         }
-        self.buffer.save();
-        // onyl status line needs to change
-        Terminal::render_status_line(
-            &Mode::Insert,
-            self.buffer.is_saved,
-            &self.size,
-            self.buffer.filename.as_deref(),
-            Some((
-                self.cursor_position.height.saturating_add(1),
-                self.buffer.len(),
+        if self.buffer.filename.is_none() {
+            // no name was given
+            return;
+        }
+        // writing over changes made by another program needs the user to keep this version
+        let mut size = self.size;
+        let resolution = disk::resolve(&mut self.buffer, &mut size);
+        self.resize(size);
+        if matches!(resolution, Ok(Resolution::Reloaded)) {
+            self.clamp_cursor();
+            return;
+        }
+        match self.buffer.save() {
+            Ok(()) => messages::info(format!(
+                "\"{}\" written",
+                self.buffer.filename.as_deref().unwrap_or_default()
             )),
-        )?;
-        Terminal::move_cursor_to(
-            self.cursor_position
-                .relative_view_position(&self.screen_offset),
-        )?;
-        Ok(())
+            Err(err) => messages::error(format!("E212: Can't open file for writing: {err}")),
+        }
     }
 
     fn paste_text(&mut self) -> Option<bool> {
         // render always
-        let Ok(paste_text) = ClipboardUtils::get_text_from_clipboard() else {
-            return Some(true); // handling an error here
+        let paste_text = match ClipboardUtils::get_text_from_clipboard() {
+            Ok(paste_text) => paste_text,
+            Err(err) => {
+                messages::error(format!("Clipboard: {err}"));
+                return Some(true);
+            }
         };
-        self.buffer
-            .add_text_from_clipboard(&paste_text, &mut self.cursor_position);
+        self.at_cursors(|view| {
+            view.buffer
+                .add_text_from_clipboard(&paste_text, &mut view.cursor_position);
+        });
         None
     }
 
+    /// runs an edit or a motion at every cursor, the primary one last if it is first in the
+    /// buffer, cursors that meet become one
+    fn at_cursors(&mut self, mut edit: impl FnMut(&mut Self)) {
+        if self.cursors.is_empty() {
+            edit(self);
+            return;
+        }
+        self.cursors.clamp(&self.buffer);
+        let mut all = self.cursors.take(self.cursor_position);
+        Cursors::edit(
+            &mut all,
+            self,
+            |view| &view.buffer,
+            |view, pos| {
+                view.cursor_position = *pos;
+                edit(view);
+                *pos = view.cursor_position;
+            },
+        );
+        self.cursor_position = self.cursors.restore(all);
+    }
+
+    fn add_cursor(&mut self, add: AddCursor) {
+        let primary = &self.cursor_position;
+        match add {
+            AddCursor::NextMatch => self.cursors.add_next_match(primary, &self.buffer),
+            AddCursor::Above => self.cursors.add_line(primary, &self.buffer, false),
+            AddCursor::Below => self.cursors.add_line(primary, &self.buffer, true),
+        };
+    }
+
     fn enter_vim_mode(&mut self) -> bool {
         let mut vim_mode = VimMode::new(
             self.cursor_position,
             self.screen_offset,
             self.size,
             &mut self.buffer,
+            self.theme.clone(),
         );
-        vim_mode.run(
+        let keep_going = vim_mode.run(
             &mut self.cursor_position,
             &mut self.screen_offset,
             &mut self.size,
-            &self.theme,
-        )
+        );
+        self.exit_code = vim_mode.exit_code;
+        self.theme = vim_mode.theme;
+        keep_going
     }
 
     fn enter_highlight_mode(&mut self) {
@@ -307,167 +703,185 @@ impl View {
             &mut self.size,
             &mut self.buffer,
         );
-        highlight.run(
-            self.theme.highlight,
-            self.theme.text,
-            parse_highlight_normal_mode,
-        );
+        highlight.run(self.theme.selection, parse_highlight_normal_mode);
+        if highlight.cursors {
+            let (first, last) = highlight.selected_lines();
+            self.cursors
+                .add_lines(&self.cursor_position, &self.buffer, first..=last);
+        }
     }
 
     fn enter_search_mode(&mut self) {
         let mut search = Search::new(
             self.cursor_position,
             self.screen_offset,
-            self.theme.highlight,
-            self.theme.text,
+            self.theme.search,
+            self.theme.current_match,
         );
         search.run(
             &mut self.cursor_position,
             &mut self.screen_offset,
             &mut self.size,
-            &self.buffer,
+            &mut self.buffer,
         );
     }
 
-    pub fn handle_event(&mut self, command: EditorCommand) -> Result<bool, Box<dyn Error>> {
-        let mut continue_status: bool = true;
-        let mut render_type: ScreenUpdateType = ScreenUpdateType::DefaultAction;
+    /// whether the completion menu is open and takes the keys that move through it
+    pub fn completing(&self) -> bool {
+        self.completion.is_some()
+    }
+
+    /// moves through the completion menu, accepting puts the selected entry in place of the typed text
+    pub fn handle_menu(&mut self, command: MenuCommand) -> Result<(), Box<dyn Error>> {
         match command {
-            EditorCommand::Move(direction) => {
-                // if offset changes, render the entire screen
-                self.move_cursor(direction);
-                let view_delta = self.check_offset();
-                render_type = if view_delta == 0 {
-                    ScreenUpdateType::DefaultAction
-                } else {
-                    ScreenUpdateType::FullScreen
-                };
-            }
-            EditorCommand::Insert(char) => {
-                let was_empty = self.buffer.is_empty();
-                self.insert_char(char);
-                render_type = if was_empty {
-                    ScreenUpdateType::FullScreen
-                } else {
-                    self.evaluate_view_state_change()
-                };
-            }
-            EditorCommand::Delete => {
-                self.deletion();
+            MenuCommand::Next | MenuCommand::Previous => {
+                if let Some(menu) = &mut self.completion {
+                    menu.select(matches!(command, MenuCommand::Next));
+                }
             }
-            EditorCommand::Tab => {
-                self.insert_tab();
-                render_type = self.evaluate_view_state_change();
+            MenuCommand::Accept => {
+                if let Some(menu) = self.completion.take() {
+                    // a directory goes on to its entries
+                    if menu.accept(&mut self.buffer, &mut self.cursor_position) {
+                        self.completion =
+                            Menu::update(None, &mut self.buffer, &self.cursor_position, None);
+                    }
+                }
             }
+            MenuCommand::Close => self.completion = None,
+        }
+        self.buffer.update_lsp();
+        self.buffer.flush_swap(false);
+        self.check_offset();
+        self.draw()
+    }
+
+    pub fn handle_event(&mut self, command: EditorCommand) -> Result<bool, Box<dyn Error>> {
+        let mut continue_status: bool = true;
+        let menu = self.completion.take();
+        let line = self.cursor_position.height;
+        if matches!(
+            command,
+            EditorCommand::Insert(_)
+                | EditorCommand::Delete
+                | EditorCommand::Tab
+                | EditorCommand::NewLine
+                | EditorCommand::Paste
+                | EditorCommand::Comment
+        ) {
+            // editing a closed fold opens it
+            self.buffer.folds.open(line);
+        }
+        // the other cursors go with commands that work at one place only, a selection
+        // can bring new ones
+        if matches!(
+            command,
+            EditorCommand::Highlight
+                | EditorCommand::Search
+                | EditorCommand::JumpLine
+                | EditorCommand::VimMode
+                | EditorCommand::Terminal
+        ) {
+            self.cursors.clear();
+        }
+        match command {
+            EditorCommand::Move(direction) => self.at_cursors(|view| view.move_cursor(direction)),
+            EditorCommand::Insert(char) => self.at_cursors(|view| view.insert_char(char)),
+            EditorCommand::Delete => self.at_cursors(Self::deletion),
+            EditorCommand::Tab => self.at_cursors(Self::insert_tab),
             EditorCommand::NewLine => {
-                self.new_line();
-                render_type = ScreenUpdateType::MultiLineRender;
+                self.at_cursors(|view| view.buffer.add_new_line(&mut view.cursor_position));
             }
-            EditorCommand::JumpWord(direction) => self.jump_word(direction),
-            EditorCommand::Save => self.save()?,
-            EditorCommand::Resize(size) => {
-                // render always
-                self.resize(size);
-                self.check_offset(); // cursor may no longer be on screen
-                render_type = ScreenUpdateType::FullScreen;
+            EditorCommand::JumpWord(direction) => {
+                self.at_cursors(|view| view.jump_word(direction));
             }
-
+            EditorCommand::AddCursor(add) => self.add_cursor(add),
+            EditorCommand::SingleCursor => self.cursors.clear(),
+            EditorCommand::Save => self.save(),
+            EditorCommand::Resize(size) => self.resize(size),
             EditorCommand::Paste => {
-                // self.render(true)?;
                 if let Some(_failed_paste) = self.paste_text() {
                     return Ok(true);
                 }
-                render_type = ScreenUpdateType::FullScreen;
             }
             EditorCommand::VimMode => {
                 if !self.enter_vim_mode() {
                     return Ok(false);
                 }
-                render_type = ScreenUpdateType::FullScreen;
-            }
-            EditorCommand::Highlight => {
-                self.enter_highlight_mode();
-                let _ = self.check_offset(); // making sure the offset is correct on a delete
-                render_type = ScreenUpdateType::FullScreen;
-            }
-            EditorCommand::Search => {
-                self.enter_search_mode();
-                render_type = ScreenUpdateType::FullScreen;
-            }
-            EditorCommand::JumpLine => {
-                render_type = self.jump_cursor()?;
             }
+            EditorCommand::Highlight => self.enter_highlight_mode(),
+            EditorCommand::Search => self.enter_search_mode(),
+            EditorCommand::JumpLine => self.jump_cursor()?,
             EditorCommand::Help => {
-                Help::render_help(&mut self.size, self.theme.highlight, self.theme.text);
-                self.full_screen_render()?;
+                messages::draw_failed(Help::render_help(&mut self.size, self.theme.selection));
             }
-
-            EditorCommand::Quit => continue_status = false,
-            EditorCommand::Theme => {
-                self.theme.set_theme();
-                render_type = ScreenUpdateType::FullScreen;
+            EditorCommand::Focus(true) => {
+                self.check_disk();
             }
+            EditorCommand::Focus(false) => self.buffer.flush_swap(true),
+            EditorCommand::Quit => continue_status = false,
+            EditorCommand::Theme => self.pick_theme()?,
+            EditorCommand::Terminal => self.enter_terminal()?,
+            EditorCommand::Comment => self.toggle_comment(),
             EditorCommand::None => {}
         }
-        self.eval_screen_update(&render_type)?;
-        self.set_cursor_and_status()?;
-        Terminal::execute()?;
-        Ok(continue_status)
-    }
-
-    fn eval_screen_update(&self, update_t: &ScreenUpdateType) -> Result<(), Box<dyn Error>> {
-        match update_t {
-            ScreenUpdateType::FullScreen => self.full_screen_render()?,
-            ScreenUpdateType::SingleLineRender => {
-                Self::render_line(
-                    self.cursor_position
-                        .height
-                        .saturating_sub(self.screen_offset.height),
-                    self.buffer.text[self.cursor_position.height].get_line_subset(
-                        self.screen_offset.width
-                            ..self.screen_offset.width.saturating_add(self.size.width),
-                    ),
-                );
-            }
-            ScreenUpdateType::MultiLineRender => {
-                self.render(false);
+        // typing opens or narrows the menu, deleting only narrows it, other keys close it
+        // a menu completes at one cursor, so there is none while there are more
+        self.completion = match command {
+            _ if !self.cursors.is_empty() => None,
+            EditorCommand::Insert(c) => {
+                Menu::update(menu, &mut self.buffer, &self.cursor_position, Some(c))
             }
-            ScreenUpdateType::DefaultAction => {
-                // no additional action is required
-            }
-        }
-        Ok(())
-    }
-
-    fn new_line(&mut self) -> ScreenUpdateType {
-        self.buffer.add_new_line(&mut self.cursor_position);
-        // handling if the new line is currently off screen
-        let view_delta = self.check_offset();
-        if view_delta > 1 {
-            ScreenUpdateType::FullScreen
-        } else {
-            ScreenUpdateType::MultiLineRender
+            EditorCommand::Delete => menu.and_then(|menu| {
+                Menu::update(Some(menu), &mut self.buffer, &self.cursor_position, None)
+            }),
+            EditorCommand::Resize(_) | EditorCommand::Focus(_) | EditorCommand::None => menu,
+            _ => None,
+        };
+        // the closers typed for the user only count while typing goes on in front of them
+        if !matches!(
+            command,
+            EditorCommand::Insert(_)
+                | EditorCommand::Delete
+                | EditorCommand::Resize(_)
+                | EditorCommand::Focus(_)
+                | EditorCommand::None
+        ) {
+            self.pairs.clear();
         }
+        self.buffer
+            .update_folds(std::cmp::min(line, self.cursor_position.height));
+        self.update_pane();
+        self.fit_pane();
+        self.buffer.update_lsp();
+        self.buffer.flush_swap(false);
+        // the cursor may have left the view, or the gutter changed width
+        self.check_offset();
+        self.draw()?;
+        Ok(continue_status)
     }
 
     #[inline]
     fn check_offset(&mut self) -> usize {
-        let view_delta =
-            self.cursor_position
-                .max_displacement_from_view(&self.screen_offset, &self.size, 2);
-        match view_delta {
-            0 => (),
-            1 => self
-                .screen_offset
-                .update_offset_single_move(&self.cursor_position, &self.size, 2),
-            _ => self.screen_offset.handle_offset_screen_snap(
+        // a cursor that went into a closed fold opens it
+        self.buffer.folds.reveal(self.cursor_position.height);
+        self.screen_offset.gutter.signs = self.buffer.lsp.is_some();
+        self.screen_offset.gutter.resize(self.buffer.len());
+        if self.screen_offset.wrap {
+            return wrap::scroll_to_cursor(
+                &mut self.screen_offset,
                 &self.cursor_position,
+                &self.buffer,
                 &self.size,
-                1,
-                self.buffer.len(),
-            ),
+                2,
+            );
         }
-        view_delta
+        fold::scroll_to_cursor(
+            &mut self.screen_offset,
+            &self.cursor_position,
+            &self.buffer,
+            &self.size,
+        )
     }
 
     #[inline]
@@ -475,6 +889,12 @@ impl View {
         if self.buffer.is_empty() || self.cursor_position == ORIGIN_POSITION {
             return;
         }
+        if self
+            .pairs
+            .delete(&mut self.buffer, &mut self.cursor_position)
+        {
+            return;
+        }
         match self.cursor_position.width {
             0 => match (
                 self.cursor_position.at_top(),
@@ -502,106 +922,73 @@ impl View {
                 self.delete_char();
             }
         };
+    }
 
-        // evaluate how much of the screen we need to render
-        let view_delta = self.check_offset();
-        self.render(view_delta > 0);
-        let move_res = Terminal::move_cursor_to(
-            self.cursor_position
-                .relative_view_position(&self.screen_offset),
-        );
-        debug_assert!(move_res.is_ok());
-    }
-
-    fn overwrite_last_line(&self) -> Result<(), Box<dyn Error>> {
-        let l = self
-            .screen_offset
-            .height
-            .saturating_add(self.size.height)
-            .saturating_sub(2);
-
-        if self.buffer.len() >= l {
-            Terminal::render_line(
-                self.size.height.saturating_sub(2),
-                self.buffer.text[l].get_line_subset(
-                    self.screen_offset.width
-                        ..self.screen_offset.width.saturating_add(self.size.width),
-                ),
-            )?;
-        } else {
-            Terminal::render_line(self.size.height.saturating_sub(1), "~")?;
+    /// the theme picker over the view, the view shows the theme picked as it is picked
+    fn pick_theme(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut picker = Picker::new(&self.theme);
+        loop {
+            if picker.theme != self.theme {
+                self.theme = picker.theme.clone();
+                self.theme.apply()?;
+            }
+            self.compose();
+            picker.draw(&self.size);
+            Screen::hide_cursor();
+            Screen::present()?;
+            let Ok(read_event) = read() else { continue }; //skipping errors here
+            let Ok(command) = PickerCommand::try_from(read_event) else {
+                continue;
+            };
+            match picker.apply(command) {
+                PickerAction::Keep => {
+                    if let Err(err) = picker.save() {
+                        messages::error(format!("theme.conf: {err}"));
+                    }
+                    return Ok(());
+                }
+                PickerAction::Restore => {
+                    self.theme = picker.before;
+                    self.theme.apply()?;
+                    return Ok(());
+                }
+                PickerAction::Resize(size) => self.resize(size),
+                PickerAction::Continue => {}
+            }
         }
-        Terminal::execute()?;
-        Ok(())
     }
 
-    fn jump_cursor(&mut self) -> Result<ScreenUpdateType, Box<dyn Error>> {
+    fn jump_cursor(&mut self) -> Result<(), Box<dyn Error>> {
         let neg_2 = self.size.height.saturating_sub(2);
-        let render_string: &str = "Jump to: ";
-        let mut line = 0_usize;
-        Terminal::move_cursor_to(ScreenPosition {
-            height: neg_2,
-            width: 0,
-        })?;
+        let mut prompt = Prompt::new("Jump to: ", Completion::None)
+            .with_filter(|c| c.is_ascii_digit())
+            .with_history(HistoryKind::Jump);
 
         loop {
-            let render_line = if line != 0 {
-                format!("{render_string}{line}")
-            } else {
-                format!("{render_string}")
-            };
-            Terminal::render_line(neg_2, render_line)?;
-            Terminal::execute()?;
+            prompt.render(neg_2, self.size.width);
+            Screen::present()?;
             let Ok(read_event) = read() else { continue }; //skipping errors here
-            match JumpCommand::try_from(read_event) {
-                Ok(command) => match command {
-                    JumpCommand::Enter(digit) => {
-                        line = std::cmp::max(1, line.saturating_mul(10).saturating_sub(digit));
-                    }
-                    #[allow(clippy::integer_division)]
-                    JumpCommand::Delete => line = if line > 9 { line / 10 } else { 0 },
-                    JumpCommand::Move => {
-                        // if line > buffer.len(), give buffer len
-                        if line < self.buffer.len() {
-                            self.cursor_position.height = line.saturating_sub(1);
-                        } else {
-                            self.move_cursor(Direction::PageDown);
-                        };
-
-                        if (self.cursor_position.height
-                            > self.size.height.saturating_add(self.screen_offset.height))
-                            | (self.cursor_position.height < self.screen_offset.height)
-                        {
-                            self.screen_offset.handle_offset_screen_snap(
-                                &self.cursor_position,
-                                &self.size,
-                                1,
-                                self.buffer.len(),
-                            );
-                            return Ok(ScreenUpdateType::FullScreen);
-                        }
-
-                        self.overwrite_last_line()?;
-                        return Ok(ScreenUpdateType::DefaultAction);
-                    }
-                    JumpCommand::Exit => {
-                        self.overwrite_last_line()?;
-                        return Ok(ScreenUpdateType::DefaultAction);
-                    }
-                    JumpCommand::NoAction => continue,
-                },
-                Err(_) => continue,
-            }
-
-            match line {
-                0 => {
-                    Terminal::render_line(neg_2, &render_string)?;
-                }
-                _ => {
-                    Terminal::render_line(neg_2, &format!("{render_string}{line}"))?;
+            let Ok(command) = PromptCommand::try_from(read_event) else {
+                continue;
+            };
+            match prompt.apply(command) {
+                PromptAction::Submit(text) => {
+                    let Ok(line) = text.parse::<usize>() else {
+                        return Ok(());
+                    };
+                    // if line > buffer.len(), give buffer len
+                    if line < self.buffer.len() {
+                        self.cursor_position.height = line.saturating_sub(1);
+                    } else {
+                        self.move_cursor(Direction::PageDown);
+                    };
+                    // the offset follows the cursor once the view is drawn again
+                    return Ok(());
                 }
+                PromptAction::Cancel => return Ok(()),
+                PromptAction::Resize(size) => self.size = size,
+                PromptAction::Continue => {}
             }
-            Terminal::execute()?;
         }
     }
 
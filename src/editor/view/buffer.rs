@@ -1,13 +1,61 @@
+use super::disk::{self, DiskChange, FileStamp};
+use super::filetype::{leading_whitespace, FileType};
+use super::fold::Folds;
 use super::line::{GraphemeWidth, Line, TextFragment};
+use super::lsp::{self, Client, TextEdit};
+use super::messages;
+use super::swap::Swap;
+use crate::editor::editorcommands::Shift;
 use crate::editor::view::Position;
-use std::fs::{read_to_string, OpenOptions};
-use std::io::{Error, LineWriter, Write};
+use std::fs::{metadata, read_to_string, OpenOptions};
+use std::io::{Error, ErrorKind, LineWriter, Write};
+use std::ops::{Range, RangeInclusive};
 
-#[derive(Default, Clone)]
+#[derive(Default)]
 pub struct Buffer {
     pub text: Vec<Line>,
     pub filename: Option<String>,
     pub is_saved: bool,
+    pub swap: Swap,
+    pub stamp: Option<FileStamp>, // the file as it was last read or written, None before it exists
+    pub autoread: bool,           // follow changes on disk while there is nothing unsaved
+    pub read_only: bool,          // writing over the file needs a bang, started with -R
+    pub lsp: Option<Client>,      // the language server for the file's type, see :lsp
+    pub filetype: FileType,       // how lines of the file are indented
+    pub folds: Folds,             // lines shown as one, see zf and :set foldmethod
+    pub line_ending: LineEnding,  // the one the file was read with, lines are written with it
+}
+
+/// what ends the lines of a file
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// the one of the first line of a text
+    fn of(text: &str) -> Self {
+        match text.split_once('\n') {
+            Some((first, _)) if first.ends_with('\r') => Self::CrLf,
+            _ => Self::Lf,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::CrLf => "CRLF",
+        }
+    }
 }
 
 impl Buffer {
@@ -51,6 +99,34 @@ impl Buffer {
             text,
             filename: Some(filename.to_string()),
             is_saved: false,
+            filetype: FileType::for_file(Some(filename)),
+            ..Default::default()
+        }
+    }
+
+    /// an unnamed buffer holding text that came from elsewhere, ie stdin
+    pub fn from_text(text: &str) -> Buffer {
+        Self {
+            text: text.lines().map(Line::from).collect(),
+            is_saved: text.is_empty(),
+            ..Default::default()
+        }
+    }
+
+    /// the buffer of a file to edit, a file that is not there yet is a new one
+    /// one that cannot be read opens empty and read only, so it is not written over by mistake
+    pub fn open(filename: &str, screen_height: usize) -> Buffer {
+        match Self::load(filename) {
+            Ok(buffer) => buffer,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                Self::load_named_empty(filename, screen_height)
+            }
+            Err(err) => {
+                messages::error(format!("\"{filename}\": {err}"));
+                let mut buffer = Self::load_named_empty(filename, screen_height);
+                buffer.read_only = true;
+                buffer
+            }
         }
     }
 
@@ -65,8 +141,12 @@ impl Buffer {
 
         Ok(Self {
             text,
+            line_ending: LineEnding::of(&file_contents),
             filename: Some(filename.to_string()),
             is_saved: true,
+            stamp: Some(FileStamp::of(&file_contents, &metadata(filename)?)),
+            filetype: FileType::for_file(Some(filename)),
+            ..Default::default()
         })
     }
 
@@ -89,32 +169,158 @@ impl Buffer {
         positions
     }
 
+    /// breaks the line at the cursor, the new line gets the indent of the filetype
+    /// between a pair like `{|}` the closer goes one line further down, at the old indent
     pub fn add_new_line(&mut self, pos: &mut Position) {
-        let grapheme_len = if self.is_empty() {
-            0
+        if self.is_empty() {
+            self.text.push(Line::from(""));
+        }
+        let line = &self.text[pos.height].string;
+        let split = std::cmp::min(pos.width, line.len());
+        let text = |fragments: &[TextFragment]| -> String {
+            fragments
+                .iter()
+                .map(|fragment| fragment.grapheme.as_str())
+                .collect()
+        };
+        let (mut before, after) = (text(&line[..split]), text(&line[split..]));
+
+        let mut lines = Vec::new();
+        let indent = if before.trim().is_empty() {
+            // breaking in the indent moves the whole line down as it is
+            lines.push(format!("{before}{after}"));
+            before.clear();
+            String::new()
+        } else if !self.filetype.auto_indent {
+            lines.push(after);
+            String::new()
         } else {
-            self.text[pos.height].grapheme_len()
+            let indent = self.filetype.new_line_indent(&before);
+            if self.filetype.splits_pair(&before, &after) {
+                lines.push(indent.clone());
+                lines.push(format!(
+                    "{}{}",
+                    leading_whitespace(&before),
+                    after.trim_start()
+                ));
+            } else {
+                lines.push(format!("{indent}{}", after.trim_start()));
+            }
+            indent
         };
 
-        // if at end of current line -> new blank line
-        // otherwise move all text right of cursor to new line
-        if pos.width == grapheme_len {
-            self.new_line(pos.height);
-        } else {
-            self.split_line(pos);
+        self.text[pos.height] = Line::from(before.as_str());
+        for (offset, line) in lines.iter().enumerate() {
+            let index = pos.height.saturating_add(offset).saturating_add(1);
+            self.text.insert(index, Line::from(line.as_str()));
         }
+        pos.height = pos.height.saturating_add(1);
+        pos.width = indent.chars().count();
+        self.is_saved = false;
+    }
 
-        pos.down(1, self.len().saturating_sub(1));
-        // if prev line starts with a tab -> this line starts with a tab
-        pos.width = if self.is_tab(&Position {
-            height: pos.height,
-            width: 4,
-            max_width: usize::default(),
-        }) {
-            self.num_tabs(pos.height).saturating_mul(4)
+    /// opens a line below the cursor's line, or above it, with the indent of the filetype
+    pub fn open_line(&mut self, pos: &mut Position, above: bool) {
+        if self.is_empty() {
+            self.text.push(Line::from(""));
+        }
+        let current = &self.text[pos.height].raw_string;
+        let indent = if above {
+            if self.filetype.auto_indent {
+                leading_whitespace(current).to_string()
+            } else {
+                String::new()
+            }
         } else {
-            0
+            self.filetype.new_line_indent(current)
         };
+        if !above {
+            pos.height = pos.height.saturating_add(1);
+        }
+        self.text.insert(pos.height, Line::from(indent.as_str()));
+        pos.width = indent.chars().count();
+        self.is_saved = false;
+    }
+
+    /// indents the lines of a range a level deeper or shallower, or as the filetype would
+    /// lines of only blanks are left as they are
+    pub fn indent_lines(&mut self, lines: RangeInclusive<usize>, shift: Shift) {
+        // reindenting follows the nearest line with text above the range
+        let mut above = self.text[..std::cmp::min(*lines.start(), self.len())]
+            .iter()
+            .rev()
+            .find(|line| !line.raw_string.trim().is_empty())
+            .map(|line| line.raw_string.clone());
+        for index in lines {
+            let Some(line) = self.text.get(index) else {
+                break;
+            };
+            let text = line.raw_string.clone();
+            if text.trim().is_empty() {
+                continue;
+            }
+            let old = leading_whitespace(&text);
+            let indent = match shift {
+                Shift::Right => self.filetype.shift(old, true),
+                Shift::Left => self.filetype.shift(old, false),
+                Shift::Reindent => self.filetype.reindent(above.as_deref(), &text),
+            };
+            if indent != old {
+                self.replace_in_line(index, 0..old.chars().count(), &indent);
+            }
+            above = Some(format!("{indent}{}", text.trim_start()));
+        }
+    }
+
+    /// comments the lines of a range out or back in, false when the filetype has no comments
+    pub fn toggle_comment(&mut self, lines: RangeInclusive<usize>) -> bool {
+        let end = std::cmp::min(lines.end().saturating_add(1), self.len());
+        let start = std::cmp::min(*lines.start(), end);
+        let texts: Vec<&str> = self.text[start..end]
+            .iter()
+            .map(|line| line.raw_string.as_str())
+            .collect();
+        let Some(toggled) = self.filetype.toggle_comments(&texts) else {
+            return false;
+        };
+        for (index, text) in (start..end).zip(toggled) {
+            if self.text[index].raw_string != text {
+                self.text[index] = Line::from(text.as_str());
+                self.is_saved = false;
+            }
+        }
+        true
+    }
+
+    /// keeps the folds on their lines after an edit at a line added or removed some
+    pub fn update_folds(&mut self, edited: usize) {
+        self.folds.update(&self.text, &self.filetype, edited);
+    }
+
+    /// finds the automatic folds again, the z commands see the text as it is now
+    pub fn refresh_folds(&mut self) {
+        self.folds.refresh(&self.text, &self.filetype);
+    }
+
+    /// takes a level of indent away before a closer typed first on its line
+    pub fn dedent_for(&mut self, pos: &mut Position, typed: char) {
+        if !self.filetype.dedents(typed) {
+            return;
+        }
+        let Some(line) = self.text.get(pos.height) else {
+            return;
+        };
+        let indent: String = line
+            .string
+            .iter()
+            .take(pos.width)
+            .map(|fragment| fragment.grapheme.as_str())
+            .collect();
+        if indent.is_empty() || !indent.trim().is_empty() {
+            return;
+        }
+        let dedented = self.filetype.dedent(&indent);
+        pos.width = self.replace_in_line(pos.height, 0..pos.width, &dedented);
     }
 
     pub fn find_prev_word(&self, position: &mut Position) {
@@ -193,30 +399,201 @@ impl Buffer {
     }
 
     pub fn assume_file_name(&mut self, filename: String) {
+        self.filetype = FileType::for_file(Some(&filename));
         self.filename = Some(filename);
     }
 
-    pub fn save(&mut self) {
+    pub fn save(&mut self) -> Result<(), Error> {
         //write buffer to disk
         let Some(filename) = &self.filename else {
             panic!("Trying to save without filename being set")
         };
+        self.write_to(filename)?;
+        self.mark_saved();
+        Ok(())
+    }
+
+    /// the buffer matches its file, so there is nothing left to recover
+    pub fn mark_saved(&mut self) {
+        self.is_saved = true;
+        self.discard_swap();
+        if let Some(filename) = &self.filename {
+            self.stamp = FileStamp::read(filename);
+        }
+        if let Some(client) = &mut self.lsp {
+            client.sync(&self.text);
+            client.did_save();
+        }
+    }
+
+    /// starts the language server configured for the file's type, or else the fallback command
+    /// returns whether one was started
+    pub fn attach_server(&mut self, fallback: Option<String>) -> Result<bool, String> {
+        let Some(filename) = &self.filename else {
+            return Ok(false);
+        };
+        let Some(command) = lsp::server_for(filename).or(fallback) else {
+            return Ok(false);
+        };
+        // the old server is shut down before the new one starts
+        self.lsp = None;
+        self.lsp = Some(Client::start(&command, filename, &self.text)?);
+        Ok(true)
+    }
+
+    /// tells the language server about the edits since the last call and takes in what it sent
+    /// returns whether its diagnostics changed, a server that exited is let go of
+    pub fn update_lsp(&mut self) -> bool {
+        let Some(client) = &mut self.lsp else {
+            return false;
+        };
+        client.sync(&self.text);
+        let changed = client.poll();
+        if client.exited() {
+            self.lsp = None;
+            return true;
+        }
+        changed
+    }
+
+    /// the language server with the text brought up to date, to ask it about the text
+    pub fn lsp_client(&mut self) -> Result<(&mut Client, &[Line]), String> {
+        let client = self
+            .lsp
+            .as_mut()
+            .ok_or("No language server, see :lsp start")?;
+        client.sync(&self.text);
+        Ok((client, &self.text))
+    }
+
+    /// the diagnostics part of the status line, empty without a language server
+    pub fn lsp_summary(&self, line: usize) -> String {
+        self.lsp
+            .as_ref()
+            .map_or_else(String::new, |client| client.summary(line))
+    }
+
+    /// applies the edits of a language server, ie a rename or formatting
+    pub fn apply_edits(&mut self, edits: &[TextEdit]) {
+        if edits.is_empty() {
+            return;
+        }
+        let lines: Vec<String> = self.text.iter().map(Line::to_string).collect();
+        self.text = lsp::apply_edits(&lines, edits)
+            .iter()
+            .map(String::as_str)
+            .map(Line::from)
+            .collect();
+        self.is_saved = false;
+    }
+
+    /// whether another program changed the file since the buffer last read or wrote it
+    pub fn disk_change(&mut self) -> DiskChange {
+        match &self.filename {
+            Some(filename) => disk::check(&mut self.stamp, filename),
+            None => DiskChange::None,
+        }
+    }
+
+    /// replaces the text with what is on disk
+    pub fn reload(&mut self, text: &str) {
+        self.text = text.lines().map(Line::from).collect();
+        self.line_ending = LineEnding::of(text);
+        self.mark_saved();
+    }
+
+    pub fn discard_swap(&mut self) {
+        if let Some(filename) = &self.filename {
+            self.swap.remove(filename);
+        }
+    }
+
+    /// brings the swap file of a modified buffer up to date
+    /// while typing this only writes when the swap file is old, idle writes whenever the text changed
+    pub fn flush_swap(&mut self, idle: bool) {
+        // a file opened to look at leaves nothing next to it
+        if self.is_saved || self.read_only || !(idle || self.swap.is_due()) {
+            return;
+        }
+        let Some(filename) = &self.filename else {
+            return;
+        };
+        // the swap file is best effort, a read only directory should not stop the editor
+        let _ = self.swap.write(filename, &self.contents());
+    }
+
+    /// the text as it is written to disk
+    pub fn contents(&self) -> String {
+        let mut contents = String::new();
+        for line in &self.text {
+            contents.push_str(&line.to_string());
+            contents.push_str(self.line_ending.as_str());
+        }
+        contents
+    }
+
+    /// writes the whole buffer to a file without changing the buffer's file name
+    pub fn write_to(&self, filename: &str) -> Result<(), Error> {
+        self.write_lines(filename, &self.text)
+    }
+
+    /// writes an inclusive span of lines to a file
+    pub fn write_range(&self, filename: &str, start: usize, end: usize) -> Result<(), Error> {
+        self.write_lines(filename, &self.text[start..=end])
+    }
+
+    fn write_lines(&self, filename: &str, lines: &[Line]) -> Result<(), Error> {
         let file = OpenOptions::new()
             .write(true)
             .create(true)
-            .open(filename)
-            .expect("Error opening file");
+            .truncate(true)
+            .open(filename)?;
         let mut file = LineWriter::new(file);
-        for line in &self.text {
-            let text_line = line.to_string();
-            file.write_all(text_line.as_bytes())
-                .expect("Error on write");
-            file.write_all(b"\n").expect("Error entering new line");
+        for line in lines {
+            file.write_all(line.to_string().as_bytes())?;
+            file.write_all(self.line_ending.as_str().as_bytes())?;
         }
-        self.is_saved = true;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// removes an inclusive span of lines and hands them back
+    pub fn delete_lines(&mut self, start: usize, end: usize) -> Vec<Line> {
+        self.is_saved = false;
+        self.text.drain(start..=end).collect()
+    }
+
+    /// inserts lines so the first new line lands at index
+    pub fn insert_lines(&mut self, index: usize, lines: Vec<Line>) {
+        self.is_saved = false;
+        let tail = self.text.split_off(index);
+        self.text.extend(lines);
+        self.text.extend(tail);
+    }
+
+    pub fn lines_to_string(&self, start: usize, end: usize) -> String {
+        let mut copy_string = String::new();
+        for line in &self.text[start..=end] {
+            copy_string.push_str(&line.raw_string);
+            copy_string.push('\n');
+        }
+        copy_string
     }
 
-    pub fn insert_tab(&mut self, pos: &Position, num_tabs: usize) {
+    pub fn sort_lines(&mut self, start: usize, end: usize, reverse: bool, unique: bool) {
+        let mut lines = self.delete_lines(start, end);
+        lines.sort_by(|a, b| a.raw_string.cmp(&b.raw_string));
+        if unique {
+            lines.dedup_by(|a, b| a.raw_string == b.raw_string);
+        }
+        if reverse {
+            lines.reverse();
+        }
+        self.insert_lines(start, lines);
+    }
+
+    /// puts levels of indent at the cursor, returns the column after them
+    pub fn insert_tab(&mut self, pos: &Position, num_tabs: usize) -> usize {
         if self.is_empty() {
             let new_line = Line {
                 string: Vec::new(),
@@ -225,21 +602,8 @@ impl Buffer {
             self.text.push(new_line);
         }
 
-        for _ in pos.width..pos.width.saturating_add(num_tabs.saturating_mul(4)) {
-            self.text
-                .get_mut(pos.height)
-                .expect("Out of bounds")
-                .string
-                .insert(
-                    pos.width,
-                    TextFragment::try_from(" ").expect("Error generating new fragment"),
-                );
-        }
-
-        self.text
-            .get_mut(pos.height)
-            .expect("Out of bounds error")
-            .generate_raw_string();
+        let unit = self.filetype.unit().repeat(num_tabs);
+        self.replace_in_line(pos.height, pos.width..pos.width, &unit)
     }
 
     pub fn update_line_insert(&mut self, pos: &mut Position, insert_char: char) {
@@ -271,18 +635,29 @@ impl Buffer {
         pos.width = pos.width.saturating_add(move_width);
     }
 
+    /// puts text in place of the graphemes in a range of a line, as one edit
+    /// returns the column after the new text
+    pub fn replace_in_line(&mut self, index: usize, range: Range<usize>, text: &str) -> usize {
+        let Some(line) = self.text.get_mut(index) else {
+            return range.start;
+        };
+        let end = std::cmp::min(range.end, line.string.len());
+        let start = std::cmp::min(range.start, end);
+        let inserted = Line::from(text).string;
+        let after = start.saturating_add(inserted.len());
+        line.string.splice(start..end, inserted);
+        line.generate_raw_string();
+        self.is_saved = false;
+        after
+    }
+
     pub fn update_line_delete(&mut self, pos: &mut Position) {
         // pop out the char we want to removed
         // return the render_width of that char
         if self.is_tab(pos) {
-            for i in (pos.width.saturating_sub(4)..pos.width).rev() {
-                self.text
-                    .get_mut(pos.height)
-                    .expect("Out of bounds error")
-                    .string
-                    .remove(i);
-            }
-            pos.left(4);
+            let width = self.filetype.indent_width;
+            self.replace_in_line(pos.height, pos.width.saturating_sub(width)..pos.width, "");
+            pos.left(width);
             return;
         }
         let removed_char = self
@@ -303,8 +678,10 @@ impl Buffer {
         pos.left(diff);
     }
 
+    /// whether the cursor is after a level of indent made of spaces
     pub fn is_tab(&self, pos: &Position) -> bool {
-        if pos.width < 4 {
+        let width = self.filetype.indent_width;
+        if pos.width < width {
             return false;
         }
         let fragments_to_check = &self
@@ -312,7 +689,7 @@ impl Buffer {
             .get(pos.height)
             .expect("Out of bounds")
             .string
-            .get(pos.width.saturating_sub(4)..pos.width);
+            .get(pos.width.saturating_sub(width)..pos.width);
         match fragments_to_check {
             Some(frags) => {
                 for fragment in frags.iter().rev() {
@@ -327,82 +704,6 @@ impl Buffer {
         true
     }
 
-    pub fn num_tabs(&self, index: usize) -> usize {
-        let bytes = self.text[index].raw_string.as_bytes();
-        let len = bytes.len();
-        let mut i = 5;
-        while i < len && bytes[i] == 32 {
-            i = i.saturating_add(1);
-        }
-
-        i >> 2 //cheaper divide by 4 and get integer divide free
-    }
-
-    fn new_line(&mut self, line_index: usize) {
-        if self.is_empty() {
-            self.text.push(Line {
-                string: Vec::new(),
-                raw_string: String::new(),
-            });
-        }
-        self.text.insert(
-            line_index.saturating_add(1),
-            Line {
-                string: Vec::new(),
-                raw_string: String::new(),
-            },
-        );
-
-        if self.is_tab(&Position {
-            height: line_index,
-            width: 4,
-            max_width: usize::default(),
-        }) {
-            let num_tabs = self.num_tabs(line_index);
-            self.insert_tab(
-                &Position {
-                    height: line_index.saturating_add(1),
-                    width: 0,
-                    max_width: usize::default(),
-                },
-                num_tabs,
-            );
-        }
-
-        self.is_saved = false;
-    }
-
-    pub fn split_line(&mut self, pos: &Position) {
-        let new_line = self
-            .text
-            .get(pos.height)
-            .expect("Out of bounds error")
-            .string
-            .get(pos.width..)
-            .expect("Out of bounds error");
-
-        self.text.insert(
-            pos.height.saturating_add(1),
-            Line {
-                string: new_line.to_vec(),
-                raw_string: String::new(),
-            },
-        );
-
-        self.text
-            .get_mut(pos.height)
-            .expect("Out of bounds error")
-            .string
-            .truncate(pos.width);
-
-        self.text
-            .get_mut(pos.height.saturating_add(1))
-            .expect("Out of bounds error")
-            .generate_raw_string();
-
-        self.is_saved = false;
-    }
-
     pub fn join_line(&mut self, line_index: usize) {
         let mut current_line = self
             .text
@@ -482,24 +783,28 @@ impl Buffer {
     }
 
     pub fn get_segment(&self, start: &Position, end: &Position) -> String {
+        // the widths are screen columns, they are taken to bytes through the graphemes
+        // a width past the end of its line, ie on an empty one, stops at the end
         let mut copy_string = String::new();
         if start.height == end.height {
-            let line_len = self.text[start.height].raw_string.len().saturating_sub(1);
-            let line_string = &self.text[start.height].raw_string;
-            let slice: String = if end.width == line_len {
-                line_string[start.width..].to_owned()
+            let line = &self.text[start.height];
+            let from = line.byte_offset(start.width);
+            let slice = if end.width >= line.grapheme_len().saturating_sub(1) {
+                &line.raw_string[from..]
             } else {
-                line_string[start.width..end.width].to_owned()
+                &line.raw_string[from..std::cmp::max(from, line.byte_offset(end.width))]
             };
-            copy_string.push_str(&slice);
+            copy_string.push_str(slice);
         } else {
-            copy_string.push_str(&self.text[start.height].raw_string[start.width..]);
+            let first = &self.text[start.height];
+            copy_string.push_str(&first.raw_string[first.byte_offset(start.width)..]);
             copy_string.push('\n');
             for h in start.height.saturating_add(1)..end.height {
                 copy_string.push_str(&self.text[h].raw_string);
                 copy_string.push('\n');
             }
-            copy_string.push_str(&self.text[end.height].raw_string[..=end.width]);
+            let last = &self.text[end.height];
+            copy_string.push_str(&last.raw_string[..last.byte_offset(end.width.saturating_add(1))]);
         }
         copy_string
     }
@@ -539,6 +844,7 @@ mod tests {
             text: lines,
             filename: None,
             is_saved: true,
+            ..Default::default()
         };
 
         let mut pos = Position {
@@ -568,6 +874,7 @@ mod tests {
             text: lines,
             filename: None,
             is_saved: true,
+            ..Default::default()
         };
 
         let mut pos = Position {
@@ -597,6 +904,7 @@ mod tests {
             text: lines,
             filename: None,
             is_saved: true,
+            ..Default::default()
         };
 
         let mut pos = Position {
@@ -627,6 +935,7 @@ mod tests {
             text: lines,
             filename: None,
             is_saved: true,
+            ..Default::default()
         };
 
         let mut pos = Position {
@@ -657,6 +966,7 @@ mod tests {
             text: lines,
             filename: None,
             is_saved: true,
+            ..Default::default()
         };
 
         let mut pos = Position {
@@ -679,15 +989,59 @@ mod tests {
     }
 
     #[test]
-    fn num_tabs() {
-        let line1 = Line::from("              I have a bunch of text ");
-        let line2 = Line::from("This is a bunch more text");
-        let lines = vec![line1, line2];
-        let buff = Buffer {
-            text: lines,
-            filename: None,
-            is_saved: true,
+    fn new_lines_follow_the_indent() {
+        let mut buff = Buffer {
+            text: vec![Line::from("    if x {}"), Line::from("  text")],
+            filetype: FileType::for_file(Some("main.rs")),
+            ..Default::default()
+        };
+        let mut pos = Position {
+            height: 0,
+            width: 10,
+            max_width: usize::default(),
+        };
+        buff.add_new_line(&mut pos);
+        let lines: Vec<&str> = buff
+            .text
+            .iter()
+            .map(|line| line.raw_string.as_str())
+            .collect();
+        assert_eq!(lines, ["    if x {", "        ", "    }", "  text"]);
+        assert_eq!((pos.height, pos.width), (1, 8));
+
+        buff.dedent_for(&mut pos, '}');
+        assert_eq!((buff.text[1].raw_string.as_str(), pos.width), ("    ", 4));
+
+        pos.height = 3;
+        buff.open_line(&mut pos, false);
+        assert_eq!((pos.height, pos.width), (4, 2));
+        assert_eq!(buff.text[4].raw_string, "  ");
+    }
+
+    #[test]
+    fn segments_ending_on_an_empty_line() {
+        let buff = Buffer::from_text("abc\n\nxyz");
+        let at = |height, width| Position {
+            height,
+            width,
+            max_width: usize::default(),
+        };
+        assert_eq!(buff.get_segment(&at(0, 1), &at(1, 0)), "bc\n");
+        assert_eq!(buff.get_segment(&at(0, 0), &at(2, 1)), "abc\n\nxy");
+        assert_eq!(buff.get_segment(&at(1, 0), &at(1, 3)), "");
+    }
+
+    #[test]
+    fn segments_of_multibyte_lines() {
+        let buff = Buffer::from_text("héllo wörld\n日本語");
+        let at = |height, width| Position {
+            height,
+            width,
+            max_width: usize::default(),
         };
-        assert_eq!(buff.num_tabs(0), 3);
+        assert_eq!(buff.get_segment(&at(0, 1), &at(0, 4)), "éll");
+        assert_eq!(buff.get_segment(&at(0, 7), &at(0, 20)), "örld");
+        assert_eq!(buff.get_segment(&at(0, 7), &at(1, 1)), "örld\n日");
+        assert_eq!(buff.get_segment(&at(0, 10), &at(1, 3)), "d\n日本");
     }
 }
@@ -0,0 +1,151 @@
+// a full screen question answered with a single key
+// asked when the text in the editor and a text on disk disagree, d toggles a diff of the two
+use crate::editor::backend::read;
+use crate::editor::screen::Screen;
+use crate::editor::terminal::Size;
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use std::io::Error;
+
+/// the two texts a choice is about
+pub struct Texts<'a> {
+    pub old: &'a str,
+    pub new: &'a str,
+}
+
+/// shows the message until one of the keys is pressed and returns it
+/// the options line names the keys, d for the diff included
+pub fn choose(
+    size: &mut Size,
+    message: &[String],
+    options: &str,
+    keys: &[char],
+    texts: &Texts,
+) -> Result<char, Error> {
+    let mut show_diff = false;
+    loop {
+        Screen::clear();
+        Screen::hide_cursor();
+        let options_row = size.height.saturating_sub(1);
+        if show_diff {
+            let old: Vec<&str> = texts.old.lines().collect();
+            let new: Vec<&str> = texts.new.lines().collect();
+            // only the start of a long diff fits, which is enough to decide
+            for (row, line) in diff(&old, &new).iter().take(options_row).enumerate() {
+                Screen::render_line(row, line);
+            }
+        } else {
+            for (row, line) in message.iter().enumerate() {
+                Screen::render_line(row, line);
+            }
+        }
+        Screen::render_line(options_row, options);
+        Screen::present()?;
+
+        match read() {
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char(c),
+                ..
+            })) => {
+                if keys.contains(&c) {
+                    return Ok(c);
+                }
+                if c == 'd' {
+                    show_diff = !show_diff;
+                }
+            }
+            #[allow(clippy::as_conversions)]
+            Ok(Event::Resize(width, height)) => {
+                *size = Size {
+                    height: height as usize,
+                    width: width as usize,
+                };
+            }
+            _ => {}
+        }
+    }
+}
+
+/// the most lines compared with each other, beyond that the changed span is shown as replaced
+const DIFF_LIMIT: usize = 4_000_000;
+
+/// a line diff from old to new, every line prefixed with "  ", "- " or "+ "
+pub fn diff(old: &[&str], new: &[&str]) -> Vec<String> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len().saturating_sub(suffix)];
+    let new_middle = &new[prefix..new.len().saturating_sub(suffix)];
+
+    let mut lines: Vec<String> = old[..prefix]
+        .iter()
+        .map(|line| format!("  {line}"))
+        .collect();
+    if old_middle.len().saturating_mul(new_middle.len()) > DIFF_LIMIT {
+        lines.extend(old_middle.iter().map(|line| format!("- {line}")));
+        lines.extend(new_middle.iter().map(|line| format!("+ {line}")));
+    } else {
+        lines.extend(common_subsequence_diff(old_middle, new_middle));
+    }
+    lines.extend(
+        old[old.len().saturating_sub(suffix)..]
+            .iter()
+            .map(|line| format!("  {line}")),
+    );
+    lines
+}
+
+fn common_subsequence_diff(old: &[&str], new: &[&str]) -> Vec<String> {
+    // longest[i][j] is the longest common subsequence of old[i..] and new[j..]
+    let width = new.len().saturating_add(1);
+    let mut longest = vec![0_usize; old.len().saturating_add(1).saturating_mul(width)];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            let at = i.saturating_mul(width).saturating_add(j);
+            longest[at] = if old[i] == new[j] {
+                longest[at.saturating_add(width).saturating_add(1)].saturating_add(1)
+            } else {
+                std::cmp::max(
+                    longest[at.saturating_add(width)],
+                    longest[at.saturating_add(1)],
+                )
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        let at = i.saturating_mul(width).saturating_add(j);
+        if old[i] == new[j] {
+            lines.push(format!("  {}", old[i]));
+            i = i.saturating_add(1);
+            j = j.saturating_add(1);
+        } else if longest[at.saturating_add(width)] >= longest[at.saturating_add(1)] {
+            lines.push(format!("- {}", old[i]));
+            i = i.saturating_add(1);
+        } else {
+            lines.push(format!("+ {}", new[j]));
+            j = j.saturating_add(1);
+        }
+    }
+    lines.extend(old[i..].iter().map(|line| format!("- {line}")));
+    lines.extend(new[j..].iter().map(|line| format!("+ {line}")));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_marks_removed_and_added_lines() {
+        let old = ["a", "b", "c", "d"];
+        let new = ["a", "c", "x", "d"];
+        assert_eq!(diff(&old, &new), vec!["  a", "- b", "  c", "+ x", "  d"]);
+        assert_eq!(diff(&[], &["a"]), vec!["+ a"]);
+    }
+}
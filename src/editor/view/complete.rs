@@ -0,0 +1,506 @@
+// the completion menu of the text, it opens while a word or a path is typed
+// candidates are the words of the buffer, the entries of a directory and what the language server offers
+// they are ranked by a fuzzy match of what was typed and the selected one is previewed beside the menu
+use super::buffer::Buffer;
+use super::line::Line;
+use super::lsp::ui::markdown_rows;
+use super::prompt::directory_entries;
+use super::theme::Theme;
+use crate::editor::screen::Screen;
+use crate::editor::terminal::{Position, ScreenPosition, Size};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use unicode_width::UnicodeWidthStr;
+
+/// the shortest word that opens the menu, paths and trigger characters open it right away
+const MIN_WORD: usize = 2;
+/// the most entries shown at once, the menu scrolls through the rest
+const MENU_HEIGHT: usize = 8;
+const PREVIEW_WIDTH: usize = 40;
+/// a preview narrower than this is left out
+const MIN_PREVIEW_WIDTH: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Word(usize), // the first line it is on
+    Path(PathBuf),
+    Lsp(&'static str, String), // the kind and the detail
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    pub text: String,
+    pub source: Source,
+}
+
+impl Item {
+    /// the kind of entry, shown after its text
+    fn tag(&self) -> &'static str {
+        match &self.source {
+            Source::Word(_) => "word",
+            Source::Path(_) if self.text.ends_with('/') => "dir",
+            Source::Path(_) => "file",
+            Source::Lsp("", _) => "lsp",
+            Source::Lsp(kind, _) => kind,
+        }
+    }
+
+    /// what the selected entry shows beside the menu
+    /// the line a word is on, the start of a file, the entries of a directory or the documentation
+    fn preview(&self, buffer: &Buffer, width: usize) -> Vec<String> {
+        match &self.source {
+            Source::Word(line) => vec![
+                format!("line {}", line.saturating_add(1)),
+                buffer
+                    .text
+                    .get(*line)
+                    .map(|text| text.to_string().trim().to_string())
+                    .unwrap_or_default(),
+            ],
+            Source::Path(path) if path.is_dir() => {
+                let (_, _, mut entries) = directory_entries(&format!("{}/", path.display()));
+                entries.sort();
+                entries
+            }
+            Source::Path(path) => match fs::read_to_string(path) {
+                Ok(text) => text.lines().take(MENU_HEIGHT).map(str::to_string).collect(),
+                Err(_) => vec!["(not a text file)".to_string()],
+            },
+            Source::Lsp(_, detail) => markdown_rows(detail, width),
+        }
+    }
+}
+
+/// the kind of text being completed, a path keeps the directory it is in
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word,
+    Path(String),
+}
+
+/// the text before a column that a menu completes, with the column it starts at
+/// a path is the name after the last '/' of text without spaces, quotes or brackets when that
+/// directory exists, a word is letters, digits and '_'
+fn typed(line: &Line, column: usize) -> (usize, Token, String) {
+    let before = line.string.get(..column).unwrap_or(&line.string);
+    let is_word = |grapheme: &str| grapheme.chars().all(|c| c.is_alphanumeric() || c == '_');
+    let graphemes = |start: usize| -> String {
+        before[start..]
+            .iter()
+            .map(|fragment| fragment.grapheme.as_str())
+            .collect()
+    };
+    let token_start = before
+        .iter()
+        .rposition(|fragment| {
+            let grapheme = fragment.grapheme.as_str();
+            grapheme.trim().is_empty() || "\"'`()[]{}<>,=".contains(grapheme)
+        })
+        .map_or(0, |index| index.saturating_add(1));
+    let slash = before[token_start..]
+        .iter()
+        .rposition(|fragment| fragment.grapheme == "/");
+    if let Some(slash) = slash {
+        let name_start = token_start.saturating_add(slash).saturating_add(1);
+        let dir: String = before[token_start..name_start]
+            .iter()
+            .map(|fragment| fragment.grapheme.as_str())
+            .collect();
+        if Path::new(&dir).is_dir() {
+            return (name_start, Token::Path(dir), graphemes(name_start));
+        }
+    }
+    let word_start = before
+        .iter()
+        .rposition(|fragment| !is_word(&fragment.grapheme))
+        .map_or(0, |index| index.saturating_add(1));
+    (word_start, Token::Word, graphemes(word_start))
+}
+
+/// how well typed text matches a candidate, None unless all its characters are in it in order
+/// every match counts, more at the start, after a separator or a change of case and right after
+/// the previous match, lower case matches either case
+fn fuzzy_score(pattern: &str, candidate: &str) -> Option<usize> {
+    let candidate: Vec<char> = candidate.chars().collect();
+    let same = |wanted: char, c: char| {
+        wanted == c || (wanted.is_lowercase() && c.to_lowercase().eq(std::iter::once(wanted)))
+    };
+    let mut score = 0_usize;
+    let mut next = 0;
+    let mut previous = None;
+    for wanted in pattern.chars() {
+        let found = (next..candidate.len()).find(|&index| same(wanted, candidate[index]))?;
+        let before = found.checked_sub(1).map(|index| candidate[index]);
+        let bonus = match before {
+            None => 8,
+            Some(_) if previous == found.checked_sub(1) => 5,
+            Some(c)
+                if !c.is_alphanumeric()
+                    || (c.is_lowercase() && candidate[found].is_uppercase()) =>
+            {
+                4
+            }
+            Some(_) => 0,
+        };
+        let exact = usize::from(wanted == candidate[found]);
+        score = score
+            .saturating_add(1)
+            .saturating_add(bonus)
+            .saturating_add(exact);
+        previous = Some(found);
+        next = found.saturating_add(1);
+    }
+    Some(score)
+}
+
+/// the words of the buffer, the first time each is seen, without the one being typed
+fn buffer_words(buffer: &Buffer, cursor: &Position, start: usize) -> Vec<Item> {
+    let mut seen = HashSet::new();
+    let mut words = Vec::new();
+    for (index, line) in buffer.text.iter().enumerate() {
+        let mut word = String::new();
+        let mut word_start = 0;
+        let fragments = line
+            .string
+            .iter()
+            .map(|fragment| fragment.grapheme.as_str());
+        for (column, grapheme) in fragments.chain(std::iter::once(" ")).enumerate() {
+            if grapheme.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                if word.is_empty() {
+                    word_start = column;
+                }
+                word.push_str(grapheme);
+                continue;
+            }
+            let typing = index == cursor.height && word_start == start;
+            let word = std::mem::take(&mut word);
+            if !typing && word.chars().count() >= MIN_WORD && seen.insert(word.clone()) {
+                words.push(Item {
+                    text: word,
+                    source: Source::Word(index),
+                });
+            }
+        }
+    }
+    words
+}
+
+/// the entries of the directory a path is typed in
+fn path_entries(dir: &str) -> Vec<Item> {
+    let (_, _, mut entries) = directory_entries(dir);
+    entries.sort();
+    entries
+        .into_iter()
+        .map(|name| Item {
+            source: Source::Path(PathBuf::from(format!("{dir}{name}"))),
+            text: name,
+        })
+        .collect()
+}
+
+/// what the language server offers at the cursor, nothing when there is none or it fails
+fn server_items(buffer: &mut Buffer, cursor: &Position) -> Vec<Item> {
+    let Ok((client, lines)) = buffer.lsp_client() else {
+        return Vec::new();
+    };
+    if !client.completes() {
+        return Vec::new();
+    }
+    client
+        .completion(lines, cursor)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|item| Item {
+            text: item.text,
+            source: Source::Lsp(item.kind, item.detail),
+        })
+        .collect()
+}
+
+pub struct Menu {
+    line: usize,
+    start: usize, // the column the completed text starts at
+    token: Token,
+    candidates: Vec<Item>,
+    matches: Vec<usize>, // indices of the candidates matching the typed text, best first
+    selected: usize,     // an index into matches
+    scroll: usize,
+}
+
+impl Menu {
+    /// the menu for the text before the cursor, None when nothing completes it
+    /// an open menu keeps its candidates while the same word is typed and only narrows them
+    /// a trigger character of the language server opens it on an empty word
+    pub fn update(
+        menu: Option<Self>,
+        buffer: &mut Buffer,
+        cursor: &Position,
+        typed_char: Option<char>,
+    ) -> Option<Self> {
+        let (start, token, text) = typed(buffer.text.get(cursor.height)?, cursor.width);
+        let kept = menu.filter(|menu| {
+            menu.line == cursor.height && menu.start == start && menu.token == token
+        });
+        let mut menu = if let Some(menu) = kept {
+            menu
+        } else {
+            let triggered = text.is_empty()
+                && typed_char.is_some_and(|c| {
+                    buffer
+                        .lsp
+                        .as_ref()
+                        .is_some_and(|client| client.is_trigger(c))
+                });
+            let candidates = match &token {
+                Token::Path(dir) => path_entries(dir),
+                Token::Word if triggered => server_items(buffer, cursor),
+                Token::Word if text.chars().count() >= MIN_WORD => {
+                    let mut items = server_items(buffer, cursor);
+                    let offered: HashSet<String> =
+                        items.iter().map(|item| item.text.clone()).collect();
+                    items.extend(
+                        buffer_words(buffer, cursor, start)
+                            .into_iter()
+                            .filter(|word| !offered.contains(&word.text)),
+                    );
+                    items
+                }
+                Token::Word => return None,
+            };
+            Self {
+                line: cursor.height,
+                start,
+                token,
+                candidates,
+                matches: Vec::new(),
+                selected: 0,
+                scroll: 0,
+            }
+        };
+        menu.narrow(&text);
+        (!menu.matches.is_empty()).then_some(menu)
+    }
+
+    /// ranks the candidates against the typed text, the best first and shorter ones on a tie
+    /// a candidate that is the typed text already has nothing to add
+    fn narrow(&mut self, text: &str) {
+        let mut scored: Vec<(usize, usize)> = self
+            .candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.text != text)
+            .filter_map(|(index, item)| Some((fuzzy_score(text, &item.text)?, index)))
+            .collect();
+        scored.sort_by_key(|(score, index)| {
+            (
+                std::cmp::Reverse(*score),
+                self.candidates[*index].text.chars().count(),
+            )
+        });
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    /// moves the selection, from the last entry around to the first and back
+    pub fn select(&mut self, forward: bool) {
+        let count = self.matches.len();
+        self.selected = if forward {
+            self.selected
+                .saturating_add(1)
+                .checked_rem(count)
+                .unwrap_or(0)
+        } else {
+            self.selected
+                .checked_sub(1)
+                .unwrap_or(count.saturating_sub(1))
+        };
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll.saturating_add(MENU_HEIGHT) {
+            self.scroll = self.selected.saturating_sub(MENU_HEIGHT.saturating_sub(1));
+        }
+    }
+
+    fn selected_item(&self) -> Option<&Item> {
+        self.matches
+            .get(self.selected)
+            .and_then(|index| self.candidates.get(*index))
+    }
+
+    /// puts the selected entry in place of the typed text as a single edit
+    /// returns whether it was a directory, whose entries can be completed next
+    pub fn accept(&self, buffer: &mut Buffer, cursor: &mut Position) -> bool {
+        let Some(item) = self.selected_item() else {
+            return false;
+        };
+        cursor.width = buffer.replace_in_line(self.line, self.start..cursor.width, &item.text);
+        cursor.max_width = cursor.width;
+        matches!(item.source, Source::Path(_)) && item.text.ends_with('/')
+    }
+
+    /// where the completed text starts, the menu is drawn below it
+    pub fn anchor(&self) -> Position {
+        Position {
+            height: self.line,
+            width: self.start,
+            max_width: self.start,
+        }
+    }
+
+    /// draws the menu below the completed text, or above it when there is no room below
+    /// the preview of the selected entry goes beside it where there is room
+    pub fn draw(&self, buffer: &Buffer, anchor: ScreenPosition, size: &Size, theme: &Theme) {
+        let text_rows = size.height.saturating_sub(1);
+        let below = text_rows.saturating_sub(anchor.height.saturating_add(1));
+        let above = anchor.height;
+        let room = std::cmp::min(std::cmp::max(below, above), MENU_HEIGHT);
+        let visible: Vec<(usize, &Item)> = self
+            .matches
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(room)
+            .filter_map(|(position, index)| Some((position, self.candidates.get(*index)?)))
+            .collect();
+        let tag_width = visible
+            .iter()
+            .map(|(_, item)| item.tag().len())
+            .max()
+            .unwrap_or(0);
+        let text_width = visible
+            .iter()
+            .map(|(_, item)| item.text.width())
+            .max()
+            .unwrap_or(0)
+            .min((size.width / 2).saturating_sub(tag_width));
+        // a space on both sides and two between the text and the tag
+        let width = text_width.saturating_add(tag_width).saturating_add(4);
+        let placed_below = visible.len() <= below;
+        let top = |rows: usize| {
+            if placed_below {
+                anchor.height.saturating_add(1)
+            } else {
+                anchor.height.saturating_sub(rows)
+            }
+        };
+        let left = std::cmp::min(anchor.width, size.width.saturating_sub(width));
+        for (row, (position, item)) in visible.iter().enumerate() {
+            let text: String = item
+                .text
+                .chars()
+                .scan(0, |used, c| {
+                    *used = c.to_string().width().saturating_add(*used);
+                    (*used <= text_width).then_some(c)
+                })
+                .collect();
+            let padding = text_width.saturating_sub(text.width());
+            Screen::print_at(
+                ScreenPosition {
+                    height: top(visible.len()).saturating_add(row),
+                    width: left,
+                },
+                format!(" {text}{}  {:tag_width$} ", " ".repeat(padding), item.tag()),
+                if *position == self.selected {
+                    theme.selection
+                } else {
+                    theme.popup
+                },
+            );
+        }
+
+        // the preview goes right of the menu, or left of it when the menu is at the right edge
+        let right = size.width.saturating_sub(left.saturating_add(width));
+        let (preview_left, preview_width) = if right >= MIN_PREVIEW_WIDTH {
+            (
+                left.saturating_add(width),
+                std::cmp::min(right, PREVIEW_WIDTH),
+            )
+        } else if left >= MIN_PREVIEW_WIDTH {
+            let preview_width = std::cmp::min(left, PREVIEW_WIDTH);
+            (left.saturating_sub(preview_width), preview_width)
+        } else {
+            return;
+        };
+        let Some(item) = self.selected_item() else {
+            return;
+        };
+        let mut rows = item.preview(buffer, preview_width.saturating_sub(2));
+        rows.retain(|row| !row.trim().is_empty());
+        rows.truncate(room);
+        for (row, text) in rows.iter().enumerate() {
+            let text: String = text
+                .replace('\t', " ")
+                .chars()
+                .scan(0, |used, c| {
+                    *used = c.to_string().width().saturating_add(*used);
+                    (*used <= preview_width.saturating_sub(2)).then_some(c)
+                })
+                .collect();
+            let padding = preview_width.saturating_sub(text.width()).saturating_sub(1);
+            Screen::print_at(
+                ScreenPosition {
+                    height: top(rows.len()).saturating_add(row),
+                    width: preview_left,
+                },
+                format!(" {text}{}", " ".repeat(padding)),
+                theme.preview,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranked(pattern: &str, candidates: &[&str]) -> Vec<String> {
+        let mut menu = Menu {
+            line: 0,
+            start: 0,
+            token: Token::Word,
+            candidates: candidates
+                .iter()
+                .map(|text| Item {
+                    text: (*text).to_string(),
+                    source: Source::Word(0),
+                })
+                .collect(),
+            matches: Vec::new(),
+            selected: 0,
+            scroll: 0,
+        };
+        menu.narrow(pattern);
+        menu.matches
+            .iter()
+            .map(|index| menu.candidates[*index].text.clone())
+            .collect()
+    }
+
+    #[test]
+    fn ranks_prefixes_then_word_starts() {
+        assert_eq!(
+            ranked("fo", &["from", "format_line", "fold", "for", "fo", "bar"]),
+            ["for", "fold", "format_line", "from"]
+        );
+        // the starts of words beat letters inside them, upper case only matches itself
+        assert_eq!(
+            ranked("gn", &["gain", "get_name", "getName", "GN"]),
+            ["get_name", "GN", "getName", "gain"]
+        );
+        assert_eq!(ranked("N", &["getName", "name"]), ["getName"]);
+    }
+
+    #[test]
+    fn finds_the_text_to_complete() {
+        // tests run in the crate, where src is a directory
+        let line = Line::from("open(src/ed, x/y.and_th");
+        assert_eq!(
+            typed(&line, 11),
+            (9, Token::Path("src/".into()), "ed".into())
+        );
+        assert_eq!(typed(&line, 23), (17, Token::Word, "and_th".into()));
+        assert_eq!(typed(&line, 12), (12, Token::Word, String::new()));
+    }
+}
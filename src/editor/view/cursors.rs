@@ -0,0 +1,333 @@
+// more cursors than the one of the view, typing, deleting, word motions and pastes happen at
+// every one of them while the view's own cursor stays the primary one
+// an edit runs at the last cursor in the buffer first and moves the cursors after it with the
+// text it changed, so the ones before it still point where they did, cursors that meet are merged
+use super::buffer::Buffer;
+use super::line::Line;
+use crate::editor::terminal::Position;
+use std::ops::RangeInclusive;
+
+#[derive(Default)]
+pub struct Cursors {
+    others: Vec<Position>, // every cursor but the primary
+}
+
+/// what an edit at a cursor did to the text after it
+struct Change {
+    from: Position,
+    to: Position,
+    lines: (usize, usize), // the length of the buffer before and after
+    width: (usize, usize), // the graphemes of the cursor line before and after
+}
+
+impl Change {
+    /// moves a cursor after the edit along with its text
+    fn follow(&self, pos: &mut Position) {
+        if pos.height != self.from.height {
+            pos.height = pos
+                .height
+                .saturating_add(self.lines.1)
+                .saturating_sub(self.lines.0);
+        } else if self.lines.0 == self.lines.1 {
+            pos.width = pos
+                .width
+                .saturating_add(self.width.1)
+                .saturating_sub(self.width.0);
+        } else {
+            // the line was split or joined, the text after the cursor went with it
+            pos.width = self
+                .to
+                .width
+                .saturating_add(pos.width.saturating_sub(self.from.width));
+            pos.height = self.to.height;
+        }
+        pos.max_width = pos.width;
+    }
+}
+
+fn is_word(grapheme: &str) -> bool {
+    grapheme
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+impl Cursors {
+    pub fn is_empty(&self) -> bool {
+        self.others.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.others.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Position> {
+        self.others.iter()
+    }
+
+    /// drops the cursors past the end of the buffer and keeps the others on their lines
+    pub fn clamp(&mut self, buffer: &Buffer) {
+        self.others.retain(|pos| pos.height < buffer.len());
+        for pos in &mut self.others {
+            pos.width = std::cmp::min(pos.width, buffer.text[pos.height].grapheme_len());
+        }
+    }
+
+    /// a cursor more, false when there is one there already
+    pub fn add(&mut self, primary: &Position, pos: Position) -> bool {
+        if *primary == pos || self.others.contains(&pos) {
+            return false;
+        }
+        self.others.push(pos);
+        true
+    }
+
+    /// every cursor the last in the buffer first, the primary marked with true
+    pub fn take(&mut self, primary: Position) -> Vec<(Position, bool)> {
+        let mut all: Vec<(Position, bool)> = self
+            .others
+            .drain(..)
+            .map(|pos| (pos, false))
+            .chain(std::iter::once((primary, true)))
+            .collect();
+        all.sort_by_key(|(pos, _)| std::cmp::Reverse((pos.height, pos.width)));
+        all
+    }
+
+    /// runs an edit at every cursor of `take`, the ones after it follow the text it changed
+    /// the target is what the edit changes, the buffer or what holds it
+    pub fn edit<T, B, E>(cursors: &mut [(Position, bool)], target: &mut T, buffer: B, mut edit: E)
+    where
+        B: Fn(&T) -> &Buffer,
+        E: FnMut(&mut T, &mut Position),
+    {
+        let width =
+            |buffer: &Buffer, line: usize| buffer.text.get(line).map_or(0, Line::grapheme_len);
+        for index in 0..cursors.len() {
+            let from = cursors[index].0;
+            let before = (buffer(target).len(), width(buffer(target), from.height));
+            let mut to = from;
+            edit(target, &mut to);
+            let change = Change {
+                from,
+                to,
+                lines: (before.0, buffer(target).len()),
+                width: (before.1, width(buffer(target), from.height)),
+            };
+            for (pos, _) in &mut cursors[..index] {
+                change.follow(pos);
+            }
+            cursors[index].0 = to;
+        }
+    }
+
+    /// puts the cursors back after an edit, returns the primary
+    pub fn restore(&mut self, cursors: Vec<(Position, bool)>) -> Position {
+        let primary = cursors
+            .iter()
+            .find(|(_, primary)| *primary)
+            .map(|(pos, _)| *pos)
+            .unwrap_or_default();
+        for (pos, is_primary) in cursors {
+            if !is_primary {
+                self.add(&primary, pos);
+            }
+        }
+        primary
+    }
+
+    /// a cursor on the line above the first cursor or below the last, at the column it keeps
+    pub fn add_line(&mut self, primary: &Position, buffer: &Buffer, below: bool) -> bool {
+        let all = self.others.iter().chain(std::iter::once(primary));
+        let edge = if below {
+            all.max_by_key(|pos| (pos.height, pos.width))
+        } else {
+            all.min_by_key(|pos| (pos.height, pos.width))
+        };
+        let Some(edge) = edge.copied() else {
+            return false;
+        };
+        let height = if below {
+            edge.height.saturating_add(1)
+        } else if edge.height > 0 {
+            edge.height.saturating_sub(1)
+        } else {
+            return false;
+        };
+        let Some(line) = buffer.text.get(height) else {
+            return false;
+        };
+        let column = std::cmp::max(edge.width, edge.max_width);
+        let pos = Position {
+            height,
+            width: std::cmp::min(column, line.grapheme_len()),
+            max_width: column,
+        };
+        self.add(primary, pos)
+    }
+
+    /// a cursor on every line of a range but the primary's, at the column of the primary
+    pub fn add_lines(&mut self, primary: &Position, buffer: &Buffer, lines: RangeInclusive<usize>) {
+        for height in lines {
+            let Some(line) = buffer.text.get(height) else {
+                break;
+            };
+            let width = std::cmp::min(primary.width, line.grapheme_len());
+            self.add(
+                primary,
+                Position {
+                    height,
+                    width,
+                    max_width: primary.width,
+                },
+            );
+        }
+    }
+
+    /// a cursor on the next whole word like the one at the primary, after the last cursor
+    /// and from the top again, at the same place in the word
+    pub fn add_next_match(&mut self, primary: &Position, buffer: &Buffer) -> bool {
+        let Some(line) = buffer.text.get(primary.height) else {
+            return false;
+        };
+        let graphemes: Vec<&str> = line
+            .string
+            .iter()
+            .map(|fragment| fragment.grapheme.as_str())
+            .collect();
+        let at = std::cmp::min(primary.width, graphemes.len());
+        let start = graphemes[..at]
+            .iter()
+            .rposition(|grapheme| !is_word(grapheme))
+            .map_or(0, |index| index.saturating_add(1));
+        let end = graphemes[at..]
+            .iter()
+            .position(|grapheme| !is_word(grapheme))
+            .map_or(graphemes.len(), |index| at.saturating_add(index));
+        if start == end {
+            return false;
+        }
+        let word = graphemes[start..end].concat();
+        let length = end.saturating_sub(start);
+        let whole = |pos: &Position| {
+            let line = &buffer.text[pos.height].string;
+            let before = pos
+                .width
+                .checked_sub(1)
+                .and_then(|index| line.get(index))
+                .is_some_and(|fragment| is_word(&fragment.grapheme));
+            let after = line
+                .get(pos.width.saturating_add(length))
+                .is_some_and(|fragment| is_word(&fragment.grapheme));
+            !before && !after
+        };
+        let candidates: Vec<Position> = buffer
+            .search(&word)
+            .into_iter()
+            .filter(whole)
+            .map(|pos| {
+                let width = pos.width.saturating_add(at.saturating_sub(start));
+                Position {
+                    height: pos.height,
+                    width,
+                    max_width: width,
+                }
+            })
+            .filter(|pos| *pos != *primary && !self.others.contains(pos))
+            .collect();
+        let last = self
+            .others
+            .iter()
+            .chain(std::iter::once(primary))
+            .map(|pos| (pos.height, pos.width))
+            .max()
+            .unwrap_or_default();
+        let next = candidates
+            .iter()
+            .find(|pos| (pos.height, pos.width) > last)
+            .or_else(|| candidates.first());
+        match next {
+            Some(pos) => self.add(primary, *pos),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(text: &str) -> Buffer {
+        Buffer {
+            text: text.lines().map(Line::from).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn at(height: usize, width: usize) -> Position {
+        Position {
+            height,
+            width,
+            max_width: width,
+        }
+    }
+
+    #[test]
+    fn edits_at_every_cursor_keep_the_others_in_place() {
+        let mut text = buffer("ab ab\nab");
+        let mut cursors = Cursors::default();
+        cursors.add(&at(0, 1), at(0, 4));
+        cursors.add(&at(0, 1), at(1, 1));
+        let mut all = cursors.take(at(0, 1));
+        Cursors::edit(
+            &mut all,
+            &mut text,
+            |buffer| buffer,
+            |buffer, pos| {
+                buffer.update_line_insert(pos, 'x');
+            },
+        );
+        let primary = cursors.restore(all);
+        assert_eq!(text.text[0].raw_string, "axb axb");
+        assert_eq!(text.text[1].raw_string, "axb");
+        assert_eq!(primary, at(0, 2));
+        assert_eq!(
+            cursors.iter().copied().collect::<Vec<_>>(),
+            [at(1, 2), at(0, 6)]
+        );
+
+        // a line split moves the cursors after it down
+        let mut all = cursors.take(primary);
+        Cursors::edit(&mut all, &mut text, |buffer| buffer, Buffer::add_new_line);
+        let primary = cursors.restore(all);
+        assert_eq!(primary, at(1, 0));
+        assert_eq!(
+            text.text
+                .iter()
+                .map(|line| line.raw_string.as_str())
+                .collect::<Vec<_>>(),
+            ["ax", "b ax", "b", "ax", "b"]
+        );
+        assert!(cursors.iter().any(|pos| *pos == at(2, 0)));
+        assert!(cursors.iter().any(|pos| *pos == at(4, 0)));
+    }
+
+    #[test]
+    fn adds_cursors_at_matches_and_lines() {
+        let text = buffer("let foo = foobar;\nfoo(foo)");
+        let mut cursors = Cursors::default();
+        let primary = at(0, 5);
+        assert!(cursors.add_next_match(&primary, &text));
+        assert!(cursors.add_next_match(&primary, &text));
+        assert!(!cursors.add_next_match(&primary, &text));
+        assert_eq!(
+            cursors.iter().copied().collect::<Vec<_>>(),
+            [at(1, 1), at(1, 5)]
+        );
+
+        cursors.clear();
+        assert!(!cursors.add_line(&primary, &text, false));
+        assert!(cursors.add_line(&primary, &text, true));
+        assert_eq!(cursors.iter().copied().collect::<Vec<_>>(), [at(1, 5)]);
+    }
+}
@@ -0,0 +1,151 @@
+// noticing when another program changes the file of a buffer
+// the modified time and size are compared first, the text is only hashed when they moved
+// so a touch or a rewrite with the same text does not count as a change
+use super::buffer::Buffer;
+use super::choice::{choose, Texts};
+use super::swap::hash;
+use crate::editor::terminal::Size;
+use std::fs;
+use std::io::Error;
+use std::time::SystemTime;
+
+/// what the file looked like when the buffer last read or wrote it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+impl FileStamp {
+    pub fn of(text: &str, meta: &fs::Metadata) -> Self {
+        Self {
+            modified: meta.modified().ok(),
+            len: meta.len(),
+            hash: hash(text),
+        }
+    }
+
+    /// the stamp of a file as it is on disk now, None when it cannot be read
+    pub fn read(filename: &str) -> Option<Self> {
+        let meta = fs::metadata(filename).ok()?;
+        let text = fs::read_to_string(filename).ok()?;
+        Some(Self::of(&text, &meta))
+    }
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum DiskChange {
+    None,
+    /// the file holds other text, with the stamp to take if the buffer follows it
+    Changed(String, FileStamp),
+    Removed,
+}
+
+/// compares a file with the stamp taken when the buffer last read or wrote it
+/// a buffer that never had a file on disk has nothing to compare against
+/// the stamp is refreshed when only the modified time moved
+pub fn check(stamp: &mut Option<FileStamp>, filename: &str) -> DiskChange {
+    let Some(known) = stamp else {
+        return DiskChange::None;
+    };
+    let Ok(meta) = fs::metadata(filename) else {
+        return DiskChange::Removed;
+    };
+    if meta.modified().ok() == known.modified && meta.len() == known.len {
+        return DiskChange::None;
+    }
+    let Ok(text) = fs::read_to_string(filename) else {
+        return DiskChange::None;
+    };
+    let current = FileStamp::of(&text, &meta);
+    if current.hash == known.hash {
+        *stamp = Some(current);
+        return DiskChange::None;
+    }
+    DiskChange::Changed(text, current)
+}
+
+/// what became of a buffer after its file was checked
+#[derive(PartialEq, Eq, Debug)]
+pub enum Resolution {
+    Unchanged,
+    Kept,
+    Reloaded,
+}
+
+/// brings a buffer back in line with a file changed on disk
+/// asks whether to load the file or keep the buffer, unless autoread can load it quietly
+pub fn resolve(buffer: &mut Buffer, size: &mut Size) -> Result<Resolution, Error> {
+    let (text, stamp) = match buffer.disk_change() {
+        DiskChange::None => return Ok(Resolution::Unchanged),
+        DiskChange::Removed => {
+            // the next write creates the file again
+            buffer.stamp = None;
+            buffer.is_saved = false;
+            return Ok(Resolution::Kept);
+        }
+        DiskChange::Changed(text, stamp) => (text, stamp),
+    };
+    if buffer.autoread && buffer.is_saved {
+        buffer.reload(&text);
+        return Ok(Resolution::Reloaded);
+    }
+
+    let filename = buffer.filename.clone().unwrap_or_default();
+    let message = [
+        format!("{filename} changed on disk since it was read"),
+        if buffer.is_saved {
+            "The buffer has no unsaved changes".to_string()
+        } else {
+            "The buffer also has unsaved changes".to_string()
+        },
+    ];
+    let key = choose(
+        size,
+        &message,
+        "l = load | k = keep mine | d = diff",
+        &['l', 'k'],
+        &Texts {
+            old: &buffer.contents(),
+            new: &text,
+        },
+    )?;
+    if key == 'l' {
+        buffer.reload(&text);
+        return Ok(Resolution::Reloaded);
+    }
+    // the buffer now differs from the file, so writing it is a change
+    buffer.stamp = Some(stamp);
+    buffer.is_saved = false;
+    Ok(Resolution::Kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notices_changed_text_but_not_a_touch() {
+        let file = std::env::temp_dir().join(format!("mini-vim-disk-{}", std::process::id()));
+        let filename = file.to_str().unwrap();
+        fs::write(&file, "one\n").unwrap();
+        let mut stamp = FileStamp::read(filename);
+        assert_eq!(check(&mut stamp, filename), DiskChange::None);
+
+        // same text written again
+        fs::write(&file, "one\n").unwrap();
+        assert_eq!(check(&mut stamp, filename), DiskChange::None);
+
+        fs::write(&file, "one\ntwo\n").unwrap();
+        let DiskChange::Changed(text, _) = check(&mut stamp, filename) else {
+            panic!("the new text should be noticed");
+        };
+        assert_eq!(text, "one\ntwo\n");
+
+        fs::remove_file(&file).unwrap();
+        assert_eq!(check(&mut stamp, filename), DiskChange::Removed);
+        // a new file was never on disk
+        assert_eq!(check(&mut None, filename), DiskChange::None);
+    }
+}
@@ -0,0 +1,424 @@
+// a directory listing to move around in and open files from, like netrw
+// directories are entered in place, a chosen file is handed back to the editor to load
+// files and directories can be created and renamed, deleting asks first
+use super::prompt::{Completion, Prompt, PromptAction};
+use super::status::Status;
+use crate::editor::backend::read;
+use crate::editor::editorcommands::PromptCommand;
+use crate::editor::screen::{Screen, Style};
+use crate::editor::terminal::{Mode, ScreenPosition, Size};
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use std::env::current_dir;
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+const PARENT: &str = "..";
+
+/// how the explorer was left
+#[derive(Debug, PartialEq, Eq)]
+pub enum Exit {
+    Open(PathBuf),
+    Quit,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Entry {
+    name: String,
+    is_dir: bool,
+}
+
+impl Entry {
+    /// directories end in a slash
+    fn label(&self) -> String {
+        if self.is_dir {
+            format!("{}/", self.name)
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+pub struct Explorer {
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    selected: usize,
+    offset: usize, // first entry on screen
+    message: String,
+    selection: Style, // of the selected entry, from the theme
+}
+
+impl Explorer {
+    pub fn new(dir: &Path, selection: Style) -> Result<Self, Error> {
+        let mut explorer = Self {
+            dir: fs::canonicalize(dir)?,
+            entries: Vec::new(),
+            selected: 0,
+            offset: 0,
+            message: String::new(),
+            selection,
+        };
+        explorer.refresh(None)?;
+        Ok(explorer)
+    }
+
+    /// parent first, then directories, then files, each sorted by name
+    fn list(dir: &Path) -> Result<Vec<Entry>, Error> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            entries.push(Entry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                // a link to a directory is entered like one
+                is_dir: entry.path().is_dir(),
+            });
+        }
+        entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+        if dir.parent().is_some() {
+            entries.insert(
+                0,
+                Entry {
+                    name: PARENT.to_string(),
+                    is_dir: true,
+                },
+            );
+        }
+        Ok(entries)
+    }
+
+    /// reads the directory again, selecting the named entry or the first one below the parent
+    fn refresh(&mut self, select: Option<&str>) -> Result<(), Error> {
+        self.entries = Self::list(&self.dir)?;
+        let first = usize::from(self.entries.len() > 1 && self.entries[0].name == PARENT);
+        self.selected = select
+            .and_then(|name| self.entries.iter().position(|entry| entry.name == name))
+            .unwrap_or(first);
+        self.offset = 0;
+        Ok(())
+    }
+
+    pub fn run(&mut self, size: &mut Size) -> Result<Exit, Error> {
+        loop {
+            self.render(size)?;
+            match read()? {
+                Event::Key(KeyEvent { code, .. }) => match code {
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        self.selected = self
+                            .selected
+                            .saturating_add(1)
+                            .min(self.entries.len().saturating_sub(1));
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        self.selected = self.selected.saturating_sub(1);
+                    }
+                    KeyCode::Enter | KeyCode::Char('l') | KeyCode::Right => {
+                        if let Some(path) = self.enter() {
+                            return Ok(Exit::Open(path));
+                        }
+                    }
+                    KeyCode::Char('h' | '-') | KeyCode::Backspace | KeyCode::Left => {
+                        self.leave();
+                    }
+                    KeyCode::Char('%') => self.create(size, false)?,
+                    KeyCode::Char('d') => self.create(size, true)?,
+                    KeyCode::Char('R') => self.rename(size)?,
+                    KeyCode::Char('D') => self.delete(size)?,
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(Exit::Quit),
+                    _ => {}
+                },
+                #[allow(clippy::as_conversions)]
+                Event::Resize(width, height) => {
+                    *size = Size {
+                        height: height as usize,
+                        width: width as usize,
+                    };
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// rows left for entries between the path at the top and the message and status lines
+    fn list_height(size: &Size) -> usize {
+        size.height.saturating_sub(3).max(1)
+    }
+
+    fn render(&mut self, size: &Size) -> Result<(), Error> {
+        let height = Self::list_height(size);
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset.saturating_add(height) {
+            self.offset = self.selected.saturating_sub(height.saturating_sub(1));
+        }
+
+        Screen::clear();
+        Screen::hide_cursor();
+        Screen::render_line(0, format!("{}/", self.dir.display()));
+        for (row, entry) in self
+            .entries
+            .iter()
+            .enumerate()
+            .skip(self.offset)
+            .take(height)
+        {
+            let position = ScreenPosition {
+                height: row.saturating_sub(self.offset).saturating_add(1),
+                width: 0,
+            };
+            let style = if row == self.selected {
+                self.selection
+            } else {
+                Style::default()
+            };
+            Screen::print_at(position, entry.label(), style);
+        }
+        Screen::render_line(size.height.saturating_sub(2), &self.message);
+        Status {
+            path: self.dir.to_str(),
+            position: Some((self.selected.saturating_add(1), None)),
+            lines: self.entries.len(),
+            ..Status::new(Mode::Explorer)
+        }
+        .render(size);
+        Screen::present()
+    }
+
+    /// goes into the selected directory or returns the selected file
+    fn enter(&mut self) -> Option<PathBuf> {
+        let entry = self.entries.get(self.selected)?;
+        if entry.name == PARENT {
+            self.leave();
+            return None;
+        }
+        let path = self.dir.join(&entry.name);
+        if !entry.is_dir {
+            return Some(relative(path));
+        }
+        let name = entry.name.clone();
+        let previous = std::mem::replace(&mut self.dir, path);
+        if let Err(err) = self.refresh(None) {
+            self.message = format!("Cannot open {name}: {err}");
+            self.dir = previous;
+            let _ = self.refresh(Some(&name));
+        } else {
+            self.message.clear();
+        }
+        None
+    }
+
+    /// goes up a directory with the one just left selected
+    fn leave(&mut self) {
+        let Some(parent) = self.dir.parent().map(Path::to_path_buf) else {
+            return;
+        };
+        let left = entry_name(&self.dir, &parent);
+        let previous = std::mem::replace(&mut self.dir, parent);
+        if let Err(err) = self.refresh(Some(&left)) {
+            self.message = format!("Cannot open {}: {err}", self.dir.display());
+            self.dir = previous;
+            let _ = self.refresh(None);
+        } else {
+            self.message.clear();
+        }
+    }
+
+    /// asks for a name on the message line, None when cancelled or left empty
+    fn ask(&mut self, size: &mut Size, prompt: Prompt) -> Result<Option<String>, Error> {
+        let mut prompt = prompt;
+        loop {
+            self.render(size)?;
+            prompt.render(size.height.saturating_sub(2), size.width);
+            Screen::present()?;
+            let Ok(command) = PromptCommand::try_from(read()?) else {
+                continue;
+            };
+            match prompt.apply(command) {
+                PromptAction::Submit(text) => {
+                    return Ok(Some(text.trim().to_string()).filter(|text| !text.is_empty()));
+                }
+                PromptAction::Cancel => return Ok(None),
+                PromptAction::Resize(new_size) => *size = new_size,
+                PromptAction::Continue => {}
+            }
+        }
+    }
+
+    /// asks a yes or no question on the message line, anything but y is a no
+    fn confirm(&mut self, size: &mut Size, question: String) -> Result<bool, Error> {
+        self.message = question;
+        loop {
+            self.render(size)?;
+            match read()? {
+                Event::Key(KeyEvent { code, .. }) => {
+                    self.message.clear();
+                    return Ok(matches!(code, KeyCode::Char('y' | 'Y')));
+                }
+                #[allow(clippy::as_conversions)]
+                Event::Resize(width, height) => {
+                    *size = Size {
+                        height: height as usize,
+                        width: width as usize,
+                    };
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn create(&mut self, size: &mut Size, is_dir: bool) -> Result<(), Error> {
+        let label = if is_dir {
+            "New directory: "
+        } else {
+            "New file: "
+        };
+        let Some(name) = self.ask(size, Prompt::new(label, Completion::None))? else {
+            self.message.clear();
+            return Ok(());
+        };
+        let path = self.dir.join(&name);
+        let created = if is_dir {
+            fs::create_dir(&path)
+        } else {
+            fs::File::create_new(&path).map(|_| ())
+        };
+        self.report(
+            created,
+            &format!("Created {name}"),
+            &format!("Cannot create {name}"),
+        );
+        self.refresh(Some(&entry_name(&path, &self.dir)))
+    }
+
+    fn rename(&mut self, size: &mut Size) -> Result<(), Error> {
+        let Some(entry) = self.entries.get(self.selected) else {
+            return Ok(());
+        };
+        if entry.name == PARENT {
+            return Ok(());
+        }
+        let old = entry.name.clone();
+        let prompt = Prompt::new("Rename to: ", Completion::None).with_text(&old);
+        let Some(new) = self.ask(size, prompt)? else {
+            self.message.clear();
+            return Ok(());
+        };
+        let to = self.dir.join(&new);
+        // rename replaces an existing file without asking, which is left to the delete
+        let renamed = if to.exists() {
+            Err(Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "it already exists",
+            ))
+        } else {
+            fs::rename(self.dir.join(&old), &to)
+        };
+        let select = if renamed.is_ok() {
+            entry_name(&to, &self.dir)
+        } else {
+            old.clone()
+        };
+        self.report(
+            renamed,
+            &format!("Renamed {old} to {new}"),
+            &format!("Cannot rename {old}"),
+        );
+        self.refresh(Some(&select))
+    }
+
+    fn delete(&mut self, size: &mut Size) -> Result<(), Error> {
+        let Some(entry) = self.entries.get(self.selected) else {
+            return Ok(());
+        };
+        if entry.name == PARENT {
+            return Ok(());
+        }
+        let path = self.dir.join(&entry.name);
+        let label = entry.label();
+        let is_dir = entry.is_dir && !path.is_symlink();
+        let has_contents =
+            is_dir && fs::read_dir(&path).is_ok_and(|mut contents| contents.next().is_some());
+        let question = if has_contents {
+            format!("Delete {label} and everything in it? (y/n)")
+        } else {
+            format!("Delete {label}? (y/n)")
+        };
+        if !self.confirm(size, question)? {
+            return Ok(());
+        }
+        let deleted = if is_dir {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        let selected = self.selected;
+        self.report(
+            deleted,
+            &format!("Deleted {label}"),
+            &format!("Cannot delete {label}"),
+        );
+        self.refresh(None)?;
+        // the entry that took its place stays selected
+        if selected < self.entries.len() {
+            self.selected = selected;
+        } else {
+            self.selected = self.entries.len().saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    fn report(&mut self, result: Result<(), Error>, done: &str, failed: &str) {
+        self.message = match result {
+            Ok(()) => done.to_string(),
+            Err(err) => format!("{failed}: {err}"),
+        };
+    }
+}
+
+/// the name a path has in the listing of a directory, ie the first part below it
+fn entry_name(path: &Path, dir: &Path) -> String {
+    path.strip_prefix(dir)
+        .ok()
+        .and_then(|rest| rest.components().next())
+        .map_or_else(
+            || {
+                path.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned()
+            },
+            |first| first.as_os_str().to_string_lossy().into_owned(),
+        )
+}
+
+/// a path below the working directory is shown relative to it, like a file named on the command line
+fn relative(path: PathBuf) -> PathBuf {
+    current_dir()
+        .ok()
+        .and_then(|cwd| fs::canonicalize(cwd).ok())
+        .and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf))
+        .unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_directories_before_files() {
+        let dir = std::env::temp_dir().join(format!("mini-vim-explorer-{}", std::process::id()));
+        fs::create_dir_all(dir.join("b")).unwrap();
+        fs::create_dir_all(dir.join("d")).unwrap();
+        fs::write(dir.join("a.txt"), "").unwrap();
+        fs::write(dir.join("c.txt"), "").unwrap();
+
+        let labels: Vec<String> = Explorer::list(&dir)
+            .unwrap()
+            .iter()
+            .map(Entry::label)
+            .collect();
+        assert_eq!(labels, ["../", "b/", "d/", "a.txt", "c.txt"]);
+        assert_eq!(entry_name(&dir.join("b/inner"), &dir), "b");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,502 @@
+// settings that follow the type of the file, ie how its lines are indented
+// and which brackets and quotes are typed in pairs, how comments are written
+// every extension has built in ones, filetype.conf in the config directory changes them with
+// `extension.option = value` lines, `*` stands for every file:
+//   *.indent = 2
+//   go.tabs = on
+//   py.indent_after = : ( [ {
+//   md.pairs = ( [ ` *
+//   ini.comment = ;
+//   rs.keywords = fn let mut
+use crate::editor::config::config_dir;
+use std::fs;
+use std::path::Path;
+
+const CONFIG_FILE: &str = "filetype.conf";
+
+/// the languages with braces, a level opens after an opening bracket or an arrow
+const BRACE_LANGUAGES: [&str; 16] = [
+    "rs", "c", "h", "cpp", "hpp", "cc", "java", "js", "ts", "jsx", "tsx", "go", "css", "json",
+    "cs", "sh",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileType {
+    pub indent_width: usize,                     // columns of a level of indent
+    pub tabs: bool,                              // a tab for every level instead of spaces
+    pub auto_indent: bool, // a new line starts with the indent of the one before
+    pub smart_indent: bool, // and a level more after an opener, one less on a closer
+    pub indent_after: Vec<String>, // line endings that open a level, ie "{" or ":"
+    pub dedent_on: Vec<char>, // typed first on a line they close a level, ie '}'
+    pub pairs: Vec<(char, char)>, // an opener typed gets its closer, ie ('(', ')')
+    pub comment: Option<String>, // starts a comment to the end of the line, ie "//"
+    pub block_comment: Option<(String, String)>, // for files without line comments, ie html
+    pub keywords: Vec<String>, // colored by the theme, without any the text is not colored at all
+}
+
+impl Default for FileType {
+    /// plain text keeps the indent of the line before and nothing more
+    fn default() -> Self {
+        Self {
+            indent_width: 4,
+            tabs: false,
+            auto_indent: true,
+            smart_indent: false,
+            indent_after: Vec::new(),
+            dedent_on: Vec::new(),
+            pairs: pairs_of("( [ { \" '"),
+            comment: None,
+            block_comment: None,
+            keywords: Vec::new(),
+        }
+    }
+}
+
+impl FileType {
+    /// the settings for a file, built in for its extension and then the ones in filetype.conf
+    pub fn for_file(filename: Option<&str>) -> Self {
+        let extension = filename
+            .and_then(|name| Path::new(name).extension())
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        let mut filetype = Self::built_in(extension);
+        if let Some(conf) =
+            config_dir().and_then(|dir| fs::read_to_string(dir.join(CONFIG_FILE)).ok())
+        {
+            filetype.configure(&conf, extension);
+        }
+        filetype
+    }
+
+    fn built_in(extension: &str) -> Self {
+        let words = |text: &str| text.split_whitespace().map(str::to_string).collect();
+        let mut filetype = Self::default();
+        if BRACE_LANGUAGES.contains(&extension) {
+            filetype.smart_indent = true;
+            filetype.indent_after = words("{ ( [ =>");
+            filetype.dedent_on = vec!['}', ')', ']'];
+        }
+        let (comment, block_comment) = match extension {
+            "rs" | "c" | "h" | "cpp" | "hpp" | "cc" | "java" | "js" | "ts" | "jsx" | "tsx"
+            | "go" | "cs" | "kt" | "swift" | "scss" | "zig" | "dart" => (Some("//"), None),
+            "py" | "sh" | "bash" | "zsh" | "rb" | "pl" | "yml" | "yaml" | "toml" | "conf"
+            | "mk" | "r" | "nix" | "fish" => (Some("#"), None),
+            "lua" | "sql" | "hs" | "elm" => (Some("--"), None),
+            "vim" => (Some("\""), None),
+            "tex" | "erl" => (Some("%"), None),
+            "lisp" | "el" | "clj" | "scm" | "ini" | "asm" => (Some(";"), None),
+            "css" => (None, Some(("/*", "*/"))),
+            "html" | "xml" | "md" | "svg" => (None, Some(("<!--", "-->"))),
+            _ => (None, None),
+        };
+        filetype.comment = comment.map(str::to_string);
+        filetype.block_comment = block_comment.map(|(open, close)| (open.into(), close.into()));
+        filetype.keywords = words(keywords_of(extension));
+        match extension {
+            "py" => {
+                filetype.smart_indent = true;
+                filetype.indent_after = words(": ( [ {");
+                filetype.dedent_on = vec![')', ']', '}'];
+            }
+            "go" | "mk" => filetype.tabs = true,
+            // a quote there starts a lifetime as often as a character
+            "rs" => filetype.pairs = pairs_of("( [ { \""),
+            "js" | "ts" | "jsx" | "tsx" | "json" | "css" | "html" | "yml" | "yaml" => {
+                filetype.indent_width = 2;
+            }
+            _ => {}
+        }
+        filetype
+    }
+
+    /// applies the lines of filetype.conf for every file and then those for the extension
+    /// lines that do not parse are left out
+    fn configure(&mut self, conf: &str, extension: &str) {
+        let options: Vec<(&str, &str, &str)> = conf
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .filter_map(|line| {
+                let (key, value) = line.split_once('=')?;
+                let (name, option) = key.trim().split_once('.')?;
+                Some((name, option, value.trim()))
+            })
+            .collect();
+        for (_, option, value) in options.iter().filter(|(name, _, _)| *name == "*") {
+            let _ = self.set(option, value);
+        }
+        for (_, option, value) in options.iter().filter(|(name, _, _)| *name == extension) {
+            let _ = self.set(option, value);
+        }
+    }
+
+    /// changes an option by its name in filetype.conf
+    pub fn set(&mut self, option: &str, value: &str) -> Result<(), String> {
+        let flag = || match value {
+            "on" | "true" | "yes" => Ok(true),
+            "off" | "false" | "no" => Ok(false),
+            _ => Err(format!("{option}: expected on or off, not {value}")),
+        };
+        match option {
+            "indent" => {
+                self.indent_width = value
+                    .parse()
+                    .ok()
+                    .filter(|width| *width > 0)
+                    .ok_or_else(|| format!("indent: expected a width, not {value}"))?;
+            }
+            "tabs" => self.tabs = flag()?,
+            "autoindent" => self.auto_indent = flag()?,
+            "smartindent" => self.smart_indent = flag()?,
+            "indent_after" => {
+                self.indent_after = value.split_whitespace().map(str::to_string).collect();
+            }
+            "dedent_on" => {
+                self.dedent_on = value
+                    .split_whitespace()
+                    .filter_map(|closer| closer.chars().next())
+                    .collect();
+            }
+            "pairs" => self.pairs = pairs_of(value),
+            "keywords" => self.keywords = value.split_whitespace().map(str::to_string).collect(),
+            "comment" => self.comment = (!value.is_empty()).then(|| value.to_string()),
+            "block_comment" => {
+                self.block_comment = match value.split_whitespace().collect::<Vec<_>>()[..] {
+                    [open, close] => Some((open.to_string(), close.to_string())),
+                    [] => None,
+                    _ => return Err(format!("block_comment: expected two markers, not {value}")),
+                };
+            }
+            _ => return Err(format!("unknown option {option}")),
+        }
+        Ok(())
+    }
+
+    /// the closer typed along with an opener
+    pub fn closer_of(&self, opener: char) -> Option<char> {
+        self.pairs
+            .iter()
+            .find(|(open, _)| *open == opener)
+            .map(|(_, close)| *close)
+    }
+
+    /// whether a character closes one of the pairs
+    pub fn closes(&self, c: char) -> bool {
+        self.pairs.iter().any(|(_, close)| *close == c)
+    }
+
+    /// comments the lines out, or takes the comments away when every line with text has one
+    /// the markers line up at the smallest indent and lines of only blanks are left alone
+    /// a line too short to hold both markers apart, like `/*/`, counts as uncommented
+    /// None when the filetype has no comments
+    pub fn toggle_comments(&self, lines: &[&str]) -> Option<Vec<String>> {
+        let (open, close) = match (&self.comment, &self.block_comment) {
+            (Some(comment), _) => (comment.as_str(), ""),
+            (None, Some((open, close))) => (open.as_str(), close.as_str()),
+            (None, None) => return None,
+        };
+        let texts = || lines.iter().filter(|line| !line.trim().is_empty());
+        let commented = texts().all(|line| {
+            let line = line.trim();
+            line.len() >= open.len() + close.len()
+                && line.starts_with(open)
+                && line.ends_with(close)
+        });
+        let column = texts()
+            .map(|line| leading_whitespace(line).len())
+            .min()
+            .unwrap_or_default();
+        let toggled = lines.iter().map(|line| {
+            if line.trim().is_empty() {
+                return (*line).to_string();
+            }
+            let (indent, text) = line.split_at(if commented {
+                leading_whitespace(line).len()
+            } else {
+                column
+            });
+            if !commented {
+                let close = if close.is_empty() {
+                    String::new()
+                } else {
+                    format!(" {close}")
+                };
+                return format!("{indent}{open} {text}{close}");
+            }
+            let text = text.trim_end();
+            let text = text[open.len()..text.len() - close.len()].trim_end();
+            let text = text.strip_prefix(' ').unwrap_or(text);
+            format!("{indent}{text}")
+        });
+        Some(toggled.collect())
+    }
+
+    /// the text of one level of indent
+    pub fn unit(&self) -> String {
+        if self.tabs {
+            "\t".to_string()
+        } else {
+            " ".repeat(self.indent_width)
+        }
+    }
+
+    /// whether a line opens a level of indent for the lines below it
+    fn opens(&self, line: &str) -> bool {
+        let line = line.trim_end();
+        self.smart_indent
+            && self
+                .indent_after
+                .iter()
+                .any(|end| line.ends_with(end.as_str()))
+    }
+
+    /// the indent of a line started below one whose text up to the cursor is given
+    pub fn new_line_indent(&self, before: &str) -> String {
+        if !self.auto_indent {
+            return String::new();
+        }
+        let mut indent = leading_whitespace(before).to_string();
+        if self.opens(before) {
+            indent.push_str(&self.unit());
+        }
+        indent
+    }
+
+    /// whether a new line between two texts puts the second on a line of its own, ie `{|}`
+    /// the cursor then goes on an indented line between them
+    pub fn splits_pair(&self, before: &str, after: &str) -> bool {
+        let closer = match before.trim_end().chars().last() {
+            Some('{') => '}',
+            Some('(') => ')',
+            Some('[') => ']',
+            _ => return false,
+        };
+        self.opens(before) && after.trim_start().starts_with(closer)
+    }
+
+    /// whether typing a character first on a line takes a level of indent away
+    pub fn dedents(&self, c: char) -> bool {
+        self.smart_indent && self.dedent_on.contains(&c)
+    }
+
+    /// the columns an indent takes, a tab counts as a level
+    pub fn columns(&self, indent: &str) -> usize {
+        indent
+            .chars()
+            .map(|c| if c == '\t' { self.indent_width } else { 1 })
+            .sum()
+    }
+
+    /// an indent of some columns in tabs or spaces, whichever the filetype uses
+    fn indent_of(&self, columns: usize) -> String {
+        if self.tabs {
+            #[allow(clippy::integer_division)]
+            let levels = columns / self.indent_width;
+            let spaces = columns % self.indent_width;
+            format!("{}{}", "\t".repeat(levels), " ".repeat(spaces))
+        } else {
+            " ".repeat(columns)
+        }
+    }
+
+    /// an indent a level deeper or shallower, written the way the filetype indents
+    pub fn shift(&self, indent: &str, deeper: bool) -> String {
+        let columns = self.columns(indent);
+        self.indent_of(if deeper {
+            columns.saturating_add(self.indent_width)
+        } else {
+            columns.saturating_sub(self.indent_width)
+        })
+    }
+
+    /// the indent a line gets below the one above it, as a new line would plus the dedent
+    /// of a closer it starts with, the first line of a file keeps its own
+    pub fn reindent(&self, above: Option<&str>, line: &str) -> String {
+        let Some(above) = above else {
+            return self.indent_of(self.columns(leading_whitespace(line)));
+        };
+        let mut columns = self.columns(leading_whitespace(above));
+        if self.opens(above) {
+            columns = columns.saturating_add(self.indent_width);
+        }
+        if line
+            .trim_start()
+            .chars()
+            .next()
+            .is_some_and(|c| self.dedents(c))
+        {
+            columns = columns.saturating_sub(self.indent_width);
+        }
+        self.indent_of(columns)
+    }
+
+    /// an indent one level shallower, a tab or up to a level of spaces less
+    pub fn dedent(&self, indent: &str) -> String {
+        if let Some(rest) = indent.strip_suffix('\t') {
+            return rest.to_string();
+        }
+        let spaces = indent
+            .len()
+            .saturating_sub(indent.trim_end_matches(' ').len());
+        let removed = std::cmp::min(spaces, self.indent_width);
+        indent[..indent.len().saturating_sub(removed)].to_string()
+    }
+}
+
+/// pairs from openers like `( [ "`, the known brackets get their closers and anything
+/// else closes with itself, a pair can be given in full as well, ie `<>`
+/// the built in keywords of a language
+fn keywords_of(extension: &str) -> &'static str {
+    match extension {
+        "rs" => {
+            "as async await break const continue crate dyn else enum extern false fn for if impl \
+             in let loop match mod move mut pub ref return self Self static struct super trait \
+             true type unsafe use where while"
+        }
+        "py" => {
+            "and as assert async await break class continue def del elif else except False \
+             finally for from global if import in is lambda None nonlocal not or pass raise \
+             return True try while with yield"
+        }
+        "js" | "ts" | "jsx" | "tsx" => {
+            "async await break case catch class const continue default delete do else export \
+             extends false finally for function if import in instanceof interface let new null \
+             return switch this throw true try type typeof undefined var void while yield"
+        }
+        "go" => {
+            "break case chan const continue default defer else fallthrough false for func go \
+             goto if import interface map nil package range return select struct switch true \
+             type var"
+        }
+        "c" | "h" | "cpp" | "hpp" | "cc" => {
+            "auto break case char class const continue default delete do double else enum \
+             extern false float for goto if inline int long namespace new nullptr private \
+             public return short signed sizeof static struct switch template this true typedef \
+             union unsigned using virtual void volatile while"
+        }
+        "java" | "cs" => {
+            "abstract break case catch class const continue default do else enum extends false \
+             final finally for if implements import interface new null package private \
+             protected public return static super switch this throw throws true try void while"
+        }
+        "sh" | "bash" | "zsh" => {
+            "case do done elif else esac export fi for function if in local return then until \
+             while"
+        }
+        "lua" => {
+            "and break do else elseif end false for function if in local nil not or repeat \
+             return then true until while"
+        }
+        _ => "",
+    }
+}
+
+fn pairs_of(text: &str) -> Vec<(char, char)> {
+    text.split_whitespace()
+        .filter_map(|pair| {
+            let mut chars = pair.chars();
+            let open = chars.next()?;
+            let close = chars.next().unwrap_or(match open {
+                '(' => ')',
+                '[' => ']',
+                '{' => '}',
+                '<' => '>',
+                _ => open,
+            });
+            Some((open, close))
+        })
+        .collect()
+}
+
+/// the spaces and tabs a text starts with
+pub fn leading_whitespace(text: &str) -> &str {
+    let end = text
+        .find(|c: char| c != ' ' && c != '\t')
+        .unwrap_or(text.len());
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indents_after_openers_and_dedents_closers() {
+        let rust = FileType::built_in("rs");
+        assert_eq!(rust.new_line_indent("    fn main() {"), "        ");
+        assert_eq!(rust.new_line_indent("    Some(x) =>"), "        ");
+        assert_eq!(rust.new_line_indent("    let x = 1;"), "    ");
+        assert!(rust.splits_pair("if x {", "}"));
+        assert!(!rust.splits_pair("if x {", "y"));
+        assert_eq!(rust.dedent("      "), "  ");
+        assert_eq!(rust.dedent("\t\t"), "\t");
+
+        let python = FileType::built_in("py");
+        assert_eq!(python.new_line_indent("def f():"), "    ");
+        let text = FileType::built_in("txt");
+        assert_eq!(text.new_line_indent("  - item:"), "  ");
+    }
+
+    #[test]
+    fn configured_options_override_the_built_in_ones() {
+        let mut go = FileType::built_in("go");
+        go.configure(
+            "# every file\n*.indent = 2\n*.smartindent = off\ngo.smartindent = on\nbad line\nrs.tabs = off\n",
+            "go",
+        );
+        assert_eq!(go.indent_width, 2);
+        assert!(go.smart_indent && go.tabs);
+        assert_eq!(go.new_line_indent("\tfunc f() {"), "\t\t");
+        assert!(go.set("indent", "0").is_err());
+    }
+
+    #[test]
+    fn toggles_comments_at_the_smallest_indent() {
+        let rust = FileType::built_in("rs");
+        let lines = ["    if x {", "", "        y();", "    }"];
+        let commented = rust.toggle_comments(&lines).unwrap();
+        assert_eq!(
+            commented,
+            ["    // if x {", "", "    //     y();", "    // }"]
+        );
+        let commented: Vec<&str> = commented.iter().map(String::as_str).collect();
+        assert_eq!(rust.toggle_comments(&commented).unwrap(), lines);
+        // a line without a comment comments them all
+        assert_eq!(
+            rust.toggle_comments(&["// a", "b"]).unwrap(),
+            ["// // a", "// b"]
+        );
+
+        let html = FileType::built_in("html");
+        assert_eq!(
+            html.toggle_comments(&["  <p>"]).unwrap(),
+            ["  <!-- <p> -->"]
+        );
+        assert_eq!(html.toggle_comments(&["  <!--<p>-->"]).unwrap(), ["  <p>"]);
+        assert!(FileType::built_in("txt").toggle_comments(&["a"]).is_none());
+    }
+
+    #[test]
+    fn overlapping_markers_count_as_uncommented() {
+        let css = FileType::built_in("css");
+        assert_eq!(css.toggle_comments(&["/*/"]).unwrap(), ["/* /*/ */"]);
+        assert_eq!(css.toggle_comments(&["/**/"]).unwrap(), [""]);
+        let html = FileType::built_in("html");
+        assert_eq!(
+            html.toggle_comments(&["<!-->"]).unwrap(),
+            ["<!-- <!--> -->"]
+        );
+    }
+
+    #[test]
+    fn shifts_and_reindents_in_the_indent_of_the_filetype() {
+        let mut rust = FileType::built_in("rs");
+        assert_eq!(rust.shift("  ", true), "      ");
+        assert_eq!(rust.shift("\t", false), "");
+        assert_eq!(rust.reindent(Some("    if x {"), "y"), "        ");
+        assert_eq!(rust.reindent(Some("        y"), "    }"), "    ");
+        assert_eq!(rust.reindent(None, "  z"), "  ");
+        rust.tabs = true;
+        assert_eq!(rust.shift("      ", true), "\t\t  ");
+        assert_eq!(rust.reindent(Some("if x {"), "        y"), "\t");
+    }
+}
@@ -0,0 +1,394 @@
+// folds hide runs of lines behind the first of them, which is drawn as a summary
+// they are made by hand with zf, or found from the indent or the brackets of the text
+// positions still address buffer lines, this only maps lines to and from display rows
+// the lines of a closed fold after its first are hidden, the cursor never rests on them
+use super::buffer::Buffer;
+use super::filetype::{leading_whitespace, FileType};
+use super::line::Line;
+use crate::editor::terminal::{Position, ScreenOffset, Size};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FoldMethod {
+    #[default]
+    Manual, // zf makes them, zd and zE remove them
+    Indent,  // lines indented deeper than the one before them
+    Bracket, // from a line with an open bracket to the line closing it
+}
+
+impl FoldMethod {
+    /// the values of `:set foldmethod`, syntax folds by brackets
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "manual" => Some(Self::Manual),
+            "indent" => Some(Self::Indent),
+            "bracket" | "syntax" => Some(Self::Bracket),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fold {
+    pub start: usize, // the line shown in place of the fold
+    pub end: usize,   // inclusive
+    pub closed: bool,
+}
+
+impl Fold {
+    fn contains(&self, line: usize) -> bool {
+        self.start <= line && line <= self.end
+    }
+
+    /// the line is one of those the fold hides while it is closed
+    fn hides(&self, line: usize) -> bool {
+        self.closed && self.start < line && line <= self.end
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Folds {
+    pub method: FoldMethod,
+    all: Vec<Fold>, // by start, an outer fold before the ones it holds
+    lines: usize,   // the length of the buffer the folds were last placed in
+}
+
+impl Folds {
+    /// the line showing a line, the first of the outermost closed fold hiding it
+    pub fn head(&self, line: usize) -> usize {
+        self.all
+            .iter()
+            .filter(|fold| fold.hides(line))
+            .map(|fold| fold.start)
+            .min()
+            .unwrap_or(line)
+    }
+
+    pub fn hidden(&self, line: usize) -> bool {
+        self.head(line) != line
+    }
+
+    /// the closed fold drawn on a visible line, if it starts one
+    pub fn closed_at(&self, line: usize) -> Option<Fold> {
+        if self.hidden(line) {
+            return None;
+        }
+        self.all
+            .iter()
+            .filter(|fold| fold.closed && fold.start == line)
+            .max_by_key(|fold| fold.end)
+            .copied()
+    }
+
+    /// the visible line after a line, past the fold it may show
+    pub fn next_visible(&self, line: usize) -> usize {
+        let line = self.head(line);
+        self.closed_at(line)
+            .map_or(line, |fold| fold.end)
+            .saturating_add(1)
+    }
+
+    /// the lines hidden by closed folds, merged and in order
+    fn hidden_ranges(&self) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for fold in self.all.iter().filter(|fold| fold.closed) {
+            match ranges.last_mut() {
+                Some(last) if fold.start <= last.1 => last.1 = std::cmp::max(last.1, fold.end),
+                _ => ranges.push((fold.start, fold.end)),
+            }
+        }
+        ranges
+    }
+
+    /// the display row of a line, counted from the top of the buffer
+    pub fn display_row(&self, line: usize) -> usize {
+        let line = self.head(line);
+        let hidden: usize = self
+            .hidden_ranges()
+            .iter()
+            .filter(|(_, end)| *end < line)
+            .map(|(start, end)| end.saturating_sub(*start))
+            .sum();
+        line.saturating_sub(hidden)
+    }
+
+    /// the line shown on a display row, the inverse of `display_row`
+    pub fn line_at(&self, row: usize) -> usize {
+        let mut line = row;
+        for (start, end) in self.hidden_ranges() {
+            if start >= line {
+                break;
+            }
+            line = line.saturating_add(end.saturating_sub(start));
+        }
+        line
+    }
+
+    /// what a closed fold is drawn as, its length and the text of its first line
+    pub fn summary(&self, line: usize, text: &Line) -> Option<String> {
+        let fold = self.closed_at(line)?;
+        Some(format!(
+            "+--{:>3} lines: {}",
+            fold.end.saturating_sub(fold.start).saturating_add(1),
+            text.raw_string.trim()
+        ))
+    }
+
+    /// opens the closed folds hiding a line, true when there were any
+    pub fn reveal(&mut self, line: usize) -> bool {
+        let mut opened = false;
+        for fold in self.all.iter_mut().filter(|fold| fold.hides(line)) {
+            fold.closed = false;
+            opened = true;
+        }
+        opened
+    }
+
+    /// zo, opens the outermost closed fold on a line
+    pub fn open(&mut self, line: usize) -> bool {
+        let fold = self
+            .all
+            .iter_mut()
+            .find(|fold| fold.closed && fold.contains(line));
+        fold.map(|fold| fold.closed = false).is_some()
+    }
+
+    /// zc, closes the innermost open fold on a line
+    pub fn close(&mut self, line: usize) -> bool {
+        let fold = self
+            .all
+            .iter_mut()
+            .rev()
+            .find(|fold| !fold.closed && fold.contains(line));
+        fold.map(|fold| fold.closed = true).is_some()
+    }
+
+    /// za, opens a closed fold on a line or closes an open one
+    pub fn toggle(&mut self, line: usize) -> bool {
+        self.open(line) || self.close(line)
+    }
+
+    /// zR and zM
+    pub fn set_all(&mut self, closed: bool) {
+        for fold in &mut self.all {
+            fold.closed = closed;
+        }
+    }
+
+    /// zf, a new fold starts closed
+    pub fn create(&mut self, start: usize, end: usize) {
+        self.all.push(Fold {
+            start,
+            end,
+            closed: true,
+        });
+        self.sort();
+    }
+
+    /// zd, removes the closed fold shown on a line or the innermost one holding it
+    pub fn delete(&mut self, line: usize) -> bool {
+        let index = match self.closed_at(line) {
+            Some(closed) => self.all.iter().position(|fold| *fold == closed),
+            None => self.all.iter().rposition(|fold| fold.contains(line)),
+        };
+        index.map(|index| self.all.remove(index)).is_some()
+    }
+
+    /// zE
+    pub fn clear(&mut self) {
+        self.all.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.all.is_empty()
+    }
+
+    fn sort(&mut self) {
+        self.all
+            .sort_by_key(|fold| (fold.start, std::cmp::Reverse(fold.end)));
+        self.all.dedup_by_key(|fold| (fold.start, fold.end));
+    }
+
+    /// finds the folds of the text again when they are automatic
+    /// the ones starting where a closed one did stay closed
+    pub fn refresh(&mut self, text: &[Line], filetype: &FileType) {
+        self.lines = text.len();
+        let ranges = match self.method {
+            FoldMethod::Manual => return,
+            FoldMethod::Indent => indent_folds(text, filetype),
+            FoldMethod::Bracket => bracket_folds(text, filetype),
+        };
+        let closed: Vec<usize> = self
+            .all
+            .iter()
+            .filter(|fold| fold.closed)
+            .map(|fold| fold.start)
+            .collect();
+        self.all = ranges
+            .into_iter()
+            .map(|(start, end)| Fold {
+                start,
+                end,
+                closed: closed.contains(&start),
+            })
+            .collect();
+        self.sort();
+    }
+
+    /// follows lines added or removed at a line, manual folds below it move with their text
+    pub fn update(&mut self, text: &[Line], filetype: &FileType, edited: usize) {
+        if self.lines == text.len() {
+            return;
+        }
+        if self.method != FoldMethod::Manual {
+            self.refresh(text, filetype);
+            return;
+        }
+        let added = text.len() > self.lines;
+        let delta = text.len().abs_diff(self.lines);
+        let moved = |line: usize| {
+            if added {
+                line.saturating_add(delta)
+            } else {
+                line.saturating_sub(delta)
+            }
+        };
+        self.all.retain_mut(|fold| {
+            if fold.start > edited {
+                fold.start = moved(fold.start);
+            }
+            if fold.end >= edited {
+                fold.end = std::cmp::max(moved(fold.end), edited);
+            }
+            fold.start < fold.end && fold.end < text.len()
+        });
+        self.lines = text.len();
+        self.sort();
+    }
+}
+
+/// moves the top of an unwrapped view so the cursor is visible, by display rows so the
+/// lines closed folds hide are not counted, returns how far the cursor was out of view
+pub fn scroll_to_cursor(
+    offset: &mut ScreenOffset,
+    pos: &Position,
+    buffer: &Buffer,
+    size: &Size,
+) -> usize {
+    let folds = &buffer.folds;
+    let mut shown = *pos;
+    shown.height = folds.display_row(pos.height);
+    offset.height = folds.display_row(offset.height);
+    let view_delta = shown.max_displacement_from_view(offset, size, 2);
+    match view_delta {
+        0 => (),
+        1 => offset.update_offset_single_move(&shown, size, 2),
+        _ => offset.handle_offset_screen_snap(&shown, size, 1, folds.display_row(buffer.len())),
+    }
+    offset.height = folds.line_at(offset.height);
+    view_delta
+}
+
+/// a fold from every line the lines after it are indented deeper than
+/// to the last of them, blank lines inside count and the ones after do not
+fn indent_folds(text: &[Line], filetype: &FileType) -> Vec<(usize, usize)> {
+    let indents: Vec<Option<usize>> = text
+        .iter()
+        .map(|line| {
+            let raw = &line.raw_string;
+            (!raw.trim().is_empty()).then(|| filetype.columns(leading_whitespace(raw)))
+        })
+        .collect();
+    let mut folds = Vec::new();
+    for (start, indent) in indents.iter().enumerate() {
+        let Some(indent) = indent else { continue };
+        let mut end = start;
+        for (line, deeper) in indents.iter().enumerate().skip(start.saturating_add(1)) {
+            match deeper {
+                Some(deeper) if deeper > indent => end = line,
+                Some(_) => break,
+                None => {}
+            }
+        }
+        if end > start {
+            folds.push((start, end));
+        }
+    }
+    folds
+}
+
+/// a fold from every line with an opening bracket to the line that closes it
+fn bracket_folds(text: &[Line], filetype: &FileType) -> Vec<(usize, usize)> {
+    let brackets: Vec<(char, char)> = filetype
+        .pairs
+        .iter()
+        .copied()
+        .filter(|(open, close)| open != close)
+        .collect();
+    let mut open: Vec<(char, usize)> = Vec::new(); // expected closer and the line it opened on
+    let mut folds = Vec::new();
+    for (line, text) in text.iter().enumerate() {
+        for c in text.raw_string.chars() {
+            if let Some((_, close)) = brackets.iter().find(|(open, _)| *open == c) {
+                open.push((*close, line));
+            } else if let Some(index) = open.iter().rposition(|(close, _)| *close == c) {
+                let (_, start) = open[index];
+                open.truncate(index);
+                if line > start && !folds.iter().any(|(first, _)| *first == start) {
+                    folds.push((start, line));
+                }
+            }
+        }
+    }
+    folds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<Line> {
+        text.lines().map(Line::from).collect()
+    }
+
+    #[test]
+    fn closed_folds_map_lines_to_display_rows() {
+        let mut folds = Folds::default();
+        folds.create(2, 5);
+        folds.create(3, 4);
+        folds.create(8, 9);
+        assert_eq!(folds.head(4), 2);
+        assert_eq!(folds.next_visible(2), 6);
+        assert_eq!(folds.display_row(6), 3);
+        assert_eq!(folds.line_at(3), 6);
+        assert_eq!(folds.line_at(6), 10);
+        // zo opens the outer fold and shows the inner one still closed
+        assert!(folds.open(2));
+        assert_eq!(folds.head(4), 3);
+        assert_eq!(folds.display_row(6), 5);
+        assert!(folds.reveal(4));
+        assert_eq!(folds.display_row(6), 6);
+        // zc closes the innermost open fold
+        assert!(folds.close(4));
+        assert_eq!(folds.closed_at(3).map(|fold| fold.end), Some(4));
+    }
+
+    #[test]
+    fn finds_folds_by_indent_and_brackets() {
+        let text = lines("fn a() {\n    if b {\n        c\n\n    }\n}\nd");
+        let filetype = FileType::for_file(Some("a.rs"));
+        assert_eq!(indent_folds(&text, &filetype), vec![(0, 4), (1, 2)]);
+        assert_eq!(bracket_folds(&text, &filetype), vec![(1, 4), (0, 5)]);
+    }
+
+    #[test]
+    fn manual_folds_move_with_their_lines() {
+        let filetype = FileType::default();
+        let mut folds = Folds::default();
+        folds.update(&lines("a\nb\nc\nd\ne"), &filetype, 0);
+        folds.create(2, 3);
+        folds.update(&lines("a\nx\nb\nc\nd\ne"), &filetype, 0);
+        assert_eq!(folds.closed_at(3).map(|fold| fold.end), Some(4));
+        folds.update(&lines("a\nb\nc\nd\ne"), &filetype, 0);
+        assert_eq!(folds.closed_at(2).map(|fold| fold.end), Some(3));
+    }
+}
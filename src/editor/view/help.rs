@@ -1,8 +1,10 @@
 use super::Size;
+use crate::editor::backend::read;
 use crate::editor::editorcommands::HelpCommand;
-use crate::editor::terminal::{ScreenPosition, Terminal};
-use crossterm::event::{read, Event, KeyEvent};
-use crossterm::style::{Color, PrintStyledContent, StyledContent, Stylize};
+use crate::editor::screen::{Screen, Style};
+use crate::editor::terminal::ScreenPosition;
+use crossterm::event::{Event, KeyEvent};
+use std::io::Error;
 
 // trying to get the help mapping items map at comptime
 // since these are static
@@ -20,6 +22,7 @@ enum HelpKeys {
     SnapDown,
     Highlight,
     VimMode,
+    Terminal,
 }
 
 impl From<&'static str> for HelpKeys {
@@ -33,6 +36,7 @@ impl From<&'static str> for HelpKeys {
             "SnapDown" => Self::SnapDown,
             "Highlight" => Self::Highlight,
             "VimMode" => Self::VimMode,
+            "Terminal" => Self::Terminal,
             _ => panic!("Unsupported item"),
         }
     }
@@ -73,11 +77,15 @@ impl HelpKeys {
                 offset: 9,
                 help_str: "Ctrl-n = vim mode   ",
             },
+            Self::Terminal => &HelpItemMap {
+                offset: 10,
+                help_str: "Ctrl-o = terminal   ",
+            },
         }
     }
 }
 
-const HELP_ITEMS: [&str; 8] = [
+const HELP_ITEMS: [&str; 9] = [
     "Save",
     "Quit",
     "JumpTo",
@@ -86,18 +94,19 @@ const HELP_ITEMS: [&str; 8] = [
     "SnapDown",
     "Highlight",
     "VimMode",
+    "Terminal",
 ];
 
 pub struct Help;
 impl Help {
-    pub fn render_help(size: &mut Size, h_color: Color, t_color: Color) {
+    pub fn render_help(size: &mut Size, style: Style) -> Result<(), Error> {
         //render the help commands
         //clear lines size - 1
         //up to size - n up to number of help commands
         //go back on esc
         //like nvim
-        Terminal::hide_cursor().unwrap();
-        Self::render(size, h_color, t_color);
+        Screen::hide_cursor();
+        Self::render(size, style)?;
         loop {
             let Ok(read_event) = read() else { continue };
             match HelpCommand::try_from(read_event) {
@@ -107,30 +116,28 @@ impl Help {
                     HelpCommand::Resize(new_size) => {
                         *size = new_size;
 
-                        Self::render(size, h_color, t_color);
+                        Self::render(size, style)?;
                     }
                 },
                 Err(_) => continue,
             }
         }
-        Terminal::show_cursor().unwrap();
-        Terminal::execute().unwrap();
+        Ok(())
     }
 
-    fn render(size: &Size, h_color: Color, t_color: Color) {
+    fn render(size: &Size, style: Style) -> Result<(), Error> {
         for item in &HELP_ITEMS {
             let help_map = HelpKeys::from(*item).value();
-            let highlight_seg: StyledContent<String> =
-                help_map.help_str.to_owned().with(t_color).on(h_color);
-            Terminal::move_cursor_to(ScreenPosition {
-                height: size.height.saturating_sub(help_map.offset),
-                width: 0,
-            })
-            .unwrap();
-
-            Terminal::queue_command(PrintStyledContent(highlight_seg)).unwrap();
+            Screen::print_at(
+                ScreenPosition {
+                    height: size.height.saturating_sub(help_map.offset),
+                    width: 0,
+                },
+                help_map.help_str,
+                style,
+            );
         }
-        Terminal::execute().unwrap();
+        Screen::present()
     }
 }
 
@@ -245,14 +252,14 @@ impl VimKeyBindings {
 
 pub struct VimHelpScreen;
 impl VimHelpScreen {
-    pub fn render_help(size: &mut Size, h_color: Color, t_color: Color) {
+    pub fn render_help(size: &mut Size, style: Style) -> Result<(), Error> {
         //render the help commands
         //clear lines size - 1
         //up to size - n up to number of help commands
         //go back on esc
         //like nvim
-        Terminal::hide_cursor().unwrap();
-        Self::render(size, h_color, t_color);
+        Screen::hide_cursor();
+        Self::render(size, style)?;
         loop {
             let Ok(event) = read() else { continue }; //clear the help screen on next key press
             match event {
@@ -260,21 +267,21 @@ impl VimHelpScreen {
                 _ => continue,
             }
         }
+        Ok(())
     }
 
-    fn render(size: &Size, h_color: Color, t_color: Color) {
+    fn render(size: &Size, style: Style) -> Result<(), Error> {
         for item in &VIM_BINDINGS {
             let help_map = VimKeyBindings::from(*item).value();
-            let highlight_seg: StyledContent<String> =
-                help_map.help_str.to_owned().with(t_color).on(h_color);
-            Terminal::move_cursor_to(ScreenPosition {
-                height: size.height.saturating_sub(help_map.offset),
-                width: 0,
-            })
-            .unwrap();
-
-            Terminal::queue_command(PrintStyledContent(highlight_seg)).unwrap();
+            Screen::print_at(
+                ScreenPosition {
+                    height: size.height.saturating_sub(help_map.offset),
+                    width: 0,
+                },
+                help_map.help_str,
+                style,
+            );
         }
-        Terminal::execute().unwrap();
+        Screen::present()
     }
 }
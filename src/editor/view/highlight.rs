@@ -1,13 +1,20 @@
 use super::clipboard_interface::ClipboardUtils;
-use crate::editor::editorcommands::HighlightCommand;
+use super::fold;
+use super::line::Line;
+use super::messages;
+use super::status::Status;
+use super::wrap;
+use crate::editor::backend::read;
+use crate::editor::editorcommands::{HighlightCommand, Shift};
 use crate::editor::{
-    terminal::{Coordinate, Position, ScreenOffset, ScreenPosition, Size, Terminal},
+    screen::{Screen, Style},
+    terminal::{Coordinate, Position, ScreenOffset, Size},
     view::{Buffer, Mode},
 };
-use crossterm::event::{read, Event};
-use crossterm::style::{Color, Print, PrintStyledContent, StyledContent, Stylize};
+use crossterm::event::Event;
 use std::error::Error;
-use std::ops::{Range, RangeInclusive};
+use std::ops::Range;
+use unicode_width::UnicodeWidthStr;
 
 /// type to identify the direction the highlight goes in
 /// whether the highlight is going forward or backward
@@ -18,23 +25,15 @@ pub enum Orientation {
     EndFirst,
 }
 
-/// seperate the logic for highlight a partial and full line
-pub enum LineType {
-    Middle,
-    Leading,
-    Trailing,
-    All,
-}
-
 /// type to handle the highlighting and copy logic
 pub struct Highlight<'a> {
     end: Position, // one copy owned here, the end of the highlight owned by highlight
     offset: ScreenOffset,
     or: Orientation,
-    line_range: RangeInclusive<usize>,
     start: &'a mut Position, //one mutably borrowed, the view's position
     size: &'a mut Size,      //owned by view
     buffer: &'a mut Buffer,  //owned by view
+    pub cursors: bool,       // ended with a cursor asked for on every selected line
 }
 
 impl Highlight<'_> {
@@ -48,19 +47,18 @@ impl Highlight<'_> {
             offset,
             end: *end,
             or: Orientation::default(),
-            line_range: 0..=0,
             start: end, // the immutable reference
             size,
             buffer,
+            cursors: false,
         }
     }
 
-    pub fn run<P>(&mut self, highlight: Color, text: Color, parser: P)
+    pub fn run<P>(&mut self, selection: Style, parser: P)
     where
         P: Fn(Event) -> Result<HighlightCommand, Box<dyn Error>>,
     {
-        let res = self.initial_set_screen();
-        debug_assert!(res.is_ok());
+        messages::draw_failed(self.initial_set_screen());
         loop {
             let Ok(read_event) = read() else { continue }; //skipping errors here
             match parser(read_event) {
@@ -79,128 +77,214 @@ impl Highlight<'_> {
                         }
                         return;
                     }
+                    HighlightCommand::Indent(shift, done) => {
+                        self.indent(shift);
+                        if done {
+                            return;
+                        }
+                    }
+                    HighlightCommand::Comment => {
+                        let (first, last) = self.selected_lines();
+                        self.buffer.toggle_comment(first..=last);
+                        self.start.width = std::cmp::min(
+                            self.start.width,
+                            self.buffer.text[self.start.height].grapheme_len(),
+                        );
+                        return;
+                    }
+                    HighlightCommand::Cursors => {
+                        self.cursors = true;
+                        return;
+                    }
+                    HighlightCommand::Wrap(opener) => {
+                        if self.wrap(opener) {
+                            return;
+                        }
+                        continue;
+                    }
                     HighlightCommand::NoAction => continue,
                 },
                 Err(_) => continue,
             }
-            let view_delta = self
-                .end
-                .max_displacement_from_view(&self.offset, self.size, 2);
-            match view_delta {
-                0_usize => {}
-                1_usize => self
-                    .offset
-                    .update_offset_single_move(&self.end, self.size, 2),
-                _ => self.offset.handle_offset_screen_snap(
-                    &self.end,
-                    self.size,
-                    2,
-                    self.buffer.len(),
-                ),
+            // a closed fold the end moves into opens, like it does for the view's cursor
+            self.buffer.folds.reveal(self.end.height);
+            if self.offset.wrap {
+                wrap::scroll_to_cursor(&mut self.offset, &self.end, self.buffer, self.size, 1);
+            } else {
+                fold::scroll_to_cursor(&mut self.offset, &self.end, self.buffer, self.size);
             }
             self.resolve_orientation();
-            self.adjust_range();
-            let res = Terminal::hide_cursor();
-            debug_assert!(res.is_ok());
-            let res = self.render();
-            debug_assert!(res.is_ok());
-            /*
-            if view_delta > 0 {
-                // only doing a full render when the offset shifts
-                let res = self.render();
-                debug_assert!(res.is_ok());
-            }
-            */
+            self.render(selection);
+            self.status_line();
 
-            let res = if self.start.height == self.end.height {
-                self.render_single_line(highlight, text)
-            } else {
-                self.multi_line_render(highlight, text)
-            };
-            debug_assert!(res.is_ok());
-            let res = self.status_line();
-            debug_assert!(res.is_ok());
-
-            let res = self.post_render();
-            debug_assert!(res.is_ok());
+            messages::draw_failed(self.post_render());
         }
 
         let copy_string = self.generate_copy_str();
 
         if !copy_string.is_empty() {
-            let res = ClipboardUtils::copy_text_to_clipboard(copy_string);
-            debug_assert!(res.is_ok());
+            if let Err(err) = ClipboardUtils::copy_text_to_clipboard(copy_string) {
+                messages::error(format!("Clipboard: {err}"));
+            }
         }
     }
 
     fn initial_set_screen(&self) -> Result<(), Box<dyn Error>> {
-        self.status_line()?; // to see status line before first event is read
-        Terminal::move_cursor_to(self.end.to_screen_position())?;
-        Terminal::execute()?;
-        Ok(())
+        self.status_line(); // to see status line before first event is read
+        self.post_render()
     }
 
     #[inline]
     fn post_render(&self) -> Result<(), Box<dyn Error>> {
-        Terminal::move_cursor_to(self.end.relative_view_position(&self.offset))?;
-        Terminal::show_cursor()?;
-        Terminal::execute()?;
+        Screen::move_cursor_to(wrap::screen_position(
+            &self.end,
+            &self.offset,
+            self.buffer,
+            self.size,
+        ));
+        Screen::present()?;
         Ok(())
     }
 
     #[inline]
-    fn status_line(&self) -> Result<(), Box<dyn Error>> {
-        Terminal::render_status_line(
-            &Mode::Highlight,
-            self.buffer.is_saved,
-            self.size,
-            self.buffer.filename.as_deref(),
-            Some((self.end.height.saturating_add(1), self.buffer.len())),
-        )?;
-        Ok(())
+    fn status_line(&self) {
+        let (first, last) = self.ends();
+        Status::of(Mode::Highlight, self.buffer, &self.end)
+            .with_selection(self.selected_len(&first, &last))
+            .render(self.size);
     }
 
-    fn render(&self) -> Result<(), Box<dyn Error>> {
-        Terminal::clear_screen()?;
-        #[allow(clippy::integer_division)]
-        for current_row in self.offset.height
-            ..self
-                .offset
-                .height
-                .saturating_add(self.size.height)
-                .saturating_sub(1)
-        {
-            let relative_row = current_row.saturating_sub(self.offset.height);
+    /// the ends of the selection, the one earlier in the buffer first
+    fn ends(&self) -> (Position, Position) {
+        if (self.start.height, self.start.width) <= (self.end.height, self.end.width) {
+            (*self.start, self.end)
+        } else {
+            (self.end, *self.start)
+        }
+    }
 
-            if self.line_range.contains(&current_row) {
-                // going to handle rendering these lines with the highlight range
-                // want to skip this so we do not render twice
+    /// the graphemes the copy of the selection holds, a line break counts as one
+    /// like the copy it takes the last grapheme in once the selection spans lines or reaches it
+    fn selected_len(&self, first: &Position, last: &Position) -> usize {
+        let len = |line: usize| self.buffer.text.get(line).map_or(0, Line::len);
+        if first.height == last.height {
+            let end = if last.width.saturating_add(1) >= len(last.height) {
+                len(last.height)
+            } else {
+                last.width
+            };
+            return end.saturating_sub(first.width);
+        }
+        let between: usize = (first.height.saturating_add(1)..last.height)
+            .map(|line| len(line).saturating_add(1))
+            .sum();
+        len(first.height)
+            .saturating_sub(first.width)
+            .saturating_add(1)
+            .saturating_add(between)
+            .saturating_add(std::cmp::min(
+                last.width.saturating_add(1),
+                len(last.height),
+            ))
+    }
+
+    /// draws the rows like the view does, then colors the selected part of them
+    fn render(&self, selection: Style) {
+        let count = self.size.height.saturating_sub(1);
+        let rows = wrap::screen_rows(self.buffer, &self.offset, self.size, self.end.height, count);
+        for (row, text) in rows.iter().enumerate() {
+            Screen::render_line(row, text);
+        }
+
+        let (first, last) = self.selected_lines();
+        let rows = wrap::display_rows(self.buffer, &self.offset, self.size, count);
+        for (screen_row, row) in rows.iter().enumerate() {
+            if row.line < first || row.line > last {
                 continue;
             }
-
-            if let Some(line) = self.buffer.text.get(current_row) {
-                Terminal::render_line(
-                    relative_row,
-                    line.get_line_subset(
-                        self.offset.width..self.offset.width.saturating_add(self.size.width),
-                    ),
-                )?;
+            let line = &self.buffer.text[row.line];
+            let columns = if let Some(summary) = self.buffer.folds.summary(row.line, line) {
+                // a closed fold is selected whole
+                Some(row.column..row.column.saturating_add(summary.width()))
+            } else if line.is_empty() {
+                // an empty line gets one cell so the selection shows it
+                Some(row.column..row.column.saturating_add(1))
             } else {
-                Terminal::render_line(relative_row, "~")?;
+                row.columns(line, self.selected_graphemes(row.line))
+            };
+            if let Some(columns) = columns {
+                Screen::restyle(screen_row, columns, selection);
             }
         }
-        Ok(())
     }
 
-    pub fn adjust_range(&mut self) {
-        match self.or {
-            Orientation::StartFirst => {
-                self.line_range = self.start.height..=self.end.height;
-            }
-            Orientation::EndFirst => {
-                self.line_range = self.end.height..=self.start.height;
-            }
+    /// the graphemes of a selected line the copy of the selection takes
+    fn selected_graphemes(&self, line: usize) -> Range<usize> {
+        let (first, last) = self.ends();
+        let len = self.buffer.text[line].len();
+        let start = if line == first.height { first.width } else { 0 };
+        let end = if line != last.height {
+            len
+        } else if first.height == last.height && last.width.saturating_add(1) < len {
+            last.width
+        } else {
+            std::cmp::min(last.width.saturating_add(1), len)
+        };
+        start..end
+    }
+
+    /// the first and last line covered by the highlight
+    pub fn selected_lines(&self) -> (usize, usize) {
+        (
+            std::cmp::min(self.start.height, self.end.height),
+            std::cmp::max(self.start.height, self.end.height),
+        )
+    }
+
+    /// indents every selected line, the ends of the selection stay on their lines
+    /// and the view's cursor goes to the text of the first one
+    fn indent(&mut self, shift: Shift) {
+        let (first, last) = self.selected_lines();
+        self.buffer.indent_lines(first..=last, shift);
+        for end in [&mut *self.start, &mut self.end] {
+            end.width = std::cmp::min(end.width, self.buffer.text[end.height].grapheme_len());
+        }
+        let text = &self.buffer.text[first].raw_string;
+        let indent = text.len().saturating_sub(text.trim_start().len());
+        if self.start.height == first {
+            self.start.set_width(indent);
+        } else {
+            self.end.set_width(indent);
+        }
+    }
+
+    /// puts the selection between an opener and its closer, the cursor goes after the closer
+    /// false when the character opens no pair of the filetype
+    fn wrap(&mut self, opener: char) -> bool {
+        let Some(closer) = self.buffer.filetype.closer_of(opener) else {
+            return false;
+        };
+        if *self.start == self.end {
+            return false;
         }
+        self.resolve_orientation();
+        let (first, last) = match self.or {
+            Orientation::StartFirst => (*self.start, self.end),
+            Orientation::EndFirst => (self.end, *self.start),
+        };
+        // the closer first, so the opener does not move where it goes
+        let after =
+            self.buffer
+                .replace_in_line(last.height, last.width..last.width, &closer.to_string());
+        self.buffer
+            .replace_in_line(first.height, first.width..first.width, &opener.to_string());
+        self.start.height = last.height;
+        self.start.width = if first.height == last.height {
+            after.saturating_add(1)
+        } else {
+            after
+        };
+        true
     }
 
     pub fn resolve_orientation(&mut self) {
@@ -260,146 +344,6 @@ impl Highlight<'_> {
     }
     */
 
-    pub fn render_single_line(
-        &self,
-        highlight_color: Color,
-        text_color: Color,
-    ) -> Result<(), Box<dyn Error>> {
-        let h_r = match self.or {
-            Orientation::EndFirst => self.end.width..self.start.width,
-            Orientation::StartFirst => self.start.width..self.end.width,
-        };
-
-        // cond for is the highlight ends at the end of the line
-        let te = self.buffer.text[self.start.height]
-            .raw_string
-            .len()
-            .saturating_sub(1)
-            == h_r.end;
-        // cond for if the highlight starts at pos 0
-        let ts = h_r.start == 0;
-
-        // determine how the single line needs to be highlighted
-        let h_t = match (te, ts) {
-            (true, true) => LineType::All,
-            (true, false) => LineType::Trailing,
-            (false, true) => LineType::Leading,
-            (false, false) => LineType::Middle,
-        };
-
-        HighlightUtility::render_highlight_line(
-            &self.buffer.text[self.start.height].raw_string,
-            self.start.height,
-            h_r,
-            &h_t,
-            highlight_color,
-            text_color,
-        )?;
-        Ok(())
-    }
-
-    pub fn multi_line_render(
-        &self,
-        highlight_color: Color,
-        text_color: Color,
-    ) -> Result<(), Box<dyn Error>> {
-        let visible_height_range = RangeInclusive::new(
-            self.offset.height,
-            self.offset.height.saturating_add(self.size.height),
-        );
-        let visible_width_range = RangeInclusive::new(
-            self.offset.width,
-            self.offset.width.saturating_add(self.size.width),
-        );
-
-        for line_height in self.line_range.clone() {
-            // if line height not on the current screen view
-            if !visible_height_range.contains(&line_height) {
-                continue;
-            }
-
-            let line_text = &self.buffer.text[line_height].raw_string;
-
-            // if line width not on screen
-            if line_text.len().saturating_sub(1) < *visible_width_range.start() {
-                continue;
-            }
-
-            // get the visible portion of the line
-            // if line is empty -> " " so we get some highlight on the line
-            let visible_line: &str = if line_text.is_empty() {
-                " "
-            } else if line_text.len() > *visible_width_range.start() {
-                &line_text[*visible_width_range.start()
-                    ..=std::cmp::min(
-                        visible_width_range.end().saturating_sub(1),
-                        line_text.len().saturating_sub(1),
-                    )]
-            } else {
-                continue;
-            };
-
-            // when the line is the start
-            // need to handle a partial line highlight
-            if line_height == self.start.height {
-                match self.or {
-                    Orientation::StartFirst => HighlightUtility::render_highlight_line(
-                        visible_line,
-                        line_height.saturating_sub(self.offset.height),
-                        self.start.width..visible_line.len(),
-                        &LineType::Trailing,
-                        highlight_color,
-                        text_color,
-                    )?,
-                    Orientation::EndFirst => HighlightUtility::render_highlight_line(
-                        visible_line,
-                        line_height.saturating_sub(self.offset.height),
-                        0..self.start.width.saturating_add(1),
-                        &LineType::Leading,
-                        highlight_color,
-                        text_color,
-                    )?,
-                }
-                continue;
-            }
-
-            // when the line is the end
-            // need to handle a partial line highlight
-            if line_height == self.end.height {
-                match self.or {
-                    Orientation::StartFirst => HighlightUtility::render_highlight_line(
-                        visible_line,
-                        line_height.saturating_sub(self.offset.height),
-                        0..self.end.width.saturating_sub(self.offset.width),
-                        &LineType::Leading,
-                        highlight_color,
-                        text_color,
-                    )?,
-                    Orientation::EndFirst => HighlightUtility::render_highlight_line(
-                        visible_line,
-                        line_height.saturating_sub(self.offset.height),
-                        self.end.width..visible_line.len(),
-                        &LineType::Trailing,
-                        highlight_color,
-                        text_color,
-                    )?,
-                };
-                continue;
-            }
-
-            // if we get here, we are highlighting the whole line
-            HighlightUtility::render_highlight_line(
-                visible_line,
-                line_height.saturating_sub(self.offset.height),
-                0..visible_line.len(),
-                &LineType::All,
-                highlight_color,
-                text_color,
-            )?;
-        }
-        Ok(())
-    }
-
     fn batch_delete(&mut self) {
         self.resolve_orientation();
 
@@ -473,46 +417,3 @@ impl Highlight<'_> {
         }
     }
 }
-
-struct HighlightUtility;
-
-impl HighlightUtility {
-    pub fn render_highlight_line(
-        line: &str,
-        height: usize,
-        h_range: Range<usize>,
-        ctx: &LineType,
-        h_color: Color,
-        t_color: Color,
-    ) -> Result<(), Box<dyn Error>> {
-        Terminal::move_cursor_to(ScreenPosition { height, width: 0 })?;
-        Terminal::clear_line()?;
-
-        let segment_to_highlight: String = line[h_range.clone()].to_owned();
-        let highlight_seg: StyledContent<String> =
-            segment_to_highlight.clone().with(t_color).on(h_color);
-
-        // order in which elements are rendered
-        // on the line based on line type
-        match ctx {
-            LineType::All => {
-                Terminal::queue_command(PrintStyledContent(highlight_seg))?;
-            }
-            LineType::Leading => {
-                Terminal::queue_command(PrintStyledContent(highlight_seg))?;
-                Terminal::queue_command(Print(&line[(h_range.end)..]))?;
-            }
-            LineType::Trailing => {
-                Terminal::queue_command(Print(&line[..h_range.start]))?;
-                Terminal::queue_command(PrintStyledContent(highlight_seg))?;
-            }
-            LineType::Middle => {
-                Terminal::queue_command(Print(&line[..h_range.start]))?;
-                Terminal::queue_command(PrintStyledContent(highlight_seg))?;
-                Terminal::queue_command(Print(&line[h_range.end..]))?;
-            }
-        }
-
-        Ok(())
-    }
-}
@@ -66,6 +66,17 @@ impl TryFrom<&str> for TextFragment {
     }
 }
 
+impl TextFragment {
+    /// the screen columns taken by the grapheme
+    #[inline]
+    pub fn width(&self) -> usize {
+        match self.render_width {
+            GraphemeWidth::Full => 2,
+            GraphemeWidth::Half => 1,
+        }
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct Line {
     pub string: Vec<TextFragment>,
@@ -504,9 +515,58 @@ impl Line {
         }
     }
 
+    /// the screen columns taken by the graphemes in range
+    pub fn range_width(&self, range: Range<usize>) -> usize {
+        let end = std::cmp::min(range.end, self.string.len());
+        self.string.get(range.start..end).map_or(0, |fragments| {
+            fragments.iter().map(TextFragment::width).sum()
+        })
+    }
+
     pub fn is_empty(&self) -> bool {
         self.string.len() == 0
     }
+
+    /// the utf-16 offset of a grapheme column in the text as written, what language servers count
+    pub fn utf16_column(&self, column: usize) -> usize {
+        self.string.iter().take(column).map(utf16_len).sum()
+    }
+
+    /// the grapheme column holding a utf-16 offset
+    pub fn grapheme_column(&self, character: usize) -> usize {
+        let mut units = 0_usize;
+        for (column, fragment) in self.string.iter().enumerate() {
+            units = units.saturating_add(utf16_len(fragment));
+            if units > character {
+                return column;
+            }
+        }
+        self.string.len()
+    }
+
+    /// the byte offset in the raw string of the graphemes starting before a screen column
+    pub fn byte_offset(&self, column: usize) -> usize {
+        let mut cells = 0_usize;
+        let mut bytes = 0_usize;
+        for fragment in &self.string {
+            if cells >= column {
+                break;
+            }
+            cells = cells.saturating_add(fragment.width());
+            bytes = bytes.saturating_add(match fragment.replacement_text {
+                Some(c) => c.len_utf8(),
+                None => fragment.grapheme.len(),
+            });
+        }
+        bytes
+    }
+}
+
+fn utf16_len(fragment: &TextFragment) -> usize {
+    match fragment.replacement_text {
+        Some(c) => c.len_utf16(),
+        None => fragment.grapheme.encode_utf16().count(),
+    }
 }
 
 #[cfg(test)]
@@ -568,4 +628,16 @@ mod tests {
             Some(len.saturating_sub(2))
         );
     }
+
+    #[test]
+    fn utf16_columns() {
+        let line = Line::from("a😀é\tb");
+        assert_eq!(line.utf16_column(2), 3);
+        assert_eq!(line.utf16_column(9), 6);
+        assert_eq!(line.grapheme_column(1), 1);
+        // the middle of the emoji is still the emoji
+        assert_eq!(line.grapheme_column(2), 1);
+        assert_eq!(line.grapheme_column(3), 2);
+        assert_eq!(line.grapheme_column(40), 5);
+    }
 }
@@ -0,0 +1,993 @@
+// a language server client, one server per buffer speaking json-rpc over its stdin and stdout
+// edits reach the server as incremental changes, it answers with diagnostics, definitions,
+// hovers, references, renames and formatting
+// positions on the wire count utf-16 code units, the editor counts graphemes
+use super::line::Line;
+use crate::editor::config::config_dir;
+use crate::editor::terminal::Position;
+use json::Value;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::time::{Duration, Instant};
+use std::{env, fs, thread};
+pub mod json;
+pub mod ui;
+
+/// how often the editor looks for diagnostics while no key is pressed
+pub const TICK: Duration = Duration::from_millis(100);
+/// how long a request waits for its answer
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// how long a server gets to exit before it is killed
+const SHUTDOWN: Duration = Duration::from_secs(1);
+const CONFIG_FILE: &str = "lsp.conf";
+
+/// the servers used when lsp.conf does not name one for an extension
+const DEFAULT_SERVERS: [(&str, &str); 10] = [
+    ("rs", "rust-analyzer"),
+    ("py", "pylsp"),
+    ("go", "gopls"),
+    ("c", "clangd"),
+    ("h", "clangd"),
+    ("cpp", "clangd"),
+    ("hpp", "clangd"),
+    ("cc", "clangd"),
+    ("ts", "typescript-language-server --stdio"),
+    ("js", "typescript-language-server --stdio"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl Severity {
+    pub fn sign(self) -> &'static str {
+        match self {
+            Self::Error => "E",
+            Self::Warning => "W",
+            Self::Information => "I",
+            Self::Hint => "H",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub character: usize, // utf-16 offset in the line
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub path: PathBuf,
+    pub line: usize,
+    pub character: usize,
+}
+
+/// a replacement of the text between two positions, lines and utf-16 offsets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub new_text: String,
+}
+
+/// something the server offers to insert at the cursor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionItem {
+    pub label: String,
+    pub text: String,       // what goes into the line in place of the typed word
+    pub kind: &'static str, // ie "function", empty when the server did not say
+    pub detail: String,     // the signature and documentation, for the preview
+}
+
+pub struct Client {
+    command: String,
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<Value>,
+    next_id: usize,
+    path: PathBuf, // the document, absolute
+    uri: String,
+    version: usize,
+    synced: Vec<String>,         // the lines as the server knows them
+    incremental: bool,           // whether the server takes changed ranges or wants the whole text
+    triggers: Option<Vec<char>>, // the characters that start a completion, None without one
+    exited: bool,
+    pub diagnostics: Vec<Diagnostic>, // sorted by line
+}
+
+/// the command configured for the type of a file, from lsp.conf or the defaults
+/// lsp.conf has `extension = command` lines, an empty command turns a default off
+pub fn server_for(filename: &str) -> Option<String> {
+    let extension = Path::new(filename).extension()?.to_str()?;
+    let conf = config_dir().and_then(|dir| fs::read_to_string(dir.join(CONFIG_FILE)).ok());
+    let configured = conf
+        .as_deref()
+        .unwrap_or_default()
+        .lines()
+        .find_map(|line| {
+            let (name, command) = line.split_once('=')?;
+            (!line.trim_start().starts_with('#') && name.trim() == extension)
+                .then(|| command.trim().to_string())
+        });
+    if let Some(command) = configured {
+        return Some(command).filter(|command| !command.is_empty());
+    }
+    // tests never start whatever servers happen to be installed
+    if cfg!(test) {
+        return None;
+    }
+    DEFAULT_SERVERS
+        .iter()
+        .find(|(name, _)| *name == extension)
+        .map(|(_, command)| (*command).to_string())
+}
+
+fn language_id(path: &Path) -> String {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    match extension {
+        "rs" => "rust",
+        "py" => "python",
+        "h" => "c",
+        "cpp" | "hpp" | "cc" => "cpp",
+        "ts" => "typescript",
+        "js" => "javascript",
+        "md" => "markdown",
+        "sh" => "shellscript",
+        other => other,
+    }
+    .to_string()
+}
+
+impl Client {
+    /// runs the server and opens the document with the text of the lines
+    pub fn start(command: &str, filename: &str, lines: &[Line]) -> Result<Self, String> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or("E471: Argument required")?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("{program}: {err}"))?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            let _ = child.kill();
+            return Err(format!("{program}: no pipes to talk over"));
+        };
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || read_messages(stdout, &sender));
+
+        let path = absolute(filename);
+        let mut client = Self {
+            command: command.to_string(),
+            child,
+            stdin,
+            messages,
+            next_id: 1,
+            uri: path_to_uri(&path),
+            path,
+            version: 0,
+            synced: lines.iter().map(Line::to_string).collect(),
+            incremental: false,
+            triggers: None,
+            exited: false,
+            diagnostics: Vec::new(),
+        };
+        let root = env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+        let result = client.request(
+            "initialize",
+            Value::object([
+                (
+                    "processId",
+                    usize::try_from(std::process::id()).map_or(Value::Null, Value::from),
+                ),
+                ("rootUri", path_to_uri(&root).into()),
+                ("capabilities", capabilities()),
+            ]),
+        )?;
+        // 1 is the whole text on every change, 2 only the changed range
+        let sync = result.get("capabilities").get("textDocumentSync");
+        let kind = sync.as_usize().or_else(|| sync.get("change").as_usize());
+        client.incremental = kind == Some(2);
+        let completion = result.get("capabilities").get("completionProvider");
+        if !completion.is_null() {
+            let triggers = completion.get("triggerCharacters").as_array();
+            client.triggers = Some(
+                triggers
+                    .iter()
+                    .filter_map(|trigger| trigger.as_str()?.chars().next())
+                    .collect(),
+            );
+        }
+        client.notify("initialized", Value::object([]))?;
+        let text = document(&client.synced);
+        let params = Value::object([(
+            "textDocument",
+            Value::object([
+                ("uri", client.uri.clone().into()),
+                ("languageId", language_id(&client.path).into()),
+                ("version", 0.into()),
+                ("text", text.into()),
+            ]),
+        )]);
+        client.notify("textDocument/didOpen", params)?;
+        Ok(client)
+    }
+
+    /// the command the server was started with
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// whether the server is gone, it is let go of after that
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    /// whether the server offers completions
+    pub fn completes(&self) -> bool {
+        self.triggers.is_some()
+    }
+
+    /// whether typing a character asks the server for completions, ie '.' in most languages
+    pub fn is_trigger(&self, c: char) -> bool {
+        self.triggers
+            .as_ref()
+            .is_some_and(|triggers| triggers.contains(&c))
+    }
+
+    /// whether a path names the document this client keeps in sync
+    pub fn is_document(&self, path: &Path) -> bool {
+        absolute(&path.to_string_lossy()) == self.path
+    }
+
+    fn send(&mut self, message: &Value) -> Result<(), String> {
+        let body = message.to_string();
+        let sent = write!(self.stdin, "Content-Length: {}\r\n\r\n{body}", body.len())
+            .and_then(|()| self.stdin.flush());
+        if sent.is_err() {
+            self.exited = true;
+            return Err("the language server exited".into());
+        }
+        Ok(())
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> Result<(), String> {
+        self.send(&Value::object([
+            ("jsonrpc", "2.0".into()),
+            ("method", method.into()),
+            ("params", params),
+        ]))
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        self.call(method, params, TIMEOUT)
+    }
+
+    /// sends a request and waits for its answer, handling whatever else the server says meanwhile
+    fn call(&mut self, method: &str, params: Value, timeout: Duration) -> Result<Value, String> {
+        let id = self.next_id;
+        self.next_id = self.next_id.saturating_add(1);
+        self.send(&Value::object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id.into()),
+            ("method", method.into()),
+            ("params", params),
+        ]))?;
+        let deadline = Instant::now()
+            .checked_add(timeout)
+            .unwrap_or_else(Instant::now);
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let message = match self.messages.recv_timeout(remaining) {
+                Ok(message) => message,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(format!("{method}: the language server did not answer"));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.exited = true;
+                    return Err("the language server exited".into());
+                }
+            };
+            if message.get("method").is_null() && message.get("id").as_usize() == Some(id) {
+                let error = message.get("error");
+                if !error.is_null() {
+                    let reason = error.get("message").as_str().unwrap_or("failed");
+                    return Err(format!("{method}: {reason}"));
+                }
+                return Ok(message.get("result").clone());
+            }
+            self.handle(&message);
+        }
+    }
+
+    /// takes in a notification or answers a request from the server
+    /// returns whether the diagnostics changed
+    fn handle(&mut self, message: &Value) -> bool {
+        let Some(method) = message.get("method").as_str() else {
+            // the answer to a request that was given up on
+            return false;
+        };
+        let id = message.get("id");
+        if !id.is_null() {
+            // nothing is configured, every item asked for is left to the server's default
+            let result = if method == "workspace/configuration" {
+                let items = message.get("params").get("items").as_array().len();
+                Value::Array(vec![Value::Null; items])
+            } else {
+                Value::Null
+            };
+            let answer = Value::object([
+                ("jsonrpc", "2.0".into()),
+                ("id", id.clone()),
+                ("result", result),
+            ]);
+            let _ = self.send(&answer);
+            return false;
+        }
+        if method != "textDocument/publishDiagnostics" {
+            return false;
+        }
+        let params = message.get("params");
+        if params.get("uri").as_str() != Some(self.uri.as_str()) {
+            return false;
+        }
+        let mut diagnostics: Vec<Diagnostic> = params
+            .get("diagnostics")
+            .as_array()
+            .iter()
+            .filter_map(diagnostic)
+            .collect();
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.severity));
+        let changed = diagnostics != self.diagnostics;
+        self.diagnostics = diagnostics;
+        changed
+    }
+
+    /// handles everything the server sent since the last poll, returns whether the diagnostics changed
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        loop {
+            match self.messages.try_recv() {
+                Ok(message) => changed |= self.handle(&message),
+                Err(TryRecvError::Empty) => return changed,
+                Err(TryRecvError::Disconnected) => {
+                    self.exited = true;
+                    return changed;
+                }
+            }
+        }
+    }
+
+    /// tells the server how the lines changed since it last heard of them
+    /// the change is the span between the unchanged lines at the start and at the end
+    pub fn sync(&mut self, lines: &[Line]) {
+        let text: Vec<String> = lines.iter().map(Line::to_string).collect();
+        if text == self.synced {
+            return;
+        }
+        let change = if self.incremental {
+            let old = &self.synced;
+            let prefix = old.iter().zip(&text).take_while(|(a, b)| a == b).count();
+            let suffix = old[prefix..]
+                .iter()
+                .rev()
+                .zip(text[prefix..].iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count();
+            let inserted = document(&text[prefix..text.len().saturating_sub(suffix)]);
+            Value::object([
+                (
+                    "range",
+                    range((prefix, 0), (old.len().saturating_sub(suffix), 0)),
+                ),
+                ("text", inserted.into()),
+            ])
+        } else {
+            Value::object([("text", document(&text).into())])
+        };
+        self.version = self.version.saturating_add(1);
+        let params = Value::object([
+            (
+                "textDocument",
+                Value::object([
+                    ("uri", self.uri.clone().into()),
+                    ("version", self.version.into()),
+                ]),
+            ),
+            ("contentChanges", vec![change].into()),
+        ]);
+        let _ = self.notify("textDocument/didChange", params);
+        self.synced = text;
+    }
+
+    pub fn did_save(&mut self) {
+        let params = Value::object([("textDocument", self.document_id())]);
+        let _ = self.notify("textDocument/didSave", params);
+    }
+
+    fn document_id(&self) -> Value {
+        Value::object([("uri", self.uri.clone().into())])
+    }
+
+    fn position_params(&self, lines: &[Line], position: &Position) -> Value {
+        let character = lines
+            .get(position.height)
+            .map_or(0, |line| line.utf16_column(position.width));
+        Value::object([
+            ("textDocument", self.document_id()),
+            ("position", wire_position((position.height, character))),
+        ])
+    }
+
+    pub fn definition(
+        &mut self,
+        lines: &[Line],
+        position: &Position,
+    ) -> Result<Vec<Location>, String> {
+        let params = self.position_params(lines, position);
+        Ok(locations(&self.request("textDocument/definition", params)?))
+    }
+
+    pub fn references(
+        &mut self,
+        lines: &[Line],
+        position: &Position,
+    ) -> Result<Vec<Location>, String> {
+        let mut params = self.position_params(lines, position);
+        if let Value::Object(fields) = &mut params {
+            fields.push((
+                "context".into(),
+                Value::object([("includeDeclaration", true.into())]),
+            ));
+        }
+        Ok(locations(&self.request("textDocument/references", params)?))
+    }
+
+    /// the hover text at a position, None when the server has nothing to say
+    pub fn hover(&mut self, lines: &[Line], position: &Position) -> Result<Option<String>, String> {
+        let params = self.position_params(lines, position);
+        let result = self.request("textDocument/hover", params)?;
+        let text = hover_text(result.get("contents"));
+        Ok(Some(text).filter(|text| !text.trim().is_empty()))
+    }
+
+    /// what the server would insert at a position, in the order it ranks them
+    pub fn completion(
+        &mut self,
+        lines: &[Line],
+        position: &Position,
+    ) -> Result<Vec<CompletionItem>, String> {
+        let params = self.position_params(lines, position);
+        let result = self.request("textDocument/completion", params)?;
+        // a plain list or one inside a CompletionList
+        let items = match &result {
+            Value::Array(items) => items.as_slice(),
+            list => list.get("items").as_array(),
+        };
+        let mut items: Vec<(String, CompletionItem)> = items
+            .iter()
+            .filter_map(|item| {
+                let sort = item.get("sortText").as_str();
+                let completion = completion_item(item)?;
+                Some((sort.unwrap_or(&completion.label).to_string(), completion))
+            })
+            .collect();
+        items.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(items.into_iter().map(|(_, item)| item).collect())
+    }
+
+    /// the edits renaming the symbol at a position, grouped by file
+    pub fn rename(
+        &mut self,
+        lines: &[Line],
+        position: &Position,
+        new_name: &str,
+    ) -> Result<Vec<(PathBuf, Vec<TextEdit>)>, String> {
+        let mut params = self.position_params(lines, position);
+        if let Value::Object(fields) = &mut params {
+            fields.push(("newName".into(), new_name.into()));
+        }
+        let result = self.request("textDocument/rename", params)?;
+        let mut files = Vec::new();
+        if let Value::Object(changes) = result.get("changes") {
+            for (uri, edits) in changes {
+                files.push((uri.as_str(), edits));
+            }
+        }
+        for change in result.get("documentChanges").as_array() {
+            // creating, renaming and deleting files are left out
+            if let Some(uri) = change.get("textDocument").get("uri").as_str() {
+                files.push((uri, change.get("edits")));
+            }
+        }
+        files
+            .into_iter()
+            .map(|(uri, edits)| {
+                let path = uri_to_path(uri).ok_or_else(|| format!("rename: cannot edit {uri}"))?;
+                Ok((path, text_edits(edits)))
+            })
+            .collect()
+    }
+
+    pub fn formatting(&mut self) -> Result<Vec<TextEdit>, String> {
+        let params = Value::object([
+            ("textDocument", self.document_id()),
+            (
+                "options",
+                Value::object([("tabSize", 4.into()), ("insertSpaces", true.into())]),
+            ),
+        ]);
+        Ok(text_edits(
+            &self.request("textDocument/formatting", params)?,
+        ))
+    }
+
+    /// counts of errors and warnings, and the first message on a line, for the status line
+    pub fn summary(&self, line: usize) -> String {
+        let count = |severity| {
+            self.diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity == severity)
+                .count()
+        };
+        let mut summary = format!(
+            "E:{} W:{}",
+            count(Severity::Error),
+            count(Severity::Warning)
+        );
+        if let Some(diagnostic) = self.diagnostics.iter().find(|d| d.line == line) {
+            let _ = write!(
+                summary,
+                " | {}",
+                diagnostic.message.lines().next().unwrap_or("")
+            );
+        }
+        summary
+    }
+
+    /// the most severe diagnostic of every line that has one
+    pub fn signs(&self) -> Vec<(usize, Severity)> {
+        let mut signs: Vec<(usize, Severity)> = Vec::new();
+        for diagnostic in &self.diagnostics {
+            match signs.last_mut() {
+                Some((line, severity)) if *line == diagnostic.line => {
+                    *severity = std::cmp::min(*severity, diagnostic.severity);
+                }
+                _ => signs.push((diagnostic.line, diagnostic.severity)),
+            }
+        }
+        signs
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if !self.exited && self.call("shutdown", Value::Null, SHUTDOWN).is_ok() {
+            let _ = self.notify("exit", Value::Null);
+        }
+        // a server that does not exit on its own is stopped
+        let deadline = Instant::now()
+            .checked_add(SHUTDOWN)
+            .unwrap_or_else(Instant::now);
+        while matches!(self.child.try_wait(), Ok(None)) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// what the editor can do, servers leave out what it cannot
+fn capabilities() -> Value {
+    let empty = || Value::object([]);
+    Value::object([
+        (
+            "textDocument",
+            Value::object([
+                ("synchronization", Value::object([("didSave", true.into())])),
+                ("publishDiagnostics", empty()),
+                (
+                    "hover",
+                    Value::object([(
+                        "contentFormat",
+                        vec!["plaintext".into(), "markdown".into()].into(),
+                    )]),
+                ),
+                (
+                    "completion",
+                    Value::object([(
+                        "completionItem",
+                        Value::object([
+                            ("snippetSupport", false.into()),
+                            (
+                                "documentationFormat",
+                                vec!["plaintext".into(), "markdown".into()].into(),
+                            ),
+                        ]),
+                    )]),
+                ),
+                ("definition", empty()),
+                ("references", empty()),
+                ("rename", empty()),
+                ("formatting", empty()),
+            ]),
+        ),
+        ("workspace", Value::object([("configuration", true.into())])),
+        (
+            "general",
+            Value::object([("positionEncodings", vec!["utf-16".into()].into())]),
+        ),
+    ])
+}
+
+/// splits what the server writes into messages, each one a header and a json body
+fn read_messages(stdout: ChildStdout, sender: &Sender<Value>) {
+    let mut reader = BufReader::new(stdout);
+    loop {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            match reader.read_line(&mut header) {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+        let Some(length) = length else {
+            continue;
+        };
+        let mut body = vec![0; length];
+        if reader.read_exact(&mut body).is_err() {
+            return;
+        }
+        let Ok(message) = json::parse(&String::from_utf8_lossy(&body)) else {
+            continue;
+        };
+        if sender.send(message).is_err() {
+            return;
+        }
+    }
+}
+
+/// the text of a document, every line ends in a newline like the file written to disk
+fn document(lines: &[String]) -> String {
+    lines.iter().fold(String::new(), |mut text, line| {
+        text.push_str(line);
+        text.push('\n');
+        text
+    })
+}
+
+fn wire_position((line, character): (usize, usize)) -> Value {
+    Value::object([("line", line.into()), ("character", character.into())])
+}
+
+fn range(start: (usize, usize), end: (usize, usize)) -> Value {
+    Value::object([("start", wire_position(start)), ("end", wire_position(end))])
+}
+
+fn read_position(value: &Value) -> Option<(usize, usize)> {
+    Some((
+        value.get("line").as_usize()?,
+        value.get("character").as_usize()?,
+    ))
+}
+
+fn diagnostic(value: &Value) -> Option<Diagnostic> {
+    let (line, character) = read_position(value.get("range").get("start"))?;
+    let severity = match value.get("severity").as_usize() {
+        Some(2) => Severity::Warning,
+        Some(3) => Severity::Information,
+        Some(4) => Severity::Hint,
+        _ => Severity::Error,
+    };
+    Some(Diagnostic {
+        line,
+        character,
+        severity,
+        message: value.get("message").as_str()?.to_string(),
+    })
+}
+
+/// a location, a list of them or of location links
+fn locations(result: &Value) -> Vec<Location> {
+    let values = match result {
+        Value::Array(values) => values.as_slice(),
+        Value::Null => &[],
+        single => std::slice::from_ref(single),
+    };
+    values
+        .iter()
+        .filter_map(|value| {
+            let uri = value
+                .get("uri")
+                .as_str()
+                .or_else(|| value.get("targetUri").as_str())?;
+            let range = match value.get("range") {
+                Value::Null => value.get("targetSelectionRange"),
+                range => range,
+            };
+            let (line, character) = read_position(range.get("start"))?;
+            Some(Location {
+                path: uri_to_path(uri)?,
+                line,
+                character,
+            })
+        })
+        .collect()
+}
+
+fn text_edits(edits: &Value) -> Vec<TextEdit> {
+    edits
+        .as_array()
+        .iter()
+        .filter_map(|edit| {
+            let range = edit.get("range");
+            Some(TextEdit {
+                start: read_position(range.get("start"))?,
+                end: read_position(range.get("end"))?,
+                new_text: edit.get("newText").as_str()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// hover contents come as a string, a marked string, markup or a list of those
+fn hover_text(contents: &Value) -> String {
+    match contents {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .map(hover_text)
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        markup => markup.get("value").as_str().unwrap_or_default().to_string(),
+    }
+}
+
+/// a completion item, the text inserted comes from the edit, the insert text or the label
+/// the range of the edit is left out, the word typed before the cursor is what gets replaced
+fn completion_item(item: &Value) -> Option<CompletionItem> {
+    let label = item.get("label").as_str()?.to_string();
+    let text = item
+        .get("textEdit")
+        .get("newText")
+        .as_str()
+        .or_else(|| item.get("insertText").as_str())
+        .unwrap_or(&label);
+    // the line only takes the first line of a longer text
+    let text = text.lines().next().unwrap_or_default().to_string();
+    let detail = [
+        item.get("detail").as_str().unwrap_or_default().to_string(),
+        hover_text(item.get("documentation")),
+    ]
+    .into_iter()
+    .filter(|part| !part.trim().is_empty())
+    .collect::<Vec<_>>()
+    .join("\n\n");
+    Some(CompletionItem {
+        kind: item.get("kind").as_usize().map_or("", completion_kind),
+        label,
+        text,
+        detail,
+    })
+}
+
+/// the names of the completion item kind numbers
+fn completion_kind(kind: usize) -> &'static str {
+    match kind {
+        2 => "method",
+        3 => "function",
+        4 => "constructor",
+        5 => "field",
+        6 => "variable",
+        7 => "class",
+        8 => "interface",
+        9 => "module",
+        10 => "property",
+        13 => "enum",
+        14 => "keyword",
+        15 => "snippet",
+        17 => "file",
+        19 => "folder",
+        20 => "variant",
+        21 => "constant",
+        22 => "struct",
+        25 => "type",
+        _ => "text",
+    }
+}
+
+/// applies edits to lines, every position refers to the text before any edit
+pub fn apply_edits(lines: &[String], edits: &[TextEdit]) -> Vec<String> {
+    let mut text = document(lines);
+    let mut edits: Vec<&TextEdit> = edits.iter().collect();
+    // from the end, so the edits still to apply keep their offsets
+    edits.sort_by_key(|edit| std::cmp::Reverse((edit.start, edit.end)));
+    for edit in edits {
+        let start = byte_offset(&text, edit.start);
+        let end = std::cmp::max(byte_offset(&text, edit.end), start);
+        text.replace_range(start..end, &edit.new_text);
+    }
+    text.lines().map(str::to_string).collect()
+}
+
+/// the byte in a text at a line and utf-16 offset, clamped to the end of the line
+fn byte_offset(text: &str, (line, character): (usize, usize)) -> usize {
+    let mut start = 0;
+    for _ in 0..line {
+        match text[start..].find('\n') {
+            Some(end) => start = start.saturating_add(end).saturating_add(1),
+            None => return text.len(),
+        }
+    }
+    let mut units = 0;
+    for (index, c) in text[start..].char_indices() {
+        if units >= character || c == '\n' {
+            return start.saturating_add(index);
+        }
+        units = units.saturating_add(c.len_utf16());
+    }
+    text.len()
+}
+
+/// applies edits to a file that is not open
+pub fn edit_file(path: &Path, edits: &[TextEdit]) -> Result<(), String> {
+    let fail = |err: std::io::Error| format!("{}: {err}", path.display());
+    let text = fs::read_to_string(path).map_err(fail)?;
+    let lines: Vec<String> = text.lines().map(str::to_string).collect();
+    fs::write(path, document(&apply_edits(&lines, edits))).map_err(fail)
+}
+
+/// the path relative to the working directory when it is below it
+pub fn display_path(path: &Path) -> String {
+    env::current_dir()
+        .ok()
+        .and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf))
+        .unwrap_or_else(|| path.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn absolute(filename: &str) -> PathBuf {
+    fs::canonicalize(filename)
+        .or_else(|_| env::current_dir().map(|dir| dir.join(filename)))
+        .unwrap_or_else(|_| PathBuf::from(filename))
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let text = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    // a windows path starts with its drive, file:///C:/dir
+    if !text.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/:".contains(&byte) {
+            uri.push(char::from(byte));
+        } else {
+            let _ = write!(uri, "%{byte:02X}");
+        }
+    }
+    uri
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut index = 0;
+    while let Some(&byte) = encoded.get(index) {
+        let escaped = encoded
+            .get(index.saturating_add(1)..index.saturating_add(3))
+            .filter(|_| byte == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(byte) = escaped {
+            bytes.push(byte);
+            index = index.saturating_add(3);
+        } else {
+            bytes.push(byte);
+            index = index.saturating_add(1);
+        }
+    }
+    let text = String::from_utf8(bytes).ok()?;
+    if cfg!(windows) {
+        return Some(PathBuf::from(text.strip_prefix('/').unwrap_or(&text)));
+    }
+    Some(PathBuf::from(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|line| (*line).to_string()).collect()
+    }
+
+    #[test]
+    fn applies_edits_in_utf16_offsets() {
+        let before = lines(&["let 😀x = 1;", "x + x"]);
+        let edit = |start, end, new_text: &str| TextEdit {
+            start,
+            end,
+            new_text: new_text.to_string(),
+        };
+        // the emoji is two utf-16 units wide
+        let edits = [
+            edit((0, 6), (0, 7), "y"),
+            edit((1, 0), (1, 1), "y"),
+            edit((1, 4), (1, 5), "y"),
+        ];
+        assert_eq!(apply_edits(&before, &edits), ["let 😀y = 1;", "y + y"]);
+        let joined = [edit((0, 12), (1, 0), " "), edit((1, 5), (9, 0), "\nz")];
+        assert_eq!(apply_edits(&before, &joined), ["let 😀x = 1; x + x", "z"]);
+    }
+
+    #[test]
+    fn uris_round_trip() {
+        let path = Path::new("/tmp/a dir/ü.rs");
+        let uri = path_to_uri(path);
+        assert_eq!(uri, "file:///tmp/a%20dir/%C3%BC.rs");
+        assert_eq!(uri_to_path(&uri).as_deref(), Some(path));
+        assert_eq!(uri_to_path("https://example.com"), None);
+    }
+
+    #[test]
+    fn reads_locations_and_links() {
+        let result = json::parse(
+            r#"[{"uri":"file:///a.rs","range":{"start":{"line":1,"character":2},"end":{"line":1,"character":3}}},
+                {"targetUri":"file:///b.rs","targetSelectionRange":{"start":{"line":4,"character":0},"end":{"line":4,"character":1}}}]"#,
+        )
+        .unwrap();
+        let found = locations(&result);
+        assert_eq!(found.len(), 2);
+        assert_eq!((found[0].line, found[0].character), (1, 2));
+        assert_eq!(found[1].path, PathBuf::from("/b.rs"));
+        assert!(locations(&Value::Null).is_empty());
+    }
+
+    #[test]
+    fn reads_completion_items() {
+        let item = json::parse(
+            r#"{"label":"push","kind":2,"detail":"fn push(&mut self, value: T)",
+                "documentation":{"kind":"markdown","value":"Appends an element"},
+                "textEdit":{"range":{"start":{"line":0,"character":2},"end":{"line":0,"character":4}},"newText":"push"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            completion_item(&item),
+            Some(CompletionItem {
+                label: "push".into(),
+                text: "push".into(),
+                kind: "method",
+                detail: "fn push(&mut self, value: T)\n\nAppends an element".into(),
+            })
+        );
+        let plain = json::parse(r#"{"label":"len()","insertText":"len"}"#).unwrap();
+        assert_eq!(
+            completion_item(&plain).map(|item| item.text),
+            Some("len".into())
+        );
+    }
+}
@@ -0,0 +1,353 @@
+// just enough json for the language server protocol
+// values are parsed into a tree and written back out, objects keep their keys in order
+use std::fmt::{self, Display, Write};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn object<const N: usize>(fields: [(&str, Value); N]) -> Self {
+        Self::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// the field of an object, Null when it is missing
+    pub fn get(&self, key: &str) -> &Value {
+        match self {
+            Self::Object(fields) => fields
+                .iter()
+                .find(|(name, _)| name == key)
+                .map_or(&Self::Null, |(_, value)| value),
+            _ => &Self::Null,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    #[allow(
+        clippy::as_conversions,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Self::Number(number) if *number >= 0.0 && number.fract() == 0.0 => {
+                Some(*number as usize)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> &[Value] {
+        match self {
+            Self::Array(values) => values,
+            _ => &[],
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Self::String(text.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Self::String(text)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<usize> for Value {
+    #[allow(clippy::as_conversions, clippy::cast_precision_loss)]
+    fn from(number: usize) -> Self {
+        Self::Number(number as f64)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(values: Vec<Value>) -> Self {
+        Self::Array(values)
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Bool(value) => write!(f, "{value}"),
+            Self::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => {
+                write!(f, "{number:.0}")
+            }
+            Self::Number(number) => write!(f, "{number}"),
+            Self::String(text) => write_string(f, text),
+            Self::Array(values) => {
+                f.write_char('[')?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_char(']')
+            }
+            Self::Object(fields) => {
+                f.write_char('{')?;
+                for (index, (key, value)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in text.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", u32::from(c))?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// parses a whole json text
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        text: text.as_bytes(),
+        at: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_space();
+    if parser.at < parser.text.len() {
+        return Err(format!("unexpected text at {}", parser.at));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn skip_space(&mut self) {
+        while self.text.get(self.at).is_some_and(u8::is_ascii_whitespace) {
+            self.at = self.at.saturating_add(1);
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.at).copied()
+    }
+
+    fn expect(&mut self, literal: &str, value: Value) -> Result<Value, String> {
+        if self.text[self.at..].starts_with(literal.as_bytes()) {
+            self.at = self.at.saturating_add(literal.len());
+            Ok(value)
+        } else {
+            Err(format!("unexpected text at {}", self.at))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_space();
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b't') => self.expect("true", Value::Bool(true)),
+            Some(b'f') => self.expect("false", Value::Bool(false)),
+            Some(b'n') => self.expect("null", Value::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(format!("unexpected text at {}", self.at)),
+            None => Err("unexpected end of json".into()),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.at = self.at.saturating_add(1);
+        let mut fields = Vec::new();
+        self.skip_space();
+        if self.peek() == Some(b'}') {
+            self.at = self.at.saturating_add(1);
+            return Ok(Value::Object(fields));
+        }
+        loop {
+            self.skip_space();
+            if self.peek() != Some(b'"') {
+                return Err(format!("expected a key at {}", self.at));
+            }
+            let key = self.string()?;
+            self.skip_space();
+            if self.peek() != Some(b':') {
+                return Err(format!("expected : at {}", self.at));
+            }
+            self.at = self.at.saturating_add(1);
+            fields.push((key, self.value()?));
+            self.skip_space();
+            match self.peek() {
+                Some(b',') => self.at = self.at.saturating_add(1),
+                Some(b'}') => {
+                    self.at = self.at.saturating_add(1);
+                    return Ok(Value::Object(fields));
+                }
+                _ => return Err(format!("expected , or }} at {}", self.at)),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.at = self.at.saturating_add(1);
+        let mut values = Vec::new();
+        self.skip_space();
+        if self.peek() == Some(b']') {
+            self.at = self.at.saturating_add(1);
+            return Ok(Value::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.skip_space();
+            match self.peek() {
+                Some(b',') => self.at = self.at.saturating_add(1),
+                Some(b']') => {
+                    self.at = self.at.saturating_add(1);
+                    return Ok(Value::Array(values));
+                }
+                _ => return Err(format!("expected , or ] at {}", self.at)),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.at;
+        while self
+            .peek()
+            .is_some_and(|byte| matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+        {
+            self.at = self.at.saturating_add(1);
+        }
+        std::str::from_utf8(&self.text[start..self.at])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .map(Value::Number)
+            .ok_or_else(|| format!("bad number at {start}"))
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let digits = self
+            .text
+            .get(self.at..self.at.saturating_add(4))
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| format!("bad escape at {}", self.at))?;
+        self.at = self.at.saturating_add(4);
+        Ok(digits)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.at = self.at.saturating_add(1);
+        let mut bytes = Vec::new();
+        loop {
+            let Some(byte) = self.peek() else {
+                return Err("unterminated string".into());
+            };
+            self.at = self.at.saturating_add(1);
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let Some(escape) = self.peek() else {
+                        return Err("unterminated string".into());
+                    };
+                    self.at = self.at.saturating_add(1);
+                    let c = match escape {
+                        b'n' => '\n',
+                        b't' => '\t',
+                        b'r' => '\r',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'u' => {
+                            let mut code = self.hex()?;
+                            // characters outside the basic plane come as a surrogate pair
+                            if (0xd800..0xdc00).contains(&code)
+                                && self.text[self.at..].starts_with(b"\\u")
+                            {
+                                self.at = self.at.saturating_add(2);
+                                let low = self.hex()?;
+                                code = 0x10000
+                                    + ((code - 0xd800) << 10)
+                                    + (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        other => char::from(other),
+                    };
+                    let mut encoded = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut encoded).as_bytes());
+                }
+                byte => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| "a string is not utf-8".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_writes_back() {
+        let text = r#"{"id":1,"result":{"ok":true,"items":[null,-2.5,"a\"b\né😀"]}}"#;
+        let value = parse(text).unwrap();
+        assert_eq!(value.get("id").as_usize(), Some(1));
+        let items = value.get("result").get("items").as_array();
+        assert_eq!(items[1], Value::Number(-2.5));
+        assert_eq!(items[2].as_str(), Some("a\"b\né😀"));
+        assert!(value.get("missing").is_null());
+        assert_eq!(parse(&value.to_string()).unwrap(), value);
+        assert_eq!(
+            Value::object([("n", 3.into()), ("s", "x\ty".into())]).to_string(),
+            r#"{"n":3,"s":"x\ty"}"#
+        );
+        assert!(parse("{\"a\":}").is_err());
+        assert!(parse("[1] 2").is_err());
+    }
+}
@@ -0,0 +1,226 @@
+// what the language server says, drawn over the text
+// signs in the gutter, a hover popup next to the cursor and a list to pick a reference from
+use super::super::buffer::Buffer;
+use super::super::status::Status;
+use super::super::theme::Theme;
+use super::super::wrap;
+use super::Severity;
+use crate::editor::backend::read;
+use crate::editor::screen::{Screen, Style};
+use crate::editor::terminal::{Mode, Position, ScreenOffset, ScreenPosition, Size};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use std::io::Error;
+use unicode_width::UnicodeWidthStr;
+
+/// the most rows a hover popup takes
+const HOVER_HEIGHT: usize = 12;
+
+fn sign_style(severity: Severity, theme: &Theme) -> Style {
+    match severity {
+        Severity::Error => theme.sign_error,
+        Severity::Warning => theme.sign_warning,
+        Severity::Information => theme.sign_info,
+        Severity::Hint => theme.sign_hint,
+    }
+}
+
+/// puts a sign in the first gutter column of every visible line with a diagnostic
+pub fn draw_signs(buffer: &Buffer, offset: &ScreenOffset, size: &Size, theme: &Theme) {
+    let Some(client) = &buffer.lsp else {
+        return;
+    };
+    let text_rows = size.height.saturating_sub(1);
+    for (line, severity) in client.signs() {
+        // a wrapped top line shown from a later row has its gutter scrolled away
+        if line < offset.height
+            || (line == offset.height && offset.wrap && offset.row > 0)
+            || line >= buffer.len()
+            || buffer.folds.hidden(line)
+        {
+            continue;
+        }
+        let start = Position {
+            height: line,
+            width: 0,
+            max_width: 0,
+        };
+        let row = wrap::screen_position(&start, offset, buffer, size).height;
+        if row >= text_rows {
+            break;
+        }
+        Screen::print_at(
+            ScreenPosition {
+                height: row,
+                width: 0,
+            },
+            severity.sign(),
+            sign_style(severity, theme),
+        );
+    }
+}
+
+/// breaks text into rows of at most width columns, at spaces where it can
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let width = std::cmp::max(width, 1);
+    let mut rows = Vec::new();
+    for line in text.lines() {
+        let mut row = String::new();
+        for word in line.split(' ') {
+            let needed = if row.is_empty() {
+                word.width()
+            } else {
+                row.width().saturating_add(1).saturating_add(word.width())
+            };
+            if needed > width && !row.is_empty() {
+                rows.push(std::mem::take(&mut row));
+            } else if !row.is_empty() {
+                row.push(' ');
+            }
+            row.push_str(word);
+            // a word wider than a row is cut
+            while row.width() > width {
+                let cut = row
+                    .char_indices()
+                    .map(|(index, _)| index)
+                    .take_while(|index| row[..*index].width() <= width)
+                    .last()
+                    .filter(|index| *index > 0)
+                    .unwrap_or(row.len());
+                let rest = row.split_off(cut);
+                rows.push(std::mem::replace(&mut row, rest));
+            }
+        }
+        rows.push(row);
+    }
+    rows
+}
+
+/// the rows of a text from the server in a box of a width
+/// markdown fences are left out, the text between them is shown as it is
+pub fn markdown_rows(text: &str, width: usize) -> Vec<String> {
+    let text: String = text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    wrap_text(text.trim(), width)
+}
+
+/// draws the hover text in a box below the cursor, or above it when there is more room there
+pub fn draw_hover(text: &str, cursor: ScreenPosition, size: &Size, style: Style) {
+    let text_rows = size.height.saturating_sub(1);
+    let below = text_rows.saturating_sub(cursor.height.saturating_add(1));
+    let above = cursor.height;
+    let room = std::cmp::min(std::cmp::max(below, above), HOVER_HEIGHT);
+    let mut rows = markdown_rows(text, size.width.saturating_sub(2));
+    rows.truncate(room);
+    let width = rows
+        .iter()
+        .map(|row| row.width())
+        .max()
+        .unwrap_or(0)
+        .saturating_add(2);
+    let top = if rows.len() <= below {
+        cursor.height.saturating_add(1)
+    } else {
+        cursor.height.saturating_sub(rows.len())
+    };
+    let left = std::cmp::min(cursor.width, size.width.saturating_sub(width));
+    for (index, row) in rows.iter().enumerate() {
+        let padding = width.saturating_sub(row.width()).saturating_sub(1);
+        Screen::print_at(
+            ScreenPosition {
+                height: top.saturating_add(index),
+                width: left,
+            },
+            format!(" {row}{}", " ".repeat(padding)),
+            style,
+        );
+    }
+}
+
+/// a full screen list to pick an entry from with j, k and Enter, the hint says what Enter does
+/// None when it was left with Esc or q
+pub fn pick(
+    size: &mut Size,
+    title: &str,
+    items: &[String],
+    hint: &str,
+    selection: Style,
+) -> Result<Option<usize>, Error> {
+    let mut selected = 0_usize;
+    let mut offset = 0_usize;
+    loop {
+        let height = std::cmp::max(size.height.saturating_sub(3), 1);
+        if selected < offset {
+            offset = selected;
+        } else if selected >= offset.saturating_add(height) {
+            offset = selected.saturating_sub(height.saturating_sub(1));
+        }
+        Screen::clear();
+        Screen::hide_cursor();
+        Screen::render_line(0, title);
+        for (index, item) in items.iter().enumerate().skip(offset).take(height) {
+            let style = if index == selected {
+                selection
+            } else {
+                Style::default()
+            };
+            let position = ScreenPosition {
+                height: index.saturating_sub(offset).saturating_add(1),
+                width: 0,
+            };
+            Screen::print_at(position, item, style);
+        }
+        Screen::render_line(size.height.saturating_sub(2), hint);
+        Status {
+            path: Some(title),
+            position: Some((selected.saturating_add(1), None)),
+            lines: items.len(),
+            ..Status::new(Mode::List)
+        }
+        .render(size);
+        Screen::present()?;
+
+        match read()? {
+            Event::Key(KeyEvent {
+                code, modifiers, ..
+            }) => match (code, modifiers) {
+                (KeyCode::Char('j') | KeyCode::Down, _)
+                | (KeyCode::Char('n'), KeyModifiers::CONTROL) => {
+                    selected =
+                        std::cmp::min(selected.saturating_add(1), items.len().saturating_sub(1));
+                }
+                (KeyCode::Char('k') | KeyCode::Up, _)
+                | (KeyCode::Char('p'), KeyModifiers::CONTROL) => {
+                    selected = selected.saturating_sub(1);
+                }
+                (KeyCode::Enter, _) => return Ok(Some(selected)),
+                (KeyCode::Esc | KeyCode::Char('q'), _) => return Ok(None),
+                _ => {}
+            },
+            #[allow(clippy::as_conversions)]
+            Event::Resize(width, height) => {
+                *size = Size {
+                    height: height as usize,
+                    width: width as usize,
+                };
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_hover_text_at_spaces() {
+        assert_eq!(
+            wrap_text("fn main() takes nothing\n\nreturns", 10),
+            ["fn main()", "takes", "nothing", "", "returns"]
+        );
+        assert_eq!(wrap_text("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+    }
+}
@@ -0,0 +1,204 @@
+// what the editor tells the user, what a command did and what went wrong
+// the last message shows on the row above the status line until it times out, every mode posts
+// to the same log, :messages lists what is kept of it
+use super::theme::Theme;
+use crate::editor::screen::{Screen, Style};
+use crate::editor::terminal::Size;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+/// how long a message shows, errors stay longer
+const SHOWN: Duration = Duration::from_secs(4);
+const ERROR_SHOWN: Duration = Duration::from_secs(8);
+/// the most messages :messages lists
+const KEPT: usize = 200;
+/// how often a view waiting for a key looks whether the message timed out
+pub const TICK: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    /// the letter :messages marks a message with, like the signs of diagnostics
+    fn sign(self) -> char {
+        match self {
+            Self::Info => 'I',
+            Self::Warning => 'W',
+            Self::Error => 'E',
+        }
+    }
+
+    fn style(self, theme: &Theme) -> Style {
+        match self {
+            Self::Info => theme.info,
+            Self::Warning => theme.warning,
+            Self::Error => theme.error,
+        }
+    }
+}
+
+struct Message {
+    level: Level,
+    text: String,
+    posted: Instant,
+}
+
+#[derive(Default)]
+struct Log {
+    messages: VecDeque<Message>,
+    shown: bool, // the last message is on the screen
+}
+
+thread_local! {
+    static LOG: RefCell<Log> = RefCell::new(Log::default());
+}
+
+fn post(level: Level, text: String) {
+    LOG.with(|log| {
+        let mut log = log.borrow_mut();
+        if log.messages.len() >= KEPT {
+            log.messages.pop_front();
+        }
+        log.messages.push_back(Message {
+            level,
+            text,
+            posted: Instant::now(),
+        });
+        log.shown = true;
+    });
+}
+
+pub fn info(text: impl Into<String>) {
+    post(Level::Info, text.into());
+}
+
+pub fn warn(text: impl Into<String>) {
+    post(Level::Warning, text.into());
+}
+
+pub fn error(text: impl Into<String>) {
+    post(Level::Error, text.into());
+}
+
+/// posts why the screen could not be drawn, for loops with no caller to return it to
+pub fn draw_failed(res: Result<(), impl Display>) {
+    if let Err(err) = res {
+        error(format!("Cannot draw the screen: {err}"));
+    }
+}
+
+/// the error of a command, one with a warning code like vim's W12 is a warning
+pub fn failed(text: impl Into<String>) {
+    let text = text.into();
+    let mut chars = text.chars();
+    let warning = chars.next() == Some('W') && chars.next().is_some_and(|c| c.is_ascii_digit());
+    if warning {
+        warn(text);
+    } else {
+        error(text);
+    }
+}
+
+/// the message to show, None once it timed out
+fn current() -> Option<(Level, String)> {
+    LOG.with(|log| {
+        let log = log.borrow();
+        let message = log.messages.back().filter(|_| log.shown)?;
+        let shown = match message.level {
+            Level::Error => ERROR_SHOWN,
+            _ => SHOWN,
+        };
+        (message.posted.elapsed() < shown).then(|| (message.level, message.text.clone()))
+    })
+}
+
+/// whether a message is on the screen, a view waits for keys a tick at a time until it goes
+pub fn showing() -> bool {
+    LOG.with(|log| log.borrow().shown)
+}
+
+/// whether the message shown timed out, once, so the row under it is drawn again
+pub fn expired() -> bool {
+    if !showing() || current().is_some() {
+        return false;
+    }
+    LOG.with(|log| log.borrow_mut().shown = false);
+    true
+}
+
+/// the message shown goes before its time, ie when a prompt takes its row
+pub fn dismiss() {
+    LOG.with(|log| log.borrow_mut().shown = false);
+}
+
+/// draws the message shown over the row above the status line, in the style of its level
+pub fn draw(size: &Size, theme: &Theme) {
+    let Some((level, text)) = current() else {
+        return;
+    };
+    let row = size.height.saturating_sub(2);
+    Screen::render_line(row, &text);
+    Screen::restyle(row, 0..size.width, level.style(theme));
+}
+
+/// every message kept, the oldest first, marked with its level
+pub fn history() -> Vec<String> {
+    LOG.with(|log| {
+        log.borrow()
+            .messages
+            .iter()
+            .map(|message| format!("{} {}", message.level.sign(), message.text))
+            .collect()
+    })
+}
+
+/// empties the log, ie :messages clear
+pub fn clear() {
+    LOG.with(|log| *log.borrow_mut() = Log::default());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_shown_until_they_time_out_and_kept() {
+        clear();
+        info("written");
+        failed("W12: changed on disk");
+        error("E212: Can't open file for writing");
+        assert_eq!(
+            current(),
+            Some((Level::Error, "E212: Can't open file for writing".into()))
+        );
+        assert!(!expired());
+        // a message from long ago is gone, the row under it is drawn again once
+        LOG.with(|log| {
+            let mut log = log.borrow_mut();
+            let message = log.messages.back_mut().unwrap();
+            message.posted = message.posted.checked_sub(ERROR_SHOWN).unwrap();
+        });
+        assert_eq!(current(), None);
+        assert!(expired());
+        assert!(!expired() && !showing());
+        assert_eq!(
+            history(),
+            [
+                "I written",
+                "W W12: changed on disk",
+                "E E212: Can't open file for writing"
+            ]
+        );
+        for index in 0..KEPT {
+            info(index.to_string());
+        }
+        assert_eq!(history().len(), KEPT);
+        assert_eq!(history()[0], "I 0");
+    }
+}
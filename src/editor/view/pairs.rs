@@ -0,0 +1,131 @@
+// brackets and quotes typed in pairs, which ones follow the filetype
+// the closers typed for the user are remembered by how many graphemes follow them on their
+// line, typing in front of them does not change that, so typing the closer steps over them
+use super::buffer::Buffer;
+use crate::editor::terminal::Position;
+
+#[derive(Default)]
+pub struct AutoPairs {
+    closers: Vec<(usize, usize)>, // the line of a closer and the graphemes after it
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl AutoPairs {
+    /// types a character at the cursor, an opener gets its closer and the closer of a pair
+    /// steps over the one typed for the user, returns false when the caller types it instead
+    pub fn insert(&mut self, buffer: &mut Buffer, pos: &mut Position, c: char) -> bool {
+        let (before, after, len) = match buffer.text.get(pos.height) {
+            Some(line) => (
+                pos.width
+                    .checked_sub(1)
+                    .and_then(|index| line.string.get(index))
+                    .and_then(|fragment| fragment.grapheme.chars().next()),
+                line.string
+                    .get(pos.width)
+                    .and_then(|fragment| fragment.grapheme.chars().next()),
+                line.string.len(),
+            ),
+            None => (None, None, 0),
+        };
+        let following = len.saturating_sub(pos.width);
+        let typed = (pos.height, following.saturating_sub(1));
+        if after == Some(c) && self.closers.contains(&typed) {
+            self.closers.retain(|closer| *closer != typed);
+            pos.width = pos.width.saturating_add(1);
+            return true;
+        }
+        let Some(close) = buffer.filetype.closer_of(c) else {
+            return false;
+        };
+        let pairs = if c == close {
+            // a quote is not paired in or right after a word, ie in don't
+            !before.is_some_and(|c| is_word(c) || c == close) && !after.is_some_and(is_word)
+        } else {
+            // nor is a bracket typed in front of text
+            after.is_none_or(|c| c.is_whitespace() || buffer.filetype.closes(c))
+        };
+        if !pairs {
+            return false;
+        }
+        if buffer.is_empty() {
+            buffer.update_line_insert(pos, c);
+        } else {
+            pos.width = buffer.replace_in_line(pos.height, pos.width..pos.width, &c.to_string());
+        }
+        buffer.replace_in_line(pos.height, pos.width..pos.width, &close.to_string());
+        self.closers.push((pos.height, following));
+        true
+    }
+
+    /// backspace between an opener and its closer removes both, returns false otherwise
+    pub fn delete(&mut self, buffer: &mut Buffer, pos: &mut Position) -> bool {
+        let Some(line) = buffer.text.get(pos.height) else {
+            return false;
+        };
+        let char_at = |index: usize| {
+            line.string
+                .get(index)
+                .and_then(|fragment| fragment.grapheme.chars().next())
+        };
+        let Some(open) = pos.width.checked_sub(1).and_then(char_at) else {
+            return false;
+        };
+        let following = line.string.len().saturating_sub(pos.width);
+        if char_at(pos.width).is_none() || char_at(pos.width) != buffer.filetype.closer_of(open) {
+            return false;
+        }
+        let start = pos.width.saturating_sub(1);
+        buffer.replace_in_line(pos.height, start..pos.width.saturating_add(1), "");
+        pos.width = start;
+        let closer = (pos.height, following.saturating_sub(1));
+        self.closers.retain(|typed| *typed != closer);
+        true
+    }
+
+    /// forgets the closers once the cursor goes elsewhere
+    pub fn clear(&mut self) {
+        self.closers.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::view::filetype::FileType;
+    use crate::editor::view::line::Line;
+
+    fn typed(text: &str, filename: &str) -> (String, usize) {
+        let mut buffer = Buffer {
+            text: vec![Line::from("")],
+            filetype: FileType::for_file(Some(filename)),
+            ..Default::default()
+        };
+        let mut pos = Position::default();
+        let mut pairs = AutoPairs::default();
+        for c in text.chars() {
+            if c == '\u{8}' {
+                if !pairs.delete(&mut buffer, &mut pos) {
+                    buffer.update_line_delete(&mut pos);
+                }
+            } else if !pairs.insert(&mut buffer, &mut pos, c) {
+                buffer.update_line_insert(&mut pos, c);
+            }
+        }
+        (buffer.text[0].raw_string.clone(), pos.width)
+    }
+
+    #[test]
+    fn pairs_openers_and_steps_over_closers() {
+        assert_eq!(typed("f(", "a.txt"), ("f()".into(), 2));
+        assert_eq!(typed("f(x)", "a.txt"), ("f(x)".into(), 4));
+        assert_eq!(typed("[{(\"a\")}]", "a.txt"), ("[{(\"a\")}]".into(), 9));
+        // only the closers typed for the user are stepped over
+        assert_eq!(typed("())", "a.txt"), ("())".into(), 3));
+        assert_eq!(typed("don't", "a.txt"), ("don't".into(), 5));
+        assert_eq!(typed("'a", "a.rs"), ("'a".into(), 2));
+        assert_eq!(typed("(\u{8}x", "a.txt"), ("x".into(), 1));
+    }
+}
@@ -0,0 +1,502 @@
+use super::theme::Theme;
+use super::vim_mode::ex_command::{command_names, ExCommand, ExCommandKind};
+use crate::editor::config::config_dir;
+use crate::editor::editorcommands::PromptCommand;
+use crate::editor::screen::Screen;
+use crate::editor::terminal::{ScreenPosition, Size};
+use std::fs::{create_dir_all, read_dir, read_to_string, write};
+use std::path::{Path, PathBuf};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const HISTORY_LIMIT: usize = 100;
+
+/// prompts that remember what was entered, each kept in its own file
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HistoryKind {
+    Command,
+    Search,
+    FileName,
+    Jump,
+}
+
+impl HistoryKind {
+    fn path(self) -> Option<PathBuf> {
+        let file_name = match self {
+            Self::Command => "command_history",
+            Self::Search => "search_history",
+            Self::FileName => "filename_history",
+            Self::Jump => "jump_history",
+        };
+        config_dir().map(|dir| dir.join(file_name))
+    }
+
+    fn load(self) -> Vec<String> {
+        let Some(path) = self.path() else {
+            return Vec::new();
+        };
+        read_to_string(path)
+            .map(|contents| contents.lines().map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+
+    fn save(self, entries: &[String]) {
+        let Some(path) = self.path() else { return };
+        // history is best effort, a read only home should not stop the editor
+        if let Some(dir) = path.parent() {
+            let _ = create_dir_all(dir);
+        }
+        let _ = write(path, entries.join("\n"));
+    }
+}
+
+/// what Tab completes in a prompt
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Completion {
+    None,
+    Files,
+    ExCommand,
+}
+
+pub enum PromptAction {
+    Continue,
+    Submit(String),
+    Cancel,
+    Resize(Size),
+}
+
+/// single line editor used by every prompt
+/// handles cursor movement, word deletes, history and tab completion
+pub struct Prompt {
+    label: &'static str,
+    text: Vec<char>,
+    cursor: usize, // index into text
+    history_kind: Option<HistoryKind>,
+    history: Vec<String>,
+    history_index: Option<usize>, // entry being shown while browsing
+    stash: String,                // what was typed before browsing history
+    completion: Completion,
+    candidates: Vec<String>,
+    candidate_index: usize,
+    token_start: usize, // where the completed token starts in text
+    filter: fn(char) -> bool,
+}
+
+impl Prompt {
+    pub fn new(label: &'static str, completion: Completion) -> Self {
+        Self {
+            label,
+            text: Vec::new(),
+            cursor: 0,
+            history_kind: None,
+            history: Vec::new(),
+            history_index: None,
+            stash: String::new(),
+            completion,
+            candidates: Vec::new(),
+            candidate_index: 0,
+            token_start: 0,
+            filter: |_| true,
+        }
+    }
+
+    pub fn with_history(mut self, kind: HistoryKind) -> Self {
+        self.history = kind.load();
+        self.history_kind = Some(kind);
+        self
+    }
+
+    /// only accept chars that pass the filter, ie digits for jump to
+    pub fn with_filter(mut self, filter: fn(char) -> bool) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// starts with text to edit, ie the current name when renaming
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.text = text.chars().collect();
+        self.cursor = self.text.len();
+        self
+    }
+
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    pub fn apply(&mut self, command: PromptCommand) -> PromptAction {
+        if !matches!(
+            command,
+            PromptCommand::Complete | PromptCommand::CompletePrevious
+        ) {
+            self.candidates.clear();
+        }
+        match command {
+            PromptCommand::Insert(c) => {
+                if (self.filter)(c) {
+                    self.text.insert(self.cursor, c);
+                    self.cursor = self.cursor.saturating_add(1);
+                }
+            }
+            PromptCommand::Backspace => {
+                // backspace on an empty prompt leaves it, like vim
+                if self.text.is_empty() {
+                    return PromptAction::Cancel;
+                }
+                if self.cursor > 0 {
+                    self.cursor = self.cursor.saturating_sub(1);
+                    self.text.remove(self.cursor);
+                }
+            }
+            PromptCommand::Delete => {
+                if self.cursor < self.text.len() {
+                    self.text.remove(self.cursor);
+                }
+            }
+            PromptCommand::Left => self.cursor = self.cursor.saturating_sub(1),
+            PromptCommand::Right => {
+                self.cursor = std::cmp::min(self.cursor.saturating_add(1), self.text.len());
+            }
+            PromptCommand::WordLeft => self.cursor = self.previous_word_start(),
+            PromptCommand::WordRight => self.cursor = self.next_word_end(),
+            PromptCommand::Home => self.cursor = 0,
+            PromptCommand::End => self.cursor = self.text.len(),
+            PromptCommand::DeleteWord => {
+                let start = self.previous_word_start();
+                self.text.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            PromptCommand::DeleteToStart => {
+                self.text.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            PromptCommand::HistoryPrevious => self.browse_history(true),
+            PromptCommand::HistoryNext => self.browse_history(false),
+            PromptCommand::Complete => self.complete(true),
+            PromptCommand::CompletePrevious => self.complete(false),
+            PromptCommand::Submit => {
+                let text = self.text();
+                self.push_history(&text);
+                return PromptAction::Submit(text);
+            }
+            PromptCommand::Cancel => return PromptAction::Cancel,
+            PromptCommand::Resize(size) => return PromptAction::Resize(size),
+            PromptCommand::NoAction => {}
+        }
+        PromptAction::Continue
+    }
+
+    /// draws the label and text on a row and puts the cursor at the edit position
+    /// long text scrolls so the cursor stays visible
+    pub fn render(&self, row: usize, width: usize) {
+        let label_width = self.label.width();
+        let cursor_column = self.text[..self.cursor]
+            .iter()
+            .map(|c| c.width().unwrap_or(0))
+            .sum::<usize>()
+            .saturating_add(label_width);
+        let skip = cursor_column.saturating_sub(width.saturating_sub(1));
+
+        let mut visible = String::new();
+        let mut column = 0_usize;
+        for c in self.label.chars().chain(self.text.iter().copied()) {
+            let c_width = c.width().unwrap_or(0);
+            if column >= skip && column.saturating_add(c_width) <= skip.saturating_add(width) {
+                visible.push(c);
+            }
+            column = column.saturating_add(c_width);
+        }
+
+        Screen::render_line(row, visible);
+        Screen::move_cursor_to(ScreenPosition {
+            height: row,
+            width: cursor_column.saturating_sub(skip),
+        });
+    }
+
+    fn previous_word_start(&self) -> usize {
+        let mut i = self.cursor;
+        while i > 0 && self.text[i.saturating_sub(1)].is_whitespace() {
+            i = i.saturating_sub(1);
+        }
+        let word = i > 0 && is_word_char(self.text[i.saturating_sub(1)]);
+        while i > 0 {
+            let c = self.text[i.saturating_sub(1)];
+            if c.is_whitespace() || is_word_char(c) != word {
+                break;
+            }
+            i = i.saturating_sub(1);
+        }
+        i
+    }
+
+    fn next_word_end(&self) -> usize {
+        let len = self.text.len();
+        let mut i = self.cursor;
+        while i < len && self.text[i].is_whitespace() {
+            i = i.saturating_add(1);
+        }
+        let word = i < len && is_word_char(self.text[i]);
+        while i < len {
+            let c = self.text[i];
+            if c.is_whitespace() || is_word_char(c) != word {
+                break;
+            }
+            i = i.saturating_add(1);
+        }
+        i
+    }
+
+    fn set_text(&mut self, text: &str) {
+        self.text = text.chars().collect();
+        self.cursor = self.text.len();
+    }
+
+    fn browse_history(&mut self, older: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+        let next = match (self.history_index, older) {
+            (None, true) => {
+                self.stash = self.text();
+                Some(self.history.len().saturating_sub(1))
+            }
+            (None, false) => None,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) => {
+                let index = index.saturating_add(1);
+                (index < self.history.len()).then_some(index)
+            }
+        };
+        self.history_index = next;
+        let text = match next {
+            Some(index) => self.history[index].clone(),
+            None => std::mem::take(&mut self.stash),
+        };
+        self.set_text(&text);
+    }
+
+    fn push_history(&mut self, entry: &str) {
+        let Some(kind) = self.history_kind else {
+            return;
+        };
+        if entry.trim().is_empty() {
+            return;
+        }
+        // the latest use of an entry moves it to the end
+        self.history.retain(|previous| previous != entry);
+        self.history.push(entry.to_string());
+        if self.history.len() > HISTORY_LIMIT {
+            let overflow = self.history.len().saturating_sub(HISTORY_LIMIT);
+            self.history.drain(..overflow);
+        }
+        kind.save(&self.history);
+    }
+
+    fn complete(&mut self, forward: bool) {
+        if self.candidates.is_empty() {
+            let before: String = self.text[..self.cursor].iter().collect();
+            let (token_start, candidates) = match self.completion {
+                Completion::None => return,
+                Completion::Files => {
+                    let start = token_start(&before);
+                    (start, file_candidates(&before[start..]))
+                }
+                Completion::ExCommand => ex_candidates(&before),
+            };
+            if candidates.is_empty() {
+                return;
+            }
+            self.token_start = before[..token_start].chars().count();
+            self.candidate_index = if forward {
+                0
+            } else {
+                candidates.len().saturating_sub(1)
+            };
+            self.candidates = candidates;
+        } else if forward {
+            self.candidate_index = self
+                .candidate_index
+                .saturating_add(1)
+                .checked_rem(self.candidates.len())
+                .unwrap_or(0);
+        } else {
+            self.candidate_index = self
+                .candidate_index
+                .checked_sub(1)
+                .unwrap_or(self.candidates.len().saturating_sub(1));
+        }
+
+        let candidate: Vec<char> = self.candidates[self.candidate_index].chars().collect();
+        let inserted = candidate.len();
+        self.text.splice(self.token_start..self.cursor, candidate);
+        self.cursor = self.token_start.saturating_add(inserted);
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// byte index of the whitespace separated token that ends the text
+fn token_start(text: &str) -> usize {
+    text.rfind(char::is_whitespace)
+        .map_or(0, |i| i.saturating_add(1))
+}
+
+/// paths starting with the token, directories end in '/'
+fn file_candidates(token: &str) -> Vec<String> {
+    let (dir, prefix, entries) = directory_entries(token);
+    let mut candidates: Vec<String> = entries
+        .into_iter()
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| format!("{dir}{name}"))
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+/// splits a path token at its last '/' and lists the directory before it
+/// directories end in '/', hidden entries are only listed when the name typed starts with a dot
+pub fn directory_entries(token: &str) -> (&str, &str, Vec<String>) {
+    let (dir, name) = match token.rfind('/') {
+        Some(i) => token.split_at(i.saturating_add(1)),
+        None => ("", token),
+    };
+    let search_dir = if dir.is_empty() {
+        Path::new(".")
+    } else {
+        Path::new(dir)
+    };
+    let Ok(entries) = read_dir(search_dir) else {
+        return (dir, name, Vec::new());
+    };
+    let entries = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let entry_name = entry.file_name().into_string().ok()?;
+            if entry_name.starts_with('.') && !name.starts_with('.') {
+                return None;
+            }
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{entry_name}{slash}"))
+        })
+        .collect();
+    (dir, name, entries)
+}
+
+/// command names while the name is being typed, then file paths for commands that take a file
+/// and theme names for :colorscheme
+fn ex_candidates(before: &str) -> (usize, Vec<String>) {
+    let name_start = before
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(before.len());
+    let name = &before[name_start..];
+    if name.chars().all(|c| c.is_ascii_alphabetic()) {
+        let candidates = command_names()
+            .filter(|command| command.starts_with(name))
+            .map(str::to_string)
+            .collect();
+        return (name_start, candidates);
+    }
+    let kind = ExCommand::parse(before)
+        .ok()
+        .and_then(|command| command.kind);
+    let start = token_start(before);
+    if kind == Some(ExCommandKind::Colorscheme) {
+        let names = Theme::names()
+            .into_iter()
+            .filter(|name| name.starts_with(&before[start..]))
+            .collect();
+        return (start, names);
+    }
+    if !kind.is_some_and(ExCommandKind::takes_file) {
+        return (0, Vec::new());
+    }
+    (start, file_candidates(&before[start..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_text(prompt: &mut Prompt, text: &str) {
+        for c in text.chars() {
+            prompt.apply(PromptCommand::Insert(c));
+        }
+    }
+
+    #[test]
+    fn cursor_editing() {
+        let mut prompt = Prompt::new(":", Completion::None);
+        type_text(&mut prompt, "w file");
+        prompt.apply(PromptCommand::Home);
+        prompt.apply(PromptCommand::Right);
+        type_text(&mut prompt, "q");
+        assert_eq!(prompt.text(), "wq file");
+        prompt.apply(PromptCommand::End);
+        prompt.apply(PromptCommand::DeleteWord);
+        assert_eq!(prompt.text(), "wq ");
+        prompt.apply(PromptCommand::Left);
+        prompt.apply(PromptCommand::Delete);
+        assert_eq!(prompt.text(), "wq");
+    }
+
+    #[test]
+    fn word_motions() {
+        let mut prompt = Prompt::new(":", Completion::None);
+        type_text(&mut prompt, "sort u");
+        prompt.apply(PromptCommand::WordLeft);
+        prompt.apply(PromptCommand::WordLeft);
+        assert_eq!(prompt.cursor, 0);
+        prompt.apply(PromptCommand::WordRight);
+        assert_eq!(prompt.cursor, 4);
+        prompt.apply(PromptCommand::DeleteToStart);
+        assert_eq!(prompt.text(), " u");
+    }
+
+    #[test]
+    fn backspace_on_empty_cancels() {
+        let mut prompt = Prompt::new(":", Completion::None);
+        assert!(matches!(
+            prompt.apply(PromptCommand::Backspace),
+            PromptAction::Cancel
+        ));
+    }
+
+    #[test]
+    fn filter_rejects_chars() {
+        let mut prompt =
+            Prompt::new("Jump to: ", Completion::None).with_filter(|c| c.is_ascii_digit());
+        type_text(&mut prompt, "1a2");
+        assert_eq!(prompt.text(), "12");
+    }
+
+    #[test]
+    fn browse_history_restores_typed_text() {
+        let mut prompt = Prompt::new(":", Completion::None);
+        prompt.history = vec!["w".into(), "q".into()];
+        type_text(&mut prompt, "so");
+        prompt.apply(PromptCommand::HistoryPrevious);
+        assert_eq!(prompt.text(), "q");
+        prompt.apply(PromptCommand::HistoryPrevious);
+        assert_eq!(prompt.text(), "w");
+        prompt.apply(PromptCommand::HistoryNext);
+        prompt.apply(PromptCommand::HistoryNext);
+        assert_eq!(prompt.text(), "so");
+    }
+
+    #[test]
+    fn complete_command_names() {
+        let mut prompt = Prompt::new(":", Completion::ExCommand);
+        type_text(&mut prompt, "'<,'>so");
+        prompt.apply(PromptCommand::Complete);
+        assert_eq!(prompt.text(), "'<,'>sort");
+        let mut prompt = Prompt::new(":", Completion::ExCommand);
+        type_text(&mut prompt, "w");
+        prompt.apply(PromptCommand::Complete);
+        assert_eq!(prompt.text(), "write");
+        prompt.apply(PromptCommand::Complete);
+        assert_eq!(prompt.text(), "wq");
+        prompt.apply(PromptCommand::CompletePrevious);
+        assert_eq!(prompt.text(), "write");
+    }
+}
@@ -1,21 +1,27 @@
+use super::fold;
+use super::prompt::{Completion, HistoryKind, Prompt, PromptAction};
+use super::status::Status;
+use super::wrap;
+use crate::editor::backend::read;
 use crate::editor::editorcommands::SearchCommand;
 use crate::editor::{
-    terminal::{Coordinate, Mode, Position, ScreenOffset, ScreenPosition, Size, Terminal},
+    screen::{Screen, Style},
+    terminal::{Coordinate, Mode, Position, ScreenOffset, Size},
     view::Buffer,
 };
-use crossterm::event::read;
-use crossterm::style::{Attribute, Color, Print, PrintStyledContent, StyledContent, Stylize};
 use std::cmp::min;
 use std::collections::HashSet;
+use unicode_segmentation::UnicodeSegmentation;
 
 pub struct Search {
     index: usize, // index of search positions we are currently on
     cursor_position: Position,
     screen_offset: ScreenOffset,
-    highlight: Color,
-    text: Color,
+    matches: Style, // every match but the one the cursor is on
+    current: Style,
     stack: Vec<Vec<Position>>,
-    string: String,
+    string: String, // the query the stack was built for
+    prompt: Prompt,
     line_indicies: HashSet<usize>,
 }
 
@@ -26,9 +32,10 @@ impl Default for Search {
             string: String::new(),
             cursor_position: Position::default(),
             screen_offset: ScreenOffset::default(),
-            highlight: Color::DarkBlue,
-            text: Color::White,
+            matches: Style::default(),
+            current: Style::default(),
             stack: Vec::new(),
+            prompt: Prompt::new("Search: ", Completion::None),
             line_indicies: HashSet::new(),
         }
     }
@@ -41,27 +48,28 @@ enum IndexResolver {
 }
 
 impl Search {
-    pub fn new(pos: Position, offset: ScreenOffset, highlight: Color, text: Color) -> Self {
+    pub fn new(pos: Position, offset: ScreenOffset, matches: Style, current: Style) -> Self {
         Self {
             index: 0,
             string: String::new(),
             cursor_position: pos,
             screen_offset: offset,
-            highlight,
-            text,
+            matches,
+            current,
             stack: Vec::new(),
+            prompt: Prompt::new("Search: ", Completion::None).with_history(HistoryKind::Search),
             line_indicies: HashSet::new(),
         }
     }
 
     // entry
-    // no changes to buffer -> immutable reference
+    // the text does not change, only a fold closed over the current match is opened
     pub fn run(
         &mut self,
         prev_pos: &mut Position,
         prev_offset: &mut ScreenOffset,
         size: &mut Size,
-        buffer: &Buffer,
+        buffer: &mut Buffer,
     ) {
         loop {
             // on errors or events that dont matter in this context
@@ -71,13 +79,6 @@ impl Search {
 
             match SearchCommand::try_from(read_event) {
                 Ok(event) => match event {
-                    SearchCommand::Insert(c) => {
-                        // add char to search query
-                        self.string.push(c);
-                        self.stack.push(buffer.search(&self.string));
-                        self.index = self.find_relative_start(prev_pos.height).unwrap_or(0);
-                        self.set_line_indicies();
-                    }
                     SearchCommand::Next => {
                         //snap to next result
                         if !self.stack.is_empty() {
@@ -100,28 +101,28 @@ impl Search {
                             };
                         }
                     }
-                    SearchCommand::RevertState => {
-                        //return to pre search screen state
-                        self.revert_screen_state(prev_pos, prev_offset);
-                        break;
-                    }
-                    SearchCommand::AssumeState => {
-                        //assume current state on screen after search
-                        *prev_pos = self.cursor_position;
-                        *prev_offset = self.screen_offset;
-                        break;
-                    }
-                    SearchCommand::BackSpace => {
-                        // remove char from search query
-                        if !self.string.is_empty() {
-                            self.string.pop();
-                            self.stack.pop();
+                    SearchCommand::Edit(command) => match self.prompt.apply(command) {
+                        PromptAction::Submit(_) => {
+                            //assume current state on screen after search
+                            *prev_pos = self.cursor_position;
+                            *prev_offset = self.screen_offset;
+                            break;
+                        }
+                        PromptAction::Cancel => {
+                            //return to pre search screen state
+                            self.revert_screen_state(prev_pos, prev_offset);
+                            break;
+                        }
+                        PromptAction::Resize(new_size) => *size = new_size,
+                        PromptAction::Continue => {
+                            if self.prompt.text() == self.string {
+                                continue;
+                            }
+                            self.update_query(buffer);
                             self.index = self.find_relative_start(prev_pos.height).unwrap_or(0);
                             self.set_line_indicies();
                         }
-                    }
-                    SearchCommand::Resize(new_size) => *size = new_size,
-                    SearchCommand::NoAction => continue,
+                    },
                 },
                 Err(_) => continue,
             }
@@ -143,104 +144,82 @@ impl Search {
             //self.cursor_position = self.stack[self.stack.len() - 1][self.index].clone();
             self.cursor_position = self.stack.last().unwrap()[self.index];
 
-            // if the search position is out of current screen bounds
-            // if out width is within 0 - size
-            // snap offset left
-            if self.cursor_position.width < size.width {
-                self.screen_offset.snap_left();
-            }
-            match self
-                .cursor_position
-                .max_displacement_from_view(&self.screen_offset, &size, 3)
-            {
-                0_usize => {}
-                1_usize => {
-                    self.screen_offset
-                        .update_offset_single_move(&self.cursor_position, &size, 3)
+            // a closed fold over the match opens to show it
+            buffer.folds.reveal(self.cursor_position.height);
+
+            // wrapped rows never scroll sideways, they move down to the match
+            if self.screen_offset.wrap {
+                wrap::scroll_to_cursor(
+                    &mut self.screen_offset,
+                    &self.cursor_position,
+                    buffer,
+                    size,
+                    2,
+                );
+            } else {
+                // if the search position is out of current screen bounds
+                // if out width is within 0 - size
+                // snap offset left
+                if self.cursor_position.width < self.screen_offset.gutter.text_width(size) {
+                    self.screen_offset.snap_left();
                 }
-                _ => self.screen_offset.handle_offset_screen_snap(
+                fold::scroll_to_cursor(
+                    &mut self.screen_offset,
                     &self.cursor_position,
-                    &size,
-                    3,
-                    buffer.len(),
-                ),
+                    buffer,
+                    size,
+                );
             }
-            /*
-                        if !self
-                            .cursor_position
-                            .height_in_view(&self.screen_offset, size, 2)
-                            | !self
-                                .cursor_position
-                                .width_in_view(&self.screen_offset, size)
-                        {
-                            self.screen_offset.handle_offset_screen_snap(
-                                &self.cursor_position,
-                                size,
-                                3,
-                                buffer.len(),
-                            );
-                        }
-            */
         }
         self.render(buffer, size);
     }
 
     fn render(&self, buffer: &Buffer, size: &Size) {
-        // this largely is the same logic as Editor::refresh_screen
-        // maybe that logic should be called out of view to not reproduce code
-        if (size.width == 0) | (size.height == 0) {
+        if size.width == 0 || size.height == 0 {
             return;
         }
-        Terminal::hide_cursor().expect("Terminal error");
-        Terminal::move_cursor_to(self.screen_offset.to_position()).expect("Terminal error");
-        Terminal::clear_screen().expect("Terminal error");
-
-        #[allow(clippy::integer_division)]
-        for current_row in self.screen_offset.height
-            ..self
-                .screen_offset
-                .height
-                .saturating_add(size.height)
-                .saturating_sub(2)
-        {
-            let relative_row = current_row.saturating_sub(self.screen_offset.height);
-            if self.line_indicies.contains(&current_row) {
-                self.render_search_line(current_row, buffer, size, self.highlight, self.text);
-                continue;
-            }
-
-            // buffer should not be empty here
-            if let Some(line) = buffer.text.get(current_row) {
-                Terminal::render_line(
-                    relative_row,
-                    line.get_line_subset(
-                        self.screen_offset.width
-                            ..self.screen_offset.width.saturating_add(size.width),
-                    ),
-                )
-                .expect("Terminal Error");
-            } else {
-                Terminal::render_line(relative_row, "~").expect("Terminal error");
-            }
+        // the rows above the prompt and the status line, drawn like the view draws them
+        let count = size.height.saturating_sub(2);
+        let rows = wrap::screen_rows(
+            buffer,
+            &self.screen_offset,
+            size,
+            self.cursor_position.height,
+            count,
+        );
+        for (row, text) in rows.iter().enumerate() {
+            Screen::render_line(row, text);
         }
+        self.render_matches(buffer, size, count);
 
-        self.render_search_string(size);
-        Terminal::render_status_line(
-            &Mode::Search,
-            buffer.is_saved,
-            size,
-            buffer.filename.as_deref(),
-            Some((self.cursor_position.height.saturating_add(1), buffer.len())),
-        )
-        .expect("Terminal Error");
+        Status::of(Mode::Search, buffer, &self.cursor_position).render(size);
 
-        Terminal::move_cursor_to(
-            self.cursor_position
-                .relative_view_position(&self.screen_offset),
-        )
-        .expect("Terminal Error");
-        Terminal::show_cursor().expect("Terminal Error");
-        Terminal::execute().expect("Terminal Error");
+        // the cursor stays in the prompt while the query is edited
+        self.prompt
+            .render(size.height.saturating_sub(2), size.width);
+        Screen::present().expect("Terminal Error");
+    }
+
+    /// searches for the prompt text
+    /// typing or deleting at the end of the query reuses the results on the stack
+    fn update_query(&mut self, buffer: &Buffer) {
+        let query = self.prompt.text();
+        let query_len = query.chars().count();
+        let current_len = self.string.chars().count();
+        if query.is_empty() {
+            self.stack.clear();
+        } else if query.starts_with(&self.string) && query_len == current_len.saturating_add(1) {
+            self.stack.push(buffer.search(&query));
+        } else if self.string.starts_with(&query)
+            && query_len.saturating_add(1) == current_len
+            && self.stack.len() > 1
+        {
+            self.stack.pop();
+        } else {
+            self.stack.clear();
+            self.stack.push(buffer.search(&query));
+        }
+        self.string = query;
     }
 
     #[inline]
@@ -316,74 +295,53 @@ impl Search {
         }
     }
 
-    #[inline]
-    fn render_search_string(&self, size: &Size) {
-        let result = Terminal::render_line(
-            size.height.saturating_sub(2),
-            format!("Search: {}", self.string),
-        );
-
-        debug_assert!(result.is_ok(), "Failed to render line");
-    }
-
     fn set_line_indicies(&mut self) {
+        self.line_indicies.clear();
         if self.stack.is_empty() {
             return;
         }
 
-        self.line_indicies.clear();
-
         // iter through search hits for current query
         for position in &self.stack[self.stack.len().saturating_sub(1)] {
             self.line_indicies.insert(position.height);
         }
     }
 
-    #[inline]
-    fn render_search_line(
-        &self,
-        line: usize,
-        buffer: &Buffer,
-        size: &Size,
-        search_highlight: Color,
-        search_text: Color,
-    ) {
-        let styled_search: StyledContent<String> = self
-            .string
-            .clone()
-            .with(search_text)
-            .on(search_highlight)
-            .attribute(Attribute::Bold);
-
-        Terminal::move_cursor_to(ScreenPosition {
-            height: line.saturating_sub(self.screen_offset.height),
-            width: 0,
-        })
-        .expect("Terminal Error");
-        Terminal::clear_line().expect("Terminal Error");
-
-        let full_line = &buffer.text[line].raw_string;
-        let start = self.screen_offset.width;
-        let end = min(
-            self.screen_offset.width.saturating_add(size.width),
-            full_line.len(),
-        );
-        let current_line = match full_line.get(start..end) {
-            Some(text) => text,
-            None => return,
+    /// colors the matches on the rows drawn, the one the cursor is on stands out
+    fn render_matches(&self, buffer: &Buffer, size: &Size, count: usize) {
+        let Some(positions) = self.stack.last() else {
+            return;
         };
-        let mut split = current_line.split(&self.string);
-
-        if let Some(first) = split.next() {
-            if !current_line.starts_with(&self.string) {
-                Terminal::queue_command(Print(first)).expect("Terminal Error");
+        let query_len = self.string.graphemes(true).count();
+        let rows = wrap::display_rows(buffer, &self.screen_offset, size, count);
+        for (screen_row, row) in rows.iter().enumerate() {
+            if !self.line_indicies.contains(&row.line) || buffer.folds.closed_at(row.line).is_some()
+            {
+                continue;
+            }
+            let line = &buffer.text[row.line];
+            for position in positions
+                .iter()
+                .filter(|position| position.height == row.line)
+            {
+                // the matches are found at bytes of the line
+                let start = line
+                    .raw_string
+                    .get(..position.width)
+                    .map_or(0, |before| before.graphemes(true).count());
+                let Some(columns) = row.columns(line, start..start.saturating_add(query_len))
+                else {
+                    continue;
+                };
+                let style = if (position.height, position.width)
+                    == (self.cursor_position.height, self.cursor_position.width)
+                {
+                    self.current
+                } else {
+                    self.matches
+                };
+                Screen::restyle(screen_row, columns, style);
             }
-        };
-
-        for text in split {
-            Terminal::queue_command(PrintStyledContent(styled_search.clone()))
-                .expect("Terminal Error");
-            Terminal::queue_command(Print(text)).expect("Terminal Error");
         }
     }
 }
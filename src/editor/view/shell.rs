@@ -0,0 +1,192 @@
+// running external commands for :!, :{range}! and :r !
+// the command gets the text on stdin, stdout and stderr are read on their own threads
+// so a chatty command cannot block on a full pipe, and one that hangs is killed
+// the output is only waited for until the timeout too, something left running in the
+// background can hold the pipes open after the command itself is done
+use crate::editor::backend::read;
+use crate::editor::screen::Screen;
+use crate::editor::terminal::Size;
+use crossterm::event::Event;
+use std::io::{Error, Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// the longest a command may run before it is killed
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+const POLL: Duration = Duration::from_millis(10);
+
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+    pub success: bool,
+}
+
+impl Output {
+    /// the last line of stderr, what goes on the message line
+    pub fn error_line(&self) -> Option<&str> {
+        self.stderr
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+    }
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    } else {
+        let program = std::env::var("SHELL")
+            .ok()
+            .filter(|shell| !shell.is_empty())
+            .unwrap_or_else(|| "sh".into());
+        let mut shell = Command::new(program);
+        shell.arg("-c").arg(command);
+        shell
+    }
+}
+
+/// reads a pipe to its end on a thread, the text arrives once every writer closed it
+fn collect(mut pipe: impl Read + Send + 'static) -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        let _ = sender.send(String::from_utf8_lossy(&bytes).into_owned());
+    });
+    receiver
+}
+
+/// runs a command through the shell, feeding it input when given
+pub fn run(command: &str, input: Option<String>, timeout: Duration) -> Result<Output, String> {
+    let start = Instant::now();
+    let too_long = || {
+        format!(
+            "{command} took longer than {}s and was stopped",
+            timeout.as_secs()
+        )
+    };
+    let mut child = shell(command)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Cannot run {command}: {e}"))?;
+
+    // a command that stops reading early closes the pipe, which is not an error here
+    // the writer is not waited for, the output says all there is about the command
+    if let Some((mut stdin, input)) = child.stdin.take().zip(input) {
+        thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        });
+    }
+    let stdout = child.stdout.take().map(collect);
+    let stderr = child.stderr.take().map(collect);
+
+    let success = wait(&mut child, timeout)
+        .map_err(|e| format!("Cannot run {command}: {e}"))?
+        .ok_or_else(too_long)?;
+    let text = |receiver: Option<Receiver<String>>| match receiver {
+        Some(receiver) => match receiver.recv_timeout(timeout.saturating_sub(start.elapsed())) {
+            Ok(text) => Ok(text),
+            Err(RecvTimeoutError::Timeout) => Err(too_long()),
+            Err(RecvTimeoutError::Disconnected) => Ok(String::new()),
+        },
+        None => Ok(String::new()),
+    };
+    Ok(Output {
+        stdout: text(stdout)?,
+        stderr: text(stderr)?,
+        success,
+    })
+}
+
+/// whether the command succeeded, None when it ran out of time and was killed
+fn wait(child: &mut Child, timeout: Duration) -> Result<Option<bool>, Error> {
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status.success()));
+        }
+        if start.elapsed() >= timeout {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(POLL);
+    }
+}
+
+/// shows the output of :!cmd over the whole screen until a key is pressed
+/// only the end fits when the output is long, like a terminal that scrolled
+pub fn show(size: &mut Size, command: &str, output: &Output) -> Result<(), Error> {
+    let mut lines = vec![format!(":!{command}")];
+    lines.extend(output.stdout.lines().map(str::to_string));
+    lines.extend(output.stderr.lines().map(str::to_string));
+    if !output.success {
+        lines.push("shell returned an error".to_string());
+    }
+    loop {
+        Screen::clear();
+        Screen::hide_cursor();
+        let last = size.height.saturating_sub(1);
+        let skip = lines.len().saturating_sub(last);
+        for (row, line) in lines.iter().skip(skip).enumerate() {
+            Screen::render_line(row, line);
+        }
+        Screen::render_line(last, "Press any key to continue");
+        Screen::present()?;
+        match read()? {
+            Event::Key(_) => return Ok(()),
+            #[allow(clippy::as_conversions)]
+            Event::Resize(width, height) => {
+                *size = Size {
+                    height: height as usize,
+                    width: width as usize,
+                };
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_input_and_keeps_stderr_apart() {
+        let output = run("sort; echo oops >&2", Some("b\na\n".into()), TIMEOUT).unwrap();
+        assert_eq!(output.stdout, "a\nb\n");
+        assert_eq!(output.error_line(), Some("oops"));
+        assert!(output.success);
+        assert!(!run("exit 3", None, TIMEOUT).unwrap().success);
+    }
+
+    #[test]
+    fn stops_commands_that_run_too_long() {
+        let start = Instant::now();
+        let err = run("sleep 5", None, Duration::from_millis(100))
+            .err()
+            .expect("the command should time out");
+        assert!(err.contains("was stopped"));
+        assert!(start.elapsed() < Duration::from_secs(4));
+    }
+
+    #[test]
+    fn stops_waiting_for_output_held_open_in_the_background() {
+        let start = Instant::now();
+        let err = run("sleep 5 & echo started", None, Duration::from_millis(300))
+            .err()
+            .expect("the output should time out");
+        assert!(err.contains("took longer than"));
+        assert!(start.elapsed() < Duration::from_secs(4));
+    }
+}
@@ -0,0 +1,500 @@
+// the status line, made of segments on the left and on the right of the last row
+// status.conf in the config directory picks them and gives them colors over the theme's status:
+//   left = mode path modified branch
+//   right = diagnostics selection filetype encoding line_ending position percent
+//   mode = black on blue bold
+// when the row is too narrow the path loses its directories and the diagnostics their message,
+// then segments are left out, the ones last in PRIORITY first
+use super::buffer::Buffer;
+use super::theme::parse_style;
+use crate::editor::config::config_dir;
+use crate::editor::screen::{Screen, Style};
+use crate::editor::terminal::{Mode, Position, Size};
+use std::cell::RefCell;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use unicode_width::UnicodeWidthStr;
+
+const CONFIG_FILE: &str = "status.conf";
+/// how long the branch of a directory is known before .git/HEAD is read again
+const BRANCH_TTL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+    Mode,
+    Path,
+    Modified,
+    FileType,
+    Encoding,
+    LineEnding,
+    Position,
+    Percent,
+    Selection,
+    Branch,
+    Diagnostics,
+}
+
+/// the names used in status.conf
+const NAMES: [(&str, Segment); 11] = [
+    ("mode", Segment::Mode),
+    ("path", Segment::Path),
+    ("modified", Segment::Modified),
+    ("filetype", Segment::FileType),
+    ("encoding", Segment::Encoding),
+    ("line_ending", Segment::LineEnding),
+    ("position", Segment::Position),
+    ("percent", Segment::Percent),
+    ("selection", Segment::Selection),
+    ("branch", Segment::Branch),
+    ("diagnostics", Segment::Diagnostics),
+];
+
+/// the segments kept the longest on a narrow row first
+const PRIORITY: [Segment; 11] = [
+    Segment::Mode,
+    Segment::Modified,
+    Segment::Position,
+    Segment::Path,
+    Segment::Diagnostics,
+    Segment::Selection,
+    Segment::Percent,
+    Segment::Branch,
+    Segment::FileType,
+    Segment::LineEnding,
+    Segment::Encoding,
+];
+
+fn segment_of(name: &str) -> Option<Segment> {
+    NAMES
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, segment)| *segment)
+}
+
+/// what the status line shows, the segments without a value are left out
+pub struct Status<'a> {
+    pub mode: Mode,
+    pub path: Option<&'a str>,
+    pub saved: bool,
+    pub position: Option<(usize, Option<usize>)>, // the line and column, from 1
+    pub lines: usize,
+    pub filetype: Option<&'a str>,
+    pub encoding: Option<&'a str>,
+    pub line_ending: Option<&'a str>,
+    pub selection: Option<usize>, // the characters selected
+    pub diagnostics: String,
+}
+
+impl<'a> Status<'a> {
+    pub fn new(mode: Mode) -> Self {
+        Self {
+            mode,
+            path: None,
+            saved: true,
+            position: None,
+            lines: 0,
+            filetype: None,
+            encoding: None,
+            line_ending: None,
+            selection: None,
+            diagnostics: String::new(),
+        }
+    }
+
+    /// the status of a buffer with the cursor at a position
+    pub fn of(mode: Mode, buffer: &'a Buffer, cursor: &Position) -> Self {
+        let path = buffer.filename.as_deref();
+        Self {
+            path,
+            saved: buffer.is_saved,
+            position: Some((
+                cursor.height.saturating_add(1),
+                Some(cursor.width.saturating_add(1)),
+            )),
+            lines: std::cmp::max(buffer.len(), 1),
+            filetype: Some(
+                path.and_then(|path| Path::new(path).extension())
+                    .and_then(|extension| extension.to_str())
+                    .unwrap_or("text"),
+            ),
+            encoding: Some("utf-8"),
+            line_ending: Some(buffer.line_ending.name()),
+            diagnostics: buffer.lsp_summary(cursor.height),
+            ..Self::new(mode)
+        }
+    }
+
+    #[must_use]
+    pub fn with_selection(self, selection: usize) -> Self {
+        Self {
+            selection: Some(selection),
+            ..self
+        }
+    }
+
+    /// the text of a segment, None when there is nothing to show
+    fn text(&self, segment: Segment) -> Option<String> {
+        let text = match segment {
+            Segment::Mode => self.mode.to_string().to_string(),
+            Segment::Path => self.path.unwrap_or("[No Name]").to_string(),
+            Segment::Modified => (!self.saved).then(|| "[+]".to_string())?,
+            Segment::FileType => self.filetype?.to_string(),
+            Segment::Encoding => self.encoding?.to_string(),
+            Segment::LineEnding => self.line_ending?.to_string(),
+            Segment::Position => match self.position? {
+                (line, Some(column)) => format!("{line}:{column}"),
+                (line, None) => format!("{line}/{}", self.lines),
+            },
+            #[allow(clippy::integer_division)]
+            Segment::Percent => {
+                let (line, _) = self.position?;
+                format!(
+                    "{}%",
+                    line.saturating_mul(100) / std::cmp::max(self.lines, 1)
+                )
+            }
+            Segment::Selection => format!("{} selected", self.selection?),
+            Segment::Branch => branch(self.path)?,
+            Segment::Diagnostics => self.diagnostics.clone(),
+        };
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// draws the status line on the last row with the configured segments
+    pub fn render(&self, size: &Size) {
+        let (text, styles) = LINE.with(|line| line.borrow().layout(self, size.width));
+        Screen::render_status_line(size, &text, &styles);
+    }
+}
+
+/// a shorter text for a segment that can lose part of it, at most `width` wide if it can be
+fn shorten(segment: Segment, text: &str, width: usize) -> Option<String> {
+    match segment {
+        // the directories go first, from the start, the file name stays
+        Segment::Path => {
+            let name = text.rsplit(['/', '\\']).next().unwrap_or(text);
+            if name == text {
+                return None;
+            }
+            let keep = std::cmp::max(width.saturating_sub(1), name.width());
+            let mut tail = text.to_string();
+            while tail.width() > keep {
+                tail.remove(0);
+            }
+            Some(format!("…{tail}"))
+        }
+        // the message of the line goes, the counts stay
+        Segment::Diagnostics => {
+            let (counts, message) = text.split_once(" | ")?;
+            if message.is_empty() {
+                return Some(counts.to_string());
+            }
+            let room = width.saturating_sub(counts.width().saturating_add(4));
+            let message: String = message.chars().take(room).collect();
+            Some(if message.is_empty() {
+                counts.to_string()
+            } else {
+                format!("{counts} | {message}…")
+            })
+        }
+        _ => None,
+    }
+}
+
+/// the segments and their colors, from status.conf
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusLine {
+    left: Vec<Segment>,
+    right: Vec<Segment>,
+    styles: Vec<(Segment, Style)>,
+}
+
+impl Default for StatusLine {
+    fn default() -> Self {
+        Self {
+            left: vec![
+                Segment::Mode,
+                Segment::Path,
+                Segment::Modified,
+                Segment::Branch,
+            ],
+            right: vec![
+                Segment::Diagnostics,
+                Segment::Selection,
+                Segment::FileType,
+                Segment::Encoding,
+                Segment::LineEnding,
+                Segment::Position,
+                Segment::Percent,
+            ],
+            styles: vec![(
+                Segment::Mode,
+                Style {
+                    bold: true,
+                    ..Style::default()
+                },
+            )],
+        }
+    }
+}
+
+thread_local! {
+    static LINE: RefCell<StatusLine> = RefCell::new(StatusLine::default());
+    /// the directory looked at last, its branch and when it was read
+    static BRANCH: RefCell<Option<(PathBuf, Option<String>, Instant)>> = const { RefCell::new(None) };
+}
+
+impl StatusLine {
+    /// the default segments changed by status.conf
+    pub fn configured() -> Self {
+        let mut line = Self::default();
+        if let Some(conf) =
+            config_dir().and_then(|dir| fs::read_to_string(dir.join(CONFIG_FILE)).ok())
+        {
+            line.configure(&conf);
+        }
+        line
+    }
+
+    /// lines that do not parse are left out, so are unknown segments
+    fn configure(&mut self, conf: &str) {
+        for line in conf.lines() {
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let segments = || value.split_whitespace().filter_map(segment_of).collect();
+            match key.trim() {
+                "left" => self.left = segments(),
+                "right" => self.right = segments(),
+                name => {
+                    let (Some(segment), Ok(style)) = (segment_of(name), parse_style(value)) else {
+                        continue;
+                    };
+                    self.styles.retain(|(known, _)| *known != segment);
+                    self.styles.push((segment, style));
+                }
+            }
+        }
+    }
+
+    /// makes the line the one every mode draws
+    pub fn install(self) {
+        LINE.with(|line| *line.borrow_mut() = self);
+    }
+
+    /// the text of the row and the columns of every segment with its color
+    fn layout(&self, status: &Status, width: usize) -> (String, Vec<(Range<usize>, Style)>) {
+        let texts = |segments: &[Segment]| -> Vec<(Segment, String)> {
+            segments
+                .iter()
+                .filter_map(|segment| Some((*segment, status.text(*segment)?)))
+                .collect()
+        };
+        let mut left = texts(&self.left);
+        let mut right = texts(&self.right);
+        // a segment is padded with a blank on each side
+        let used = |left: &[(Segment, String)], right: &[(Segment, String)]| {
+            left.iter()
+                .chain(right)
+                .map(|(_, text)| text.width().saturating_add(2))
+                .sum::<usize>()
+        };
+        while used(&left, &right) > width {
+            let over = used(&left, &right).saturating_sub(width);
+            let shortened = [Segment::Diagnostics, Segment::Path].iter().any(|kind| {
+                let Some((segment, text)) = left
+                    .iter_mut()
+                    .chain(right.iter_mut())
+                    .find(|(segment, _)| segment == kind)
+                else {
+                    return false;
+                };
+                match shorten(*segment, text, text.width().saturating_sub(over)) {
+                    Some(shorter) if shorter.width() < text.width() => {
+                        *text = shorter;
+                        true
+                    }
+                    _ => false,
+                }
+            });
+            if shortened {
+                continue;
+            }
+            let Some(last) = PRIORITY.iter().rev().find(|kind| {
+                left.iter()
+                    .chain(&right)
+                    .any(|(segment, _)| segment == *kind)
+            }) else {
+                break;
+            };
+            // the one most important segment stays even when it does not fit
+            if left.len().saturating_add(right.len()) == 1 {
+                break;
+            }
+            left.retain(|(segment, _)| segment != last);
+            right.retain(|(segment, _)| segment != last);
+        }
+
+        let style_of = |segment: Segment| {
+            self.styles
+                .iter()
+                .find(|(known, _)| *known == segment)
+                .map_or_else(Style::default, |(_, style)| *style)
+        };
+        let gap = width.saturating_sub(used(&left, &right));
+        let mut text = String::new();
+        let mut styles = Vec::new();
+        let mut column: usize = 0;
+        for (index, (segment, segment_text)) in left.iter().chain(&right).enumerate() {
+            // the right segments end at the last column
+            if index == left.len() {
+                text.push_str(&" ".repeat(gap));
+                column = column.saturating_add(gap);
+            }
+            let end = column
+                .saturating_add(segment_text.width())
+                .saturating_add(2);
+            text.push(' ');
+            text.push_str(segment_text);
+            text.push(' ');
+            styles.push((column..end, style_of(*segment)));
+            column = end;
+        }
+        (text, styles)
+    }
+}
+
+/// the git branch of the directory of a path, or of the working directory without one
+fn branch(path: Option<&str>) -> Option<String> {
+    let start = match path.map(Path::new) {
+        Some(path) if path.is_dir() => path.to_path_buf(),
+        Some(path) => path.parent()?.to_path_buf(),
+        None => PathBuf::from("."),
+    };
+    BRANCH.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if let Some((dir, branch, read)) = cache.as_ref() {
+            if *dir == start && read.elapsed() < BRANCH_TTL {
+                return branch.clone();
+            }
+        }
+        let branch = branch_of(&start);
+        *cache = Some((start, branch.clone(), Instant::now()));
+        branch
+    })
+}
+
+/// the branch checked out in the repository a directory is in, a detached head shows its commit
+fn branch_of(dir: &Path) -> Option<String> {
+    let dir = fs::canonicalize(dir).ok()?;
+    for ancestor in dir.ancestors() {
+        let git = ancestor.join(".git");
+        // a worktree or submodule has a file pointing at its git directory
+        let git = match fs::read_to_string(&git) {
+            Ok(link) => ancestor.join(link.strip_prefix("gitdir:")?.trim()),
+            Err(_) if git.is_dir() => git,
+            Err(_) => continue,
+        };
+        let head = fs::read_to_string(git.join("HEAD")).ok()?;
+        let head = head.trim();
+        return Some(match head.strip_prefix("ref: ") {
+            Some(name) => name.strip_prefix("refs/heads/").unwrap_or(name).to_string(),
+            None => head.chars().take(7).collect(),
+        });
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(path: &str) -> Status<'_> {
+        Status {
+            path: Some(path),
+            saved: false,
+            position: Some((3, Some(7))),
+            lines: 12,
+            filetype: Some("rs"),
+            encoding: Some("utf-8"),
+            line_ending: Some("LF"),
+            diagnostics: "E:1 W:0 | expected type".to_string(),
+            ..Status::new(Mode::Vim)
+        }
+    }
+
+    #[test]
+    fn segments_are_aligned_and_left_out_when_narrow() {
+        let line = StatusLine::default();
+        let (text, styles) = line.layout(&status("nowhere/main.rs"), 80);
+        assert_eq!(
+            text,
+            " Vim  nowhere/main.rs  [+]     \
+             E:1 W:0 | expected type  rs  utf-8  LF  3:7  25% "
+        );
+        assert_eq!(text.width(), 80);
+        assert_eq!(styles[0], (0..5, line.styles[0].1));
+
+        // the message goes before the directories, then the segments last in priority
+        let (text, _) = line.layout(&status("nowhere/main.rs"), 48);
+        assert_eq!(text, " Vim  …main.rs  [+]   E:1 W:0  rs  LF  3:7  25% ");
+        let (text, _) = line.layout(&status("a/long/way/down/main.rs"), 30);
+        assert_eq!(text, " Vim  …main.rs  [+]       3:7 ");
+        let (text, _) = line.layout(&status("main.rs"), 3);
+        assert_eq!(text, " Vim ");
+    }
+
+    #[test]
+    fn a_selection_leaves_out_the_line_ending_when_narrow() {
+        let line = StatusLine::default();
+        let status = Status {
+            mode: Mode::Highlight,
+            position: Some((1, Some(2))),
+            lines: 2,
+            filetype: Some("txt"),
+            line_ending: Some("CRLF"),
+            diagnostics: String::new(),
+            ..status("/tmp/mini-vim-headless-4242-status.txt")
+        }
+        .with_selection(2);
+        let (text, _) = line.layout(&status, 100);
+        assert_eq!(
+            text,
+            " Highlight  /tmp/mini-vim-headless-4242-status.txt  [+]      \
+             2 selected  txt  utf-8  CRLF  1:2  50% "
+        );
+        let (text, _) = line.layout(&status, 80);
+        assert_eq!(
+            text,
+            " Highlight  …mini-vim-headless-4242-status.txt  [+]   2 selected  txt  1:2  50% "
+        );
+    }
+
+    #[test]
+    fn status_conf_picks_segments_and_colors() {
+        let mut line = StatusLine::default();
+        line.configure("# mine\nleft = position nothing\nright = mode\nposition = red on black\n");
+        assert_eq!(line.left, [Segment::Position]);
+        assert_eq!(line.right, [Segment::Mode]);
+        let (text, styles) = line.layout(&status("main.rs"), 14);
+        assert_eq!(text, " 3:7      Vim ");
+        assert_eq!(styles[0].1, parse_style("red on black").unwrap());
+    }
+
+    #[test]
+    fn finds_the_branch_of_a_repository() {
+        let dir = std::env::temp_dir().join(format!("mini-vim-branch-{}", std::process::id()));
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join(".git/HEAD"), "ref: refs/heads/topic\n").unwrap();
+        assert_eq!(branch_of(&dir.join("src")).as_deref(), Some("topic"));
+        fs::write(dir.join(".git/HEAD"), "0123456789abcdef\n").unwrap();
+        assert_eq!(branch_of(&dir).as_deref(), Some("0123456"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
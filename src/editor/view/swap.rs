@@ -0,0 +1,129 @@
+// crash recovery, a copy of a modified buffer kept next to its file as .name.swp
+// the copy is written on the first edit, when the editor goes idle and when it has not been written for a while
+// a save or a normal exit removes it, a crash leaves it behind for the next start
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Error;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// how long without input before the swap file is brought up to date
+pub const IDLE: Duration = Duration::from_secs(2);
+/// the longest a modified buffer goes without its swap file being written while typing
+const MAX_AGE: Duration = Duration::from_secs(30);
+
+/// the swap file of a file, hidden in the same directory
+pub fn swap_path(filename: &str) -> PathBuf {
+    let path = Path::new(filename);
+    let name = path
+        .file_name()
+        .map_or_else(|| filename.into(), |name| name.to_string_lossy());
+    path.with_file_name(format!(".{name}.swp"))
+}
+
+pub fn hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// what has been written to the swap file of a buffer
+#[derive(Default, Clone)]
+pub struct Swap {
+    written: Option<u64>, // hash of the text in the swap file
+    last_write: Option<Instant>,
+}
+
+impl Swap {
+    /// a write while typing, only when the last one is old enough
+    pub fn is_due(&self) -> bool {
+        self.last_write
+            .is_none_or(|last_write| last_write.elapsed() >= MAX_AGE)
+    }
+
+    /// writes the text unless the swap file already holds it
+    pub fn write(&mut self, filename: &str, text: &str) -> Result<(), Error> {
+        let hash = hash(text);
+        if self.written != Some(hash) {
+            fs::write(swap_path(filename), text)?;
+            self.written = Some(hash);
+        }
+        self.last_write = Some(Instant::now());
+        Ok(())
+    }
+
+    /// the swap file already holds text, ie it was just recovered from
+    pub fn adopt(&mut self, text: &str) {
+        self.written = Some(hash(text));
+        self.last_write = Some(Instant::now());
+    }
+
+    pub fn remove(&mut self, filename: &str) {
+        if self.written.take().is_some() || swap_path(filename).exists() {
+            // a swap file that cannot be removed is offered again on the next start
+            let _ = fs::remove_file(swap_path(filename));
+        }
+        self.last_write = None;
+    }
+}
+
+/// a swap file left behind by an editor that did not exit normally
+pub struct Recovery {
+    pub text: String,
+    pub file_text: String,
+}
+
+/// the swap file of a file when it is at least as new as the file and holds something else
+/// a swap file matching the file has nothing to recover and is removed
+pub fn find(filename: &str) -> Option<Recovery> {
+    let swap = swap_path(filename);
+    let swap_modified = fs::metadata(&swap).and_then(|meta| meta.modified()).ok()?;
+    if let Ok(file_modified) = fs::metadata(filename).and_then(|meta| meta.modified()) {
+        if swap_modified < file_modified {
+            return None;
+        }
+    }
+    let text = fs::read_to_string(&swap).ok()?;
+    let file_text = fs::read_to_string(filename).unwrap_or_default();
+    if text == file_text {
+        let _ = fs::remove_file(&swap);
+        return None;
+    }
+    Some(Recovery { text, file_text })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swap_file_is_hidden_next_to_the_file() {
+        assert_eq!(swap_path("notes.txt"), PathBuf::from(".notes.txt.swp"));
+        assert_eq!(
+            swap_path("/tmp/dir/notes.txt"),
+            PathBuf::from("/tmp/dir/.notes.txt.swp")
+        );
+    }
+
+    #[test]
+    fn finds_newer_swap_files_only_when_they_differ() {
+        let file = std::env::temp_dir().join(format!("mini-vim-swap-{}", std::process::id()));
+        let filename = file.to_str().unwrap();
+        fs::write(&file, "one\n").unwrap();
+
+        let mut swap = Swap::default();
+        swap.write(filename, "one\ntwo\n").unwrap();
+        let recovery = find(filename).expect("a newer swap file is offered");
+        assert_eq!(recovery.text, "one\ntwo\n");
+        assert_eq!(recovery.file_text, "one\n");
+
+        // a swap file holding what the file holds is dropped
+        fs::write(swap_path(filename), "one\n").unwrap();
+        assert!(find(filename).is_none());
+        assert!(!swap_path(filename).exists());
+
+        swap.remove(filename);
+        let _ = fs::remove_file(&file);
+    }
+}
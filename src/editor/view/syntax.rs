@@ -0,0 +1,168 @@
+// the words of a line the theme colors, keywords, strings, comments, numbers, types and calls
+// a line is read on its own, a string or block comment left open runs to the end of the line
+// files whose type has no keywords are not colored
+use super::filetype::FileType;
+use super::line::Line;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    Keyword,
+    String,
+    Comment,
+    Number,
+    Type,     // a word starting with a capital
+    Function, // a word followed by an opening parenthesis
+}
+
+fn is_word(grapheme: &str) -> bool {
+    grapheme
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// whether the graphemes from start spell the text
+fn starts_with(graphemes: &[&str], start: usize, text: &str) -> bool {
+    let mut rest = text;
+    for grapheme in graphemes.iter().skip(start) {
+        if rest.is_empty() {
+            return true;
+        }
+        match rest.strip_prefix(grapheme) {
+            Some(after) => rest = after,
+            None => return false,
+        }
+    }
+    rest.is_empty()
+}
+
+/// the grapheme ranges of a line with the kind of token each is
+pub fn tokens(line: &Line, filetype: &FileType) -> Vec<(Range<usize>, Token)> {
+    if filetype.keywords.is_empty() {
+        return Vec::new();
+    }
+    let graphemes: Vec<&str> = line
+        .string
+        .iter()
+        .map(|fragment| fragment.grapheme.as_str())
+        .collect();
+    // quotes typed in pairs start strings, so a lifetime in rust does not
+    let quotes: Vec<&str> = ["\"", "'", "`"]
+        .into_iter()
+        .filter(|quote| {
+            filetype
+                .pairs
+                .iter()
+                .any(|(open, _)| quote.starts_with(*open))
+        })
+        .collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while index < graphemes.len() {
+        let grapheme = graphemes[index];
+        let start = index;
+        if filetype
+            .comment
+            .as_deref()
+            .is_some_and(|comment| starts_with(&graphemes, index, comment))
+        {
+            tokens.push((start..graphemes.len(), Token::Comment));
+            break;
+        }
+        if let Some((open, close)) = &filetype.block_comment {
+            if starts_with(&graphemes, index, open) {
+                index = index.saturating_add(open.chars().count());
+                while index < graphemes.len() && !starts_with(&graphemes, index, close) {
+                    index = index.saturating_add(1);
+                }
+                index = std::cmp::min(index.saturating_add(close.chars().count()), graphemes.len());
+                tokens.push((start..index, Token::Comment));
+                continue;
+            }
+        }
+        if quotes.contains(&grapheme) {
+            index = index.saturating_add(1);
+            while index < graphemes.len() && graphemes[index] != grapheme {
+                // an escaped quote does not end the string
+                if graphemes[index] == "\\" {
+                    index = index.saturating_add(1);
+                }
+                index = index.saturating_add(1);
+            }
+            index = std::cmp::min(index.saturating_add(1), graphemes.len());
+            tokens.push((start..index, Token::String));
+            continue;
+        }
+        if !is_word(grapheme) {
+            index = index.saturating_add(1);
+            continue;
+        }
+        while index < graphemes.len()
+            && (is_word(graphemes[index])
+                || (graphemes[index] == "." && graphemes[start].starts_with(char::is_numeric)))
+        {
+            index = index.saturating_add(1);
+        }
+        let word = graphemes[start..index].concat();
+        let next = graphemes[index..]
+            .iter()
+            .find(|grapheme| !grapheme.trim().is_empty());
+        let token = if word.starts_with(char::is_numeric) {
+            Some(Token::Number)
+        } else if filetype.keywords.contains(&word) {
+            Some(Token::Keyword)
+        } else if next == Some(&"(") {
+            Some(Token::Function)
+        } else if word.starts_with(char::is_uppercase) {
+            Some(Token::Type)
+        } else {
+            None
+        };
+        if let Some(token) = token {
+            tokens.push((start..index, token));
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(text: &str, extension: &str) -> Vec<(String, Token)> {
+        let line = Line::from(text);
+        let filetype = FileType::for_file(Some(&format!("file.{extension}")));
+        tokens(&line, &filetype)
+            .into_iter()
+            .map(|(range, token)| (line.get_line_subset(range).to_string(), token))
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_tokens_of_a_line() {
+        let found = kinds("let s: Vec<u8> = parse(\"a \\\" b\", 1.5); // 'x'", "rs");
+        let expected = [
+            ("let", Token::Keyword),
+            ("Vec", Token::Type),
+            ("parse", Token::Function),
+            ("\"a \\\" b\"", Token::String),
+            ("1.5", Token::Number),
+            ("// 'x'", Token::Comment),
+        ];
+        assert_eq!(
+            found,
+            expected.map(|(text, token)| (text.to_string(), token))
+        );
+        // a lifetime is no string in rust, a quote is one in python
+        assert_eq!(kinds("&'a str", "rs"), []);
+        assert_eq!(
+            kinds("x = 'it' # no", "py"),
+            [
+                ("'it'".to_string(), Token::String),
+                ("# no".to_string(), Token::Comment)
+            ]
+        );
+        assert_eq!(kinds("let x = 1", "txt"), []);
+    }
+}
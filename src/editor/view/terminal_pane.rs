@@ -0,0 +1,359 @@
+// a shell running below the text, so tests and builds do not mean leaving the editor
+// the pane takes the bottom rows of the screen, its grid is kept up to date from the shell output
+// with the keys on it everything typed goes to the shell, Ctrl-o hands them back to the text
+// and Ctrl-n freezes the output to move through it with vim keys and yank lines
+mod grid;
+mod pty;
+use super::clipboard_interface::ClipboardUtils;
+use super::messages;
+use crate::editor::backend::read;
+use crate::editor::screen::{Cell, Screen, Style};
+use crate::editor::terminal::{Mode, ScreenPosition, Size};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use grid::Grid;
+use pty::Pty;
+use std::io::Error;
+use std::path::Path;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::Duration;
+
+/// how often the editor looks for output while the pane is open
+pub const TICK: Duration = Duration::from_millis(30);
+
+pub struct TerminalPane {
+    grid: Grid,
+    pty: Pty,
+    output: Receiver<Vec<u8>>,
+    shell: String,
+    height: usize, // rows of the screen, the status line included
+    pub focused: bool,
+    exited: bool,
+}
+
+impl TerminalPane {
+    /// starts the user's shell in a pane at the bottom of the screen
+    pub fn open(screen: Size) -> Result<Self, Error> {
+        let height = Self::height_for(screen);
+        let size = Self::grid_size(screen, height);
+        let program = pty::shell();
+        let pty = Pty::spawn(&program, size)?;
+        // the status line only has room for the name
+        let shell = Path::new(&program)
+            .file_name()
+            .map_or(program.clone(), |name| name.to_string_lossy().into_owned());
+        let output = pty.output()?;
+        Ok(Self {
+            grid: Grid::new(size),
+            pty,
+            output,
+            shell,
+            height,
+            focused: false,
+            exited: false,
+        })
+    }
+
+    /// two fifths of the screen, leaving the text at least a line and its status
+    #[allow(clippy::integer_division)]
+    fn height_for(screen: Size) -> usize {
+        (screen.height.saturating_mul(2) / 5)
+            .max(2)
+            .min(screen.height.saturating_sub(2))
+    }
+
+    fn grid_size(screen: Size, height: usize) -> Size {
+        Size {
+            height: height.saturating_sub(1).max(1),
+            width: screen.width.max(1),
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// splits the screen between the pane and the text, returns the size left for the text
+    pub fn fit(&mut self, screen: Size) -> Size {
+        self.height = Self::height_for(screen);
+        let size = Self::grid_size(screen, self.height);
+        if size != self.grid.size() {
+            self.grid.resize(size);
+            let _ = self.pty.resize(size);
+        }
+        Size {
+            height: screen.height.saturating_sub(self.height),
+            width: screen.width,
+        }
+    }
+
+    /// takes in what the shell wrote since the last look
+    /// returns whether the pane needs drawing again
+    pub fn update(&mut self) -> bool {
+        let mut changed = false;
+        loop {
+            match self.output.try_recv() {
+                Ok(bytes) => {
+                    self.grid.feed(&bytes);
+                    changed = true;
+                }
+                Err(TryRecvError::Empty) => return changed,
+                Err(TryRecvError::Disconnected) => {
+                    self.exited = true;
+                    return true;
+                }
+            }
+        }
+    }
+
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    /// types a key into the shell
+    pub fn send(&mut self, key: KeyEvent) {
+        if let Some(bytes) = encode(key, self.grid.application_cursor) {
+            if self.pty.write(&bytes).is_err() {
+                self.exited = true;
+            }
+        }
+    }
+
+    /// draws the grid and the status line of the pane, below the rows of the text
+    pub fn render(&self, top: usize) {
+        for (row, cells) in self.grid.rows().iter().enumerate() {
+            render_cells(top.saturating_add(row), cells, None);
+        }
+        let hint = if self.focused {
+            "Ctrl-o = text | Ctrl-n = scrollback"
+        } else {
+            "Ctrl-o = terminal"
+        };
+        self.render_status(top, &Mode::Terminal, hint);
+    }
+
+    fn render_status(&self, top: usize, mode: &Mode, hint: &str) {
+        let status = format!(
+            "Mode: {} | Shell: {} | {hint}",
+            mode.to_string(),
+            self.shell
+        );
+        let width = self.grid.size().width;
+        Screen::render_line(
+            top.saturating_add(self.height.saturating_sub(1)),
+            status.chars().take(width).collect::<String>(),
+        );
+    }
+
+    /// where the shell left its cursor, None when a program hid it
+    pub fn cursor(&self, top: usize) -> Option<ScreenPosition> {
+        let cursor = self.grid.cursor();
+        self.grid.cursor_visible.then_some(ScreenPosition {
+            height: top.saturating_add(cursor.height),
+            width: cursor.width,
+        })
+    }
+
+    /// the output so far, read only and moved through with vim keys, the shell waits meanwhile
+    /// V selects lines and y yanks them, or the current line, to the clipboard
+    /// returns the new screen size when the terminal was resized
+    pub fn scrollback(&mut self, top: usize, selection: Style) -> Result<Option<Size>, Error> {
+        let height = self.grid.size().height;
+        let last = self.grid.len().saturating_sub(1);
+        let mut line = self.grid.cursor_line();
+        let mut offset = self.grid.len().saturating_sub(height);
+        let mut anchor: Option<usize> = None;
+        let mut pending_g = false;
+        #[allow(clippy::integer_division)]
+        let half = (height / 2).max(1);
+        loop {
+            if line < offset {
+                offset = line;
+            } else if line >= offset.saturating_add(height) {
+                offset = line.saturating_add(1).saturating_sub(height);
+            }
+            let selected = |index: usize| {
+                anchor.is_some_and(|anchor| (anchor.min(line)..=anchor.max(line)).contains(&index))
+            };
+            for row in 0..height {
+                let index = offset.saturating_add(row);
+                let cells = self.grid.line(index).unwrap_or_default();
+                render_cells(
+                    top.saturating_add(row),
+                    cells,
+                    selected(index).then_some(selection),
+                );
+            }
+            let hint = format!(
+                "Line: {} / {} | V = select | y = yank | q = back",
+                line.saturating_add(1),
+                last.saturating_add(1)
+            );
+            self.render_status(top, &Mode::Scrollback, &hint);
+            Screen::move_cursor_to(ScreenPosition {
+                height: top.saturating_add(line.saturating_sub(offset)),
+                width: 0,
+            });
+            Screen::present()?;
+
+            let Ok(event) = read() else { continue };
+            match event {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    let g = std::mem::take(&mut pending_g);
+                    match (key.code, key.modifiers) {
+                        (KeyCode::Char('d'), KeyModifiers::CONTROL) => {
+                            line = line.saturating_add(half).min(last);
+                        }
+                        (KeyCode::Char('u'), KeyModifiers::CONTROL) => {
+                            line = line.saturating_sub(half);
+                        }
+                        (KeyCode::Char('f'), KeyModifiers::CONTROL) | (KeyCode::PageDown, _) => {
+                            line = line.saturating_add(height).min(last);
+                        }
+                        (KeyCode::Char('b'), KeyModifiers::CONTROL) | (KeyCode::PageUp, _) => {
+                            line = line.saturating_sub(height);
+                        }
+                        (KeyCode::Char('j') | KeyCode::Down, _) => {
+                            line = line.saturating_add(1).min(last);
+                        }
+                        (KeyCode::Char('k') | KeyCode::Up, _) => line = line.saturating_sub(1),
+                        (KeyCode::Char('g'), _) if g => line = 0,
+                        (KeyCode::Char('g'), _) => pending_g = true,
+                        (KeyCode::Char('G'), _) => line = last,
+                        (KeyCode::Char('V' | 'v'), _) => {
+                            anchor = if anchor.is_some() { None } else { Some(line) };
+                        }
+                        (KeyCode::Char('y'), _) => {
+                            let text = self.text(anchor.unwrap_or(line), line);
+                            if let Err(err) = ClipboardUtils::copy_text_to_clipboard(text) {
+                                messages::error(format!("Clipboard: {err}"));
+                            }
+                            return Ok(None);
+                        }
+                        (KeyCode::Esc, _) if anchor.is_some() => anchor = None,
+                        (KeyCode::Char('q' | 'i') | KeyCode::Esc, _) => return Ok(None),
+                        _ => {}
+                    }
+                }
+                #[allow(clippy::as_conversions)]
+                Event::Resize(width, height) => {
+                    return Ok(Some(Size {
+                        height: height as usize,
+                        width: width as usize,
+                    }))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// the lines between two lines of the scrollback, in either order
+    fn text(&self, from: usize, to: usize) -> String {
+        (from.min(to)..=from.max(to))
+            .map(|line| self.grid.line_text(line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// draws a row of the grid, runs of cells sharing a style go out together
+/// the cells of selected lines all take the selection style
+fn render_cells(row: usize, cells: &[Cell], selection: Option<Style>) {
+    Screen::render_line(row, "");
+    let mut column = 0;
+    let mut run = String::new();
+    let mut run_style = None;
+    for cell in cells.iter().filter(|cell| !cell.grapheme.is_empty()) {
+        let style = selection.unwrap_or(cell.style);
+        if run_style.is_some_and(|run_style| run_style != style) {
+            column = Screen::print_at(
+                ScreenPosition {
+                    height: row,
+                    width: column,
+                },
+                &run,
+                run_style.unwrap_or_default(),
+            );
+            run.clear();
+        }
+        run_style = Some(style);
+        run.push_str(&cell.grapheme);
+    }
+    if let Some(style) = run_style {
+        Screen::print_at(
+            ScreenPosition {
+                height: row,
+                width: column,
+            },
+            &run,
+            style,
+        );
+    }
+}
+
+/// the bytes a terminal sends for a key, None for keys without any
+fn encode(key: KeyEvent, application_cursor: bool) -> Option<Vec<u8>> {
+    let arrow = |letter: char| {
+        let prefix = if application_cursor { "\x1bO" } else { "\x1b[" };
+        format!("{prefix}{letter}").into_bytes()
+    };
+    let mut bytes = match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            // control clears the upper bits, Ctrl-a is 1 and Ctrl-[ is escape
+            let byte = u8::try_from(c).ok().filter(u8::is_ascii_graphic)?;
+            vec![byte & 0x1f]
+        }
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => b"\r".to_vec(),
+        KeyCode::Tab => b"\t".to_vec(),
+        KeyCode::BackTab => b"\x1b[Z".to_vec(),
+        KeyCode::Backspace => b"\x7f".to_vec(),
+        KeyCode::Esc => b"\x1b".to_vec(),
+        KeyCode::Up => arrow('A'),
+        KeyCode::Down => arrow('B'),
+        KeyCode::Right => arrow('C'),
+        KeyCode::Left => arrow('D'),
+        KeyCode::Home => arrow('H'),
+        KeyCode::End => arrow('F'),
+        KeyCode::Insert => b"\x1b[2~".to_vec(),
+        KeyCode::Delete => b"\x1b[3~".to_vec(),
+        KeyCode::PageUp => b"\x1b[5~".to_vec(),
+        KeyCode::PageDown => b"\x1b[6~".to_vec(),
+        KeyCode::F(number @ 1..=4) => {
+            vec![0x1b, b'O', b'P'.saturating_add(number.saturating_sub(1))]
+        }
+        KeyCode::F(number @ 5..=12) => {
+            let code = [15, 17, 18, 19, 20, 21, 23, 24][usize::from(number.saturating_sub(5))];
+            format!("\x1b[{code}~").into_bytes()
+        }
+        _ => return None,
+    };
+    // alt is sent as escape in front of the key
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        bytes.insert(0, 0x1b);
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Vec<u8> {
+        encode(KeyEvent::new(code, modifiers), false).unwrap()
+    }
+
+    #[test]
+    fn encodes_keys_like_a_terminal() {
+        assert_eq!(key(KeyCode::Char('c'), KeyModifiers::CONTROL), [3]);
+        assert_eq!(key(KeyCode::Char('['), KeyModifiers::CONTROL), [0x1b]);
+        assert_eq!(key(KeyCode::Char('b'), KeyModifiers::ALT), b"\x1bb");
+        assert_eq!(
+            key(KeyCode::Char('界'), KeyModifiers::NONE),
+            "界".as_bytes()
+        );
+        assert_eq!(key(KeyCode::Up, KeyModifiers::NONE), b"\x1b[A");
+        assert_eq!(key(KeyCode::F(5), KeyModifiers::NONE), b"\x1b[15~");
+        let application = encode(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE), true);
+        assert_eq!(application.as_deref(), Some(&b"\x1bOA"[..]));
+    }
+}
@@ -0,0 +1,676 @@
+// the screen of the terminal pane, kept up to date from what the shell writes
+// understands the VT100 and xterm sequences shells, prompts and test runners use
+// anything else is read past so it cannot garble the screen
+use crate::editor::screen::{Cell, Style};
+use crate::editor::terminal::{ScreenPosition, Size};
+use crossterm::style::Color;
+use std::collections::VecDeque;
+use unicode_width::UnicodeWidthChar;
+
+/// how many lines that scrolled off the top are kept
+pub const SCROLLBACK: usize = 10_000;
+const TAB_WIDTH: usize = 8;
+// a runaway sequence is dropped rather than collected forever
+const MAX_PARAMS: usize = 64;
+
+const ANSI: [Color; 16] = [
+    Color::Black,
+    Color::DarkRed,
+    Color::DarkGreen,
+    Color::DarkYellow,
+    Color::DarkBlue,
+    Color::DarkMagenta,
+    Color::DarkCyan,
+    Color::Grey,
+    Color::DarkGrey,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+];
+
+#[derive(Default)]
+enum State {
+    #[default]
+    Ground,
+    Escape,
+    // the byte after ESC ( and friends, which picks a character set
+    Charset,
+    Csi(String),
+    // titles and other strings, ended by BEL or ESC \
+    Text,
+    TextEscape,
+}
+
+/// the colors and attributes new characters are written with
+#[derive(Copy, Clone, Default)]
+struct Pen {
+    foreground: Option<Color>,
+    background: Option<Color>,
+    bold: bool,
+    reverse: bool,
+}
+
+impl Pen {
+    fn style(self) -> Style {
+        let (foreground, background) = if self.reverse {
+            (
+                Some(self.background.unwrap_or(Color::Black)),
+                Some(self.foreground.unwrap_or(Color::White)),
+            )
+        } else {
+            (self.foreground, self.background)
+        };
+        Style {
+            foreground,
+            background,
+            bold: self.bold,
+            ..Style::default()
+        }
+    }
+
+    /// erased cells keep the background
+    fn blank(self) -> Cell {
+        Cell {
+            grapheme: " ".to_string(),
+            style: Style {
+                background: self.style().background,
+                ..Style::default()
+            },
+        }
+    }
+}
+
+pub struct Grid {
+    size: Size,
+    rows: Vec<Vec<Cell>>,
+    scrollback: VecDeque<Vec<Cell>>,
+    // the main screen while a full screen program uses the alternate one
+    main: Option<Vec<Vec<Cell>>>,
+    cursor: ScreenPosition,
+    saved: ScreenPosition,
+    pen: Pen,
+    state: State,
+    // the start of a character split between two reads
+    pending: Vec<u8>,
+    // the last column was written, the next character starts a new line
+    wrap_next: bool,
+    // the scroll region, first and last row
+    top: usize,
+    bottom: usize,
+    pub cursor_visible: bool,
+    // the arrow keys are sent as ESC O instead of ESC [
+    pub application_cursor: bool,
+}
+
+impl Grid {
+    pub fn new(size: Size) -> Self {
+        let size = Size {
+            height: size.height.max(1),
+            width: size.width.max(1),
+        };
+        Self {
+            size,
+            rows: vec![vec![Cell::default(); size.width]; size.height],
+            scrollback: VecDeque::new(),
+            main: None,
+            cursor: ScreenPosition::default(),
+            saved: ScreenPosition::default(),
+            pen: Pen::default(),
+            state: State::default(),
+            pending: Vec::new(),
+            wrap_next: false,
+            top: 0,
+            bottom: size.height.saturating_sub(1),
+            cursor_visible: true,
+            application_cursor: false,
+        }
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    pub fn cursor(&self) -> ScreenPosition {
+        self.cursor
+    }
+
+    pub fn rows(&self) -> &[Vec<Cell>] {
+        &self.rows
+    }
+
+    /// lines of scrollback and screen together
+    pub fn len(&self) -> usize {
+        self.scrollback.len().saturating_add(self.rows.len())
+    }
+
+    /// a line counted from the oldest one in the scrollback
+    pub fn line(&self, index: usize) -> Option<&[Cell]> {
+        match index.checked_sub(self.scrollback.len()) {
+            None => self.scrollback.get(index).map(Vec::as_slice),
+            Some(row) => self.rows.get(row).map(Vec::as_slice),
+        }
+    }
+
+    /// the text of a line without its trailing blanks
+    pub fn line_text(&self, index: usize) -> String {
+        let text: String = self
+            .line(index)
+            .unwrap_or_default()
+            .iter()
+            .map(|cell| cell.grapheme.as_str())
+            .collect();
+        text.trim_end().to_string()
+    }
+
+    /// the line the cursor is on, counted like `line`
+    pub fn cursor_line(&self) -> usize {
+        self.scrollback.len().saturating_add(self.cursor.height)
+    }
+
+    pub fn resize(&mut self, size: Size) {
+        let size = Size {
+            height: size.height.max(1),
+            width: size.width.max(1),
+        };
+        if size == self.size {
+            return;
+        }
+        let blank = Cell::default();
+        for row in self.rows.iter_mut().chain(self.main.iter_mut().flatten()) {
+            row.resize(size.width, blank.clone());
+        }
+        // shrinking keeps the cursor on screen, the lines above it go to the scrollback
+        while self.rows.len() > size.height {
+            if self.cursor.height >= size.height {
+                let line = self.rows.remove(0);
+                self.push_scrollback(line);
+                self.cursor.height = self.cursor.height.saturating_sub(1);
+            } else {
+                self.rows.pop();
+            }
+        }
+        self.rows
+            .resize(size.height, vec![blank.clone(); size.width]);
+        if let Some(main) = &mut self.main {
+            main.resize(size.height, vec![blank; size.width]);
+        }
+        self.size = size;
+        self.top = 0;
+        self.bottom = size.height.saturating_sub(1);
+        self.move_to(self.cursor.height, self.cursor.width);
+        self.saved.height = self.saved.height.min(self.bottom);
+    }
+
+    /// takes in what the program wrote
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+        let pending = std::mem::take(&mut self.pending);
+        let mut rest = pending.as_slice();
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(text) => {
+                    text.chars().for_each(|c| self.input(c));
+                    return;
+                }
+                Err(error) => {
+                    let (valid, after) = rest.split_at(error.valid_up_to());
+                    std::str::from_utf8(valid)
+                        .unwrap_or_default()
+                        .chars()
+                        .for_each(|c| self.input(c));
+                    let Some(len) = error.error_len() else {
+                        self.pending = after.to_vec();
+                        return;
+                    };
+                    self.input(char::REPLACEMENT_CHARACTER);
+                    rest = after.get(len..).unwrap_or_default();
+                }
+            }
+        }
+    }
+
+    fn input(&mut self, c: char) {
+        match std::mem::take(&mut self.state) {
+            State::Ground => self.ground(c),
+            State::Escape => self.escape(c),
+            State::Charset => {} // always utf-8 here
+            State::Csi(mut params) => {
+                if ('\x40'..='\x7e').contains(&c) {
+                    self.csi(&params, c);
+                } else if c == '\x1b' {
+                    self.state = State::Escape;
+                } else if c.is_control() {
+                    // controls in the middle of a sequence still act
+                    self.ground(c);
+                    self.state = State::Csi(params);
+                } else if params.len() < MAX_PARAMS {
+                    params.push(c);
+                    self.state = State::Csi(params);
+                }
+            }
+            State::Text => match c {
+                '\x07' => {}
+                '\x1b' => self.state = State::TextEscape,
+                _ => self.state = State::Text,
+            },
+            State::TextEscape => {
+                if c != '\\' {
+                    self.escape(c);
+                }
+            }
+        }
+    }
+
+    fn ground(&mut self, c: char) {
+        match c {
+            '\x1b' => self.state = State::Escape,
+            '\r' => {
+                self.cursor.width = 0;
+                self.wrap_next = false;
+            }
+            '\n' | '\x0b' | '\x0c' => self.line_feed(),
+            '\x08' => {
+                self.cursor.width = self.cursor.width.saturating_sub(1);
+                self.wrap_next = false;
+            }
+            '\t' => {
+                let next = (self.cursor.width / TAB_WIDTH)
+                    .saturating_add(1)
+                    .saturating_mul(TAB_WIDTH);
+                self.cursor.width = next.min(self.size.width.saturating_sub(1));
+            }
+            c if c.is_control() => {} // the bell and the rest
+            c => self.print(c),
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        let width = c.width().unwrap_or(0);
+        if width == 0 {
+            // a combining mark joins the character before it
+            let mut column = if self.wrap_next {
+                self.cursor.width
+            } else {
+                self.cursor.width.saturating_sub(1)
+            };
+            let row = &mut self.rows[self.cursor.height];
+            if row[column].grapheme.is_empty() {
+                column = column.saturating_sub(1);
+            }
+            row[column].grapheme.push(c);
+            return;
+        }
+        if width > self.size.width {
+            return;
+        }
+        if self.wrap_next || self.cursor.width.saturating_add(width) > self.size.width {
+            self.cursor.width = 0;
+            self.line_feed();
+        }
+        let style = self.pen.style();
+        let column = self.cursor.width;
+        self.put(
+            column,
+            Cell {
+                grapheme: c.to_string(),
+                style,
+            },
+        );
+        if width == 2 {
+            self.put(
+                column.saturating_add(1),
+                Cell {
+                    grapheme: String::new(),
+                    style,
+                },
+            );
+        }
+        let next = column.saturating_add(width);
+        if next >= self.size.width {
+            self.cursor.width = self.size.width.saturating_sub(1);
+            self.wrap_next = true;
+        } else {
+            self.cursor.width = next;
+        }
+    }
+
+    /// writes one cell, blanking the other half of a wide character it cuts
+    fn put(&mut self, column: usize, cell: Cell) {
+        let blank = self.pen.blank();
+        let row = &mut self.rows[self.cursor.height];
+        if !cell.grapheme.is_empty() && row[column].grapheme.is_empty() && column > 0 {
+            row[column.saturating_sub(1)] = blank.clone();
+        }
+        if let Some(next) = row.get_mut(column.saturating_add(1)) {
+            if next.grapheme.is_empty() {
+                *next = blank;
+            }
+        }
+        row[column] = cell;
+    }
+
+    fn line_feed(&mut self) {
+        self.wrap_next = false;
+        if self.cursor.height == self.bottom {
+            self.scroll_up(1);
+        } else if self.cursor.height < self.size.height.saturating_sub(1) {
+            self.cursor.height = self.cursor.height.saturating_add(1);
+        }
+    }
+
+    fn reverse_line_feed(&mut self) {
+        self.wrap_next = false;
+        if self.cursor.height == self.top {
+            self.scroll_down(1);
+        } else {
+            self.cursor.height = self.cursor.height.saturating_sub(1);
+        }
+    }
+
+    fn blank_row(&self) -> Vec<Cell> {
+        vec![self.pen.blank(); self.size.width]
+    }
+
+    fn push_scrollback(&mut self, line: Vec<Cell>) {
+        if self.scrollback.len() >= SCROLLBACK {
+            self.scrollback.pop_front();
+        }
+        self.scrollback.push_back(line);
+    }
+
+    /// moves the lines of the scroll region up, the top ones leave it
+    fn scroll_up(&mut self, count: usize) {
+        for _ in 0..count.min(self.size.height) {
+            let line = self.rows.remove(self.top);
+            // only what scrolls off the whole main screen is worth keeping
+            if self.top == 0 && self.main.is_none() {
+                self.push_scrollback(line);
+            }
+            self.rows.insert(self.bottom, self.blank_row());
+        }
+    }
+
+    fn scroll_down(&mut self, count: usize) {
+        for _ in 0..count.min(self.size.height) {
+            self.rows.remove(self.bottom);
+            self.rows.insert(self.top, self.blank_row());
+        }
+    }
+
+    fn move_to(&mut self, row: usize, column: usize) {
+        self.cursor = ScreenPosition {
+            height: row.min(self.size.height.saturating_sub(1)),
+            width: column.min(self.size.width.saturating_sub(1)),
+        };
+        self.wrap_next = false;
+    }
+
+    fn erase(&mut self, row: usize, from: usize, to: usize) {
+        let blank = self.pen.blank();
+        if let Some(cells) = self.rows[row].get_mut(from..to.min(self.size.width)) {
+            cells.fill(blank);
+        }
+    }
+
+    fn escape(&mut self, c: char) {
+        match c {
+            '[' => self.state = State::Csi(String::new()),
+            ']' | 'P' | '_' | '^' | 'X' => self.state = State::Text,
+            '(' | ')' | '*' | '+' | '#' | '%' => self.state = State::Charset,
+            '7' => self.saved = self.cursor,
+            '8' => self.move_to(self.saved.height, self.saved.width),
+            'D' => self.line_feed(),
+            'E' => {
+                self.cursor.width = 0;
+                self.line_feed();
+            }
+            'M' => self.reverse_line_feed(),
+            'c' => {
+                let scrollback = std::mem::take(&mut self.scrollback);
+                *self = Self::new(self.size);
+                self.scrollback = scrollback;
+            }
+            _ => {}
+        }
+    }
+
+    fn csi(&mut self, params: &str, action: char) {
+        let private = params.starts_with(['?', '>', '<', '=']);
+        let numbers: Vec<usize> = params
+            .trim_start_matches(['?', '>', '<', '='])
+            .split(';')
+            .map(|number| number.parse().unwrap_or(0))
+            .collect();
+        let arg = |index: usize| numbers.get(index).copied().unwrap_or(0);
+        let count = arg(0).max(1);
+        if private {
+            if matches!(action, 'h' | 'l') {
+                self.set_modes(&numbers, action == 'h');
+            }
+            return;
+        }
+        let ScreenPosition { height, width } = self.cursor;
+        match action {
+            'A' => self.move_to(height.saturating_sub(count), width),
+            'B' | 'e' => self.move_to(height.saturating_add(count), width),
+            'C' | 'a' => self.move_to(height, width.saturating_add(count)),
+            'D' => self.move_to(height, width.saturating_sub(count)),
+            'E' => self.move_to(height.saturating_add(count), 0),
+            'F' => self.move_to(height.saturating_sub(count), 0),
+            'G' | '`' => self.move_to(height, count.saturating_sub(1)),
+            'd' => self.move_to(count.saturating_sub(1), width),
+            'H' | 'f' => self.move_to(
+                arg(0).max(1).saturating_sub(1),
+                arg(1).max(1).saturating_sub(1),
+            ),
+            'J' => self.erase_display(arg(0)),
+            'K' => match arg(0) {
+                0 => self.erase(height, width, self.size.width),
+                1 => self.erase(height, 0, width.saturating_add(1)),
+                _ => self.erase(height, 0, self.size.width),
+            },
+            'L' | 'M' if (self.top..=self.bottom).contains(&height) => {
+                // lines move within the region below the cursor
+                let top = std::mem::replace(&mut self.top, height);
+                if action == 'L' {
+                    self.scroll_down(count);
+                } else {
+                    self.scroll_up(count);
+                }
+                self.top = top;
+                self.cursor.width = 0;
+            }
+            '@' => {
+                let row = &mut self.rows[height];
+                for _ in 0..count.min(self.size.width.saturating_sub(width)) {
+                    row.pop();
+                    row.insert(width, self.pen.blank());
+                }
+            }
+            'P' => {
+                let row = &mut self.rows[height];
+                for _ in 0..count.min(self.size.width.saturating_sub(width)) {
+                    row.remove(width);
+                    row.push(self.pen.blank());
+                }
+            }
+            'X' => self.erase(height, width, width.saturating_add(count)),
+            'S' => self.scroll_up(count),
+            'T' => self.scroll_down(count),
+            'm' => self.select_graphics(&numbers),
+            'r' => {
+                let last = self.size.height.saturating_sub(1);
+                let top = arg(0).max(1).saturating_sub(1);
+                let bottom = match arg(1) {
+                    0 => last,
+                    bottom => bottom.saturating_sub(1).min(last),
+                };
+                if top < bottom {
+                    self.top = top;
+                    self.bottom = bottom;
+                    self.move_to(0, 0);
+                }
+            }
+            's' => self.saved = self.cursor,
+            'u' => self.move_to(self.saved.height, self.saved.width),
+            _ => {}
+        }
+    }
+
+    fn erase_display(&mut self, mode: usize) {
+        let ScreenPosition { height, width } = self.cursor;
+        match mode {
+            0 => {
+                self.erase(height, width, self.size.width);
+                for row in height.saturating_add(1)..self.size.height {
+                    self.erase(row, 0, self.size.width);
+                }
+            }
+            1 => {
+                for row in 0..height {
+                    self.erase(row, 0, self.size.width);
+                }
+                self.erase(height, 0, width.saturating_add(1));
+            }
+            2 => {
+                for row in 0..self.size.height {
+                    self.erase(row, 0, self.size.width);
+                }
+            }
+            3 => self.scrollback.clear(),
+            _ => {}
+        }
+    }
+
+    fn set_modes(&mut self, modes: &[usize], on: bool) {
+        for mode in modes {
+            match mode {
+                1 => self.application_cursor = on,
+                25 => self.cursor_visible = on,
+                47 | 1047 | 1049 => {
+                    // full screen programs draw on a screen of their own and leave the shell output be
+                    if on && self.main.is_none() {
+                        self.saved = self.cursor;
+                        let blank = vec![vec![Cell::default(); self.size.width]; self.size.height];
+                        self.main = Some(std::mem::replace(&mut self.rows, blank));
+                        self.move_to(0, 0);
+                    } else if let (false, Some(main)) = (on, self.main.take()) {
+                        self.rows = main;
+                        self.move_to(self.saved.height, self.saved.width);
+                    }
+                    self.top = 0;
+                    self.bottom = self.size.height.saturating_sub(1);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn select_graphics(&mut self, numbers: &[usize]) {
+        let mut numbers = numbers.iter().copied();
+        while let Some(number) = numbers.next() {
+            match number {
+                0 => self.pen = Pen::default(),
+                1 => self.pen.bold = true,
+                22 => self.pen.bold = false,
+                7 => self.pen.reverse = true,
+                27 => self.pen.reverse = false,
+                30..=37 => self.pen.foreground = Some(ANSI[number.saturating_sub(30)]),
+                90..=97 => self.pen.foreground = Some(ANSI[number.saturating_sub(82)]),
+                39 => self.pen.foreground = None,
+                40..=47 => self.pen.background = Some(ANSI[number.saturating_sub(40)]),
+                100..=107 => self.pen.background = Some(ANSI[number.saturating_sub(92)]),
+                49 => self.pen.background = None,
+                38 | 48 => {
+                    let mut channel = || numbers.next().and_then(|n| u8::try_from(n).ok());
+                    let color = match channel() {
+                        Some(5) => channel().map(Color::AnsiValue),
+                        Some(2) => match (channel(), channel(), channel()) {
+                            (Some(r), Some(g), Some(b)) => Some(Color::Rgb { r, g, b }),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    if number == 38 {
+                        self.pen.foreground = color;
+                    } else {
+                        self.pen.background = color;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(width: usize, height: usize) -> Grid {
+        Grid::new(Size { height, width })
+    }
+
+    fn screen(grid: &Grid) -> Vec<String> {
+        (grid.scrollback.len()..grid.len())
+            .map(|line| grid.line_text(line))
+            .collect()
+    }
+
+    #[test]
+    fn writes_wraps_and_scrolls_into_the_scrollback() {
+        let mut grid = grid(4, 2);
+        grid.feed(b"abcdef\r\nxy\r\nz");
+        assert_eq!(screen(&grid), ["xy", "z"]);
+        assert_eq!(grid.line_text(0), "abcd");
+        assert_eq!(grid.line_text(1), "ef");
+        assert_eq!(
+            grid.cursor(),
+            ScreenPosition {
+                height: 1,
+                width: 1
+            }
+        );
+        // a character split between reads waits for the rest
+        grid.feed(&"界".as_bytes()[..1]);
+        grid.feed(&"界".as_bytes()[1..]);
+        assert_eq!(screen(&grid), ["xy", "z界"]);
+    }
+
+    #[test]
+    fn moves_the_cursor_and_erases() {
+        let mut grid = grid(6, 3);
+        grid.feed(b"hello\r\nworld\x1b[1;2H\x1b[K\x1b[3;3Hok\x1b[2;1H\x1b[2P");
+        assert_eq!(screen(&grid), ["h", "rld", "  ok"]);
+        grid.feed(b"\x1b[2J");
+        assert_eq!(screen(&grid), ["", "", ""]);
+        // a title and unknown private modes are read past
+        grid.feed(b"\x1b]0;title\x07\x1b[?2004hx");
+        assert_eq!(grid.line_text(grid.cursor_line()), "x");
+    }
+
+    #[test]
+    fn colors_the_cells() {
+        let mut grid = grid(6, 1);
+        grid.feed(b"\x1b[1;31mE\x1b[0m \x1b[38;5;42mo\x1b[7mk");
+        let row = &grid.rows()[0];
+        assert_eq!(row[0].style.foreground, Some(Color::DarkRed));
+        assert!(row[0].style.bold);
+        assert_eq!(row[1].style, Style::default());
+        assert_eq!(row[2].style.foreground, Some(Color::AnsiValue(42)));
+        assert_eq!(row[3].style.background, Some(Color::AnsiValue(42)));
+    }
+
+    #[test]
+    fn full_screen_programs_leave_the_shell_output_alone() {
+        let mut grid = grid(5, 2);
+        grid.feed(b"$ top\r\n\x1b[?1049h\x1b[Hbusy\r\n\n\n");
+        assert_eq!(grid.scrollback.len(), 0);
+        grid.feed(b"\x1b[?1049l");
+        assert_eq!(screen(&grid), ["$ top", ""]);
+    }
+}
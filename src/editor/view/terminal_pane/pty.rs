@@ -0,0 +1,186 @@
+// a shell on a pseudo terminal, so it prompts, colors and edits lines as it would in a real one
+// what it writes is read on a thread of its own and handed over through a channel
+use crate::editor::terminal::Size;
+use std::fs::File;
+use std::io::{Error, Read, Write};
+use std::process::Child;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+pub struct Pty {
+    master: File,
+    child: Child,
+}
+
+/// the user's shell
+pub fn shell() -> String {
+    std::env::var("SHELL")
+        .ok()
+        .filter(|shell| !shell.is_empty())
+        .unwrap_or_else(|| "sh".into())
+}
+
+#[cfg(unix)]
+impl Pty {
+    pub fn spawn(program: &str, size: Size) -> Result<Self, Error> {
+        use std::os::fd::{AsRawFd, FromRawFd};
+        use std::os::unix::fs::OpenOptionsExt;
+        use std::os::unix::process::CommandExt;
+        use std::process::Command;
+
+        // SAFETY: the descriptor is checked and owned by the File from here on
+        let fd = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        let master = unsafe { File::from_raw_fd(fd) };
+        let fd = master.as_raw_fd();
+        // children started later, ie :! commands and language servers, must not keep the master
+        // open, the shell would then never see the hangup when the pane goes
+        if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        if unsafe { libc::grantpt(fd) } != 0 || unsafe { libc::unlockpt(fd) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        let slave = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(slave_name(fd)?)?;
+        Self::resize_fd(fd, size)?;
+
+        let mut command = Command::new(program);
+        command
+            .stdin(slave.try_clone()?)
+            .stdout(slave.try_clone()?)
+            .stderr(slave)
+            .env("TERM", "xterm-256color");
+        // SAFETY: only async signal safe calls run between fork and exec
+        // the shell leads a session of its own with the pty as its terminal, so job control works
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                    return Err(Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        // the command holds the only copies of the slave, dropping it lets reads end when the shell exits
+        let child = command.spawn()?;
+        Ok(Self { master, child })
+    }
+
+    /// tells the shell and what runs in it the new size, they get a SIGWINCH
+    pub fn resize(&self, size: Size) -> Result<(), Error> {
+        use std::os::fd::AsRawFd;
+        Self::resize_fd(self.master.as_raw_fd(), size)
+    }
+
+    fn resize_fd(fd: std::os::fd::RawFd, size: Size) -> Result<(), Error> {
+        let window = libc::winsize {
+            ws_row: u16::try_from(size.height).unwrap_or(u16::MAX),
+            ws_col: u16::try_from(size.width).unwrap_or(u16::MAX),
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        // SAFETY: the window size outlives the call
+        if unsafe { libc::ioctl(fd, libc::TIOCSWINSZ, &window) } < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn slave_name(fd: std::os::fd::RawFd) -> Result<String, Error> {
+    let mut name = [0; 128];
+    // SAFETY: the buffer length is passed along and the name ends in a nul
+    if unsafe { libc::ptsname_r(fd, name.as_mut_ptr(), name.len()) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(name.as_ptr()) };
+    Ok(name.to_string_lossy().into_owned())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn slave_name(fd: std::os::fd::RawFd) -> Result<String, Error> {
+    // SAFETY: the name is copied before anything else can call ptsname
+    let name = unsafe { libc::ptsname(fd) };
+    if name.is_null() {
+        return Err(Error::last_os_error());
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(name) };
+    Ok(name.to_string_lossy().into_owned())
+}
+
+#[cfg(not(unix))]
+impl Pty {
+    pub fn spawn(_program: &str, _size: Size) -> Result<Self, Error> {
+        Err(Error::new(
+            std::io::ErrorKind::Unsupported,
+            "the terminal pane needs a unix pseudo terminal",
+        ))
+    }
+
+    pub fn resize(&self, _size: Size) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl Pty {
+    /// what the shell writes, the channel closes when it exits
+    pub fn output(&self) -> Result<Receiver<Vec<u8>>, Error> {
+        let mut master = self.master.try_clone()?;
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut bytes = [0; 4096];
+            // the read fails once the last program on the pty is gone
+            while let Ok(read @ 1..) = master.read(&mut bytes) {
+                if sender.send(bytes[..read].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(receiver)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.master.write_all(bytes)?;
+        self.master.flush()
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn runs_a_shell_on_a_terminal() {
+        let size = Size {
+            height: 10,
+            width: 33,
+        };
+        let mut pty = Pty::spawn("sh", size).unwrap();
+        let output = pty.output().unwrap();
+        pty.write(b"stty size; test -t 1 && echo tty; exit\n")
+            .unwrap();
+        let start = Instant::now();
+        let mut text = String::new();
+        while let Ok(bytes) = output.recv_timeout(Duration::from_secs(5)) {
+            text.push_str(&String::from_utf8_lossy(&bytes));
+            assert!(start.elapsed() < Duration::from_secs(5));
+        }
+        assert!(text.contains("10 33"), "{text:?}");
+        // the typed line is echoed back, the answer is on a line of its own
+        assert!(text.contains("tty\r\n"), "{text:?}");
+    }
+}
@@ -1,47 +1,32 @@
-use crate::editor::terminal::{ScreenPosition, Terminal};
+// a theme styles the text and every part of the screen drawn around it
+// themes are files of `slot = style` lines, a style is colors and attributes in any order:
+//   text = #ebdbb2 on #282828
+//   status = black on 244 bold
+//   comment = #928374 italic underline
+// a color is a name, a number of the 256 color palette or #rrggbb, the one after `on` is the
+// background, `theme = name` starts from another theme and `cursor = SteadyBar` shapes the cursor
+// a theme is looked up in the themes directory of the config directory, then among the built in
+// ones, theme.conf there picks the theme the editor starts with and can change its slots too
+// colors the terminal cannot show are replaced with the nearest ones it can
+use super::buffer::Buffer;
+use super::syntax::{self, Token};
+use super::wrap;
+use crate::editor::backend;
+use crate::editor::config::config_dir;
+use crate::editor::screen::{Screen, Style};
+use crate::editor::terminal::{ScreenOffset, Size};
 use crossterm::cursor::SetCursorStyle;
-use crossterm::event::{read, Event, KeyCode, KeyEvent};
 use crossterm::style::Color;
+use std::env;
+use std::fs;
+use std::io::Error;
 
-pub struct Theme {
-    foreground: Color,
-    background: Color,
-    pub highlight: Color,
-    pub text: Color,
-    cursor_style: SetCursorStyle,
-}
-
-impl Default for Theme {
-    fn default() -> Theme {
-        Theme {
-            foreground: Color::White,
-            background: Color::Black,
-            highlight: Color::Blue,
-            text: Color::White,
-            cursor_style: SetCursorStyle::DefaultUserShape,
-        }
-    }
-}
-
-const OPTIONS: [&str; 16] = [
-    "DarkGrey",
-    "Red",
-    "Green",
-    "Yellow",
-    "Blue",
-    "Magenta",
-    "Cyan",
-    "White",
-    "Black",
-    "DarkRed",
-    "DarkGreen",
-    "DarkYellow",
-    "DarkBlue",
-    "DarkMagenta",
-    "DarkCyan",
-    "Grey",
-];
+mod picker;
+pub use picker::{Picker, PickerAction};
 
+const THEMES_DIR: &str = "themes";
+const CONFIG_FILE: &str = "theme.conf";
+/// the shapes of the cursor a theme can set
 const CURSOR_OPTIONS: [&str; 7] = [
     "DefaultUserShape",
     "BlinkingBlock",
@@ -51,97 +36,562 @@ const CURSOR_OPTIONS: [&str; 7] = [
     "BlinkingBar",
     "SteadyBar",
 ];
+/// the slots of a theme file, in the order the picker lists them
+pub const SLOTS: [&str; 22] = [
+    "text",
+    "status",
+    "gutter",
+    "selection",
+    "search",
+    "current_match",
+    "cursor_line",
+    "keyword",
+    "string",
+    "comment",
+    "number",
+    "type",
+    "function",
+    "popup",
+    "preview",
+    "sign_error",
+    "sign_warning",
+    "sign_info",
+    "sign_hint",
+    "info",
+    "warning",
+    "error",
+];
+/// how many themes a theme can start from in a row, so two naming each other end
+const MAX_DEPTH: usize = 8;
 
-const RENDER_OPTION: [&str; 5] = [
-    "Select text color:",
-    "Select background color:",
-    "Select search highlight color:",
-    "Select search text color:",
-    "Select cursor style:",
+const BUILT_IN: [(&str, &str); 5] = [
+    (
+        "default",
+        "gutter = darkgrey
+        selection = white on blue
+        search = white on blue bold
+        current_match = black on yellow bold
+        keyword = yellow
+        string = green
+        comment = darkgrey italic
+        number = cyan
+        type = blue
+        popup = black on grey
+        preview = white on darkgrey
+        sign_error = red bold
+        sign_warning = yellow bold
+        sign_info = blue bold
+        sign_hint = cyan bold
+        warning = yellow bold
+        error = white on darkred bold",
+    ),
+    (
+        "dark",
+        "text = #abb2bf on #282c34
+        status = #282c34 on #61afef bold
+        gutter = #5c6370
+        cursor_line = on #2c313c
+        selection = on #3e4451
+        search = #282c34 on #e5c07b
+        current_match = #282c34 on #d19a66 bold
+        keyword = #c678dd
+        string = #98c379
+        comment = #5c6370 italic
+        number = #d19a66
+        type = #e5c07b
+        function = #61afef
+        popup = #abb2bf on #3e4451
+        preview = #abb2bf on #21252b
+        sign_error = #e06c75 bold
+        sign_warning = #e5c07b bold
+        sign_info = #61afef bold
+        sign_hint = #56b6c2 bold
+        warning = #e5c07b
+        error = #282c34 on #e06c75 bold",
+    ),
+    (
+        "light",
+        "text = #383a42 on #fafafa
+        status = #fafafa on #4078f2 bold
+        gutter = #9d9d9f
+        cursor_line = on #f0f0f0
+        selection = on #e5e5e6
+        search = #fafafa on #c18401
+        current_match = #fafafa on #e45649 bold
+        keyword = #a626a4
+        string = #50a14f
+        comment = #a0a1a7 italic
+        number = #986801
+        type = #c18401
+        function = #4078f2
+        popup = #383a42 on #e5e5e6
+        preview = #383a42 on #f0f0f0
+        sign_error = #e45649 bold
+        sign_warning = #c18401 bold
+        sign_info = #4078f2 bold
+        sign_hint = #0184bc bold
+        warning = #c18401
+        error = #fafafa on #e45649 bold",
+    ),
+    (
+        "gruvbox",
+        "text = #ebdbb2 on #282828
+        status = #282828 on #a89984 bold
+        gutter = #7c6f64
+        cursor_line = on #3c3836
+        selection = on #504945
+        search = #282828 on #fabd2f
+        current_match = #282828 on #fe8019 bold
+        keyword = #fb4934
+        string = #b8bb26
+        comment = #928374 italic
+        number = #d3869b
+        type = #fabd2f
+        function = #8ec07c
+        popup = #ebdbb2 on #504945
+        preview = #ebdbb2 on #3c3836
+        sign_error = #fb4934 bold
+        sign_warning = #fabd2f bold
+        sign_info = #83a598 bold
+        sign_hint = #8ec07c bold
+        warning = #fabd2f
+        error = #282828 on #fb4934 bold",
+    ),
+    (
+        "solarized",
+        "text = #839496 on #002b36
+        status = #002b36 on #93a1a1 bold
+        gutter = #586e75
+        cursor_line = on #073642
+        selection = #fdf6e3 on #586e75
+        search = #002b36 on #b58900
+        current_match = #002b36 on #cb4b16 bold
+        keyword = #859900
+        string = #2aa198
+        comment = #586e75 italic
+        number = #d33682
+        type = #b58900
+        function = #268bd2
+        popup = #93a1a1 on #073642
+        preview = #839496 on #073642
+        sign_error = #dc322f bold
+        sign_warning = #b58900 bold
+        sign_info = #268bd2 bold
+        sign_hint = #2aa198 bold
+        warning = #b58900
+        error = #fdf6e3 on #dc322f bold",
+    ),
 ];
 
-impl Theme {
-    pub fn set_theme(&mut self) {
-        let mut cursor_position: usize = 1;
-        let mut user_choices: [String; 5] = Default::default();
-        Terminal::clear_screen().unwrap();
-        for (line_index, color) in OPTIONS.iter().enumerate() {
-            Terminal::render_line(line_index.saturating_add(1), color).unwrap();
+/// the 16 colors every terminal has, in the order of their palette numbers, as they mostly look
+const BASIC: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::DarkRed, (128, 0, 0)),
+    (Color::DarkGreen, (0, 128, 0)),
+    (Color::DarkYellow, (128, 128, 0)),
+    (Color::DarkBlue, (0, 0, 128)),
+    (Color::DarkMagenta, (128, 0, 128)),
+    (Color::DarkCyan, (0, 128, 128)),
+    (Color::Grey, (192, 192, 192)),
+    (Color::DarkGrey, (128, 128, 128)),
+    (Color::Red, (255, 0, 0)),
+    (Color::Green, (0, 255, 0)),
+    (Color::Yellow, (255, 255, 0)),
+    (Color::Blue, (0, 0, 255)),
+    (Color::Magenta, (255, 0, 255)),
+    (Color::Cyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// the levels of each channel in the 6x6x6 cube of the 256 color palette
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// the colors a terminal can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colors {
+    TrueColor,
+    Palette, // the 256 colors
+    Basic,   // the 16 named ones
+}
+
+impl Colors {
+    /// what the terminal says it supports in COLORTERM and TERM
+    pub fn detect() -> Self {
+        let var = |name: &str| env::var(name).unwrap_or_default().to_lowercase();
+        if matches!(var("COLORTERM").as_str(), "truecolor" | "24bit") || cfg!(windows) {
+            Self::TrueColor
+        } else if var("TERM").contains("256") {
+            Self::Palette
+        } else {
+            Self::Basic
         }
-        for (choice_index, render_screen) in RENDER_OPTION.iter().enumerate() {
-            if cursor_position != 1_usize {
-                cursor_position = 1;
+    }
+
+    /// the color itself when the terminal shows it, else the nearest one it does
+    pub fn fit(self, color: Color) -> Color {
+        match (self, color) {
+            (Self::Palette, Color::Rgb { r, g, b }) => Color::AnsiValue(palette_index((r, g, b))),
+            (Self::Basic, Color::Rgb { r, g, b }) => nearest_basic((r, g, b)),
+            (Self::Basic, Color::AnsiValue(index)) => nearest_basic(palette_rgb(index)),
+            _ => color,
+        }
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let channel = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+    channel(a.0, b.0) + channel(a.1, b.1) + channel(a.2, b.2)
+}
+
+/// the palette number of the cube color or grey nearest to a color
+#[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+fn palette_index(rgb: (u8, u8, u8)) -> u8 {
+    let level = |channel: u8| {
+        (0..CUBE.len())
+            .min_by_key(|index| CUBE[*index].abs_diff(channel))
+            .unwrap_or_default()
+    };
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = 16_usize + 36 * r + 6 * g + b;
+    // the greys run from 8 to 238 in steps of 10
+    #[allow(clippy::integer_division)]
+    let grey = ((u32::from(rgb.0) + u32::from(rgb.1) + u32::from(rgb.2)) / 3)
+        .saturating_sub(3)
+        .min(238)
+        / 10;
+    let grey_level = (8 + 10 * grey) as u8;
+    if distance(rgb, (grey_level, grey_level, grey_level))
+        < distance(rgb, (CUBE[r], CUBE[g], CUBE[b]))
+    {
+        (232 + grey.min(23)) as u8
+    } else {
+        cube as u8
+    }
+}
+
+/// the color of a palette number
+#[allow(clippy::integer_division)]
+fn palette_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => BASIC[usize::from(index)].1,
+        16..=231 => {
+            let cube = usize::from(index - 16);
+            (CUBE[cube / 36], CUBE[cube / 6 % 6], CUBE[cube % 6])
+        }
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            (level, level, level)
+        }
+    }
+}
+
+fn nearest_basic(rgb: (u8, u8, u8)) -> Color {
+    BASIC
+        .iter()
+        .min_by_key(|(_, basic)| distance(rgb, *basic))
+        .map_or(Color::White, |(color, _)| *color)
+}
+
+/// a color of a theme file
+fn parse_color(text: &str) -> Option<Color> {
+    if let Some(hex) = text.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |at: usize| u8::from_str_radix(hex.get(at..at.saturating_add(2))?, 16).ok();
+        return Some(Color::Rgb {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+    if let Ok(index) = text.parse::<u8>() {
+        return Some(Color::AnsiValue(index));
+    }
+    let name = text.to_lowercase().replace("gray", "grey");
+    BASIC
+        .iter()
+        .map(|(color, _)| *color)
+        .find(|color| format!("{color:?}").to_lowercase() == name)
+}
+
+/// a color the way a theme file writes it
+fn color_text(color: Color) -> String {
+    match color {
+        Color::Rgb { r, g, b } => format!("#{r:02x}{g:02x}{b:02x}"),
+        Color::AnsiValue(index) => index.to_string(),
+        _ => format!("{color:?}").to_lowercase(),
+    }
+}
+
+/// a style the way a theme file writes it, `parse_style` reads it back
+pub fn style_text(style: Style) -> String {
+    let mut words = Vec::new();
+    if let Some(color) = style.foreground {
+        words.push(color_text(color));
+    }
+    if let Some(color) = style.background {
+        words.push(format!("on {}", color_text(color)));
+    }
+    for (set, attribute) in [
+        (style.bold, "bold"),
+        (style.italic, "italic"),
+        (style.underline, "underline"),
+    ] {
+        if set {
+            words.push(attribute.to_string());
+        }
+    }
+    words.join(" ")
+}
+
+/// the style of a theme file slot, colors and attributes in any order
+pub fn parse_style(text: &str) -> Result<Style, String> {
+    let mut style = Style::default();
+    let mut words = text.split_whitespace();
+    while let Some(word) = words.next() {
+        match word {
+            "bold" => style.bold = true,
+            "italic" => style.italic = true,
+            "underline" => style.underline = true,
+            "on" => {
+                let color = words.next().ok_or("expected a color after on")?;
+                style.background =
+                    Some(parse_color(color).ok_or_else(|| format!("unknown color {color}"))?);
+            }
+            _ => {
+                style.foreground =
+                    Some(parse_color(word).ok_or_else(|| format!("unknown color {word}"))?);
             }
-            if choice_index == 4 {
-                Terminal::clear_screen().unwrap();
-                for (line_index, option) in CURSOR_OPTIONS.iter().enumerate() {
-                    Terminal::render_line(line_index.saturating_add(1), option).unwrap();
+        }
+    }
+    Ok(style)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    pub text: Style,   // cells without a style of their own
+    pub status: Style, // the status line
+    pub gutter: Style,
+    pub selection: Style, // highlighted text, the picked entry of a menu and help screens
+    pub search: Style,    // every match of a search
+    pub current_match: Style, // the match the cursor is on
+    pub cursor_line: Style,
+    pub keyword: Style,
+    pub string: Style,
+    pub comment: Style,
+    pub number: Style,
+    pub type_name: Style,
+    pub function: Style,
+    pub popup: Style,      // the completion menu and hover text
+    pub preview: Style,    // the text beside the completion menu
+    pub sign_error: Style, // diagnostics in the gutter
+    pub sign_warning: Style,
+    pub sign_info: Style,
+    pub sign_hint: Style,
+    pub info: Style, // messages, see messages.rs
+    pub warning: Style,
+    pub error: Style,
+    pub cursor: &'static str, // one of CURSOR_OPTIONS
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        let mut theme = Theme {
+            name: String::new(),
+            text: Style::default(),
+            status: Style::default(),
+            gutter: Style::default(),
+            selection: Style::default(),
+            search: Style::default(),
+            current_match: Style::default(),
+            cursor_line: Style::default(),
+            keyword: Style::default(),
+            string: Style::default(),
+            comment: Style::default(),
+            number: Style::default(),
+            type_name: Style::default(),
+            function: Style::default(),
+            popup: Style::default(),
+            preview: Style::default(),
+            sign_error: Style::default(),
+            sign_warning: Style::default(),
+            sign_info: Style::default(),
+            sign_hint: Style::default(),
+            info: Style::default(),
+            warning: Style::default(),
+            error: Style::default(),
+            cursor: CURSOR_OPTIONS[0],
+        };
+        theme.configure(BUILT_IN[0].1, MAX_DEPTH);
+        theme.name = BUILT_IN[0].0.to_string();
+        theme
+    }
+}
+
+impl Theme {
+    /// a theme of the themes directory, or a built in one
+    pub fn named(name: &str) -> Result<Self, String> {
+        Self::load(name, 0)
+    }
+
+    /// the theme theme.conf of the config directory picks, the default one without it
+    pub fn configured() -> Self {
+        let mut theme = Self::default();
+        if let Some(conf) =
+            config_dir().and_then(|dir| fs::read_to_string(dir.join(CONFIG_FILE)).ok())
+        {
+            theme.configure(&conf, 0);
+        }
+        theme
+    }
+
+    /// the names of the built in themes and of the files in the themes directory
+    pub fn names() -> Vec<String> {
+        let mut names: Vec<String> = BUILT_IN.iter().map(|(name, _)| name.to_string()).collect();
+        if let Some(entries) = config_dir().and_then(|dir| fs::read_dir(dir.join(THEMES_DIR)).ok())
+        {
+            for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+                if path
+                    .extension()
+                    .is_some_and(|extension| extension == "theme")
+                {
+                    if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                        names.push(name.to_string());
+                    }
                 }
             }
-            Terminal::render_line(0_usize, render_screen).unwrap();
-            Terminal::execute().unwrap();
-            Self::move_cursor(cursor_position);
-            loop {
-                if let Ok(Event::Key(KeyEvent { code, .. })) = read() {
-                    match code {
-                        KeyCode::Up => {
-                            if cursor_position > 1_usize {
-                                cursor_position = cursor_position.saturating_sub(1);
-                                Self::move_cursor(cursor_position);
-                            }
-                        }
-                        KeyCode::Down => {
-                            if choice_index == 4 {
-                                cursor_position =
-                                    std::cmp::min(cursor_position.saturating_add(1), 7_usize);
-                            } else {
-                                cursor_position =
-                                    std::cmp::min(cursor_position.saturating_add(1), 16_usize);
-                            }
-                            Self::move_cursor(cursor_position);
-                        }
-                        KeyCode::Enter => {
-                            if choice_index == 4 {
-                                user_choices[choice_index] = (*CURSOR_OPTIONS
-                                    [cursor_position.saturating_sub(1)])
-                                .to_string();
-                            } else {
-                                user_choices[choice_index] =
-                                    (*OPTIONS[cursor_position.saturating_sub(1)]).to_string();
-                            }
-                            break;
-                        }
-                        KeyCode::Esc => {
-                            // do not change the state at all and return
-                            return;
-                        }
-                        _ => {
-                            //not addressing any other key presses
-                        }
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    fn load(name: &str, depth: usize) -> Result<Self, String> {
+        let file = config_dir().and_then(|dir| {
+            fs::read_to_string(dir.join(THEMES_DIR).join(format!("{name}.theme"))).ok()
+        });
+        let text = file
+            .as_deref()
+            .or_else(|| {
+                BUILT_IN
+                    .iter()
+                    .find(|(built_in, _)| *built_in == name)
+                    .map(|(_, text)| *text)
+            })
+            .ok_or_else(|| format!("E185: Cannot find color scheme '{name}'"))?;
+        let mut theme = Self::default();
+        theme.configure(text, depth);
+        theme.name = name.to_string();
+        Ok(theme)
+    }
+
+    /// applies the lines of a theme file, lines that do not parse are left out
+    fn configure(&mut self, text: &str, depth: usize) {
+        for line in text.lines() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some((slot, value)) = line.split_once('=') else {
+                continue;
+            };
+            let (slot, value) = (slot.trim(), value.trim());
+            match slot {
+                "theme" if depth < MAX_DEPTH => {
+                    if let Ok(base) = Self::load(value, depth.saturating_add(1)) {
+                        *self = base;
+                    }
+                }
+                "cursor" => {
+                    if let Some(cursor) = CURSOR_OPTIONS.iter().find(|cursor| **cursor == value) {
+                        self.cursor = cursor;
+                    }
+                }
+                _ => {
+                    if let (Some(slot), Ok(style)) = (self.slot(slot), parse_style(value)) {
+                        *slot = style;
                     }
                 }
             }
         }
-        self.foreground = Self::get_color(&user_choices[0]);
-        self.background = Self::get_color(&user_choices[1]);
-        self.highlight = Self::get_color(&user_choices[2]);
-        self.text = Self::get_color(&user_choices[3]);
-        self.cursor_style = Self::get_cursor_style(&user_choices[4]);
-        Terminal::set_foreground_color(self.foreground).unwrap();
-        Terminal::set_background_color(self.background).unwrap();
-        Terminal::set_cursor_style(self.cursor_style).unwrap();
-        Terminal::execute().unwrap();
-    }
-
-    fn move_cursor(position: usize) {
-        Terminal::hide_cursor().expect("Error hiding cursor");
-        Terminal::move_cursor_to(ScreenPosition {
-            height: position,
-            width: 0,
+    }
+
+    /// the style of a slot by its name in theme files
+    pub fn style(&self, name: &str) -> Option<Style> {
+        // slot lends the style out mutably, a copy of the theme looks it up
+        self.clone().slot(name).copied()
+    }
+
+    fn slot(&mut self, name: &str) -> Option<&mut Style> {
+        Some(match name {
+            "text" => &mut self.text,
+            "status" => &mut self.status,
+            "gutter" => &mut self.gutter,
+            "selection" => &mut self.selection,
+            "search" => &mut self.search,
+            "current_match" => &mut self.current_match,
+            "cursor_line" => &mut self.cursor_line,
+            "keyword" => &mut self.keyword,
+            "string" => &mut self.string,
+            "comment" => &mut self.comment,
+            "number" => &mut self.number,
+            "type" => &mut self.type_name,
+            "function" => &mut self.function,
+            "popup" => &mut self.popup,
+            "preview" => &mut self.preview,
+            "sign_error" => &mut self.sign_error,
+            "sign_warning" => &mut self.sign_warning,
+            "sign_info" => &mut self.sign_info,
+            "sign_hint" => &mut self.sign_hint,
+            "info" => &mut self.info,
+            "warning" => &mut self.warning,
+            "error" => &mut self.error,
+            _ => return None,
         })
-        .expect("Error moving cursor");
-        Terminal::show_cursor().expect("Error showing cursor");
-        Terminal::execute().expect("Error flushing terminal queue");
+    }
+
+    /// makes the theme the one the screen is drawn with
+    /// its colors stay as the theme has them, the screen writes the nearest ones the terminal shows
+    pub fn apply(&self) -> Result<(), Error> {
+        let colors = Colors::detect();
+        Screen::set_colors(move |color| colors.fit(color));
+        Screen::set_styles(self.text, self.status);
+        backend::set_cursor_style(Self::get_cursor_style(self.cursor))
+    }
+
+    pub fn token(&self, token: Token) -> Style {
+        match token {
+            Token::Keyword => self.keyword,
+            Token::String => self.string,
+            Token::Comment => self.comment,
+            Token::Number => self.number,
+            Token::Type => self.type_name,
+            Token::Function => self.function,
+        }
+    }
+
+    /// styles the rows of a buffer already drawn, the gutter, the cursor line and the syntax
+    pub fn paint(&self, buffer: &Buffer, offset: &ScreenOffset, size: &Size, cursor_line: usize) {
+        let rows = wrap::display_rows(buffer, offset, size, size.height.saturating_sub(1));
+        let gutter = offset.gutter.width;
+        let mut tokens = (usize::MAX, Vec::new());
+        for (screen_row, row) in rows.iter().enumerate() {
+            Screen::restyle(screen_row, 0..gutter, self.gutter);
+            if row.line == cursor_line {
+                Screen::restyle(screen_row, gutter..size.width, self.cursor_line);
+            }
+            let line = &buffer.text[row.line];
+            if tokens.0 != row.line {
+                tokens = (row.line, syntax::tokens(line, &buffer.filetype));
+            }
+            for (range, token) in &tokens.1 {
+                if let Some(columns) = row.columns(line, range.clone()) {
+                    Screen::restyle(screen_row, columns, self.token(*token));
+                }
+            }
+        }
     }
 
     fn get_cursor_style(style_str: &str) -> SetCursorStyle {
@@ -155,25 +605,106 @@ impl Theme {
             _ => SetCursorStyle::DefaultUserShape,
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn get_color(color_str: &str) -> Color {
-        match color_str {
-            "DarkGrey" => Color::DarkGrey,
-            "Red" => Color::Red,
-            "Green" => Color::Green,
-            "Yellow" => Color::Yellow,
-            "Blue" => Color::Blue,
-            "Magenta" => Color::Magenta,
-            "Cyan" => Color::Cyan,
-            "Black" => Color::Black,
-            "DarkRed" => Color::DarkRed,
-            "DarkGreen" => Color::DarkGreen,
-            "DarkYellow" => Color::DarkYellow,
-            "DarkBlue" => Color::DarkBlue,
-            "DarkMagenta" => Color::DarkMagenta,
-            "DarkCyan" => Color::DarkCyan,
-            "Grey" => Color::Grey,
-            _ => Color::White,
+    #[test]
+    fn parses_colors_and_attributes() {
+        let style = parse_style("#ff8000 on 236 bold italic").unwrap();
+        assert_eq!(
+            style.foreground,
+            Some(Color::Rgb {
+                r: 255,
+                g: 128,
+                b: 0
+            })
+        );
+        assert_eq!(style.background, Some(Color::AnsiValue(236)));
+        assert!(style.bold && style.italic && !style.underline);
+        // a style is written back the way it is read
+        assert_eq!(style_text(style), "#ff8000 on 236 bold italic");
+        assert_eq!(parse_style(&style_text(style)), Ok(style));
+        assert_eq!(
+            parse_style("on DarkGray").unwrap().background,
+            Some(Color::DarkGrey)
+        );
+        assert!(parse_style("#ff80").is_err());
+        assert!(parse_style("purple").is_err());
+        assert!(parse_style("red on").is_err());
+    }
+
+    #[test]
+    fn themes_start_from_others_and_keep_what_they_do_not_set() {
+        let mut theme = Theme::default();
+        theme.configure(
+            "theme = gruvbox\nkeyword = red underline\nsearch = nonsense",
+            0,
+        );
+        assert_eq!(theme.name, "gruvbox");
+        assert_eq!(theme.keyword.foreground, Some(Color::Red));
+        assert!(theme.keyword.underline);
+        assert_eq!(theme.style("keyword"), Some(theme.keyword));
+        assert!(SLOTS.iter().all(|slot| theme.style(slot).is_some()));
+        assert_eq!(theme.style("cursor"), None);
+        // a slot that does not parse keeps the style it had
+        assert_eq!(theme.search, Theme::load("gruvbox", 0).unwrap().search);
+        assert_eq!(theme.selection.foreground, None);
+        assert!(Theme::named("nothing").is_err());
+        assert_eq!(Theme::names().len(), BUILT_IN.len());
+    }
+
+    #[test]
+    fn colors_fall_back_to_what_the_terminal_shows() {
+        let orange = Color::Rgb {
+            r: 255,
+            g: 135,
+            b: 0,
+        };
+        assert_eq!(Colors::TrueColor.fit(orange), orange);
+        assert_eq!(Colors::Palette.fit(orange), Color::AnsiValue(208));
+        assert_eq!(
+            Colors::Palette.fit(Color::Rgb {
+                r: 40,
+                g: 40,
+                b: 40
+            }),
+            Color::AnsiValue(235)
+        );
+        assert_eq!(Colors::Basic.fit(orange), Color::Yellow);
+        assert_eq!(Colors::Basic.fit(Color::AnsiValue(196)), Color::Red);
+        assert_eq!(Colors::Basic.fit(Color::Blue), Color::Blue);
+
+        let theme = Theme::named("dark").unwrap();
+        assert!(!matches!(
+            theme.text.fit(|color| Colors::Basic.fit(color)).background,
+            Some(Color::Rgb { .. } | Color::AnsiValue(_))
+        ));
+    }
+
+    #[test]
+    fn built_in_themes_style_popups_and_signs() {
+        for (name, _) in BUILT_IN {
+            let theme = Theme::load(name, 0).unwrap();
+            for slot in ["popup", "preview"] {
+                assert!(
+                    theme.style(slot).unwrap().background.is_some(),
+                    "{name} {slot}"
+                );
+            }
+            for slot in ["sign_error", "sign_warning", "sign_info", "sign_hint"] {
+                assert!(
+                    theme.style(slot).unwrap().foreground.is_some(),
+                    "{name} {slot}"
+                );
+            }
+            // and fall back like the rest of the theme
+            assert!(!matches!(
+                theme.popup.fit(|color| Colors::Basic.fit(color)).background,
+                Some(Color::Rgb { .. } | Color::AnsiValue(_))
+            ));
         }
     }
 }
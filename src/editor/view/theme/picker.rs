@@ -0,0 +1,305 @@
+// the theme picker, a list drawn over the view while the view shows its buffer in the theme picked
+// Up and Down go through the themes, Tab to the slots of the one picked, where Left and Right change
+// the color of a slot, with Shift its background, and b, i and u its attributes
+// Esc puts the theme from before back, Enter keeps the one picked and writes it to theme.conf
+use super::{style_text, Theme, BASIC, CONFIG_FILE, CURSOR_OPTIONS, SLOTS};
+use crate::editor::config::config_dir;
+use crate::editor::editorcommands::PickerCommand;
+use crate::editor::screen::{Screen, Style};
+use crate::editor::terminal::{ScreenPosition, Size};
+use crossterm::style::Color;
+use std::fmt::Write;
+use std::fs;
+use std::io::Error;
+
+/// the columns the list takes at most
+const WIDTH: usize = 36;
+/// the columns of a slot name in the list of slots
+const NAME_WIDTH: usize = 15;
+
+/// what a key did in the picker
+#[derive(Debug, PartialEq, Eq)]
+pub enum PickerAction {
+    Continue,
+    Keep,
+    Restore,
+    Resize(Size),
+}
+
+pub struct Picker {
+    pub theme: Theme,  // the one the view shows
+    pub before: Theme, // the one to put back
+    names: Vec<String>,
+    index: usize,              // of the theme picked in names
+    slot: Option<usize>,       // of the slot changed, past SLOTS is the cursor, None picks themes
+    edited: Vec<&'static str>, // slots changed by hand, written to theme.conf after the theme
+}
+
+/// the color after or before one, no color and then the 16 named ones
+fn cycle(color: Option<Color>, next: bool) -> Option<Color> {
+    let colors: Vec<Option<Color>> = std::iter::once(None)
+        .chain(BASIC.iter().map(|(color, _)| Some(*color)))
+        .collect();
+    let count = colors.len();
+    let index = match (colors.iter().position(|known| *known == color), next) {
+        (Some(at), true) => at.saturating_add(1) % count,
+        (Some(at), false) => at.saturating_add(count).saturating_sub(1) % count,
+        // a color of the palette or #rrggbb goes on with the named ones
+        (None, true) => 1,
+        (None, false) => count.saturating_sub(1),
+    };
+    colors[index]
+}
+
+impl Picker {
+    pub fn new(theme: &Theme) -> Self {
+        let names = Theme::names();
+        let index = names
+            .iter()
+            .position(|name| *name == theme.name)
+            .unwrap_or_default();
+        // the slots theme.conf changed stay changed
+        let edited = match Theme::load(&theme.name, 0) {
+            Ok(loaded) => SLOTS
+                .into_iter()
+                .filter(|slot| loaded.style(slot) != theme.style(slot))
+                .chain((loaded.cursor != theme.cursor).then_some("cursor"))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        Self {
+            theme: theme.clone(),
+            before: theme.clone(),
+            names,
+            index,
+            slot: None,
+            edited,
+        }
+    }
+
+    pub fn apply(&mut self, command: PickerCommand) -> PickerAction {
+        match command {
+            PickerCommand::Move(down) => match self.slot {
+                None => {
+                    let index = if down {
+                        std::cmp::min(
+                            self.index.saturating_add(1),
+                            self.names.len().saturating_sub(1),
+                        )
+                    } else {
+                        self.index.saturating_sub(1)
+                    };
+                    if index != self.index {
+                        if let Ok(theme) = Theme::named(&self.names[index]) {
+                            self.theme = theme;
+                            self.index = index;
+                            self.edited.clear();
+                        }
+                    }
+                }
+                Some(slot) => {
+                    self.slot = Some(if down {
+                        std::cmp::min(slot.saturating_add(1), SLOTS.len())
+                    } else {
+                        slot.saturating_sub(1)
+                    });
+                }
+            },
+            PickerCommand::Switch => {
+                self.slot = match self.slot {
+                    None => Some(0),
+                    Some(_) => None,
+                };
+            }
+            PickerCommand::Color { background, next } => self.change(|theme, slot| {
+                let Some(style) = theme.slot(slot) else {
+                    // the cursor shapes go round the same way
+                    let count = CURSOR_OPTIONS.len();
+                    let at = CURSOR_OPTIONS
+                        .iter()
+                        .position(|cursor| *cursor == theme.cursor)
+                        .unwrap_or_default();
+                    let index = if next {
+                        at.saturating_add(1) % count
+                    } else {
+                        at.saturating_add(count).saturating_sub(1) % count
+                    };
+                    theme.cursor = CURSOR_OPTIONS[index];
+                    return;
+                };
+                if background {
+                    style.background = cycle(style.background, next);
+                } else {
+                    style.foreground = cycle(style.foreground, next);
+                }
+            }),
+            PickerCommand::Toggle(attribute) => self.change(|theme, slot| {
+                if let Some(style) = theme.slot(slot) {
+                    match attribute {
+                        'b' => style.bold = !style.bold,
+                        'i' => style.italic = !style.italic,
+                        _ => style.underline = !style.underline,
+                    }
+                }
+            }),
+            PickerCommand::Keep => return PickerAction::Keep,
+            PickerCommand::Restore => return PickerAction::Restore,
+            PickerCommand::Resize(size) => return PickerAction::Resize(size),
+            PickerCommand::NoAction => {}
+        }
+        PickerAction::Continue
+    }
+
+    /// changes the slot picked, nothing while themes are picked
+    fn change(&mut self, edit: impl FnOnce(&mut Theme, &'static str)) {
+        let Some(slot) = self.slot else {
+            return;
+        };
+        let name = SLOTS.get(slot).copied().unwrap_or("cursor");
+        edit(&mut self.theme, name);
+        if !self.edited.contains(&name) {
+            self.edited.push(name);
+        }
+    }
+
+    /// theme.conf for the theme picked, its name and the slots changed by hand
+    pub fn conf(&self) -> String {
+        let mut conf = format!("theme = {}\n", self.names[self.index]);
+        for slot in &self.edited {
+            let value = match self.theme.style(slot) {
+                Some(style) => style_text(style),
+                None => self.theme.cursor.to_string(),
+            };
+            let _ = writeln!(conf, "{slot} = {value}");
+        }
+        conf
+    }
+
+    /// writes theme.conf in the config directory, the editor starts with the theme next time
+    pub fn save(&self) -> Result<(), Error> {
+        let Some(dir) = config_dir() else {
+            return Ok(());
+        };
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(CONFIG_FILE), self.conf())
+    }
+
+    /// the list at the top right of the view, above the status line
+    pub fn draw(&self, size: &Size) {
+        let width = std::cmp::min(WIDTH, size.width);
+        let left = size.width.saturating_sub(width);
+        let rows = size.height.saturating_sub(2);
+        let print = |row: usize, text: &str, style: Style| {
+            let text: String = format!("{text:<width$}").chars().take(width).collect();
+            Screen::print_at(
+                ScreenPosition {
+                    height: row,
+                    width: left,
+                },
+                text,
+                style,
+            );
+        };
+        let header = match self.slot {
+            None => " Themes, Tab for the slots".to_string(),
+            Some(_) => format!(" {}, Tab for the themes", self.names[self.index]),
+        };
+        print(0, &header, self.theme.status);
+
+        let (count, picked) = match self.slot {
+            None => (self.names.len(), self.index),
+            Some(slot) => (SLOTS.len().saturating_add(1), slot),
+        };
+        let first = picked.saturating_sub(rows.saturating_sub(1));
+        for (row, item) in (first..count).take(rows).enumerate() {
+            let row = row.saturating_add(1);
+            let style = if item == picked {
+                self.theme.selection
+            } else {
+                self.theme.text
+            };
+            let Some(_) = self.slot else {
+                print(row, &format!(" {}", self.names[item]), style);
+                continue;
+            };
+            // a slot shows its style drawn in it
+            let name = SLOTS.get(item).copied().unwrap_or("cursor");
+            let (value, sample) = match self.theme.style(name) {
+                Some(sample) => (style_text(sample), sample.over(self.theme.text)),
+                None => (self.theme.cursor.to_string(), self.theme.text),
+            };
+            print(row, &format!(" {name:<NAME_WIDTH$}"), style);
+            let value = if value.is_empty() { "-" } else { &value };
+            let value: String = value
+                .chars()
+                .take(width.saturating_sub(NAME_WIDTH.saturating_add(1)))
+                .collect();
+            Screen::print_at(
+                ScreenPosition {
+                    height: row,
+                    width: left.saturating_add(NAME_WIDTH).saturating_add(1),
+                },
+                value,
+                sample,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_themes_and_changes_their_slots() {
+        let mut picker = Picker::new(&Theme::default());
+        assert_eq!(picker.conf(), "theme = default\n");
+        // the themes are sorted by name, dark is the first one
+        picker.apply(PickerCommand::Move(false));
+        picker.apply(PickerCommand::Move(false));
+        assert_eq!(picker.theme.name, "dark");
+        picker.apply(PickerCommand::Move(true));
+        picker.apply(PickerCommand::Move(true));
+        let name = picker.theme.name.clone();
+        assert_eq!(name, "gruvbox");
+
+        // Left and Right go through the colors of a slot, no color first
+        picker.apply(PickerCommand::Switch);
+        picker.apply(PickerCommand::Move(true));
+        picker.theme.status = Style::default();
+        picker.apply(PickerCommand::Color {
+            background: true,
+            next: true,
+        });
+        picker.apply(PickerCommand::Toggle('b'));
+        assert_eq!(
+            picker.conf(),
+            format!("theme = {name}\nstatus = on black bold\n")
+        );
+        assert_eq!(picker.apply(PickerCommand::Keep), PickerAction::Keep);
+
+        // the cursor is the last slot
+        picker.apply(PickerCommand::Move(true));
+        for _ in 0..SLOTS.len() {
+            picker.apply(PickerCommand::Move(true));
+        }
+        picker.apply(PickerCommand::Color {
+            background: false,
+            next: false,
+        });
+        assert!(picker.conf().ends_with("cursor = SteadyBar\n"));
+    }
+
+    #[test]
+    fn keeps_the_colors_the_terminal_cannot_show() {
+        // theme.conf gets the color set, not the nearest one of the palette the screen writes
+        let mut theme = Theme::named("gruvbox").unwrap();
+        theme.keyword.foreground = Some(Color::Rgb {
+            r: 255,
+            g: 135,
+            b: 0,
+        });
+        let picker = Picker::new(&theme);
+        assert_eq!(picker.conf(), "theme = gruvbox\nkeyword = #ff8700\n");
+    }
+}
@@ -1,9 +1,23 @@
 use super::clipboard_interface::ClipboardUtils;
+use super::disk::{self, DiskChange, Resolution};
+use super::explorer::{Exit, Explorer};
+use super::fold::{self, FoldMethod};
+use super::line::Line;
+use super::lsp;
+use super::messages;
+use super::prompt::{Completion, HistoryKind, Prompt, PromptAction};
+use super::shell;
+use super::status::Status;
+use super::swap::IDLE;
+use super::wrap;
 use super::{Search, Theme};
+use crate::editor::backend::{self, read, Idle};
+use crate::editor::screen::{Screen, Style};
+use crate::editor::terminal::Gutter;
 use crate::editor::Terminal;
 use crate::editor::{
     editorcommands::{
-        parse_highlight_vim_mode, ColonQueueActions, Direction, QueueInitCommand, VimColonQueue,
+        parse_highlight_vim_mode, Direction, PromptCommand, QueueInitCommand, Shift,
         VimModeCommands,
     },
     view::{
@@ -11,15 +25,25 @@ use crate::editor::{
         ScreenOffset, Size,
     },
 };
-use crossterm::event::{read, Event, KeyCode, KeyEvent};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use std::collections::VecDeque;
 use std::error::Error;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::time::Duration;
+pub mod ex_command;
+mod lsp_commands;
+use ex_command::{
+    Address, ExCommand, ExCommandKind, ExRange, LineEdit, LineSpecifier, RangeContext,
+};
 
 enum ContinueState {
     ExitSession,
     ContinueVim,
-    ContinueVimPersistError,
     InvalidCommand,
     JumpCursor(usize),
+    Message(String), // done, with something to say on the message line
 }
 
 pub struct VimMode<'a> {
@@ -27,6 +51,11 @@ pub struct VimMode<'a> {
     screen_offset: ScreenOffset,
     size: Size,
     buffer: &'a mut Buffer,
+    last_selection: Option<(usize, usize)>, // lines of the last highlight, for '< and '>
+    pending_keys: VecDeque<Event>,          // keys queued by :normal
+    replaying: bool,
+    pub exit_code: u8, // what the editor exits with when a command ends the session
+    pub theme: Theme,  // handed back to the view, :colorscheme changes it
 }
 
 impl VimMode<'_> {
@@ -35,12 +64,18 @@ impl VimMode<'_> {
         screen_offset: ScreenOffset,
         size: Size,
         buffer: &'_ mut Buffer, // mutable reference to buffer
+        theme: Theme,
     ) -> VimMode<'_> {
         VimMode {
             cursor_position,
             screen_offset,
             size,
             buffer,
+            last_selection: None,
+            pending_keys: VecDeque::new(),
+            replaying: false,
+            exit_code: 0,
+            theme,
         }
     }
     pub fn run(
@@ -48,89 +83,53 @@ impl VimMode<'_> {
         cursor_position: &mut Position,
         screen_offset: &mut ScreenOffset,
         size: &mut Size,
-        theme: &Theme,
     ) -> bool {
-        let res = self.start();
-        debug_assert!(res.is_ok());
+        messages::draw_failed(self.cursor_and_status());
         loop {
             let mut needs_render = false;
-            let Ok(read_event) = read() else { continue }; //skipping an error on read cursor action
+            let gutter = self.screen_offset.gutter;
+            let line = self.cursor_position.height;
+            let Ok(read_event) = backend::read_or_idle(self.tick(), |idle| self.idle(idle)) else {
+                continue;
+            }; //skipping an error on read cursor action
 
             match VimModeCommands::try_from(read_event) {
                 Ok(event) => match event {
-                    VimModeCommands::Move(dir) => match dir {
-                        Direction::Right
-                        | Direction::Left
-                        | Direction::Up
-                        | Direction::Down
-                        | Direction::End
-                        | Direction::Home => {
-                            if self.move_cursor(dir) > 0 {
-                                needs_render = true;
-                            }
-                        }
-                        _ => continue,
-                    },
-                    VimModeCommands::JumpUp => {
-                        if self.jump_up() > 0 {
-                            needs_render = true;
-                        }
-                    }
-                    VimModeCommands::JumpDown => {
-                        if self.jump_down() > 0 {
-                            needs_render = true;
-                        }
-                    }
-                    VimModeCommands::NewLine => {
-                        // new line
-                        self.buffer.add_new_line(&mut self.cursor_position);
-                        needs_render = true;
-                    }
-                    VimModeCommands::StartOfNextWord => {
-                        self.buffer.begining_of_next_word(&mut self.cursor_position);
-                    }
-                    VimModeCommands::EndOfCurrentWord => {
-                        self.buffer.end_of_current_word(&mut self.cursor_position);
-                    }
-                    VimModeCommands::BeginingOfCurrentWord => self
-                        .buffer
-                        .begining_of_current_word(&mut self.cursor_position),
                     VimModeCommands::ComplexCommand(queue_command) => {
                         // if we get true back, staying in vim mode
                         // else user is exiting the session
                         match self.determine_queue_command(&queue_command) {
-                            ContinueState::ContinueVimPersistError => continue,
-                            ContinueState::ContinueVim => {
-                                needs_render = true;
-                            } // no action
+                            ContinueState::ContinueVim => needs_render = true,
                             ContinueState::InvalidCommand => {
                                 // if the command is invalid, render the help
-                                VimHelpScreen::render_help(
+                                messages::draw_failed(VimHelpScreen::render_help(
                                     &mut self.size,
-                                    theme.highlight,
-                                    theme.text,
-                                );
+                                    self.theme.selection,
+                                ));
+                                needs_render = true;
                             }
                             ContinueState::JumpCursor(line) => {
-                                if self.jump_cursor_to(line) > 0 {
-                                    needs_render = true;
-                                }
+                                needs_render = self.jump_cursor_to(line) > 0;
                             }
                             ContinueState::ExitSession => return false,
+                            ContinueState::Message(text) => {
+                                messages::info(text);
+                                needs_render = true;
+                            }
                         }
                     }
                     VimModeCommands::Search => {
                         let mut search = Search::new(
                             self.cursor_position,
                             self.screen_offset,
-                            theme.highlight,
-                            theme.text,
+                            self.theme.search,
+                            self.theme.current_match,
                         );
                         search.run(
                             &mut self.cursor_position,
                             &mut self.screen_offset,
                             &mut self.size,
-                            &self.buffer,
+                            self.buffer,
                         );
                         needs_render = true;
                     }
@@ -141,39 +140,204 @@ impl VimMode<'_> {
                             &mut self.size,
                             self.buffer,
                         );
-                        highlight.run(theme.highlight, theme.text, parse_highlight_vim_mode);
-                        if self.resolve_displacement() > 0 {
-                            needs_render = true;
-                        } // making sure the offset is correct on a delete
+                        highlight.run(self.theme.selection, parse_highlight_vim_mode);
+                        self.last_selection = Some(highlight.selected_lines());
+                        // making sure the offset is correct on a delete
+                        self.resolve_displacement();
+                        // redrawing is cheap, only the cells that lose the highlight are written
+                        needs_render = true;
                     }
+                    VimModeCommands::Focus(true) => needs_render = self.check_disk(),
+                    VimModeCommands::Focus(false) => self.buffer.flush_swap(true),
                     VimModeCommands::Resize(new_size) => {
                         self.resize(new_size);
                         needs_render = true;
                     }
+                    VimModeCommands::OpenLine(above) => {
+                        // the new line is edited out of vim mode, like after i
+                        self.start_insert(if above { 'O' } else { 'o' });
+                        self.resolve_displacement();
+                        self.hand_back_state(cursor_position, screen_offset, size);
+                        return true;
+                    }
                     VimModeCommands::Exit => {
                         // here user is staying in terminal session
                         // but exiting vim mode
                         self.hand_back_state(cursor_position, screen_offset, size);
                         return true;
                     }
-                    VimModeCommands::Paste => {
-                        self.add_from_clipboard();
-                        needs_render = true;
-                    }
                     VimModeCommands::NoAction => {
-                        VimHelpScreen::render_help(&mut self.size, theme.highlight, theme.text);
+                        messages::draw_failed(VimHelpScreen::render_help(
+                            &mut self.size,
+                            self.theme.selection,
+                        ));
                         needs_render = true;
                     } // skipping other
+                    motion => {
+                        let Some(render) = self.apply_motion(&motion) else {
+                            continue;
+                        };
+                        needs_render = render;
+                    }
                 },
                 Err(_) => continue, //ignoring error
             }
-            if needs_render {
-                let res = self.render_proc();
-                debug_assert!(res.is_ok());
+            self.refresh(gutter, line, needs_render);
+        }
+    }
+
+    /// brings the screen up to date after a key, gutter and line are from before it
+    fn refresh(&mut self, gutter: Gutter, line: usize, mut needs_render: bool) {
+        self.buffer.flush_swap(false);
+        // folds below lines added or removed move with them, one the cursor went into opens
+        self.buffer
+            .update_folds(std::cmp::min(line, self.cursor_position.height));
+        if self.buffer.folds.reveal(self.cursor_position.height) {
+            self.resolve_displacement();
+            needs_render = true;
+        }
+        // new signs are drawn with the text
+        needs_render |= self.buffer.update_lsp();
+        // numbers in the gutter change when it widens or the cursor leaves its line
+        self.screen_offset.gutter.signs = self.buffer.lsp.is_some();
+        self.screen_offset.gutter.resize(self.buffer.len());
+        if gutter != self.screen_offset.gutter
+            || (gutter.numbers.is_relative() && line != self.cursor_position.height)
+        {
+            self.resolve_displacement();
+            needs_render = true;
+        }
+        // the cursor line style moves with the cursor
+        needs_render |=
+            line != self.cursor_position.height && self.theme.cursor_line != Style::default();
+        if needs_render && self.screen_offset.wrap {
+            // moves that only know buffer lines may leave the wrapped cursor row out of view
+            self.resolve_displacement();
+        }
+        if needs_render {
+            self.render();
+        }
+
+        messages::draw_failed(self.cursor_and_status());
+    }
+
+    /// runs the ex commands given with -c, false when one of them ended the session
+    pub fn run_commands(
+        &mut self,
+        commands: &[String],
+        cursor_position: &mut Position,
+        screen_offset: &mut ScreenOffset,
+        size: &mut Size,
+    ) -> Result<bool, String> {
+        for text in commands {
+            let command =
+                ExCommand::parse(text).map_err(|message| format!("-c {text}: {message}"))?;
+            match self.execute_ex(&command, &mut Vec::new()) {
+                Ok(ContinueState::ExitSession) => return Ok(false),
+                Ok(ContinueState::JumpCursor(line)) => {
+                    self.jump_cursor_to(line);
+                }
+                Ok(_) => {}
+                Err(message) => return Err(format!("-c {text}: {message}")),
+            }
+        }
+        self.hand_back_state(cursor_position, screen_offset, size);
+        Ok(true)
+    }
+
+    /// applies the commands that only move the cursor or edit at the cursor
+    /// shared between key presses and :normal
+    /// returns if the screen needs a render, None when the command is not a motion
+    fn apply_motion(&mut self, command: &VimModeCommands) -> Option<bool> {
+        let needs_render = match command {
+            VimModeCommands::Move(dir) => match dir {
+                Direction::Right
+                | Direction::Left
+                | Direction::Up
+                | Direction::Down
+                | Direction::End
+                | Direction::Home => self.move_cursor(*dir) > 0,
+                _ => return None,
+            },
+            VimModeCommands::JumpUp => self.jump_up() > 0,
+            VimModeCommands::JumpDown => self.jump_down() > 0,
+            VimModeCommands::StartOfNextWord => {
+                self.buffer.begining_of_next_word(&mut self.cursor_position);
+                false
+            }
+            VimModeCommands::EndOfCurrentWord => {
+                self.buffer.end_of_current_word(&mut self.cursor_position);
+                false
             }
+            VimModeCommands::BeginingOfCurrentWord => {
+                self.buffer
+                    .begining_of_current_word(&mut self.cursor_position);
+                false
+            }
+            VimModeCommands::Paste => {
+                self.add_from_clipboard();
+                true
+            }
+            _ => return None,
+        };
+        Some(needs_render)
+    }
 
-            let res = self.cursor_and_status();
-            debug_assert!(res.is_ok());
+    /// how long to wait for a key before calling idle
+    fn tick(&self) -> Duration {
+        if self.buffer.lsp.is_some() {
+            lsp::TICK
+        } else if messages::showing() {
+            messages::TICK
+        } else {
+            IDLE
+        }
+    }
+
+    /// called while no key is pressed, once the idle time passed the swap file is written
+    /// with a language server it is called every tick to show its diagnostics
+    fn idle(&mut self, idle: Idle) {
+        let mut redraw = self.buffer.update_lsp();
+        redraw |= messages::expired();
+        if idle.reached(IDLE) {
+            self.buffer.flush_swap(true);
+        }
+        if idle.every(IDLE) {
+            redraw |= self.check_disk();
+        }
+        if redraw {
+            self.resolve_displacement();
+            self.render();
+            // nothing waits on the idle work to hear about it, so the message line does
+            messages::draw_failed(self.cursor_and_status());
+        }
+    }
+
+    /// looks for changes to the file by other programs
+    /// returns whether the screen needs drawing again
+    fn check_disk(&mut self) -> bool {
+        let mut size = self.size;
+        let resolution = disk::resolve(self.buffer, &mut size);
+        if size != self.size {
+            self.resize(size);
+        }
+        match resolution {
+            Ok(Resolution::Unchanged) => false,
+            Ok(Resolution::Reloaded) => {
+                let last = self.buffer.len().saturating_sub(1);
+                self.cursor_position.height = std::cmp::min(self.cursor_position.height, last);
+                self.cursor_position.width = std::cmp::min(
+                    self.cursor_position.width,
+                    self.buffer
+                        .text
+                        .get(self.cursor_position.height)
+                        .map_or(0, Line::len),
+                );
+                self.cursor_position.max_width = self.cursor_position.width;
+                self.resolve_displacement();
+                true
+            }
+            Ok(Resolution::Kept) | Err(_) => true,
         }
     }
 
@@ -197,61 +361,37 @@ impl VimMode<'_> {
 
     #[inline]
     fn cursor_and_status(&self) -> Result<(), Box<dyn Error>> {
-        self.status_line()?;
-        Terminal::move_cursor_to(
-            self.cursor_position
-                .relative_view_position(&self.screen_offset),
-        )?;
-
-        Terminal::show_cursor()?;
-        Terminal::execute()?;
-        Ok(())
-    }
-
-    fn start(&self) -> Result<(), Box<dyn Error>> {
-        self.status_line()?;
-        Terminal::move_cursor_to(
-            self.cursor_position
-                .relative_view_position(&self.screen_offset),
-        )?;
-        Terminal::execute()?;
-        Ok(())
-    }
-
-    #[inline]
-    fn render_proc(&self) -> Result<(), Box<dyn Error>> {
-        Terminal::hide_cursor()?;
-        Terminal::move_cursor_to(self.screen_offset.to_position())?;
-        Terminal::clear_screen()?;
-        self.render()?;
-
+        self.status_line();
+        messages::draw(&self.size, &self.theme);
+        Screen::move_cursor_to(wrap::screen_position(
+            &self.cursor_position,
+            &self.screen_offset,
+            self.buffer,
+            &self.size,
+        ));
+        Screen::present()?;
         Ok(())
     }
 
     fn add_from_clipboard(&mut self) {
-        if let Ok(paste_text) = ClipboardUtils::get_text_from_clipboard() {
-            self.buffer
-                .add_text_from_clipboard(&paste_text, &mut self.cursor_position);
+        match ClipboardUtils::get_text_from_clipboard() {
+            Ok(paste_text) => self
+                .buffer
+                .add_text_from_clipboard(&paste_text, &mut self.cursor_position),
+            Err(err) => messages::error(format!("Clipboard: {err}")),
         }
     }
 
     #[inline]
-    fn status_line(&self) -> Result<(), Box<dyn Error>> {
-        Terminal::render_status_line(
-            &Mode::Vim,
-            self.buffer.is_saved,
-            &self.size,
-            self.buffer.filename.as_deref(),
-            Some((
-                self.cursor_position.height.saturating_add(1),
-                self.buffer.len(),
-            )),
-        )?;
-        Ok(())
+    fn status_line(&self) {
+        Status::of(Mode::Vim, self.buffer, &self.cursor_position).render(&self.size);
     }
 
     fn resize(&mut self, new_size: Size) {
         self.size = new_size;
+        if self.screen_offset.wrap {
+            self.resolve_displacement();
+        }
     }
 
     fn hand_back_state(&self, pos: &mut Position, offset: &mut ScreenOffset, size: &mut Size) {
@@ -262,35 +402,64 @@ impl VimMode<'_> {
         }
     }
 
-    fn render(&self) -> Result<(), Box<dyn Error>> {
-        #[allow(clippy::integer_division)]
-        for current_row in self.screen_offset.height
-            ..self
-                .screen_offset
-                .height
-                .saturating_add(self.size.height)
-                .saturating_sub(1)
-        {
-            let relative_row = current_row.saturating_sub(self.screen_offset.height);
+    fn render(&self) {
+        if self.screen_offset.wrap && !self.buffer.is_empty() {
+            let rows = wrap::screen_rows(
+                self.buffer,
+                &self.screen_offset,
+                &self.size,
+                self.cursor_position.height,
+                self.size.height.saturating_sub(1),
+            );
+            for (row, text) in rows.iter().enumerate() {
+                Screen::render_line(row, text);
+            }
+        } else {
+            self.render_rows();
+        }
+        self.theme.paint(
+            self.buffer,
+            &self.screen_offset,
+            &self.size,
+            self.cursor_position.height,
+        );
+        lsp::ui::draw_signs(self.buffer, &self.screen_offset, &self.size, &self.theme);
+    }
 
+    fn render_rows(&self) {
+        // a closed fold takes one row, the next row shows the line after it
+        let mut current_row = self.screen_offset.height;
+        #[allow(clippy::integer_division)]
+        for relative_row in 0..self.size.height.saturating_sub(1) {
             if let Some(line) = self.buffer.text.get(current_row) {
-                Terminal::render_line(
+                let gutter = &self.screen_offset.gutter;
+                let start = self.screen_offset.width;
+                Screen::render_line(
                     relative_row,
-                    line.get_line_subset(
-                        self.screen_offset.width
-                            ..self.screen_offset.width.saturating_add(self.size.width),
+                    format!(
+                        "{}{}",
+                        gutter.label(current_row, self.cursor_position.height),
+                        self.buffer
+                            .folds
+                            .summary(current_row, line)
+                            .unwrap_or_else(|| {
+                                line.get_line_subset(
+                                    start..start.saturating_add(gutter.text_width(&self.size)),
+                                )
+                                .to_string()
+                            }),
                     ),
-                )?;
+                );
             } else if self.buffer.is_empty() && (current_row == self.size.height / 3) {
-                Terminal::render_line(
+                Screen::render_line(
                     relative_row,
                     Terminal::get_welcome_message(&self.size, &self.screen_offset),
-                )?;
+                );
             } else {
-                Terminal::render_line(relative_row, "~")?;
+                Screen::render_line(relative_row, "~");
             }
+            current_row = self.buffer.folds.next_visible(current_row);
         }
-        Ok(())
     }
 
     // handing back view delta
@@ -306,25 +475,24 @@ impl VimMode<'_> {
     }
 
     fn resolve_displacement(&mut self) -> usize {
-        let dis =
-            self.cursor_position
-                .max_displacement_from_view(&self.screen_offset, &self.size, 2);
-        match dis {
-            0 => {}
-            1 => {
-                self.screen_offset
-                    .update_offset_single_move(&self.cursor_position, &self.size, 2);
-            }
-            _ => {
-                self.screen_offset.handle_offset_screen_snap(
-                    &self.cursor_position,
-                    &self.size,
-                    1,
-                    self.buffer.len(),
-                );
-            }
+        self.buffer.folds.reveal(self.cursor_position.height);
+        self.screen_offset.gutter.signs = self.buffer.lsp.is_some();
+        self.screen_offset.gutter.resize(self.buffer.len());
+        if self.screen_offset.wrap {
+            return wrap::scroll_to_cursor(
+                &mut self.screen_offset,
+                &self.cursor_position,
+                self.buffer,
+                &self.size,
+                2,
+            );
         }
-        dis
+        fold::scroll_to_cursor(
+            &mut self.screen_offset,
+            &self.cursor_position,
+            self.buffer,
+            &self.size,
+        )
     }
 
     #[inline]
@@ -333,15 +501,7 @@ impl VimMode<'_> {
         // otherwise we are staying in terminal session, thus true
         match command {
             QueueInitCommand::Colon => self.queue_colon(),
-            QueueInitCommand::PageUp => {
-                let valid = self.queue_page_up();
-                // stay in vim mode
-                if valid {
-                    ContinueState::ContinueVim
-                } else {
-                    ContinueState::InvalidCommand
-                }
-            }
+            QueueInitCommand::PageUp => self.queue_page_up(),
             QueueInitCommand::PageDown => {
                 let valid = self.queue_page_down();
                 // stay in vim mode
@@ -367,6 +527,15 @@ impl VimMode<'_> {
                     ContinueState::InvalidCommand
                 }
             }
+            QueueInitCommand::Hover => self.hover(),
+            QueueInitCommand::Fold => self.queue_fold(),
+            QueueInitCommand::Indent(shift) => {
+                if self.queue_indent(*shift) {
+                    ContinueState::ContinueVim
+                } else {
+                    ContinueState::InvalidCommand
+                }
+            }
         }
     }
 
@@ -374,124 +543,805 @@ impl VimMode<'_> {
         // return true if we are staying in vim mode after executing command
         // return false if we are ending the terminal session from here
         // in the case the command executes, propogate up the state result
-        let mut queue: String = String::new();
-        self.command_status_line(&queue);
+        let mut prompt = Prompt::new(":", Completion::ExCommand).with_history(HistoryKind::Command);
+        // the prompt takes the row of the message
+        messages::dismiss();
+        self.command_prompt(&prompt);
 
         loop {
             let Ok(read_event) = read() else { continue }; //skipping an error on read cursor action
-            match VimColonQueue::try_from(read_event) {
-                Ok(event) => match event {
-                    VimColonQueue::New(c) => queue.push(c), //queue any of these commands
-                    VimColonQueue::Backspace => {
-                        if queue.is_empty() {
+            let Ok(command) = PromptCommand::try_from(read_event) else {
+                continue;
+            };
+            match prompt.apply(command) {
+                PromptAction::Continue => {}
+                PromptAction::Cancel => return ContinueState::ContinueVim,
+                PromptAction::Resize(size) => self.resize(size),
+                PromptAction::Submit(queue) => {
+                    let command = match ExCommand::parse(&queue) {
+                        Ok(command) => command,
+                        Err(message) => {
+                            messages::failed(message);
                             return ContinueState::ContinueVim;
                         }
-                        let _ = queue.pop();
+                    };
+                    // execute action
+                    return match self.execute_ex(&command, &mut Vec::new()) {
+                        Ok(state) => state,
+                        Err(message) => {
+                            messages::failed(message);
+                            ContinueState::ContinueVim
+                        }
+                    };
+                }
+            }
+            self.command_prompt(&prompt);
+        }
+    }
+
+    fn command_prompt(&self, prompt: &Prompt) {
+        prompt.render(self.size.height.saturating_sub(2), self.size.width);
+        messages::draw_failed(Screen::present());
+    }
+
+    fn range_context(&self) -> RangeContext {
+        RangeContext {
+            current: self.cursor_position.height,
+            len: self.buffer.len(),
+            selection: self.last_selection,
+        }
+    }
+
+    /// resolves the typed range, or the cursor line when none was typed
+    fn command_lines(&self, command: &ExCommand) -> Result<(usize, usize), String> {
+        if self.buffer.is_empty() {
+            return Err("--No lines in buffer--".into());
+        }
+        match &command.range {
+            Some(range) => range.resolve(&self.range_context()),
+            None => Ok((self.cursor_position.height, self.cursor_position.height)),
+        }
+    }
+
+    /// runs a parsed ex command
+    /// line level changes are pushed to edits so :g can keep its marks in place
+    fn execute_ex(
+        &mut self,
+        command: &ExCommand,
+        edits: &mut Vec<LineEdit>,
+    ) -> Result<ContinueState, String> {
+        let Some(kind) = command.kind else {
+            // a bare range jumps to the last line in it
+            let ctx = self.range_context();
+            let line = match command.range {
+                Some(ExRange::Single(spec) | ExRange::Span(_, spec)) => spec.resolve(&ctx)?,
+                None => return Ok(ContinueState::ContinueVim),
+            };
+            return Ok(ContinueState::JumpCursor(line.saturating_sub(1)));
+        };
+
+        match kind {
+            ExCommandKind::Write => {
+                self.ex_write(command)?;
+                Ok(ContinueState::ContinueVim)
+            }
+            ExCommandKind::WriteQuit => {
+                self.ex_write(command)?;
+                Ok(ContinueState::ExitSession)
+            }
+            ExCommandKind::Xit => {
+                if !self.buffer.is_saved && !self.buffer.is_empty() {
+                    self.ex_write(command)?;
+                }
+                Ok(ContinueState::ExitSession)
+            }
+            ExCommandKind::Quit => {
+                // exit session
+                if !command.bang && !self.buffer.is_saved && !self.buffer.is_empty() {
+                    return Err("E37: No write since last change (add ! to override)".into());
+                }
+                Ok(ContinueState::ExitSession)
+            }
+            ExCommandKind::CQuit => {
+                // quits without writing, ie to abort a commit message from git
+                self.exit_code = match command.args.trim() {
+                    "" => 1,
+                    code => code
+                        .parse()
+                        .map_err(|_| format!("E488: Trailing characters: {code}"))?,
+                };
+                Ok(ContinueState::ExitSession)
+            }
+            ExCommandKind::Edit => {
+                self.ex_edit(command)?;
+                Ok(ContinueState::ContinueVim)
+            }
+            ExCommandKind::SaveAs => {
+                self.ex_save_as(command)?;
+                Ok(ContinueState::ContinueVim)
+            }
+            ExCommandKind::Delete => {
+                let (start, end) = self.command_lines(command)?;
+                self.buffer.delete_lines(start, end);
+                edits.push(LineEdit::Removed {
+                    start,
+                    count: end.saturating_sub(start).saturating_add(1),
+                });
+                self.cursor_position.height = start;
+                self.clamp_cursor();
+                Ok(ContinueState::ContinueVim)
+            }
+            ExCommandKind::Yank => {
+                let (start, end) = self.command_lines(command)?;
+                ClipboardUtils::copy_text_to_clipboard(self.buffer.lines_to_string(start, end))
+                    .map_err(|err| format!("Clipboard: {err}"))?;
+                Ok(ContinueState::ContinueVim)
+            }
+            ExCommandKind::Move => self.ex_transfer(command, edits, true),
+            ExCommandKind::Copy => self.ex_transfer(command, edits, false),
+            ExCommandKind::Sort => {
+                let (start, end) = match command.range {
+                    Some(_) => self.command_lines(command)?,
+                    None if self.buffer.is_empty() => return Ok(ContinueState::ContinueVim),
+                    None => (0, self.buffer.len().saturating_sub(1)),
+                };
+                let len_before = self.buffer.len();
+                self.buffer
+                    .sort_lines(start, end, command.bang, command.args.contains('u'));
+                if let Some(edit) = Self::length_edit(start, len_before, self.buffer.len()) {
+                    edits.push(edit);
+                }
+                self.clamp_cursor();
+                Ok(ContinueState::ContinueVim)
+            }
+            ExCommandKind::Normal => self.ex_normal(command, edits),
+            ExCommandKind::Global | ExCommandKind::VGlobal => {
+                let invert = command.bang || matches!(kind, ExCommandKind::VGlobal);
+                self.execute_global(command, invert)
+            }
+            ExCommandKind::Set => {
+                self.ex_set(command)?;
+                Ok(ContinueState::ContinueVim)
+            }
+            ExCommandKind::Shell => self.ex_shell(command, edits),
+            ExCommandKind::ShiftRight => self.ex_shift(command, Shift::Right),
+            ExCommandKind::ShiftLeft => self.ex_shift(command, Shift::Left),
+            ExCommandKind::Read => self.ex_read(command, edits),
+            ExCommandKind::Rename => self.ex_rename(command),
+            ExCommandKind::Format => self.ex_format(),
+            ExCommandKind::Lsp => self.ex_lsp(command),
+            ExCommandKind::Colorscheme => self.ex_colorscheme(command),
+            ExCommandKind::Messages => self.ex_messages(command),
+        }
+    }
+
+    /// :> and :< shift the lines a level for every sign typed
+    fn ex_shift(&mut self, command: &ExCommand, shift: Shift) -> Result<ContinueState, String> {
+        let (start, end) = self.command_lines(command)?;
+        for _ in command.args.chars() {
+            self.buffer.indent_lines(start..=end, shift);
+        }
+        // the cursor ends on the last line, as in vim
+        self.cursor_position.height = end;
+        self.cursor_to_first_non_blank();
+        Ok(ContinueState::ContinueVim)
+    }
+
+    /// :!cmd shows what the command printed, :{range}!cmd replaces the lines with it
+    fn ex_shell(
+        &mut self,
+        command: &ExCommand,
+        edits: &mut Vec<LineEdit>,
+    ) -> Result<ContinueState, String> {
+        if command.args.is_empty() {
+            return Err("E471: Argument required".into());
+        }
+        if command.range.is_none() {
+            let output = shell::run(&command.args, None, shell::TIMEOUT)?;
+            shell::show(&mut self.size, &command.args, &output).map_err(|e| e.to_string())?;
+            return Ok(ContinueState::ContinueVim);
+        }
+        let (start, end) = self.command_lines(command)?;
+        let input = self.buffer.lines_to_string(start, end);
+        let output = shell::run(&command.args, Some(input), shell::TIMEOUT)?;
+        if !output.success {
+            // a failed filter keeps the lines instead of replacing them with its complaint
+            return Err(Self::shell_failed(&output));
+        }
+        self.buffer.delete_lines(start, end);
+        edits.push(LineEdit::Removed {
+            start,
+            count: end.saturating_sub(start).saturating_add(1),
+        });
+        self.insert_output(start, &output.stdout, edits);
+        Ok(Self::shell_message(&output))
+    }
+
+    /// :r file and :r !cmd put the text below the line
+    fn ex_read(
+        &mut self,
+        command: &ExCommand,
+        edits: &mut Vec<LineEdit>,
+    ) -> Result<ContinueState, String> {
+        // both :r !cmd and :r!cmd run a command
+        let shell_command = if command.bang {
+            Some(command.args.as_str())
+        } else {
+            command.args.strip_prefix('!')
+        };
+        let (text, state) = match shell_command.map(str::trim) {
+            Some("") => return Err("E471: Argument required".into()),
+            Some(shell_command) => {
+                let output = shell::run(shell_command, None, shell::TIMEOUT)?;
+                if !output.success {
+                    return Err(Self::shell_failed(&output));
+                }
+                let state = Self::shell_message(&output);
+                (output.stdout, state)
+            }
+            None if command.args.is_empty() => return Err("E32: No file name".into()),
+            None => (
+                fs::read_to_string(&command.args)
+                    .map_err(|_| format!("E484: Can't open file {}", command.args))?,
+                ContinueState::ContinueVim,
+            ),
+        };
+        let below = if self.buffer.is_empty() {
+            0
+        } else {
+            self.command_lines(command)?.1.saturating_add(1)
+        };
+        self.insert_output(below, &text, edits);
+        Ok(state)
+    }
+
+    /// puts the lines of a text at index with the cursor on the first one
+    fn insert_output(&mut self, index: usize, text: &str, edits: &mut Vec<LineEdit>) {
+        let lines: Vec<Line> = text.lines().map(Line::from).collect();
+        let count = lines.len();
+        self.buffer.insert_lines(index, lines);
+        edits.push(LineEdit::Added {
+            start: index,
+            count,
+        });
+        self.cursor_position.height = index;
+        self.cursor_position.width = 0;
+        self.clamp_cursor();
+    }
+
+    fn shell_failed(output: &shell::Output) -> String {
+        match output.error_line() {
+            Some(line) => format!("shell returned an error: {line}"),
+            None => "shell returned an error".into(),
+        }
+    }
+
+    /// what the command said on stderr while it still succeeded
+    fn shell_message(output: &shell::Output) -> ContinueState {
+        output
+            .error_line()
+            .map_or(ContinueState::ContinueVim, |line| {
+                ContinueState::Message(line.to_string())
+            })
+    }
+
+    /// :colorscheme says which theme the screen is drawn with, :colorscheme name changes it
+    fn ex_colorscheme(&mut self, command: &ExCommand) -> Result<ContinueState, String> {
+        let name = command.args.trim();
+        if name.is_empty() {
+            return Ok(ContinueState::Message(self.theme.name.clone()));
+        }
+        self.theme = Theme::named(name)?;
+        self.theme.apply().map_err(|err| err.to_string())?;
+        Ok(ContinueState::ContinueVim)
+    }
+
+    /// :messages lists the messages kept, :messages clear forgets them
+    fn ex_messages(&mut self, command: &ExCommand) -> Result<ContinueState, String> {
+        match command.args.trim() {
+            "" => {}
+            "clear" => {
+                messages::clear();
+                return Ok(ContinueState::ContinueVim);
+            }
+            args => return Err(format!("E488: Trailing characters: {args}")),
+        }
+        let history = messages::history();
+        let hint = "Enter/Esc = back | j/k = move";
+        lsp::ui::pick(
+            &mut self.size,
+            "Messages",
+            &history,
+            hint,
+            self.theme.selection,
+        )
+        .map_err(|err| err.to_string())?;
+        self.resolve_displacement();
+        Ok(ContinueState::ContinueVim)
+    }
+
+    /// :set with the line number, wrap and indent options, several can be given at once
+    fn ex_set(&mut self, command: &ExCommand) -> Result<(), String> {
+        let mut numbers = self.screen_offset.gutter.numbers;
+        let mut filetype = self.buffer.filetype.clone();
+        let mut wrap = self.screen_offset.wrap;
+        let mut autoread = self.buffer.autoread;
+        let mut read_only = self.buffer.read_only;
+        let mut fold_method = self.buffer.folds.method;
+        for option in command.args.split_whitespace() {
+            numbers = match option {
+                "wrap" => {
+                    wrap = true;
+                    continue;
+                }
+                "nowrap" => {
+                    wrap = false;
+                    continue;
+                }
+                "autoread" | "ar" => {
+                    autoread = true;
+                    continue;
+                }
+                "noautoread" | "noar" => {
+                    autoread = false;
+                    continue;
+                }
+                "readonly" | "ro" => {
+                    read_only = true;
+                    continue;
+                }
+                "noreadonly" | "noro" => {
+                    read_only = false;
+                    continue;
+                }
+                "autoindent" | "ai" | "noautoindent" | "noai" => {
+                    filetype.auto_indent = !option.starts_with("no");
+                    continue;
+                }
+                "smartindent" | "si" | "nosmartindent" | "nosi" => {
+                    filetype.smart_indent = !option.starts_with("no");
+                    continue;
+                }
+                "expandtab" | "et" | "noexpandtab" | "noet" => {
+                    filetype.tabs = option.starts_with("no");
+                    continue;
+                }
+                "number" | "nu" => numbers.with_number(true),
+                "nonumber" | "nonu" => numbers.with_number(false),
+                "relativenumber" | "rnu" => numbers.with_relative(true),
+                "norelativenumber" | "nornu" => numbers.with_relative(false),
+                _ => match option.split_once('=') {
+                    Some(("shiftwidth" | "sw", width)) => {
+                        filetype
+                            .set("indent", width)
+                            .map_err(|_| format!("E521: Number required after =: {option}"))?;
+                        continue;
                     }
-                    VimColonQueue::Execute => {
-                        let Ok(mapped) = Self::map_string_to_queue_vec(&queue) else {
-                            self.command_status_line("Invalid command");
-                            queue.clear();
-                            continue;
-                        };
-                        // execute action
-                        return self.eval_colon_queue(&mapped);
+                    Some(("foldmethod" | "fdm", name)) => {
+                        fold_method = FoldMethod::parse(name)
+                            .ok_or_else(|| format!("E474: Invalid argument: {option}"))?;
+                        continue;
                     }
-                    VimColonQueue::Resize(size) => self.resize(size),
-                    VimColonQueue::Other => continue,
+                    _ => return Err(format!("E518: Unknown option: {option}")),
                 },
-                Err(_) => continue,
+            };
+        }
+        self.screen_offset.gutter.numbers = numbers;
+        self.buffer.filetype = filetype;
+        self.buffer.autoread = autoread;
+        self.buffer.read_only = read_only;
+        if fold_method != self.buffer.folds.method {
+            // the folds of the new method start closed, as with a foldlevel of 0
+            self.buffer.folds.method = fold_method;
+            self.buffer.folds.clear();
+            self.buffer.refresh_folds();
+            self.buffer.folds.set_all(true);
+            self.resolve_displacement();
+        }
+        if wrap != self.screen_offset.wrap {
+            self.screen_offset.wrap = wrap;
+            self.screen_offset.row = 0;
+            self.resolve_displacement();
+        }
+        Ok(())
+    }
+
+    fn ex_save_as(&mut self, command: &ExCommand) -> Result<(), String> {
+        if command.args.is_empty() {
+            return Err("E471: Argument required".into());
+        }
+        if !command.bang && Path::new(&command.args).exists() {
+            return Err("E13: File exists (add ! to override)".into());
+        }
+        self.buffer
+            .write_to(&command.args)
+            .map_err(|e| format!("E212: Can't open file for writing: {e}"))?;
+        // the swap file belongs to the old name
+        self.buffer.discard_swap();
+        self.buffer.assume_file_name(command.args.clone());
+        self.buffer.read_only = false;
+        self.buffer.mark_saved();
+        // the server knows the document by its old name
+        let command = self
+            .buffer
+            .lsp
+            .take()
+            .map(|client| client.command().to_string());
+        let _ = self.buffer.attach_server(command);
+        Ok(())
+    }
+
+    /// :m and :t, moving removes the source lines while copying keeps them
+    fn ex_transfer(
+        &mut self,
+        command: &ExCommand,
+        edits: &mut Vec<LineEdit>,
+        remove: bool,
+    ) -> Result<ContinueState, String> {
+        let (start, end) = self.command_lines(command)?;
+        let destination =
+            ExCommand::parse_address(&command.args)?.resolve(&self.range_context())?;
+        let count = end.saturating_sub(start).saturating_add(1);
+        // the destination is a 1 based line to insert after
+        // which is the same as the 0 based index to insert at
+        let index = if remove {
+            if (start.saturating_add(1)..=end).contains(&destination) {
+                return Err("E134: Cannot move a range of lines into itself".into());
+            }
+            let lines = self.buffer.delete_lines(start, end);
+            edits.push(LineEdit::Removed { start, count });
+            let index = if destination > end {
+                destination.saturating_sub(count)
+            } else {
+                destination
+            };
+            self.buffer.insert_lines(index, lines);
+            index
+        } else {
+            let lines = self.buffer.text[start..=end].to_vec();
+            self.buffer.insert_lines(destination, lines);
+            destination
+        };
+        edits.push(LineEdit::Added {
+            start: index,
+            count,
+        });
+        self.cursor_position.height = index.saturating_add(count).saturating_sub(1);
+        self.clamp_cursor();
+        Ok(ContinueState::ContinueVim)
+    }
+
+    fn ex_normal(
+        &mut self,
+        command: &ExCommand,
+        edits: &mut Vec<LineEdit>,
+    ) -> Result<ContinueState, String> {
+        if command.args.is_empty() {
+            return Err("E471: Argument required".into());
+        }
+        let (start, end) = self.command_lines(command)?;
+        let mut line = start;
+        let mut last = end;
+        while line <= last && line < self.buffer.len() {
+            let len_before = self.buffer.len();
+            self.cursor_position.height = line;
+            self.cursor_position.width = 0;
+            self.execute_normal(&command.args);
+            if let Some(edit) = Self::length_edit(line, len_before, self.buffer.len()) {
+                edits.push(edit);
+                last = edit.adjust(last).unwrap_or(line);
+            }
+            line = line.saturating_add(1);
+        }
+        self.clamp_cursor();
+        Ok(ContinueState::ContinueVim)
+    }
+
+    fn ex_write(&mut self, command: &ExCommand) -> Result<(), String> {
+        if let Some(range) = command.range {
+            if command.args.is_empty() {
+                return Err("E140: Use ! to write partial buffer".into());
+            }
+            let (start, end) = range.resolve(&self.range_context())?;
+            return self
+                .buffer
+                .write_range(&command.args, start, end)
+                .map_err(|e| format!("E212: Can't open file for writing: {e}"));
+        }
+        if !command.args.is_empty() && self.buffer.filename.is_some() {
+            // writing to another file leaves the buffer name alone
+            return self
+                .buffer
+                .write_to(&command.args)
+                .map_err(|e| format!("E212: Can't open file for writing: {e}"));
+        }
+        if !command.args.is_empty() {
+            self.buffer.assume_file_name(command.args.clone());
+        }
+        let Some(filename) = self.buffer.filename.clone() else {
+            return Err("E32: No file name".into());
+        };
+        if !command.bang && self.buffer.read_only {
+            return Err("E45: 'readonly' option is set (add ! to override)".into());
+        }
+        if !command.bang && matches!(self.buffer.disk_change(), DiskChange::Changed(..)) {
+            return Err(
+                "W12: The file changed on disk since it was read (add ! to override)".into(),
+            );
+        }
+        self.buffer
+            .write_to(&filename)
+            .map_err(|e| format!("E212: Can't open file for writing: {e}"))?;
+        self.buffer.read_only = false;
+        self.buffer.mark_saved();
+        Ok(())
+    }
+
+    fn ex_edit(&mut self, command: &ExCommand) -> Result<(), String> {
+        if !command.bang && !self.buffer.is_saved && !self.buffer.is_empty() {
+            return Err("E37: No write since last change (add ! to override)".into());
+        }
+        let mut filename = if command.args.is_empty() {
+            // :e! reverts to the file on disk
+            self.buffer.filename.clone().ok_or("E32: No file name")?
+        } else {
+            command.args.clone()
+        };
+        if Path::new(&filename).is_dir() {
+            let mut explorer = Explorer::new(Path::new(&filename), self.theme.selection)
+                .map_err(|err| format!("{filename}: {err}"))?;
+            match explorer.run(&mut self.size) {
+                Ok(Exit::Open(path)) => filename = path.to_string_lossy().into_owned(),
+                Ok(Exit::Quit) => return Ok(()),
+                Err(err) => return Err(err.to_string()),
+            }
+        }
+        // leaving a buffer on purpose drops its changes
+        self.buffer.discard_swap();
+        let (autoread, read_only) = (self.buffer.autoread, self.buffer.read_only);
+        // a server started by hand keeps serving the files opened after it
+        let server = self
+            .buffer
+            .lsp
+            .as_ref()
+            .map(|client| client.command().to_string());
+        *self.buffer = Buffer::open(&filename, self.size.height);
+        self.buffer.autoread = autoread;
+        // a file that could not be read stays read only
+        self.buffer.read_only |= read_only;
+        let _ = self.buffer.attach_server(server);
+        self.cursor_position = Position::default();
+        self.screen_offset = ScreenOffset::default();
+        self.last_selection = None;
+        Ok(())
+    }
+
+    /// :g/pattern/cmd and :v/pattern/cmd, the pattern is plain text like a / search
+    fn execute_global(
+        &mut self,
+        command: &ExCommand,
+        invert: bool,
+    ) -> Result<ContinueState, String> {
+        let mut chars = command.args.chars();
+        let Some(delimiter) = chars.next() else {
+            return Err("E35: No previous regular expression".into());
+        };
+        if delimiter.is_alphanumeric() || matches!(delimiter, '\\' | '"' | '|') {
+            return Err("E146: Regular expressions can't be delimited by letters".into());
+        }
+        let rest = chars.as_str();
+        let (pattern, sub_command) = match rest.find(delimiter) {
+            Some(i) => (&rest[..i], &rest[i.saturating_add(delimiter.len_utf8())..]),
+            None => (rest, ""),
+        };
+        if pattern.is_empty() {
+            return Err("E35: No previous regular expression".into());
+        }
+        let sub_command = ExCommand::parse(sub_command)?;
+        if matches!(
+            sub_command.kind,
+            Some(ExCommandKind::Global | ExCommandKind::VGlobal) | None
+        ) {
+            return Err("E147: Cannot do :global recursive".into());
+        }
+
+        let (start, end) = match command.range {
+            Some(_) => self.command_lines(command)?,
+            None if self.buffer.is_empty() => return Err("--No lines in buffer--".into()),
+            None => (0, self.buffer.len().saturating_sub(1)),
+        };
+        // mark every line first, then run the command on each marked line
+        let mut marks: Vec<usize> = (start..=end)
+            .filter(|i| self.buffer.text[*i].raw_string.contains(pattern) != invert)
+            .collect();
+        if marks.is_empty() {
+            return Err(format!("E486: Pattern not found: {pattern}"));
+        }
+
+        let mut index = 0;
+        while index < marks.len() {
+            let line = marks[index];
+            index = index.saturating_add(1);
+            if line >= self.buffer.len() {
+                continue;
             }
-            self.command_status_line(&queue);
+            self.cursor_position.height = line;
+            self.cursor_position.width = 0;
+            let mut edits = Vec::new();
+            let mut line_command = sub_command.clone();
+            if line_command.range.is_none() {
+                line_command.range = Some(ExRange::Single(LineSpecifier {
+                    address: Address::Current,
+                    offset: 0,
+                }));
+            }
+            if matches!(
+                self.execute_ex(&line_command, &mut edits)?,
+                ContinueState::ExitSession
+            ) {
+                return Ok(ContinueState::ExitSession);
+            }
+            // keep the remaining marks on the lines they were set on
+            let remaining: Vec<usize> = marks[index..]
+                .iter()
+                .filter_map(|mark| edits.iter().try_fold(*mark, |mark, edit| edit.adjust(mark)))
+                .collect();
+            marks.truncate(index);
+            marks.extend(remaining);
         }
+        self.clamp_cursor();
+        Ok(ContinueState::ContinueVim)
     }
 
-    fn command_status_line(&self, message: &str) {
-        let render =
-            Terminal::render_line(self.size.height.saturating_sub(2), format!(":{message}"));
-        let flush = Terminal::execute();
-        debug_assert!(render.is_ok() & flush.is_ok());
+    fn length_edit(line: usize, before: usize, after: usize) -> Option<LineEdit> {
+        let start = line.saturating_add(1);
+        match after.cmp(&before) {
+            std::cmp::Ordering::Greater => Some(LineEdit::Added {
+                start,
+                count: after.saturating_sub(before),
+            }),
+            std::cmp::Ordering::Less => Some(LineEdit::Removed {
+                start,
+                count: before.saturating_sub(after),
+            }),
+            std::cmp::Ordering::Equal => None,
+        }
     }
 
-    fn eval_colon_queue(&mut self, queue: &[ColonQueueActions]) -> ContinueState {
-        // return true if we are staying in vim mode after executing the command
-        // false if we are ending our terminal session
-        match queue.len() {
-            1 => match queue[0] {
-                ColonQueueActions::Write => {
-                    // execute and stay in vim mode
-                    self.buffer.save();
+    /// runs a string of keys as if they were typed in vim mode
+    /// i, a, A, I, o and O insert the rest of the keys as text
+    fn execute_normal(&mut self, keys: &str) {
+        self.pending_keys = keys
+            .chars()
+            .map(|c| Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)))
+            .collect();
+        self.replaying = true;
+
+        while let Some(event) = self.pending_keys.pop_front() {
+            if let Event::Key(KeyEvent {
+                code: KeyCode::Char(c @ ('i' | 'a' | 'A' | 'I' | 'o' | 'O')),
+                ..
+            }) = event
+            {
+                self.start_insert(c);
+                let text: String = self
+                    .pending_keys
+                    .drain(..)
+                    .filter_map(|event| match event {
+                        Event::Key(KeyEvent {
+                            code: KeyCode::Char(c),
+                            ..
+                        }) => Some(c),
+                        _ => None,
+                    })
+                    .collect();
+                for c in text.chars() {
+                    self.buffer.update_line_insert(&mut self.cursor_position, c);
                 }
-                ColonQueueActions::Quit => {
-                    // exit session
-                    if !self.buffer.is_saved {
-                        self.command_status_line("not saved: ! to override, w: to save");
-                        return ContinueState::ContinueVimPersistError;
+                break;
+            }
+            match VimModeCommands::try_from(event) {
+                Ok(VimModeCommands::ComplexCommand(
+                    command @ (QueueInitCommand::Delete
+                    | QueueInitCommand::Yank
+                    | QueueInitCommand::Indent(_)
+                    | QueueInitCommand::Fold
+                    | QueueInitCommand::PageUp
+                    | QueueInitCommand::PageDown),
+                )) => {
+                    if !matches!(
+                        self.determine_queue_command(&command),
+                        ContinueState::ContinueVim
+                    ) {
+                        break;
                     }
-                    return ContinueState::ExitSession;
                 }
-                ColonQueueActions::Override => {
-                    self.command_status_line("Invalid command");
-                    return ContinueState::ContinueVimPersistError;
-                }
-                ColonQueueActions::Jump(line) => {
-                    // jump to the line
-                    // continue state is continue vim
-                    // figure out where to do the rendering if the cursor moves off screen
-                    return ContinueState::JumpCursor(line);
-                }
-            },
-            2 => {
-                match queue {
-                    [ColonQueueActions::Write, ColonQueueActions::Quit] => {
-                        self.buffer.save();
-                        // exit terminal session
-                        return ContinueState::ExitSession;
+                Ok(command) => {
+                    // anything that is not a motion aborts the key sequence
+                    if self.apply_motion(&command).is_none() {
+                        break;
                     }
-                    [ColonQueueActions::Quit, ColonQueueActions::Override] => {
-                        //exit terminal session
-                        return ContinueState::ExitSession;
-                    }
-                    _ => self.command_status_line("Invalid command!"),
                 }
+                Err(_) => break,
             }
-            _ => self.command_status_line("Invalid command!"),
         }
-        ContinueState::ContinueVim
+        self.pending_keys.clear();
+        self.replaying = false;
     }
 
-    fn map_string_to_queue_vec(string_queue: &str) -> Result<Vec<ColonQueueActions>, String> {
-        if let Ok(line) = string_queue.parse::<usize>() {
-            return Ok(vec![ColonQueueActions::Jump(line)]);
+    fn start_insert(&mut self, key: char) {
+        if matches!(key, 'o' | 'O') {
+            self.buffer.open_line(&mut self.cursor_position, key == 'O');
+            return;
         }
-        let mut res: Vec<ColonQueueActions> = Vec::with_capacity(6);
-        for c in string_queue.chars() {
-            let mapped_val = ColonQueueActions::try_from(c)?;
-            res.push(mapped_val);
+        if self.buffer.is_empty() {
+            return;
+        }
+        let line = &self.buffer.text[self.cursor_position.height];
+        match key {
+            'a' => self.cursor_position.right(1, line.grapheme_len()),
+            'A' => self.cursor_position.snap_right(line.grapheme_len()),
+            'I' => self.cursor_to_first_non_blank(),
+            _ => {}
         }
+    }
 
-        Ok(res)
+    /// moves the cursor to the text of its line, past the indent
+    fn cursor_to_first_non_blank(&mut self) {
+        let Some(line) = self.buffer.text.get(self.cursor_position.height) else {
+            return;
+        };
+        let indent = line
+            .raw_string
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .count();
+        self.cursor_position.set_width(indent);
     }
 
-    fn queue_page_up(&mut self) -> bool {
-        // bool propogates up an invalid complex command
-        let event = Self::wait_for_successful_event();
+    /// keeps the cursor inside the buffer after lines are removed
+    fn clamp_cursor(&mut self) {
+        if self.buffer.is_empty() {
+            self.cursor_position = Position::default();
+            return;
+        }
+        self.cursor_position.height = std::cmp::min(
+            self.cursor_position.height,
+            self.buffer.len().saturating_sub(1),
+        );
+        self.cursor_position.width = std::cmp::min(
+            self.cursor_position.width,
+            self.buffer.text[self.cursor_position.height].grapheme_len(),
+        );
+        self.resolve_displacement();
+    }
+
+    fn queue_page_up(&mut self) -> ContinueState {
+        let event = self.wait_for_successful_event();
         if let Event::Key(KeyEvent { code, .. }) = event {
-            if matches!(code, KeyCode::Char('g')) {
-                //only handling if gg, otherwise skip
-                self.move_and_resolve(Direction::PageUp);
-                true
-            } else {
-                false
+            match code {
+                //only handling gg, gj, gk, gd, gr and gc, otherwise skip
+                KeyCode::Char('g') => {
+                    self.move_and_resolve(Direction::PageUp);
+                    ContinueState::ContinueVim
+                }
+                KeyCode::Char(c @ ('j' | 'k')) => {
+                    self.move_display_row(c == 'j');
+                    ContinueState::ContinueVim
+                }
+                KeyCode::Char('d') => self.goto_definition(),
+                KeyCode::Char('r') => self.find_references(),
+                KeyCode::Char('c') => self.queue_comment(),
+                _ => ContinueState::InvalidCommand,
             }
         } else {
-            false
+            ContinueState::InvalidCommand
         }
     }
 
     fn queue_page_down(&mut self) -> bool {
         // bool propogates up an invalid complex command
-        let event = Self::wait_for_successful_event();
+        let event = self.wait_for_successful_event();
         if let Event::Key(KeyEvent { code, .. }) = event {
             if matches!(code, KeyCode::Char('G')) {
                 // only handling if GG otherwise skip
@@ -505,23 +1355,48 @@ impl VimMode<'_> {
         }
     }
 
+    /// gj and gk, moving by screen rows when lines wrap and by lines otherwise
+    fn move_display_row(&mut self, down: bool) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        if self.screen_offset.wrap {
+            let text_width = self.screen_offset.gutter.text_width(&self.size);
+            wrap::move_display_row(&mut self.cursor_position, self.buffer, text_width, down);
+            self.resolve_displacement();
+        } else if down {
+            self.move_and_resolve(Direction::Down);
+        } else {
+            self.move_and_resolve(Direction::Up);
+        }
+    }
+
     #[inline]
     fn move_and_resolve(&mut self, dir: Direction) -> usize {
         dir.move_cursor(&mut self.cursor_position, self.buffer);
         self.resolve_displacement()
     }
 
-    fn wait_for_successful_event() -> Event {
+    fn wait_for_successful_event(&mut self) -> Event {
         // we are waiting on a single event
         // so wait for an ok event
+        // keys queued by :normal come first, an empty queue cancels the command
+        if self.replaying {
+            return self
+                .pending_keys
+                .pop_front()
+                .unwrap_or(Event::Key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)));
+        }
         loop {
-            let Ok(read_event) = read() else { continue };
-            return read_event;
+            match read() {
+                Ok(Event::FocusGained | Event::FocusLost) | Err(_) => {}
+                Ok(read_event) => return read_event,
+            }
         }
     }
 
     fn queue_delete(&mut self) -> bool {
-        let event = Self::wait_for_successful_event();
+        let event = self.wait_for_successful_event();
         if let Event::Key(KeyEvent { code, .. }) = event {
             match code {
                 KeyCode::Char('w') => {
@@ -552,8 +1427,138 @@ impl VimMode<'_> {
         true
     }
 
+    /// the lines an operator works on: the cursor line when its key is typed again,
+    /// or with j, k, G or gg the lines to where they go
+    fn motion_lines(&mut self, repeat: char) -> Option<RangeInclusive<usize>> {
+        if self.buffer.is_empty() {
+            return None;
+        }
+        let Event::Key(KeyEvent {
+            code: KeyCode::Char(key),
+            ..
+        }) = self.wait_for_successful_event()
+        else {
+            return None;
+        };
+        let line = self.cursor_position.height;
+        let last = self.buffer.len().saturating_sub(1);
+        // a closed fold counts as one line, with all the lines it holds
+        let folds = &self.buffer.folds;
+        let end_of = |line: usize| folds.closed_at(line).map_or(line, |fold| fold.end);
+        let here = end_of(line);
+        let below = end_of(std::cmp::min(folds.next_visible(line), last));
+        let above = folds.head(line.saturating_sub(1));
+        match key {
+            'j' => Some(line..=std::cmp::max(below, here)),
+            'k' => Some(above..=here),
+            'G' => Some(line..=last),
+            'g' => match self.wait_for_successful_event() {
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('g'),
+                    ..
+                }) => Some(0..=here),
+                _ => None,
+            },
+            _ if key == repeat => Some(line..=here),
+            _ => None,
+        }
+    }
+
+    /// >>, << and == on the cursor line, or > < and = with a motion
+    fn queue_indent(&mut self, shift: Shift) -> bool {
+        let Some(lines) = self.motion_lines(shift.key()) else {
+            return false;
+        };
+        self.indent_lines(lines, shift);
+        true
+    }
+
+    /// zf with a motion folds the lines it goes over, zo, zc and za open and close the fold
+    /// at the cursor, zR and zM all of them, zd and zE take manual folds away
+    fn queue_fold(&mut self) -> ContinueState {
+        let Event::Key(KeyEvent {
+            code: KeyCode::Char(key),
+            ..
+        }) = self.wait_for_successful_event()
+        else {
+            return ContinueState::InvalidCommand;
+        };
+        let manual = self.buffer.folds.method == FoldMethod::Manual;
+        match key {
+            'f' if !manual => {
+                return ContinueState::Message(
+                    "E350: Cannot create fold with current 'foldmethod'".into(),
+                )
+            }
+            'd' | 'E' if !manual => {
+                return ContinueState::Message(
+                    "E351: Cannot delete fold with current 'foldmethod'".into(),
+                )
+            }
+            _ => {}
+        }
+        self.buffer.refresh_folds();
+        if key == 'f' {
+            let Some(lines) = self.motion_lines('f') else {
+                return ContinueState::InvalidCommand;
+            };
+            self.buffer.folds.create(*lines.start(), *lines.end());
+        }
+        let line = self.cursor_position.height;
+        let folds = &mut self.buffer.folds;
+        let found = match key {
+            'f' => true,
+            'o' => folds.open(line),
+            'c' => folds.close(line),
+            'a' => folds.toggle(line),
+            'R' | 'M' => {
+                folds.set_all(key == 'M');
+                !folds.is_empty()
+            }
+            'd' => folds.delete(line),
+            'E' => {
+                folds.clear();
+                true
+            }
+            _ => return ContinueState::InvalidCommand,
+        };
+        if !found {
+            return ContinueState::Message("E490: No fold found".into());
+        }
+        // a closed fold is shown on its first line, the cursor goes there
+        self.cursor_position.height = self.buffer.folds.head(self.cursor_position.height);
+        self.cursor_position.width = std::cmp::min(
+            self.cursor_position.width,
+            self.buffer.text[self.cursor_position.height].grapheme_len(),
+        );
+        self.resolve_displacement();
+        ContinueState::ContinueVim
+    }
+
+    /// gcc comments the cursor line out or back in, gc with a motion the lines it goes over
+    fn queue_comment(&mut self) -> ContinueState {
+        let Some(lines) = self.motion_lines('c') else {
+            return ContinueState::InvalidCommand;
+        };
+        let first = *lines.start();
+        if !self.buffer.toggle_comment(lines) {
+            return ContinueState::Message("No comments for this file type".into());
+        }
+        self.cursor_position.height = first;
+        self.cursor_to_first_non_blank();
+        ContinueState::ContinueVim
+    }
+
+    /// indents lines and puts the cursor on the text of the first one, as vim does
+    fn indent_lines(&mut self, lines: RangeInclusive<usize>, shift: Shift) {
+        let first = *lines.start();
+        self.buffer.indent_lines(lines, shift);
+        self.cursor_position.height = first;
+        self.cursor_to_first_non_blank();
+    }
+
     fn queue_yank(&mut self) -> bool {
-        let event = Self::wait_for_successful_event();
+        let event = self.wait_for_successful_event();
         if let Event::Key(KeyEvent { code, .. }) = event {
             let copy_string = match code {
                 KeyCode::Char('w') => {
@@ -578,8 +1583,9 @@ impl VimMode<'_> {
                 _ => return false,
             };
             if !copy_string.is_empty() {
-                let res = ClipboardUtils::copy_text_to_clipboard(copy_string);
-                debug_assert!(res.is_ok());
+                if let Err(err) = ClipboardUtils::copy_text_to_clipboard(copy_string) {
+                    messages::error(format!("Clipboard: {err}"));
+                }
             }
         } else {
             return false;
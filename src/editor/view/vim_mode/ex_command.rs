@@ -0,0 +1,684 @@
+// parser for the text typed after ':' in vim mode
+// a command line is made of an optional range, a command name, an optional bang and arguments
+// ie ":'<,'>sort!" or ":10,20m0" or ":w other.txt"
+
+/// a single line reference in a range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Address {
+    Absolute(usize), // 1 based line number as typed
+    Current,         // .
+    Last,            // $
+    Mark(char),      // 'x, only '< and '> are tracked for now
+}
+
+/// an address plus any trailing +n/-n offsets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineSpecifier {
+    pub address: Address,
+    pub offset: isize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExRange {
+    Single(LineSpecifier),
+    Span(LineSpecifier, LineSpecifier),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExCommandKind {
+    Write,
+    Quit,
+    CQuit,
+    WriteQuit,
+    Xit,
+    Edit,
+    SaveAs,
+    Delete,
+    Yank,
+    Move,
+    Copy,
+    Normal,
+    Sort,
+    Global,
+    VGlobal,
+    Set,
+    Shell,      // :!cmd runs it, with a range the lines are filtered through it
+    ShiftRight, // :> indents the lines a level, :>> two and so on
+    ShiftLeft,  // :< takes levels away the same way
+    Read,
+    Rename, // the symbol under the cursor, through the language server
+    Format,
+    Lsp,
+    Colorscheme,
+    Messages,
+}
+
+impl ExCommandKind {
+    fn accepts_range(self) -> bool {
+        !matches!(
+            self,
+            Self::Quit
+                | Self::CQuit
+                | Self::Edit
+                | Self::SaveAs
+                | Self::Set
+                | Self::Rename
+                | Self::Format
+                | Self::Lsp
+                | Self::Colorscheme
+                | Self::Messages
+        )
+    }
+
+    /// commands whose argument is a path, for completion
+    pub fn takes_file(self) -> bool {
+        matches!(
+            self,
+            Self::Write | Self::WriteQuit | Self::Xit | Self::Edit | Self::SaveAs | Self::Read
+        )
+    }
+}
+
+struct CommandEntry {
+    name: &'static str,
+    min_len: usize, // shortest accepted abbreviation
+    kind: ExCommandKind,
+}
+
+// order matters, the first entry the typed name abbreviates wins
+const COMMAND_TABLE: [CommandEntry; 23] = [
+    CommandEntry {
+        name: "write",
+        min_len: 1,
+        kind: ExCommandKind::Write,
+    },
+    CommandEntry {
+        name: "wq",
+        min_len: 2,
+        kind: ExCommandKind::WriteQuit,
+    },
+    CommandEntry {
+        name: "quit",
+        min_len: 1,
+        kind: ExCommandKind::Quit,
+    },
+    CommandEntry {
+        name: "cquit",
+        min_len: 2,
+        kind: ExCommandKind::CQuit,
+    },
+    CommandEntry {
+        name: "xit",
+        min_len: 1,
+        kind: ExCommandKind::Xit,
+    },
+    CommandEntry {
+        name: "edit",
+        min_len: 1,
+        kind: ExCommandKind::Edit,
+    },
+    CommandEntry {
+        name: "saveas",
+        min_len: 3,
+        kind: ExCommandKind::SaveAs,
+    },
+    CommandEntry {
+        name: "read",
+        min_len: 1,
+        kind: ExCommandKind::Read,
+    },
+    CommandEntry {
+        name: "delete",
+        min_len: 1,
+        kind: ExCommandKind::Delete,
+    },
+    CommandEntry {
+        name: "yank",
+        min_len: 1,
+        kind: ExCommandKind::Yank,
+    },
+    CommandEntry {
+        name: "move",
+        min_len: 1,
+        kind: ExCommandKind::Move,
+    },
+    CommandEntry {
+        name: "t",
+        min_len: 1,
+        kind: ExCommandKind::Copy,
+    },
+    CommandEntry {
+        name: "copy",
+        min_len: 2,
+        kind: ExCommandKind::Copy,
+    },
+    CommandEntry {
+        name: "normal",
+        min_len: 4,
+        kind: ExCommandKind::Normal,
+    },
+    CommandEntry {
+        name: "sort",
+        min_len: 3,
+        kind: ExCommandKind::Sort,
+    },
+    CommandEntry {
+        name: "set",
+        min_len: 2,
+        kind: ExCommandKind::Set,
+    },
+    CommandEntry {
+        name: "global",
+        min_len: 1,
+        kind: ExCommandKind::Global,
+    },
+    CommandEntry {
+        name: "vglobal",
+        min_len: 1,
+        kind: ExCommandKind::VGlobal,
+    },
+    CommandEntry {
+        name: "rename",
+        min_len: 3,
+        kind: ExCommandKind::Rename,
+    },
+    CommandEntry {
+        name: "format",
+        min_len: 3,
+        kind: ExCommandKind::Format,
+    },
+    CommandEntry {
+        name: "lsp",
+        min_len: 3,
+        kind: ExCommandKind::Lsp,
+    },
+    CommandEntry {
+        name: "colorscheme",
+        min_len: 4,
+        kind: ExCommandKind::Colorscheme,
+    },
+    CommandEntry {
+        name: "messages",
+        min_len: 3,
+        kind: ExCommandKind::Messages,
+    },
+];
+
+/// names of every ex command, used for completion
+pub fn command_names() -> impl Iterator<Item = &'static str> {
+    COMMAND_TABLE.iter().map(|entry| entry.name)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExCommand {
+    pub range: Option<ExRange>,
+    pub kind: Option<ExCommandKind>, // None when only a range was typed, ie ":10"
+    pub bang: bool,
+    pub args: String,
+}
+
+/// everything needed to turn an address into a line index
+pub struct RangeContext {
+    pub current: usize,                    // 0 based cursor line
+    pub len: usize,                        // number of lines in the buffer
+    pub selection: Option<(usize, usize)>, // 0 based lines of the last highlight
+}
+
+/// line level changes made while executing a command
+/// used to keep the lines marked by :g pointing at the same text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEdit {
+    Removed { start: usize, count: usize },
+    Added { start: usize, count: usize },
+}
+
+impl LineEdit {
+    /// where a line index ends up after this edit, None if the line was removed
+    pub fn adjust(self, line: usize) -> Option<usize> {
+        match self {
+            Self::Removed { start, count } => {
+                if line < start {
+                    Some(line)
+                } else if line < start.saturating_add(count) {
+                    None
+                } else {
+                    Some(line.saturating_sub(count))
+                }
+            }
+            Self::Added { start, count } => {
+                if line < start {
+                    Some(line)
+                } else {
+                    Some(line.saturating_add(count))
+                }
+            }
+        }
+    }
+}
+
+struct Scanner<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Scanner<'_> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos = self.pos.saturating_add(c.len_utf8());
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+        }
+        self.input[start..self.pos].parse::<usize>().ok()
+    }
+
+    fn rest(&self) -> &str {
+        &self.input[self.pos..]
+    }
+
+    fn specifier(&mut self) -> Result<Option<LineSpecifier>, String> {
+        let address = match self.peek() {
+            Some(c) if c.is_ascii_digit() => {
+                Address::Absolute(self.number().ok_or("E16: Invalid range")?)
+            }
+            Some('.') => {
+                self.bump();
+                Address::Current
+            }
+            Some('$') => {
+                self.bump();
+                Address::Last
+            }
+            Some('\'') => {
+                self.bump();
+                match self.bump() {
+                    Some(mark) => Address::Mark(mark),
+                    None => return Err("E20: Mark not set".into()),
+                }
+            }
+            // an offset on its own is relative to the cursor
+            Some('+' | '-') => Address::Current,
+            _ => return Ok(None),
+        };
+
+        let mut offset: isize = 0;
+        while let Some(sign @ ('+' | '-')) = self.peek() {
+            self.bump();
+            let amount = self.number().unwrap_or(1);
+            let amount = isize::try_from(amount).map_err(|_| "E16: Invalid range")?;
+            offset = if sign == '+' {
+                offset.saturating_add(amount)
+            } else {
+                offset.saturating_sub(amount)
+            };
+        }
+        Ok(Some(LineSpecifier { address, offset }))
+    }
+
+    fn range(&mut self) -> Result<Option<ExRange>, String> {
+        if self.peek() == Some('%') {
+            self.bump();
+            return Ok(Some(ExRange::Span(
+                LineSpecifier {
+                    address: Address::Absolute(1),
+                    offset: 0,
+                },
+                LineSpecifier {
+                    address: Address::Last,
+                    offset: 0,
+                },
+            )));
+        }
+        let first = self.specifier()?;
+        if matches!(self.peek(), Some(',' | ';')) {
+            self.bump();
+            let current = LineSpecifier {
+                address: Address::Current,
+                offset: 0,
+            };
+            let second = self.specifier()?.unwrap_or(current);
+            return Ok(Some(ExRange::Span(first.unwrap_or(current), second)));
+        }
+        Ok(first.map(ExRange::Single))
+    }
+}
+
+impl ExCommand {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut scanner = Scanner { input, pos: 0 };
+        // vim allows any number of leading colons and spaces
+        while matches!(scanner.peek(), Some(':' | ' ')) {
+            scanner.bump();
+        }
+        let range = scanner.range()?;
+        scanner.skip_whitespace();
+
+        let name_start = scanner.pos;
+        while scanner.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            scanner.bump();
+        }
+        let name = &input[name_start..scanner.pos];
+
+        if name.is_empty() {
+            let shifts = scanner.rest().trim_end();
+            if let Some(symbol @ ('>' | '<')) = shifts.chars().next() {
+                if shifts.chars().any(|c| c != symbol) {
+                    return Err(format!("E488: Trailing characters: {shifts}"));
+                }
+                return Ok(Self {
+                    range,
+                    kind: Some(if symbol == '>' {
+                        ExCommandKind::ShiftRight
+                    } else {
+                        ExCommandKind::ShiftLeft
+                    }),
+                    bang: false,
+                    args: shifts.to_string(),
+                });
+            }
+            if let Some(shell_command) = scanner.rest().strip_prefix('!') {
+                return Ok(Self {
+                    range,
+                    kind: Some(ExCommandKind::Shell),
+                    bang: false,
+                    args: shell_command.trim().to_string(),
+                });
+            }
+            if !scanner.rest().trim().is_empty() {
+                return Err(format!("E492: Not an editor command: {}", input.trim()));
+            }
+            if range.is_none() {
+                return Err("E471: Argument required".into());
+            }
+            return Ok(Self {
+                range,
+                kind: None,
+                bang: false,
+                args: String::new(),
+            });
+        }
+
+        let kind = Self::lookup(name)?;
+        if range.is_some() && !kind.accepts_range() {
+            return Err("E481: No range allowed".into());
+        }
+        let bang = scanner.peek() == Some('!');
+        if bang {
+            scanner.bump();
+        }
+        // :g/pat/ keeps its delimiter right after the name
+        if !matches!(kind, ExCommandKind::Global | ExCommandKind::VGlobal) {
+            scanner.skip_whitespace();
+        }
+
+        Ok(Self {
+            range,
+            kind: Some(kind),
+            bang,
+            args: scanner.rest().trim_end().to_string(),
+        })
+    }
+
+    fn lookup(name: &str) -> Result<ExCommandKind, String> {
+        COMMAND_TABLE
+            .iter()
+            .find(|entry| name.len() >= entry.min_len && entry.name.starts_with(name))
+            .map(|entry| entry.kind)
+            .ok_or_else(|| format!("E492: Not an editor command: {name}"))
+    }
+
+    /// parses the destination of :m and :t, line 0 means above the first line
+    pub fn parse_address(input: &str) -> Result<LineSpecifier, String> {
+        let mut scanner = Scanner { input, pos: 0 };
+        scanner.skip_whitespace();
+        let spec = scanner.specifier()?.ok_or("E14: Invalid address")?;
+        scanner.skip_whitespace();
+        if !scanner.rest().is_empty() {
+            return Err("E488: Trailing characters".into());
+        }
+        Ok(spec)
+    }
+}
+
+impl LineSpecifier {
+    /// resolves to a 1 based line number, 0 is allowed here and checked by the caller
+    pub fn resolve(&self, ctx: &RangeContext) -> Result<usize, String> {
+        let base = match self.address {
+            Address::Absolute(line) => line,
+            Address::Current => ctx.current.saturating_add(1),
+            Address::Last => ctx.len,
+            Address::Mark('<') => match ctx.selection {
+                Some((start, _)) => start.saturating_add(1),
+                None => return Err("E20: Mark not set".into()),
+            },
+            Address::Mark('>') => match ctx.selection {
+                Some((_, end)) => end.saturating_add(1),
+                None => return Err("E20: Mark not set".into()),
+            },
+            Address::Mark(_) => return Err("E20: Mark not set".into()),
+        };
+        let line = base
+            .checked_add_signed(self.offset)
+            .ok_or("E16: Invalid range")?;
+        if line > ctx.len {
+            return Err("E16: Invalid range".into());
+        }
+        Ok(line)
+    }
+}
+
+impl ExRange {
+    /// resolves to a 0 based inclusive span of lines, swapping backwards ranges
+    pub fn resolve(&self, ctx: &RangeContext) -> Result<(usize, usize), String> {
+        let (start, end) = match self {
+            Self::Single(spec) => {
+                let line = spec.resolve(ctx)?;
+                (line, line)
+            }
+            Self::Span(first, second) => (first.resolve(ctx)?, second.resolve(ctx)?),
+        };
+        if start == 0 || end == 0 {
+            return Err("E16: Invalid range".into());
+        }
+        let (start, end) = if start > end {
+            (end, start)
+        } else {
+            (start, end)
+        };
+        Ok((start.saturating_sub(1), end.saturating_sub(1)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(address: Address, offset: isize) -> LineSpecifier {
+        LineSpecifier { address, offset }
+    }
+
+    #[test]
+    fn parse_set_options() {
+        let cmd = ExCommand::parse("se nu rnu").unwrap();
+        assert_eq!(cmd.kind, Some(ExCommandKind::Set));
+        assert_eq!(cmd.args, "nu rnu");
+        assert!(ExCommand::parse("1,2set nu").is_err());
+    }
+
+    #[test]
+    fn parse_write_with_file() {
+        let cmd = ExCommand::parse("w other.txt").unwrap();
+        assert_eq!(cmd.kind, Some(ExCommandKind::Write));
+        assert_eq!(cmd.args, "other.txt");
+        assert!(cmd.range.is_none());
+        assert!(!cmd.bang);
+    }
+
+    #[test]
+    fn parse_abbreviations() {
+        assert_eq!(
+            ExCommand::parse("q!").unwrap(),
+            ExCommand {
+                range: None,
+                kind: Some(ExCommandKind::Quit),
+                bang: true,
+                args: String::new(),
+            }
+        );
+        assert_eq!(
+            ExCommand::parse("wq").unwrap().kind,
+            Some(ExCommandKind::WriteQuit)
+        );
+        assert_eq!(
+            ExCommand::parse("sav f").unwrap().kind,
+            Some(ExCommandKind::SaveAs)
+        );
+        assert_eq!(
+            ExCommand::parse("co0").unwrap().kind,
+            Some(ExCommandKind::Copy)
+        );
+        assert_eq!(
+            ExCommand::parse("norm x").unwrap().kind,
+            Some(ExCommandKind::Normal)
+        );
+        assert!(ExCommand::parse("no x").is_err());
+        assert!(ExCommand::parse("s").is_err());
+    }
+
+    #[test]
+    fn parse_ranges() {
+        let cmd = ExCommand::parse("%sort!").unwrap();
+        assert_eq!(
+            cmd.range,
+            Some(ExRange::Span(
+                spec(Address::Absolute(1), 0),
+                spec(Address::Last, 0)
+            ))
+        );
+        assert!(cmd.bang);
+
+        let cmd = ExCommand::parse("'<,'>d").unwrap();
+        assert_eq!(
+            cmd.range,
+            Some(ExRange::Span(
+                spec(Address::Mark('<'), 0),
+                spec(Address::Mark('>'), 0)
+            ))
+        );
+
+        let cmd = ExCommand::parse(".,$-2y").unwrap();
+        assert_eq!(
+            cmd.range,
+            Some(ExRange::Span(
+                spec(Address::Current, 0),
+                spec(Address::Last, -2)
+            ))
+        );
+
+        let cmd = ExCommand::parse("10,20m0").unwrap();
+        assert_eq!(cmd.kind, Some(ExCommandKind::Move));
+        assert_eq!(cmd.args, "0");
+
+        let cmd = ExCommand::parse("42").unwrap();
+        assert_eq!(cmd.kind, None);
+        assert_eq!(
+            cmd.range,
+            Some(ExRange::Single(spec(Address::Absolute(42), 0)))
+        );
+    }
+
+    #[test]
+    fn parse_shell_commands() {
+        let cmd = ExCommand::parse("%!jq .").unwrap();
+        assert_eq!(cmd.kind, Some(ExCommandKind::Shell));
+        assert_eq!(
+            cmd.range,
+            Some(ExRange::Span(
+                spec(Address::Absolute(1), 0),
+                spec(Address::Last, 0)
+            ))
+        );
+        assert_eq!(cmd.args, "jq .");
+        let cmd = ExCommand::parse("! ls -l").unwrap();
+        assert_eq!((cmd.range, cmd.args.as_str()), (None, "ls -l"));
+        let cmd = ExCommand::parse("r !date").unwrap();
+        assert_eq!(cmd.kind, Some(ExCommandKind::Read));
+        assert_eq!(cmd.args, "!date");
+    }
+
+    #[test]
+    fn parse_shifts() {
+        let cmd = ExCommand::parse("'<,'>>>").unwrap();
+        assert_eq!(cmd.kind, Some(ExCommandKind::ShiftRight));
+        assert_eq!(
+            cmd.range,
+            Some(ExRange::Span(
+                spec(Address::Mark('<'), 0),
+                spec(Address::Mark('>'), 0)
+            ))
+        );
+        assert_eq!(cmd.args, ">>");
+        let cmd = ExCommand::parse("<").unwrap();
+        assert_eq!(
+            (cmd.kind, cmd.range),
+            (Some(ExCommandKind::ShiftLeft), None)
+        );
+        assert!(ExCommand::parse("><").is_err());
+    }
+
+    #[test]
+    fn parse_global_keeps_delimiter() {
+        let cmd = ExCommand::parse("g/fn main/d").unwrap();
+        assert_eq!(cmd.kind, Some(ExCommandKind::Global));
+        assert_eq!(cmd.args, "/fn main/d");
+        let cmd = ExCommand::parse("g!/x/d").unwrap();
+        assert!(cmd.bang);
+    }
+
+    #[test]
+    fn resolve_ranges() {
+        let ctx = RangeContext {
+            current: 4,
+            len: 10,
+            selection: Some((2, 6)),
+        };
+        let resolve = |input: &str| {
+            ExCommand::parse(input)
+                .unwrap()
+                .range
+                .unwrap()
+                .resolve(&ctx)
+        };
+        assert_eq!(resolve("%d"), Ok((0, 9)));
+        assert_eq!(resolve(".,+2d"), Ok((4, 6)));
+        assert_eq!(resolve("'<,'>d"), Ok((2, 6)));
+        assert_eq!(resolve("8,3d"), Ok((2, 7)));
+        assert!(resolve("11d").is_err());
+        assert!(resolve("0d").is_err());
+    }
+
+    #[test]
+    fn line_edit_adjust() {
+        let removed = LineEdit::Removed { start: 3, count: 2 };
+        assert_eq!(removed.adjust(2), Some(2));
+        assert_eq!(removed.adjust(4), None);
+        assert_eq!(removed.adjust(7), Some(5));
+        let added = LineEdit::Added { start: 3, count: 2 };
+        assert_eq!(added.adjust(3), Some(5));
+        assert_eq!(added.adjust(1), Some(1));
+    }
+}
@@ -0,0 +1,226 @@
+// the vim mode commands answered by the language server
+// gd jumps to a definition, gr lists references, K shows a hover
+// :rename, :format and :lsp to look at, start and stop the server
+use super::ex_command::{ExCommand, ExCommandKind};
+use super::{ContinueState, VimMode};
+use crate::editor::screen::Screen;
+use crate::editor::view::lsp::{self, ui, Location};
+use crate::editor::view::messages;
+use crate::editor::view::wrap;
+use std::collections::HashMap;
+use std::fs;
+
+impl VimMode<'_> {
+    pub(super) fn goto_definition(&mut self) -> ContinueState {
+        let position = self.cursor_position;
+        let found = self
+            .buffer
+            .lsp_client()
+            .and_then(|(client, lines)| client.definition(lines, &position));
+        match found {
+            Ok(locations) => match locations.first() {
+                Some(location) => self.jump_to(location),
+                None => ContinueState::Message("No definition found".into()),
+            },
+            Err(message) => ContinueState::Message(message),
+        }
+    }
+
+    pub(super) fn find_references(&mut self) -> ContinueState {
+        let position = self.cursor_position;
+        let found = self
+            .buffer
+            .lsp_client()
+            .and_then(|(client, lines)| client.references(lines, &position));
+        let locations = match found {
+            Ok(locations) if locations.is_empty() => {
+                return ContinueState::Message("No references found".into());
+            }
+            Ok(locations) => locations,
+            Err(message) => return ContinueState::Message(message),
+        };
+        let items = self.reference_items(&locations);
+        let title = format!("{} references", locations.len());
+        let hint = "Enter = jump | Esc = back | j/k = move";
+        match ui::pick(&mut self.size, &title, &items, hint, self.theme.selection) {
+            Ok(Some(index)) => self.jump_to(&locations[index]),
+            Ok(None) => {
+                self.resolve_displacement();
+                ContinueState::ContinueVim
+            }
+            Err(err) => ContinueState::Message(err.to_string()),
+        }
+    }
+
+    /// a line per reference, the file, the line number and the text there
+    fn reference_items(&self, locations: &[Location]) -> Vec<String> {
+        let mut files: HashMap<&std::path::Path, Vec<String>> = HashMap::new();
+        locations
+            .iter()
+            .map(|location| {
+                let is_open = self
+                    .buffer
+                    .lsp
+                    .as_ref()
+                    .is_some_and(|client| client.is_document(&location.path));
+                let text = if is_open {
+                    self.buffer.text.get(location.line).map(ToString::to_string)
+                } else {
+                    files
+                        .entry(&location.path)
+                        .or_insert_with(|| {
+                            fs::read_to_string(&location.path)
+                                .map(|text| text.lines().map(str::to_string).collect())
+                                .unwrap_or_default()
+                        })
+                        .get(location.line)
+                        .cloned()
+                };
+                format!(
+                    "{}:{}: {}",
+                    lsp::display_path(&location.path),
+                    location.line.saturating_add(1),
+                    text.unwrap_or_default().trim()
+                )
+            })
+            .collect()
+    }
+
+    /// moves the cursor to a location, opening its file when it is another one
+    fn jump_to(&mut self, location: &Location) -> ContinueState {
+        let is_open = self
+            .buffer
+            .lsp
+            .as_ref()
+            .is_some_and(|client| client.is_document(&location.path));
+        if !is_open {
+            let command = ExCommand {
+                range: None,
+                kind: Some(ExCommandKind::Edit),
+                bang: false,
+                args: lsp::display_path(&location.path),
+            };
+            if let Err(message) = self.ex_edit(&command) {
+                return ContinueState::Message(message);
+            }
+        }
+        self.cursor_position.height = location.line;
+        self.cursor_position.width = self
+            .buffer
+            .text
+            .get(location.line)
+            .map_or(0, |line| line.grapheme_column(location.character));
+        self.cursor_position.max_width = self.cursor_position.width;
+        self.clamp_cursor();
+        ContinueState::ContinueVim
+    }
+
+    /// shows the hover text next to the cursor until a key is pressed
+    pub(super) fn hover(&mut self) -> ContinueState {
+        let position = self.cursor_position;
+        let found = self
+            .buffer
+            .lsp_client()
+            .and_then(|(client, lines)| client.hover(lines, &position));
+        let text = match found {
+            Ok(Some(text)) => text,
+            Ok(None) => return ContinueState::Message("No information available".into()),
+            Err(message) => return ContinueState::Message(message),
+        };
+        let cursor = wrap::screen_position(
+            &self.cursor_position,
+            &self.screen_offset,
+            self.buffer,
+            &self.size,
+        );
+        ui::draw_hover(&text, cursor, &self.size, self.theme.popup);
+        Screen::move_cursor_to(cursor);
+        if let Err(err) = Screen::present() {
+            messages::error(err.to_string());
+            return ContinueState::ContinueVim;
+        }
+        // any key puts the text back
+        self.wait_for_successful_event();
+        ContinueState::ContinueVim
+    }
+
+    /// :rename newname, the open file is changed in the buffer and other files on disk
+    pub(super) fn ex_rename(&mut self, command: &ExCommand) -> Result<ContinueState, String> {
+        let new_name = command.args.trim();
+        if new_name.is_empty() {
+            return Err("E471: Argument required".into());
+        }
+        let position = self.cursor_position;
+        let (client, lines) = self.buffer.lsp_client()?;
+        let changes = client.rename(lines, &position, new_name)?;
+        let (open, others): (Vec<_>, Vec<_>) = changes
+            .into_iter()
+            .partition(|(path, _)| client.is_document(path));
+        let count: usize = open
+            .iter()
+            .chain(&others)
+            .map(|(_, edits)| edits.len())
+            .sum();
+        for (path, edits) in &others {
+            lsp::edit_file(path, edits)?;
+        }
+        for (_, edits) in &open {
+            self.buffer.apply_edits(edits);
+        }
+        self.clamp_cursor();
+        Ok(ContinueState::Message(format!(
+            "Renamed {count} occurrences in {} files",
+            open.len().saturating_add(others.len())
+        )))
+    }
+
+    pub(super) fn ex_format(&mut self) -> Result<ContinueState, String> {
+        let (client, _) = self.buffer.lsp_client()?;
+        let edits = client.formatting()?;
+        self.buffer.apply_edits(&edits);
+        self.clamp_cursor();
+        Ok(ContinueState::ContinueVim)
+    }
+
+    /// :lsp says which server runs, :lsp start [command] and :lsp stop
+    pub(super) fn ex_lsp(&mut self, command: &ExCommand) -> Result<ContinueState, String> {
+        let args = command.args.trim();
+        let (action, rest) = args.split_once(' ').unwrap_or((args, ""));
+        match action {
+            "" => Ok(ContinueState::Message(match &self.buffer.lsp {
+                Some(client) => format!(
+                    "{} | {}",
+                    client.command(),
+                    self.buffer.lsp_summary(self.cursor_position.height)
+                ),
+                None => "No language server".into(),
+            })),
+            "start" => {
+                if self.buffer.filename.is_none() {
+                    return Err("E32: No file name".into());
+                }
+                let rest = rest.trim();
+                let started = if rest.is_empty() {
+                    self.buffer.attach_server(None)?
+                } else {
+                    // the command given wins over the configured one
+                    let filename = self.buffer.filename.clone().unwrap_or_default();
+                    self.buffer.lsp = None;
+                    self.buffer.lsp = Some(lsp::Client::start(rest, &filename, &self.buffer.text)?);
+                    true
+                };
+                if !started {
+                    return Err("No language server configured for this file, see lsp.conf".into());
+                }
+                self.resolve_displacement();
+                Ok(ContinueState::ContinueVim)
+            }
+            "stop" => {
+                self.buffer.lsp = None;
+                self.resolve_displacement();
+                Ok(ContinueState::ContinueVim)
+            }
+            _ => Err(format!("E475: Invalid argument: {args}")),
+        }
+    }
+}
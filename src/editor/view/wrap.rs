@@ -0,0 +1,455 @@
+// soft wrapping, a buffer line is shown over as many screen rows as it needs
+// positions still address graphemes in buffer lines, this only maps them to and from display rows
+// the top of the view is the display row `offset.row` of the buffer line `offset.height`
+// lines hidden in a closed fold take no row and the fold's first line takes one for its summary
+use super::buffer::Buffer;
+use super::line::Line;
+use crate::editor::terminal::{Position, ScreenOffset, ScreenPosition, Size};
+use std::ops::Range;
+
+/// drawn at the start of every row continuing a wrapped line
+pub const CONTINUATION: &str = "↪ ";
+const CONTINUATION_WIDTH: usize = 2;
+
+/// the grapheme ranges of a line shown on each display row
+/// breaks after the last whitespace that fits, or mid word when a word is wider than a row
+pub fn line_rows(line: &Line, width: usize) -> Vec<Range<usize>> {
+    let first_width = std::cmp::max(width, 1);
+    let rest_width = std::cmp::max(width.saturating_sub(CONTINUATION_WIDTH), 1);
+    let mut rows = Vec::new();
+    let mut start = 0_usize;
+    let mut columns = 0_usize;
+    let mut last_break: Option<usize> = None;
+
+    for (i, fragment) in line.string.iter().enumerate() {
+        let limit = if rows.is_empty() {
+            first_width
+        } else {
+            rest_width
+        };
+        if columns.saturating_add(fragment.width()) > limit && i > start {
+            let end = match last_break {
+                Some(end) if end > start => end,
+                _ => i,
+            };
+            rows.push(start..end);
+            start = end;
+            columns = line.range_width(start..i);
+            last_break = None;
+            if columns.saturating_add(fragment.width()) > rest_width && i > start {
+                // the carried word does not fit the narrower row either
+                rows.push(start..i);
+                start = i;
+                columns = 0;
+            }
+        }
+        columns = columns.saturating_add(fragment.width());
+        if fragment.grapheme.chars().all(char::is_whitespace) {
+            last_break = Some(i.saturating_add(1));
+        }
+    }
+    rows.push(start..line.len());
+    rows
+}
+
+/// the display row of a line holding the grapheme at width
+/// the end of a row is the start of the next, so it belongs to the next row
+#[inline]
+pub fn row_of(rows: &[Range<usize>], width: usize) -> usize {
+    rows.iter().rposition(|row| row.start <= width).unwrap_or(0)
+}
+
+/// the screen columns before the text of a display row
+#[inline]
+fn row_indent(row: usize) -> usize {
+    if row == 0 {
+        0
+    } else {
+        CONTINUATION_WIDTH
+    }
+}
+
+fn rows_at(buffer: &Buffer, line: usize, width: usize) -> Vec<Range<usize>> {
+    if buffer.folds.hidden(line) {
+        return Vec::new();
+    }
+    if buffer.folds.closed_at(line).is_some() {
+        return vec![Range::default()];
+    }
+    // past the end of the buffer counts as one empty row
+    line_rows(buffer.text.get(line).unwrap_or(&Line::default()), width)
+}
+
+/// where the cursor is drawn, the wrapped equivalent of `Position::relative_view_position`
+pub fn screen_position(
+    pos: &Position,
+    offset: &ScreenOffset,
+    buffer: &Buffer,
+    size: &Size,
+) -> ScreenPosition {
+    let folds = &buffer.folds;
+    if folds.hidden(pos.height) {
+        let head = Position {
+            height: folds.head(pos.height),
+            ..Position::default()
+        };
+        return screen_position(&head, offset, buffer, size);
+    }
+    // on a closed fold the cursor sits at the start of its summary
+    let folded = folds.closed_at(pos.height).is_some();
+    if !offset.wrap {
+        let mut position = pos.relative_view_position(offset);
+        position.height = folds
+            .display_row(pos.height)
+            .saturating_sub(folds.display_row(offset.height));
+        if folded {
+            position.width = offset.gutter.width;
+        }
+        return position;
+    }
+    let text_width = offset.gutter.text_width(size);
+    let Some(line) = buffer.text.get(pos.height) else {
+        return pos.relative_view_position(offset);
+    };
+    let rows = rows_at(buffer, pos.height, text_width);
+    let cursor_row = row_of(&rows, pos.width);
+
+    // display rows from the top of the view to the cursor
+    let mut height = 0_usize;
+    for current in offset.height..pos.height {
+        height = height.saturating_add(rows_at(buffer, current, text_width).len());
+    }
+    height = height.saturating_add(cursor_row).saturating_sub(offset.row);
+
+    let column = if folded {
+        0
+    } else {
+        row_indent(cursor_row).saturating_add(line.range_width(rows[cursor_row].start..pos.width))
+    };
+    ScreenPosition {
+        height,
+        width: offset.gutter.width.saturating_add(column),
+    }
+}
+
+/// moves the top of the view so the cursor row is visible
+/// returns the number of display rows the view moved
+pub fn scroll_to_cursor(
+    offset: &mut ScreenOffset,
+    pos: &Position,
+    buffer: &Buffer,
+    size: &Size,
+    reserved: usize,
+) -> usize {
+    let text_width = offset.gutter.text_width(size);
+    let visible = std::cmp::max(size.height.saturating_sub(reserved), 1);
+    let cursor_row = row_of(&rows_at(buffer, pos.height, text_width), pos.width);
+    offset.width = 0;
+    // a fold closed over the top line shows it on the fold's first line
+    offset.height = buffer.folds.head(offset.height);
+    // the top line may have been edited or scrolled to while lines were not wrapped
+    offset.row = std::cmp::min(
+        offset.row,
+        rows_at(buffer, offset.height, text_width)
+            .len()
+            .saturating_sub(1),
+    );
+
+    if (pos.height, cursor_row) < (offset.height, offset.row) {
+        let moved = if pos.height == offset.height {
+            offset.row.saturating_sub(cursor_row)
+        } else {
+            offset.height.saturating_sub(pos.height)
+        };
+        offset.height = pos.height;
+        offset.row = cursor_row;
+        return moved;
+    }
+
+    // every shown line takes at least one row, so a cursor this far down is off screen
+    let folds = &buffer.folds;
+    if folds
+        .display_row(pos.height)
+        .saturating_sub(folds.display_row(offset.height))
+        < visible
+    {
+        let below = screen_position(pos, offset, buffer, size).height;
+        if below < visible {
+            return 0;
+        }
+    }
+
+    // put the cursor row at the bottom of the view
+    let previous = (offset.height, offset.row);
+    let mut remaining = visible.saturating_sub(1);
+    let mut line = pos.height;
+    let mut row = cursor_row;
+    while remaining > 0 {
+        if row >= remaining {
+            row = row.saturating_sub(remaining);
+            remaining = 0;
+        } else if line == 0 {
+            row = 0;
+            remaining = 0;
+        } else {
+            remaining = remaining.saturating_sub(row.saturating_add(1));
+            line = folds.head(line.saturating_sub(1));
+            row = rows_at(buffer, line, text_width).len().saturating_sub(1);
+        }
+    }
+    offset.height = line;
+    offset.row = row;
+    if (line, row) == previous {
+        0
+    } else {
+        line.saturating_sub(previous.0).max(1)
+    }
+}
+
+/// the text of every screen row from the top of the view, wrapped or not
+/// gutter and continuation included, a closed fold shows its summary
+pub fn screen_rows(
+    buffer: &Buffer,
+    offset: &ScreenOffset,
+    size: &Size,
+    cursor_line: usize,
+    count: usize,
+) -> Vec<String> {
+    let blank_gutter = " ".repeat(offset.gutter.width);
+    let mut screen: Vec<String> = display_rows(buffer, offset, size, count)
+        .into_iter()
+        .map(|row| {
+            let line = &buffer.text[row.line];
+            let text = buffer
+                .folds
+                .summary(row.line, line)
+                .unwrap_or_else(|| line.get_line_subset(row.graphemes).to_string());
+            if row.column == offset.gutter.width {
+                format!("{}{text}", offset.gutter.label(row.line, cursor_line))
+            } else {
+                format!("{blank_gutter}{CONTINUATION}{text}")
+            }
+        })
+        .collect();
+    screen.resize(count, "~".to_string());
+    screen
+}
+
+/// a screen row showing part of a buffer line
+pub struct DisplayRow {
+    pub line: usize,
+    pub graphemes: Range<usize>, // the part of the line on the row, none on a closed fold
+    pub column: usize,           // the screen column of its first grapheme
+}
+
+impl DisplayRow {
+    /// the screen columns of the graphemes of its line in range that are on the row
+    pub fn columns(&self, line: &Line, graphemes: Range<usize>) -> Option<Range<usize>> {
+        let start = std::cmp::max(graphemes.start, self.graphemes.start);
+        let end = std::cmp::min(graphemes.end, self.graphemes.end);
+        if start >= end {
+            return None;
+        }
+        let column = self
+            .column
+            .saturating_add(line.range_width(self.graphemes.start..start));
+        Some(column..column.saturating_add(line.range_width(start..end)))
+    }
+}
+
+/// the buffer lines on the rows of the view from the top, wrapped or not
+pub fn display_rows(
+    buffer: &Buffer,
+    offset: &ScreenOffset,
+    size: &Size,
+    count: usize,
+) -> Vec<DisplayRow> {
+    let text_width = offset.gutter.text_width(size);
+    let mut rows = Vec::with_capacity(count);
+    let mut line = offset.height;
+    let mut skip = if offset.wrap { offset.row } else { 0 };
+    while rows.len() < count && line < buffer.len() {
+        let ranges = if offset.wrap {
+            rows_at(buffer, line, text_width)
+        } else if buffer.folds.closed_at(line).is_some() {
+            vec![Range::default()]
+        } else {
+            vec![Range {
+                start: offset.width,
+                end: offset.width.saturating_add(text_width),
+            }]
+        };
+        for (row, graphemes) in ranges.into_iter().enumerate() {
+            if skip > 0 {
+                skip = skip.saturating_sub(1);
+                continue;
+            }
+            if rows.len() == count {
+                break;
+            }
+            rows.push(DisplayRow {
+                line,
+                graphemes,
+                column: offset.gutter.width.saturating_add(if offset.wrap {
+                    row_indent(row)
+                } else {
+                    0
+                }),
+            });
+        }
+        line = buffer.folds.next_visible(line);
+    }
+    rows
+}
+
+/// moves the cursor one display row, `gj` and `gk`
+/// keeps the screen column where the row is long enough
+pub fn move_display_row(pos: &mut Position, buffer: &Buffer, text_width: usize, down: bool) {
+    let Some(line) = buffer.text.get(pos.height) else {
+        return;
+    };
+    let rows = rows_at(buffer, pos.height, text_width);
+    let Some(current) = (!rows.is_empty()).then(|| row_of(&rows, pos.width)) else {
+        return;
+    };
+    let column =
+        row_indent(current).saturating_add(line.range_width(rows[current].start..pos.width));
+
+    let (height, row) = if down {
+        if current.saturating_add(1) < rows.len() {
+            (pos.height, current.saturating_add(1))
+        } else if buffer.folds.next_visible(pos.height) < buffer.len() {
+            (buffer.folds.next_visible(pos.height), 0)
+        } else {
+            return;
+        }
+    } else if current > 0 {
+        (pos.height, current.saturating_sub(1))
+    } else if pos.height > 0 {
+        let above = buffer.folds.head(pos.height.saturating_sub(1));
+        (
+            above,
+            rows_at(buffer, above, text_width).len().saturating_sub(1),
+        )
+    } else {
+        return;
+    };
+
+    let target = &buffer.text[height];
+    let target_rows = rows_at(buffer, height, text_width);
+    let range = target_rows[row].clone();
+    // the end of a row that is not the last belongs to the next row
+    let last = if row.saturating_add(1) < target_rows.len() {
+        range.end.saturating_sub(1)
+    } else {
+        range.end
+    };
+    let mut width = range.start;
+    let mut columns = row_indent(row);
+    while width < last {
+        let next = columns.saturating_add(target.range_width(width..width.saturating_add(1)));
+        if next > column {
+            break;
+        }
+        columns = next;
+        width = width.saturating_add(1);
+    }
+    pos.height = height;
+    pos.width = width;
+    pos.max_width = width;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(text: &str, width: usize) -> Vec<Range<usize>> {
+        line_rows(&Line::from(text), width)
+    }
+
+    #[test]
+    fn short_and_empty_lines_take_one_row() {
+        assert_eq!(ranges("", 10), vec![0..0]);
+        assert_eq!(ranges("short", 10), vec![0..5]);
+    }
+
+    #[test]
+    fn breaks_at_word_boundaries() {
+        // the continuation rows lose two columns to the indicator
+        assert_eq!(
+            ranges("the quick brown fox", 10),
+            vec![0..10, 10..16, 16..19]
+        );
+    }
+
+    #[test]
+    fn long_words_break_mid_word() {
+        assert_eq!(ranges("abcdefghijkl", 5), vec![0..5, 5..8, 8..11, 11..12]);
+    }
+
+    #[test]
+    fn row_of_width() {
+        let rows = ranges("the quick brown fox", 10);
+        assert_eq!(row_of(&rows, 0), 0);
+        assert_eq!(row_of(&rows, 9), 0);
+        assert_eq!(row_of(&rows, 10), 1);
+        assert_eq!(row_of(&rows, 19), 2);
+    }
+
+    fn wrapped_buffer() -> Buffer {
+        Buffer {
+            text: vec![
+                Line::from("the quick brown fox"),
+                Line::from("jumps"),
+                Line::from("over the lazy dog"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn display_row_motion() {
+        let buffer = wrapped_buffer();
+        let mut pos = Position {
+            height: 0,
+            width: 2,
+            max_width: 2,
+        };
+        move_display_row(&mut pos, &buffer, 10, true);
+        // the continuation indicator takes the first two columns
+        assert_eq!((pos.height, pos.width), (0, 10));
+        move_display_row(&mut pos, &buffer, 10, true);
+        move_display_row(&mut pos, &buffer, 10, true);
+        // same screen column, which is past the indicator
+        assert_eq!((pos.height, pos.width), (1, 2));
+        move_display_row(&mut pos, &buffer, 10, false);
+        assert_eq!((pos.height, pos.width), (0, 16));
+    }
+
+    #[test]
+    fn scrolls_by_display_rows() {
+        let buffer = wrapped_buffer();
+        let size = Size {
+            height: 5,
+            width: 10,
+        };
+        let mut offset = ScreenOffset {
+            wrap: true,
+            ..Default::default()
+        };
+        let pos = Position {
+            height: 2,
+            width: 14,
+            max_width: 14,
+        };
+        // five rows of text above the cursor row, with three rows visible
+        // "over the lazy dog" takes two rows
+        assert_eq!(screen_position(&pos, &offset, &buffer, &size).height, 5);
+        assert!(scroll_to_cursor(&mut offset, &pos, &buffer, &size, 2) > 0);
+        assert_eq!((offset.height, offset.row), (1, 0));
+        assert_eq!(screen_position(&pos, &offset, &buffer, &size).height, 2);
+
+        let rows = screen_rows(&buffer, &offset, &size, pos.height, 4);
+        assert_eq!(rows, vec!["jumps", "over the ", "↪ lazy dog", "~"]);
+    }
+}
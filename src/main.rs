@@ -5,22 +5,67 @@
     clippy::as_conversions
 )]
 mod editor;
+use editor::cli::{self, Action};
 use editor::terminal::Terminal;
 use editor::Editor;
+use std::io::{stdout, IsTerminal, Write};
+use std::process::ExitCode;
 
-fn main() {
-    let loader = Editor::new();
+fn main() -> ExitCode {
+    let args = match cli::parse(std::env::args().skip(1)) {
+        Ok(Action::Edit(args)) => args,
+        Ok(Action::Help) => {
+            let _ = writeln!(stdout(), "{}", cli::USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Ok(Action::Version) => {
+            let _ = writeln!(stdout(), "mini-vim {}", env!("CARGO_PKG_VERSION"));
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("mini-vim: {message}\nTry 'mini-vim --help' for more information.");
+            return ExitCode::from(2);
+        }
+    };
+    let loader = Editor::new(&args);
     match loader {
         Ok(mut editor) => match editor.run() {
-            Ok(()) => {}
+            Ok(()) => {
+                let code = editor.exit_code();
+                // as a filter the edited text goes on down the pipe
+                let filtered = if stdout().is_terminal() {
+                    None
+                } else {
+                    editor.output(&args.source)
+                };
+                // restores the terminal before anything else is written
+                drop(editor);
+                if let Some(text) = filtered {
+                    let mut out = stdout();
+                    if out
+                        .write_all(text.as_bytes())
+                        .and_then(|()| out.flush())
+                        .is_err()
+                    {
+                        return ExitCode::from(2);
+                    }
+                }
+                ExitCode::from(code)
+            }
             Err(e) => {
-                let _ = Terminal::terminate();
-                let _ = Terminal::print(format!("MiniVim Error:\n{e}"));
+                drop(editor);
+                report(&e)
             }
         },
         Err(e) => {
             let _ = Terminal::terminate();
-            let _ = Terminal::print(format!("MiniVim Error:\n{e}"));
+            report(&e)
         }
     }
 }
+
+/// the terminal is restored by now, so the error reaches stderr even at the end of a pipe
+fn report(error: &std::io::Error) -> ExitCode {
+    eprintln!("MiniVim Error:\n{error}");
+    ExitCode::from(2)
+}